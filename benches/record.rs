@@ -0,0 +1,59 @@
+//! Timings of the record write and verify paths.
+//!
+//! Run with `cargo bench`.
+
+use mosaic_core::*;
+use rand::rngs::OsRng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    // Warm up
+    for _ in 0..iterations / 10 + 1 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let per_iteration: Duration = start.elapsed() / iterations;
+    println!("{name:<32} {per_iteration:>12.2?}");
+}
+
+fn main() {
+    let secret_key = SecretKey::generate(&mut OsRng);
+
+    for (label, size, iterations) in [
+        ("64 B", 64, 2000),
+        ("4 KiB", 4096, 2000),
+        ("1 MiB", 1_048_576 - 208, 20),
+    ] {
+        let payload = vec![0x55_u8; size];
+        let parts = RecordParts {
+            kind: Kind::BLOG_POST,
+            deterministic_key: None,
+            timestamp: Timestamp::now().unwrap(),
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: &payload,
+        };
+
+        bench(&format!("new ({label})"), iterations, || {
+            let _ = black_box(OwnedRecord::new(&secret_key, black_box(&parts)).unwrap());
+        });
+
+        let mut buffer = vec![0; parts.record_len()];
+        bench(&format!("write_record ({label})"), iterations, || {
+            let _ = black_box(Record::write_record(&mut buffer, &secret_key, &parts).unwrap());
+        });
+
+        let record = OwnedRecord::new(&secret_key, &parts).unwrap();
+        bench(&format!("verify ({label})"), iterations, || {
+            black_box(&record).verify().unwrap();
+        });
+        bench(&format!("full_hash ({label})"), iterations, || {
+            let _ = black_box(black_box(&record).full_hash());
+        });
+    }
+}
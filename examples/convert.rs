@@ -1,7 +1,8 @@
 use base64::prelude::*;
+use mosaic_core::SecretKey;
 
 fn main() {
     let s = "7AgCGv/SF6EThqVuoxU4edrKzqrzqD9yd4e11eTkGIQ=";
-    let bytes = BASE64_STANDARD.decode(s).unwrap();
-    println!("mosec0{}", z32::encode(&bytes));
+    let bytes: [u8; 32] = BASE64_STANDARD.decode(s).unwrap().try_into().unwrap();
+    println!("{}", SecretKey::from_bytes(&bytes).printable());
 }
@@ -0,0 +1,345 @@
+use crate::{
+    Error, Id, InnerError, Kind, OwnedRecord, PublicKey, Record, RecordFlags, RecordParts,
+    SecretKey, Timestamp,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Why a record was reported.
+///
+/// The numeric codes are part of the report record format and never
+/// change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ReportReason {
+    /// Unsolicited bulk or commercial content
+    Spam,
+
+    /// Targeted abuse of a person
+    Harassment,
+
+    /// Attacks on people for who they are
+    HateSpeech,
+
+    /// Threats or incitement of violence
+    Violence,
+
+    /// Sexual content not flagged as such
+    SexualContent,
+
+    /// Content endangering children
+    ChildSafety,
+
+    /// Pretending to be someone else
+    Impersonation,
+
+    /// Content that is illegal where the server operates
+    Illegal,
+
+    /// Malware or phishing
+    Malware,
+
+    /// Anything else; the comment should explain
+    Other,
+}
+
+const REASON_CODES: &[(ReportReason, u8)] = &[
+    (ReportReason::Spam, 1),
+    (ReportReason::Harassment, 2),
+    (ReportReason::HateSpeech, 3),
+    (ReportReason::Violence, 4),
+    (ReportReason::SexualContent, 5),
+    (ReportReason::ChildSafety, 6),
+    (ReportReason::Impersonation, 7),
+    (ReportReason::Illegal, 8),
+    (ReportReason::Malware, 9),
+    (ReportReason::Other, 255),
+];
+
+impl ReportReason {
+    /// The stable numeric code of this reason
+    #[must_use]
+    pub fn code(self) -> u8 {
+        REASON_CODES
+            .iter()
+            .find(|(r, _)| *r == self)
+            .map_or(255, |(_, c)| *c)
+    }
+
+    /// The reason with this numeric code, if there is one
+    #[must_use]
+    pub fn from_code(code: u8) -> Option<ReportReason> {
+        REASON_CODES
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(r, _)| *r)
+    }
+}
+
+impl std::fmt::Display for ReportReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportReason::Spam => write!(f, "spam"),
+            ReportReason::Harassment => write!(f, "harassment"),
+            ReportReason::HateSpeech => write!(f, "hate speech"),
+            ReportReason::Violence => write!(f, "violence"),
+            ReportReason::SexualContent => write!(f, "sexual content"),
+            ReportReason::ChildSafety => write!(f, "child safety"),
+            ReportReason::Impersonation => write!(f, "impersonation"),
+            ReportReason::Illegal => write!(f, "illegal content"),
+            ReportReason::Malware => write!(f, "malware"),
+            ReportReason::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// How urgently a reporter thinks a report needs attention. Severities
+/// order from `Low` to `Critical`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Can wait for routine review
+    Low = 1,
+
+    /// Should be reviewed soon
+    Medium = 2,
+
+    /// Should be reviewed promptly
+    High = 3,
+
+    /// Needs immediate action
+    Critical = 4,
+}
+
+impl Severity {
+    /// The severity with this numeric code, if there is one
+    #[must_use]
+    pub fn from_code(code: u8) -> Option<Severity> {
+        match code {
+            1 => Some(Severity::Low),
+            2 => Some(Severity::Medium),
+            3 => Some(Severity::High),
+            4 => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// A report of an abusive record, as carried by a `REPORT` record.
+///
+/// The payload of a report record is the reason code, the severity code,
+/// six zero bytes, the 48-byte `Id` of the reported record, and then an
+/// optional UTF-8 comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbuseReport {
+    /// The reported record
+    pub target: Id,
+
+    /// Why it was reported
+    pub reason: ReportReason,
+
+    /// How urgent the reporter thinks it is
+    pub severity: Severity,
+
+    /// Free text from the reporter
+    pub comment: String,
+}
+
+const TARGET_RANGE: std::ops::Range<usize> = 8..56;
+
+impl AbuseReport {
+    /// Create a report record signed by the reporter
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the comment is too long for a record, or if
+    /// signing fails
+    pub fn to_record(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        let mut payload = vec![0; TARGET_RANGE.end];
+        payload[0] = self.reason.code();
+        payload[1] = self.severity as u8;
+        payload[TARGET_RANGE].copy_from_slice(self.target.as_bytes());
+        payload.extend_from_slice(self.comment.as_bytes());
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::REPORT,
+                deterministic_key: None,
+                timestamp,
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: &payload,
+            },
+        )
+    }
+
+    /// Read a report from a report record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `REPORT`, or if its payload
+    /// is malformed or uses an unknown reason or severity
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_record(record: &Record) -> Result<AbuseReport, Error> {
+        if record.kind() != Kind::REPORT {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let payload = record.payload_bytes();
+        if payload.len() < TARGET_RANGE.end || payload[2..TARGET_RANGE.start] != [0; 6] {
+            return Err(InnerError::InvalidReport.into());
+        }
+        let reason = ReportReason::from_code(payload[0])
+            .ok_or_else(|| InnerError::InvalidReport.into_err())?;
+        let severity =
+            Severity::from_code(payload[1]).ok_or_else(|| InnerError::InvalidReport.into_err())?;
+        let target = Id::from_bytes(payload[TARGET_RANGE].try_into().unwrap())?;
+        let comment = std::str::from_utf8(&payload[TARGET_RANGE.end..])?.to_owned();
+        Ok(AbuseReport {
+            target,
+            reason,
+            severity,
+            comment,
+        })
+    }
+}
+
+/// Collects report records and answers questions about the reports
+/// against each record.
+///
+/// Each reporter counts once per reported record: a later report from the
+/// same reporter replaces their earlier one.
+#[derive(Debug, Clone, Default)]
+pub struct ReportIndex {
+    by_target: HashMap<Id, BTreeMap<PublicKey, (Timestamp, AbuseReport)>>,
+}
+
+impl ReportIndex {
+    /// Create an empty `ReportIndex`
+    #[must_use]
+    pub fn new() -> ReportIndex {
+        ReportIndex::default()
+    }
+
+    /// Add a report record. Returns false if the reporter already made a
+    /// later report against the same record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record does not verify or is not a valid
+    /// report
+    pub fn insert(&mut self, record: &Record) -> Result<bool, Error> {
+        record.verify()?;
+        let report = AbuseReport::from_record(record)?;
+        let reports = self.by_target.entry(report.target).or_default();
+        let reporter = record.author_public_key();
+        if let Some((timestamp, _)) = reports.get(&reporter) {
+            if *timestamp >= record.timestamp() {
+                return Ok(false);
+            }
+        }
+        let _ = reports.insert(reporter, (record.timestamp(), report));
+        Ok(true)
+    }
+
+    /// The reports against a record, one per reporter
+    pub fn reports_for(&self, target: &Id) -> impl Iterator<Item = (&PublicKey, &AbuseReport)> {
+        self.by_target
+            .get(target)
+            .into_iter()
+            .flat_map(|reports| reports.iter().map(|(k, (_, r))| (k, r)))
+    }
+
+    /// The number of reporters of a record
+    #[must_use]
+    pub fn count_for(&self, target: &Id) -> usize {
+        self.by_target.get(target).map_or(0, BTreeMap::len)
+    }
+
+    /// The most severe report against a record
+    #[must_use]
+    pub fn max_severity(&self, target: &Id) -> Option<Severity> {
+        self.reports_for(target).map(|(_, r)| r.severity).max()
+    }
+
+    /// How many reporters gave each reason against a record
+    #[must_use]
+    pub fn reason_counts(&self, target: &Id) -> BTreeMap<ReportReason, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, report) in self.reports_for(target) {
+            *counts.entry(report.reason).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The reported records, most reporters first
+    #[must_use]
+    pub fn most_reported(&self) -> Vec<(Id, usize)> {
+        let mut targets: Vec<(Id, usize)> = self
+            .by_target
+            .iter()
+            .map(|(id, reports)| (*id, reports.len()))
+            .collect();
+        targets.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        targets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_index() {
+        use rand::rngs::OsRng;
+
+        let author = SecretKey::generate(&mut OsRng);
+        let target = OwnedRecord::new(
+            &author,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"buy now",
+            },
+        )
+        .unwrap();
+
+        let report = AbuseReport {
+            target: target.id(),
+            reason: ReportReason::Spam,
+            severity: Severity::Low,
+            comment: "advertising".to_owned(),
+        };
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+        let ts = |ms| Timestamp::from_millis(ms).unwrap();
+
+        let r1 = report.to_record(&alice, ts(2000)).unwrap();
+        assert_eq!(AbuseReport::from_record(&r1).unwrap(), report);
+
+        let mut escalated = report.clone();
+        escalated.reason = ReportReason::Malware;
+        escalated.severity = Severity::High;
+        let r2 = escalated.to_record(&alice, ts(3000)).unwrap();
+        let r3 = report.to_record(&bob, ts(2500)).unwrap();
+
+        let mut index = ReportIndex::new();
+        assert!(index.insert(&r2).unwrap());
+        assert!(!index.insert(&r1).unwrap()); // older, from the same reporter
+        assert!(index.insert(&r3).unwrap());
+
+        assert_eq!(index.count_for(&target.id()), 2);
+        assert_eq!(index.max_severity(&target.id()), Some(Severity::High));
+        let counts = index.reason_counts(&target.id());
+        assert_eq!(counts[&ReportReason::Spam], 1);
+        assert_eq!(counts[&ReportReason::Malware], 1);
+        assert_eq!(index.most_reported(), vec![(target.id(), 2)]);
+        assert!(index.insert(&target).is_err());
+    }
+}
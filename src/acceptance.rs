@@ -0,0 +1,560 @@
+use crate::{
+    Counter, Histogram, Id, Kind, Metrics, PublicKey, RecentIds, Record, SizeStats, Timestamp,
+    MAX_RECORD_LEN,
+};
+use std::sync::Arc;
+
+/// Why a record was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    /// The record is malformed or failed verification
+    Invalid(String),
+
+    /// The record was already accepted
+    Duplicate,
+
+    /// The record is larger than allowed
+    TooLarge {
+        /// The length of the record
+        len: usize,
+
+        /// The maximum length allowed
+        max: usize,
+    },
+
+    /// Records of this kind are not accepted
+    KindNotAllowed(Kind),
+
+    /// The timestamp is too far in the future
+    TimestampInFuture,
+
+    /// The timestamp is too far in the past
+    TimestampTooOld,
+
+    /// The proof-of-work is insufficient
+    InsufficientPow {
+        /// The difficulty required
+        required: u32,
+
+        /// The difficulty of the record
+        actual: u32,
+    },
+
+    /// A `Policy` rejected the record
+    Policy(String),
+
+    /// A `SpamScorer` rejected the record
+    Spam {
+        /// The score it gave
+        score: u32,
+    },
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectReason::Invalid(s) => write!(f, "invalid: {s}"),
+            RejectReason::Duplicate => write!(f, "duplicate"),
+            RejectReason::TooLarge { len, max } => {
+                write!(f, "too large: {len} bytes exceeds {max}")
+            }
+            RejectReason::KindNotAllowed(kind) => write!(f, "kind not allowed: {kind}"),
+            RejectReason::TimestampInFuture => write!(f, "timestamp is too far in the future"),
+            RejectReason::TimestampTooOld => write!(f, "timestamp is too old"),
+            RejectReason::InsufficientPow { required, actual } => {
+                write!(f, "insufficient pow: {actual} < {required}")
+            }
+            RejectReason::Policy(s) => write!(f, "policy: {s}"),
+            RejectReason::Spam { score } => write!(f, "spam (score {score})"),
+        }
+    }
+}
+
+/// The outcome of passing a record through an `AcceptancePipeline`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The record was accepted
+    Accepted(Id),
+
+    /// The record was rejected
+    Rejected(RejectReason),
+}
+
+impl Outcome {
+    /// Was the record accepted?
+    #[must_use]
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Outcome::Accepted(_))
+    }
+}
+
+/// A pluggable admission policy.
+///
+/// Policies run after the built-in checks, on records that have been fully
+/// verified.
+pub trait Policy {
+    /// Check the record, returning a reason if it should be rejected
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the record is rejected
+    fn check(&self, record: &Record) -> Result<(), RejectReason>;
+}
+
+/// What is known about the connection a record arrived on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ConnectionContext {
+    /// The key the peer authenticated as, if it did
+    pub peer: Option<PublicKey>,
+
+    /// The peer's network address, if known
+    pub remote_addr: Option<std::net::SocketAddr>,
+
+    /// An application-defined identifier of the connection, if any
+    pub connection_id: Option<u64>,
+}
+
+/// What a `SpamScorer` recommends doing with a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpamAction {
+    /// Accept the record
+    Accept,
+
+    /// Reject the record as spam
+    Reject,
+}
+
+/// A `SpamScorer`'s judgement of a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpamVerdict {
+    /// How spammy the record looks, on a scale the scorer defines
+    pub score: u32,
+
+    /// What to do with the record
+    pub action: SpamAction,
+}
+
+/// A pluggable spam scorer, such as a set of heuristics or a model.
+///
+/// Scorers run last, after every `Policy`, on records that have been fully
+/// verified, and are told about the connection the record arrived on.
+/// The first scorer to recommend `SpamAction::Reject` rejects the record.
+pub trait SpamScorer {
+    /// Score the record
+    fn score(&self, record: &Record, context: &ConnectionContext) -> SpamVerdict;
+}
+
+/// An `AcceptancePipeline` decides whether to accept records arriving at a
+/// server.
+///
+/// Checks run from cheapest to most expensive: size, kind, timestamp skew,
+/// proof-of-work, deduplication, full verification, any registered
+/// `Policy`s, and finally any registered `SpamScorer`s.
+pub struct AcceptancePipeline {
+    /// Maximum record length in bytes (`MAX_RECORD_LEN` by default). This
+    /// may be lowered, but values above `MAX_RECORD_LEN` have no effect.
+    pub max_record_len: usize,
+
+    /// If set, only these kinds are accepted
+    pub allowed_kinds: Option<Vec<Kind>>,
+
+    /// How far in the future (in milliseconds) a timestamp may be
+    pub max_future_ms: u64,
+
+    /// If set, how far in the past (in milliseconds) a timestamp may be
+    pub max_age_ms: Option<u64>,
+
+    /// Minimum proof-of-work difficulty (see `Id::pow_difficulty()`)
+    pub min_pow: u32,
+
+    policies: Vec<Box<dyn Policy>>,
+    scorers: Vec<Box<dyn SpamScorer>>,
+    seen: RecentIds,
+    metrics: Option<Arc<dyn Metrics>>,
+    size_stats: Option<SizeStats>,
+}
+
+/// How many accepted ids an `AcceptancePipeline` remembers by default
+const DEFAULT_RECENT_IDS: usize = 65_536;
+
+impl Default for AcceptancePipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for AcceptancePipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcceptancePipeline")
+            .field("max_record_len", &self.max_record_len)
+            .field("allowed_kinds", &self.allowed_kinds)
+            .field("max_future_ms", &self.max_future_ms)
+            .field("max_age_ms", &self.max_age_ms)
+            .field("min_pow", &self.min_pow)
+            .field("policies", &self.policies.len())
+            .field("scorers", &self.scorers.len())
+            .field("seen", &self.seen.len())
+            .field("metrics", &self.metrics)
+            .field("size_stats", &self.size_stats.is_some())
+            .finish()
+    }
+}
+
+impl AcceptancePipeline {
+    /// Create a new `AcceptancePipeline` that accepts any valid record up
+    /// to the maximum record size, with a timestamp no more than 15 minutes
+    /// in the future.
+    #[must_use]
+    pub fn new() -> AcceptancePipeline {
+        AcceptancePipeline {
+            max_record_len: MAX_RECORD_LEN,
+            allowed_kinds: None,
+            max_future_ms: 15 * 60 * 1000,
+            max_age_ms: None,
+            min_pow: 0,
+            policies: vec![],
+            scorers: vec![],
+            seen: RecentIds::new(DEFAULT_RECENT_IDS),
+            metrics: None,
+            size_stats: None,
+        }
+    }
+
+    /// Report to the given `Metrics`
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Feed the sizes of accepted records to the given `SizeStats`
+    pub fn set_size_stats(&mut self, size_stats: SizeStats) {
+        self.size_stats = Some(size_stats);
+    }
+
+    /// Remember at most this many accepted ids for duplicate suppression
+    /// (65,536 by default). This forgets the ids remembered so far.
+    pub fn set_recent_capacity(&mut self, capacity: usize) {
+        self.seen = RecentIds::new(capacity);
+    }
+
+    /// Add a `Policy`
+    pub fn add_policy<P: Policy + 'static>(&mut self, policy: P) {
+        self.policies.push(Box::new(policy));
+    }
+
+    /// Add a `SpamScorer`
+    pub fn add_spam_scorer<S: SpamScorer + 'static>(&mut self, scorer: S) {
+        self.scorers.push(Box::new(scorer));
+    }
+
+    /// Pass the bytes of a record through the pipeline.
+    ///
+    /// If accepted, the record's `Id` is remembered so that it will be
+    /// rejected as a duplicate while it remains among the recently
+    /// accepted ids.
+    pub fn accept(&mut self, bytes: &[u8], now: Timestamp) -> Outcome {
+        self.accept_with_context(bytes, now, &ConnectionContext::default())
+    }
+
+    /// Pass the bytes of a record that arrived on a connection through the
+    /// pipeline. The context is passed to the `SpamScorer`s.
+    pub fn accept_with_context(
+        &mut self,
+        bytes: &[u8],
+        now: Timestamp,
+        context: &ConnectionContext,
+    ) -> Outcome {
+        let outcome = self.evaluate(bytes, now, context);
+        if let Some(ref metrics) = self.metrics {
+            match outcome {
+                Outcome::Accepted(_) => {
+                    metrics.increment(Counter::Accepts, 1);
+                    metrics.increment(Counter::BytesAccepted, bytes.len() as u64);
+                    metrics.observe(Histogram::RecordBytes, bytes.len() as u64);
+                }
+                Outcome::Rejected(ref reason) => metrics.rejected(reason),
+            }
+        }
+        outcome
+    }
+
+    fn evaluate(&mut self, bytes: &[u8], now: Timestamp, context: &ConnectionContext) -> Outcome {
+        let max_record_len = self.max_record_len.min(MAX_RECORD_LEN);
+        if bytes.len() > max_record_len {
+            return Outcome::Rejected(RejectReason::TooLarge {
+                len: bytes.len(),
+                max: max_record_len,
+            });
+        }
+
+        // SAFETY: the record is fully verified below before it is accepted
+        let record = match unsafe { Record::from_bytes_strict(bytes) } {
+            Ok(r) => r,
+            Err(e) => return Outcome::Rejected(RejectReason::Invalid(format!("{}", e.inner))),
+        };
+
+        if let Some(ref kinds) = self.allowed_kinds {
+            if !kinds.contains(&record.kind()) {
+                return Outcome::Rejected(RejectReason::KindNotAllowed(record.kind()));
+            }
+        }
+
+        // The record's own timestamp, not the unsigned copy in its Id
+        let id = record.id();
+        let ts = record.timestamp().as_millis();
+        if ts > now.as_millis().saturating_add(self.max_future_ms) {
+            return Outcome::Rejected(RejectReason::TimestampInFuture);
+        }
+        if let Some(max_age) = self.max_age_ms {
+            if ts < now.as_millis().saturating_sub(max_age) {
+                return Outcome::Rejected(RejectReason::TimestampTooOld);
+            }
+        }
+
+        let pow = id.pow_difficulty();
+        if pow < self.min_pow {
+            return Outcome::Rejected(RejectReason::InsufficientPow {
+                required: self.min_pow,
+                actual: pow,
+            });
+        }
+
+        if self.seen.contains(&id) {
+            return Outcome::Rejected(RejectReason::Duplicate);
+        }
+
+        let verified = record.verify();
+        if let Some(ref metrics) = self.metrics {
+            metrics.increment(Counter::Verifications, 1);
+            if verified.is_err() {
+                metrics.increment(Counter::VerificationFailures, 1);
+            }
+        }
+        if let Err(e) = verified {
+            return Outcome::Rejected(RejectReason::Invalid(format!("{}", e.inner)));
+        }
+
+        for policy in &self.policies {
+            if let Err(reason) = policy.check(record) {
+                return Outcome::Rejected(reason);
+            }
+        }
+
+        for scorer in &self.scorers {
+            let verdict = scorer.score(record, context);
+            if let Some(ref metrics) = self.metrics {
+                metrics.observe(Histogram::SpamScore, u64::from(verdict.score));
+            }
+            if verdict.action == SpamAction::Reject {
+                return Outcome::Rejected(RejectReason::Spam {
+                    score: verdict.score,
+                });
+            }
+        }
+
+        if let Some(ref size_stats) = self.size_stats {
+            size_stats.observe(record);
+        }
+        self.seen.insert(id);
+        Outcome::Accepted(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metrics::test::TestMetrics;
+    use crate::{OwnedRecord, RecordFlags, RecordParts, SecretKey};
+
+    struct NoEmptyPayloads;
+
+    impl Policy for NoEmptyPayloads {
+        fn check(&self, record: &Record) -> Result<(), RejectReason> {
+            if record.payload_len() == 0 {
+                Err(RejectReason::Policy("empty payload".to_owned()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    // Rejects records from unauthenticated peers that mention "free"
+    struct FreeStuff;
+
+    impl SpamScorer for FreeStuff {
+        fn score(&self, record: &Record, context: &ConnectionContext) -> SpamVerdict {
+            let score = if record.payload_bytes().windows(4).any(|w| w == b"free") {
+                80
+            } else {
+                0
+            };
+            let action = if score > 50 && context.peer.is_none() {
+                SpamAction::Reject
+            } else {
+                SpamAction::Accept
+            };
+            SpamVerdict { score, action }
+        }
+    }
+
+    #[test]
+    fn test_acceptance_pipeline() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |kind: Kind, millis: u64, payload: &[u8]| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload,
+                },
+            )
+            .unwrap()
+        };
+
+        let now = Timestamp::from_millis(10_000_000).unwrap();
+        let metrics = Arc::new(TestMetrics::default());
+        let mut pipeline = AcceptancePipeline::new();
+        pipeline.set_metrics(metrics.clone());
+        pipeline.allowed_kinds = Some(vec![Kind::MICROBLOG_ROOT]);
+        pipeline.max_age_ms = Some(1_000_000);
+        pipeline.add_policy(NoEmptyPayloads);
+        pipeline.add_spam_scorer(FreeStuff);
+
+        let good = make(Kind::MICROBLOG_ROOT, 9_999_000, b"hello");
+        assert!(pipeline.accept(good.as_bytes(), now).is_accepted());
+        assert_eq!(
+            pipeline.accept(good.as_bytes(), now),
+            Outcome::Rejected(RejectReason::Duplicate)
+        );
+
+        let r = make(Kind::CHAT_MESSAGE, 9_999_000, b"hello");
+        assert_eq!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::KindNotAllowed(Kind::CHAT_MESSAGE))
+        );
+
+        let r = make(Kind::MICROBLOG_ROOT, 20_000_000, b"hello");
+        assert_eq!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::TimestampInFuture)
+        );
+
+        let r = make(Kind::MICROBLOG_ROOT, 1_000, b"hello");
+        assert_eq!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::TimestampTooOld)
+        );
+
+        let r = make(Kind::MICROBLOG_ROOT, 9_999_000, b"");
+        assert_eq!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::Policy("empty payload".to_owned()))
+        );
+
+        let mut tampered = make(Kind::MICROBLOG_ROOT, 9_999_000, b"hello")
+            .as_bytes()
+            .to_owned();
+        let last = tampered.len() - 4;
+        tampered[last] ^= 0xFF;
+        assert!(matches!(
+            pipeline.accept(&tampered, now),
+            Outcome::Rejected(RejectReason::Invalid(_))
+        ));
+
+        let mut padded = make(Kind::MICROBLOG_ROOT, 9_999_000, b"padded")
+            .as_bytes()
+            .to_owned();
+        padded.extend_from_slice(b"garbage");
+        assert!(matches!(
+            pipeline.accept(&padded, now),
+            Outcome::Rejected(RejectReason::Invalid(_))
+        ));
+
+        let r = make(Kind::MICROBLOG_ROOT, 9_999_000, b"free stuff");
+        assert_eq!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::Spam { score: 80 })
+        );
+        let context = ConnectionContext {
+            peer: Some(secret_key.public()),
+            ..ConnectionContext::default()
+        };
+        assert!(pipeline
+            .accept_with_context(r.as_bytes(), now, &context)
+            .is_accepted());
+
+        pipeline.min_pow = 200;
+        let r = make(Kind::MICROBLOG_ROOT, 9_999_000, b"hello");
+        assert!(matches!(
+            pipeline.accept(r.as_bytes(), now),
+            Outcome::Rejected(RejectReason::InsufficientPow { required: 200, .. })
+        ));
+
+        // The maximum length cannot be raised above the ceiling
+        pipeline.max_record_len = usize::MAX;
+        let oversized = vec![0; MAX_RECORD_LEN + 1];
+        assert_eq!(
+            pipeline.accept(&oversized, now),
+            Outcome::Rejected(RejectReason::TooLarge {
+                len: MAX_RECORD_LEN + 1,
+                max: MAX_RECORD_LEN
+            })
+        );
+
+        assert_eq!(metrics.get(Counter::Accepts), 2);
+        assert_eq!(metrics.get(Counter::Rejects), 10);
+        assert_eq!(metrics.get(Counter::Verifications), 5);
+        assert_eq!(metrics.get(Counter::VerificationFailures), 1);
+    }
+
+    #[test]
+    fn test_acceptance_id_timestamp() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let now = Timestamp::from_millis(10_000_000).unwrap();
+        let mut pipeline = AcceptancePipeline::new();
+
+        // The copy of the timestamp in the Id is not signed, so it cannot
+        // be used to pass the time checks, and must match
+        let mut backdated = make(20_000_000).as_bytes().to_owned();
+        backdated[64..70]
+            .copy_from_slice(&Timestamp::from_millis(9_999_000).unwrap().to_be_bytes());
+        assert_eq!(
+            pipeline.accept(&backdated, now),
+            Outcome::Rejected(RejectReason::TimestampInFuture)
+        );
+        let mut retimed = make(9_999_000).as_bytes().to_owned();
+        retimed[64..70].copy_from_slice(&Timestamp::from_millis(9_999_001).unwrap().to_be_bytes());
+        assert!(matches!(
+            pipeline.accept(&retimed, now),
+            Outcome::Rejected(RejectReason::Invalid(_))
+        ));
+    }
+}
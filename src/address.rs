@@ -1,4 +1,4 @@
-use crate::{Error, InnerError, Kind, PublicKey};
+use crate::{zbase32, Error, InnerError, Kind, PublicKey};
 use rand_core::{OsRng, RngCore};
 
 /// An Address identifies a record group where the latest one in
@@ -39,8 +39,21 @@ impl Address {
     /// Create a new Address with a random nonce
     #[must_use]
     pub fn new_random(author_public_key: PublicKey, kind: Kind) -> Address {
+        Self::new_random_with_rng(&mut OsRng, author_public_key, kind)
+    }
+
+    /// Create a new Address with a nonce drawn from `rng`.
+    ///
+    /// The nonce only needs to be unique, not secret, so a seeded generator
+    /// may be used to make addresses reproducible.
+    #[must_use]
+    pub fn new_random_with_rng<R: RngCore + ?Sized>(
+        rng: &mut R,
+        author_public_key: PublicKey,
+        kind: Kind,
+    ) -> Address {
         let mut nonce: [u8; 14] = [0; 14];
-        OsRng.fill_bytes(&mut nonce);
+        rng.fill_bytes(&mut nonce);
         Self::from_parts(author_public_key, kind, &nonce)
     }
 
@@ -73,7 +86,7 @@ impl Address {
     /// Convert an `Address` into the human printable `moref0` form.
     #[must_use]
     pub fn printable(&self) -> String {
-        format!("moref0{}", z32::encode(self.as_ref()))
+        format!("moref0{}", zbase32::encode(self.as_ref()))
     }
 
     /// Import an `Address` from its printable form
@@ -86,7 +99,11 @@ impl Address {
         if !s.starts_with("moref0") {
             return Err(InnerError::InvalidPrintable.into_err());
         }
-        let bytes = z32::decode(&s.as_bytes()[6..])?;
+        let bytes = zbase32::decode(&s.as_bytes()[6..])?;
+        // Reject strings that only decode because spare bits are ignored
+        if zbase32::encode(&bytes).as_bytes() != &s.as_bytes()[6..] {
+            return Err(InnerError::InvalidPrintable.into_err());
+        }
         let bytes: [u8; 48] = bytes
             .try_into()
             .map_err(|_| InnerError::ReferenceLength.into_err())?;
@@ -129,12 +146,39 @@ impl AsRef<[u8]> for Address {
     }
 }
 
+impl TryFrom<&[u8]> for Address {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Address, Error> {
+        let bytes: &[u8; 48] = bytes
+            .try_into()
+            .map_err(|_| InnerError::ReferenceLength.into_err())?;
+        Address::from_bytes(bytes)
+    }
+}
+
+impl TryFrom<[u8; 48]> for Address {
+    type Error = Error;
+
+    fn try_from(bytes: [u8; 48]) -> Result<Address, Error> {
+        Address::from_bytes(&bytes)
+    }
+}
+
 impl std::fmt::Display for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.printable())
     }
 }
 
+impl std::str::FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address, Error> {
+        Address::from_printable(s)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -158,5 +202,12 @@ mod test {
         let addr = Address::from_printable(printable).unwrap();
         assert_eq!(addr.author_public_key(), author_key);
         assert_eq!(addr.kind(), Kind::MICROBLOG_ROOT);
+
+        assert_eq!(Address::try_from(addr.as_ref()).unwrap(), addr);
+        assert_eq!(Address::try_from(*addr.as_bytes()).unwrap(), addr);
+        assert!(Address::try_from(&addr.as_ref()[1..]).is_err());
+
+        assert_eq!(printable.parse::<Address>().unwrap(), addr);
+        assert!(printable.to_uppercase().parse::<Address>().is_err());
     }
 }
@@ -0,0 +1,184 @@
+use crate::{zbase32, Error, InnerError, OwnedRecord, Record, MAX_RECORD_LEN};
+use std::io::{BufRead, Read, Write};
+
+const JSONL_PREFIX: &str = "{\"record\":\"";
+const JSONL_SUFFIX: &str = "\"}";
+
+// Longer than any line `write_record()` produces (z-base-32 takes 8
+// characters per 5 bytes), so that a line without an end is not read
+// into memory without limit
+const MAX_JSONL_LINE_LEN: usize = 2 * MAX_RECORD_LEN + JSONL_PREFIX.len() + JSONL_SUFFIX.len() + 2;
+
+/// The format of a record archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    /// Each record is preceded by its length as a little-endian `u32`
+    Binary,
+
+    /// One JSON object per line, of the form `{"record":"<zbase32>"}`
+    Jsonl,
+}
+
+/// How much verification to do on records imported from an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VerifyLevel {
+    /// Check lengths only. Only use this for archives you created yourself
+    /// and have kept safe.
+    LengthsOnly,
+
+    /// Fully verify every record, including hashes and signatures
+    #[default]
+    Full,
+}
+
+/// Write a record to an archive
+pub(crate) fn write_record(
+    writer: &mut dyn Write,
+    format: ArchiveFormat,
+    record: &Record,
+) -> Result<(), Error> {
+    match format {
+        ArchiveFormat::Binary => {
+            #[allow(clippy::cast_possible_truncation)]
+            let len = record.as_bytes().len() as u32;
+            writer.write_all(len.to_le_bytes().as_slice())?;
+            writer.write_all(record.as_bytes())?;
+        }
+        ArchiveFormat::Jsonl => {
+            writeln!(
+                writer,
+                "{JSONL_PREFIX}{}{JSONL_SUFFIX}",
+                zbase32::encode(record.as_bytes())
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the next record from an archive, or `None` at the end
+pub(crate) fn read_record(
+    reader: &mut dyn BufRead,
+    format: ArchiveFormat,
+    verify: VerifyLevel,
+) -> Result<Option<OwnedRecord>, Error> {
+    let bytes = match format {
+        ArchiveFormat::Binary => {
+            let mut len_bytes: [u8; 4] = [0; 4];
+            if reader.read(&mut len_bytes[..1])? == 0 {
+                return Ok(None);
+            }
+            reader.read_exact(&mut len_bytes[1..])?;
+            let len = u32::from_le_bytes(len_bytes) as usize;
+            if len > MAX_RECORD_LEN {
+                return Err(InnerError::RecordTooLong.into());
+            }
+            let mut bytes = vec![0; len];
+            reader.read_exact(&mut bytes)?;
+            bytes
+        }
+        ArchiveFormat::Jsonl => loop {
+            let mut line = String::new();
+            let len = (&mut *reader)
+                .take(MAX_JSONL_LINE_LEN as u64)
+                .read_line(&mut line)?;
+            if len == 0 {
+                return Ok(None);
+            }
+            if len == MAX_JSONL_LINE_LEN && !line.ends_with('\n') {
+                return Err(InnerError::RecordTooLong.into());
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(encoded) = line
+                .strip_prefix(JSONL_PREFIX)
+                .and_then(|s| s.strip_suffix(JSONL_SUFFIX))
+            else {
+                return Err(InnerError::InvalidArchive.into());
+            };
+            break zbase32::decode(encoded.as_bytes())?;
+        },
+    };
+
+    match verify {
+        VerifyLevel::Full => Ok(Some(OwnedRecord::from_vec(bytes)?)),
+        VerifyLevel::LengthsOnly => {
+            // SAFETY: the caller has chosen to trust this archive
+            let record = unsafe { Record::from_bytes_strict(&bytes)? };
+            Ok(Some(record.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Filter, Kind, MemoryStore, RecordFlags, RecordParts, RecordStore, SecretKey, Timestamp,
+    };
+
+    #[test]
+    fn test_archive_roundtrip() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let mut store = MemoryStore::new();
+        for i in 1..=3 {
+            let record = OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(i * 1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap();
+            let _ = store.put(&record).unwrap();
+        }
+
+        for format in [ArchiveFormat::Binary, ArchiveFormat::Jsonl] {
+            let mut archive: Vec<u8> = vec![];
+            let exported = store.export(&Filter::new(), format, &mut archive).unwrap();
+            assert_eq!(exported, 3);
+
+            for verify in [VerifyLevel::Full, VerifyLevel::LengthsOnly] {
+                let mut restored = MemoryStore::new();
+                let imported = restored
+                    .import(format, &mut archive.as_slice(), verify)
+                    .unwrap();
+                assert_eq!(imported, 3);
+                assert_eq!(
+                    restored.query(&Filter::new()).unwrap(),
+                    store.query(&Filter::new()).unwrap()
+                );
+            }
+        }
+
+        // Corruption is detected
+        let mut archive: Vec<u8> = vec![];
+        let _ = store
+            .export(&Filter::new(), ArchiveFormat::Binary, &mut archive)
+            .unwrap();
+        archive[300] ^= 0xFF;
+        let mut restored = MemoryStore::new();
+        assert!(restored
+            .import(
+                ArchiveFormat::Binary,
+                &mut archive.as_slice(),
+                VerifyLevel::Full
+            )
+            .is_err());
+
+        // A line without an end is not read forever
+        let mut endless = std::io::BufReader::new(std::io::repeat(b'y'));
+        let err = read_record(&mut endless, ArchiveFormat::Jsonl, VerifyLevel::Full).unwrap_err();
+        assert!(matches!(err.inner, InnerError::RecordTooLong));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{Error, Id, InnerError, OwnedRecord};
+use std::io::{Read, Write};
+
+// A backup is a header followed by chunks and an end marker:
+//
+//   header:  b"MOSAICBK", version (u32 LE)
+//   chunk:   record count (u32 LE, nonzero), payload length (u32 LE),
+//            payload, BLAKE3 hash of the payload (32 bytes)
+//   payload: for each record, its length (u32 LE) then its bytes
+//   end:     record count of zero (u32 LE)
+//
+// Records are written in `Id` order, oldest first.
+
+const MAGIC: &[u8; 8] = b"MOSAICBK";
+const VERSION: u32 = 1;
+
+// Length of the backup header, in bytes
+pub(crate) const HEADER_LEN: u64 = 12;
+
+// Chunk framing: record count, payload length, and the BLAKE3 hash
+const CHUNK_OVERHEAD: u64 = 4 + 4 + 32;
+
+/// How many records `RecordStore::backup()` writes per chunk
+pub const BACKUP_CHUNK_RECORDS: u32 = 1000;
+
+/// Progress through a backup or restore.
+///
+/// Pass a default `BackupProgress` to start. If a backup or restore is
+/// interrupted, the progress reflects every complete chunk, and passing it
+/// again resumes after the last one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackupProgress {
+    /// Number of complete chunks written or restored
+    pub chunks: u64,
+
+    /// Number of records written or restored
+    pub records: u64,
+
+    /// The `Id` of the last record of the last complete chunk
+    pub last_id: Option<Id>,
+
+    /// The offset in the backup just past the last complete chunk, in
+    /// bytes. An interrupted backup is truncated to this length before it
+    /// is resumed.
+    pub offset: u64,
+}
+
+pub(crate) fn write_header(writer: &mut dyn Write) -> Result<(), Error> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(VERSION.to_le_bytes().as_slice())?;
+    Ok(())
+}
+
+pub(crate) fn read_header(reader: &mut dyn Read) -> Result<(), Error> {
+    let mut header: [u8; 12] = [0; 12];
+    reader.read_exact(&mut header)?;
+    if &header[0..8] != MAGIC || header[8..12] != VERSION.to_le_bytes() {
+        return Err(InnerError::InvalidBackup.into());
+    }
+    Ok(())
+}
+
+/// Write a chunk, returning its length in bytes
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn write_chunk(writer: &mut dyn Write, records: &[OwnedRecord]) -> Result<u64, Error> {
+    let mut payload: Vec<u8> = vec![];
+    for record in records {
+        payload.extend_from_slice((record.as_bytes().len() as u32).to_le_bytes().as_slice());
+        payload.extend_from_slice(record.as_bytes());
+    }
+    writer.write_all((records.len() as u32).to_le_bytes().as_slice())?;
+    writer.write_all((payload.len() as u32).to_le_bytes().as_slice())?;
+    writer.write_all(&payload)?;
+    writer.write_all(blake3::hash(&payload).as_bytes())?;
+    Ok(CHUNK_OVERHEAD + payload.len() as u64)
+}
+
+pub(crate) fn write_end(writer: &mut dyn Write) -> Result<(), Error> {
+    writer.write_all(0_u32.to_le_bytes().as_slice())?;
+    Ok(())
+}
+
+fn read_u32(reader: &mut dyn Read) -> Result<u32, Error> {
+    let mut bytes: [u8; 4] = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read the next chunk and its length in bytes, or `None` at the end
+/// marker. Records are fully verified.
+///
+/// Each entry is read on its own and is at most `MAX_RECORD_LEN` bytes, so
+/// a corrupt or hostile length cannot force a large allocation.
+pub(crate) fn read_chunk(reader: &mut dyn Read) -> Result<Option<(Vec<OwnedRecord>, u64)>, Error> {
+    let count = read_u32(reader)? as usize;
+    if count == 0 {
+        return Ok(None);
+    }
+    let len = read_u32(reader)? as usize;
+    if count > crate::MAX_FILTER_LIMIT as usize || len > count * (4 + crate::MAX_RECORD_LEN) {
+        return Err(InnerError::InvalidBackup.into());
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    let mut remaining = len;
+    let mut entries: Vec<Vec<u8>> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut len_bytes: [u8; 4] = [0; 4];
+        reader.read_exact(&mut len_bytes)?;
+        let _ = hasher.update(&len_bytes);
+        let entry_len = u32::from_le_bytes(len_bytes) as usize;
+        if entry_len > crate::MAX_RECORD_LEN || 4 + entry_len > remaining {
+            return Err(InnerError::InvalidBackup.into());
+        }
+        remaining -= 4 + entry_len;
+        let mut bytes: Vec<u8> = vec![0; entry_len];
+        reader.read_exact(&mut bytes)?;
+        let _ = hasher.update(&bytes);
+        entries.push(bytes);
+    }
+    if remaining != 0 {
+        return Err(InnerError::InvalidBackup.into());
+    }
+    let mut hash: [u8; 32] = [0; 32];
+    reader.read_exact(&mut hash)?;
+    if hasher.finalize().as_bytes() != &hash {
+        return Err(InnerError::BackupChecksumMismatch.into());
+    }
+
+    let records = entries
+        .into_iter()
+        .map(OwnedRecord::from_vec)
+        .collect::<Result<Vec<OwnedRecord>, Error>>()?;
+    Ok(Some((records, CHUNK_OVERHEAD + len as u64)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, MemoryStore, RecordFlags, RecordParts, RecordStore, SecretKey, Timestamp};
+
+    #[test]
+    fn test_backup_restore() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let mut store = MemoryStore::new();
+        for millis in [1000, 2000, 3000] {
+            let record = OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap();
+            let _ = store.put(&record).unwrap();
+        }
+
+        let mut backup: Vec<u8> = vec![];
+        let mut progress = BackupProgress::default();
+        store.backup(&mut backup, &mut progress).unwrap();
+        assert_eq!(progress.chunks, 1);
+        assert_eq!(progress.records, 3);
+        assert_eq!(progress.offset, backup.len() as u64 - 4);
+
+        // Resuming after the last complete chunk writes only the end marker
+        let mut resumed = backup.clone();
+        resumed.truncate(usize::try_from(progress.offset).unwrap());
+        let mut resumed_progress = progress;
+        store.backup(&mut resumed, &mut resumed_progress).unwrap();
+        assert_eq!(resumed, backup);
+        assert_eq!(resumed_progress, progress);
+
+        let mut restored = MemoryStore::new();
+        let mut restore_progress = BackupProgress::default();
+        restored
+            .restore(&mut backup.as_slice(), &mut restore_progress)
+            .unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restore_progress, progress);
+
+        // Resuming skips chunks already restored
+        let mut empty = MemoryStore::new();
+        empty
+            .restore(&mut backup.as_slice(), &mut progress)
+            .unwrap();
+        assert!(empty.is_empty());
+
+        // Corruption is detected
+        backup[40] ^= 1;
+        let mut progress = BackupProgress::default();
+        assert!(MemoryStore::new()
+            .restore(&mut backup.as_slice(), &mut progress)
+            .is_err());
+
+        // Oversized entry lengths are refused before anything is allocated
+        let mut hostile: Vec<u8> = vec![];
+        write_header(&mut hostile).unwrap();
+        for n in [1, u32::MAX, u32::MAX] {
+            hostile.extend_from_slice(n.to_le_bytes().as_slice());
+        }
+        let err = MemoryStore::new()
+            .restore(&mut hostile.as_slice(), &mut BackupProgress::default())
+            .unwrap_err();
+        assert!(matches!(err.inner, InnerError::InvalidBackup));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{Error, PublicKey, Resolver, ServerBootstrap, Timestamp, UserBootstrap};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    value: Option<T>,
+    fetched: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Freshness {
+    Fresh,
+    Stale,
+    Expired,
+}
+
+/// A cache of resolved `ServerBootstrap` and `UserBootstrap` records, so
+/// that clients do not look the same keys up on the DHT over and over.
+///
+/// An entry is served from the cache for `ttl` after it was resolved. For
+/// a further `stale` period it is still served, but its key is queued for
+/// revalidation; `refresh()` re-resolves the queued keys, typically from a
+/// background task of the caller's runtime. After that the entry has
+/// expired and the next lookup resolves it again before answering.
+///
+/// Keys with no bootstrap published are cached too, as `None`.
+///
+/// Lookups take the current time so that callers (and tests) control the
+/// clock; pass `Timestamp::now()`.
+#[derive(Debug, Clone)]
+pub struct BootstrapCache {
+    servers: HashMap<PublicKey, Entry<ServerBootstrap>>,
+    users: HashMap<PublicKey, Entry<UserBootstrap>>,
+    stale_servers: HashSet<PublicKey>,
+    stale_users: HashSet<PublicKey>,
+    ttl: Duration,
+    stale: Duration,
+}
+
+impl BootstrapCache {
+    /// Create an empty `BootstrapCache` whose entries are fresh for `ttl`
+    /// and then served stale for up to `stale` while being revalidated
+    #[must_use]
+    pub fn new(ttl: Duration, stale: Duration) -> BootstrapCache {
+        BootstrapCache {
+            servers: HashMap::new(),
+            users: HashMap::new(),
+            stale_servers: HashSet::new(),
+            stale_users: HashSet::new(),
+            ttl,
+            stale,
+        }
+    }
+
+    fn freshness(&self, fetched: Timestamp, now: Timestamp) -> Freshness {
+        let age = u128::from(now.as_millis().saturating_sub(fetched.as_millis()));
+        if age < self.ttl.as_millis() {
+            Freshness::Fresh
+        } else if age < self.ttl.as_millis() + self.stale.as_millis() {
+            Freshness::Stale
+        } else {
+            Freshness::Expired
+        }
+    }
+
+    /// Look up the `ServerBootstrap` for a server, from the cache if it
+    /// has not expired and from `resolver` otherwise
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the entry had expired and resolving failed
+    pub async fn server<R: Resolver + ?Sized>(
+        &mut self,
+        resolver: &R,
+        public_key: PublicKey,
+        now: Timestamp,
+    ) -> Result<Option<ServerBootstrap>, Error> {
+        if let Some(entry) = self.servers.get(&public_key) {
+            match self.freshness(entry.fetched, now) {
+                Freshness::Fresh => return Ok(entry.value.clone()),
+                Freshness::Stale => {
+                    let value = entry.value.clone();
+                    let _ = self.stale_servers.insert(public_key);
+                    return Ok(value);
+                }
+                Freshness::Expired => {}
+            }
+        }
+
+        let value = resolver.resolve_server(public_key).await?;
+        let _ = self.stale_servers.remove(&public_key);
+        let _ = self.servers.insert(
+            public_key,
+            Entry {
+                value: value.clone(),
+                fetched: now,
+            },
+        );
+        Ok(value)
+    }
+
+    /// Look up the `UserBootstrap` for a user, from the cache if it has
+    /// not expired and from `resolver` otherwise
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the entry had expired and resolving failed
+    pub async fn user<R: Resolver + ?Sized>(
+        &mut self,
+        resolver: &R,
+        public_key: PublicKey,
+        now: Timestamp,
+    ) -> Result<Option<UserBootstrap>, Error> {
+        if let Some(entry) = self.users.get(&public_key) {
+            match self.freshness(entry.fetched, now) {
+                Freshness::Fresh => return Ok(entry.value.clone()),
+                Freshness::Stale => {
+                    let value = entry.value.clone();
+                    let _ = self.stale_users.insert(public_key);
+                    return Ok(value);
+                }
+                Freshness::Expired => {}
+            }
+        }
+
+        let value = resolver.resolve_user(public_key).await?;
+        let _ = self.stale_users.remove(&public_key);
+        let _ = self.users.insert(
+            public_key,
+            Entry {
+                value: value.clone(),
+                fetched: now,
+            },
+        );
+        Ok(value)
+    }
+
+    /// Re-resolve every entry that has been served stale since the last
+    /// refresh, returning how many were updated.
+    ///
+    /// An entry that fails to resolve keeps its old value, and will be
+    /// resolved again on lookup once it expires.
+    pub async fn refresh<R: Resolver + ?Sized>(&mut self, resolver: &R, now: Timestamp) -> usize {
+        let mut refreshed = 0;
+        for public_key in std::mem::take(&mut self.stale_servers) {
+            if let Ok(value) = resolver.resolve_server(public_key).await {
+                let _ = self.servers.insert(
+                    public_key,
+                    Entry {
+                        value,
+                        fetched: now,
+                    },
+                );
+                refreshed += 1;
+            }
+        }
+        for public_key in std::mem::take(&mut self.stale_users) {
+            if let Ok(value) = resolver.resolve_user(public_key).await {
+                let _ = self.users.insert(
+                    public_key,
+                    Entry {
+                        value,
+                        fetched: now,
+                    },
+                );
+                refreshed += 1;
+            }
+        }
+        refreshed
+    }
+
+    /// Forget anything cached for a public key, so that the next lookup
+    /// resolves it again
+    pub fn invalidate(&mut self, public_key: &PublicKey) {
+        let _ = self.servers.remove(public_key);
+        let _ = self.users.remove(public_key);
+        let _ = self.stale_servers.remove(public_key);
+        let _ = self.stale_users.remove(public_key);
+    }
+
+    /// Number of cached entries, servers and users together
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.servers.len() + self.users.len()
+    }
+
+    /// Is the cache empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty() && self.users.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MemoryResolver, SecretKey};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    // Drive a future that never waits, without any runtime
+    fn now_or_never<F: Future>(future: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future was not ready"),
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_cache() {
+        use rand::rngs::OsRng;
+
+        let server_key = SecretKey::generate(&mut OsRng).public();
+        let user_key = SecretKey::generate(&mut OsRng).public();
+        let first = ServerBootstrap::from_dht_string_and_seq("S\nwss://one.example", 1).unwrap();
+        let second = ServerBootstrap::from_dht_string_and_seq("S\nwss://two.example", 2).unwrap();
+        let at = |secs: u64| Timestamp::from_millis(secs * 1000).unwrap();
+
+        let mut resolver = MemoryResolver::new();
+        resolver.insert_server(server_key, first.clone());
+        let mut cache = BootstrapCache::new(Duration::from_mins(1), Duration::from_mins(1));
+
+        let found = now_or_never(cache.server(&resolver, server_key, at(0))).unwrap();
+        assert_eq!(found, Some(first.clone()));
+        let found = now_or_never(cache.user(&resolver, user_key, at(0))).unwrap();
+        assert!(found.is_none());
+        assert_eq!(cache.len(), 2);
+
+        // While fresh, the cached value is served and nothing is queued
+        resolver.insert_server(server_key, second.clone());
+        let found = now_or_never(cache.server(&resolver, server_key, at(30))).unwrap();
+        assert_eq!(found, Some(first.clone()));
+        assert_eq!(now_or_never(cache.refresh(&resolver, at(30))), 0);
+
+        // Stale values are served, then revalidated by refresh()
+        let found = now_or_never(cache.server(&resolver, server_key, at(90))).unwrap();
+        assert_eq!(found, Some(first));
+        assert_eq!(now_or_never(cache.refresh(&resolver, at(90))), 1);
+        let found = now_or_never(cache.server(&resolver, server_key, at(100))).unwrap();
+        assert_eq!(found, Some(second.clone()));
+
+        // Expired entries are resolved again before answering
+        let mut bootstrap = UserBootstrap::new();
+        bootstrap.append_server(crate::user_bootstrap::ServerUsage::OUTBOX, server_key);
+        resolver.insert_user(user_key, bootstrap.clone());
+        let found = now_or_never(cache.user(&resolver, user_key, at(200))).unwrap();
+        assert_eq!(found, Some(bootstrap));
+
+        cache.invalidate(&server_key);
+        assert_eq!(cache.len(), 1);
+        assert!(!cache.is_empty());
+    }
+}
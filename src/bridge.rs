@@ -0,0 +1,204 @@
+use crate::{Error, InnerError, Kind, OwnedRecord, Record, RecordFlags, SecretKey};
+use std::collections::HashMap;
+
+/// Converts Mosaic records into items of a foreign protocol `F` (such as an
+/// activity, a chat event or a feed item)
+pub trait ToForeign<F> {
+    /// Convert a record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record cannot be represented in `F`
+    fn to_foreign(&self, record: &Record) -> Result<F, Error>;
+}
+
+/// Converts items of a foreign protocol `F` into Mosaic records
+pub trait FromForeign<F> {
+    /// Does this converter handle this item?
+    fn accepts(&self, foreign: &F) -> bool;
+
+    /// Convert an item into a record signed with `secret_key`.
+    ///
+    /// Bridged records should be flagged `NO_BRIDGE` so that they are not
+    /// bridged back out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the item cannot be represented as a record
+    fn to_record(&self, foreign: &F, secret_key: &SecretKey) -> Result<OwnedRecord, Error>;
+}
+
+/// A registry of converters between Mosaic records and a foreign protocol
+/// `F`.
+///
+/// Bridges register one `ToForeign` converter per `Kind`, and any number of
+/// `FromForeign` converters which are tried in registration order. The
+/// registry refuses to bridge out records flagged `NO_BRIDGE`.
+pub struct ConverterRegistry<F> {
+    to: HashMap<Kind, Box<dyn ToForeign<F> + Send + Sync>>,
+    from: Vec<Box<dyn FromForeign<F> + Send + Sync>>,
+}
+
+impl<F> Default for ConverterRegistry<F> {
+    fn default() -> Self {
+        ConverterRegistry {
+            to: HashMap::new(),
+            from: Vec::new(),
+        }
+    }
+}
+
+impl<F> std::fmt::Debug for ConverterRegistry<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConverterRegistry")
+            .field("to", &self.to.keys().collect::<Vec<_>>())
+            .field("from", &self.from.len())
+            .finish()
+    }
+}
+
+impl<F> ConverterRegistry<F> {
+    /// Create an empty `ConverterRegistry`
+    #[must_use]
+    pub fn new() -> ConverterRegistry<F> {
+        ConverterRegistry::default()
+    }
+
+    /// Register the converter for records of `kind`, replacing any
+    /// previous one
+    pub fn register_to<C>(&mut self, kind: Kind, converter: C)
+    where
+        C: ToForeign<F> + Send + Sync + 'static,
+    {
+        let _ = self.to.insert(kind, Box::new(converter));
+    }
+
+    /// Register a converter for foreign items
+    pub fn register_from<C>(&mut self, converter: C)
+    where
+        C: FromForeign<F> + Send + Sync + 'static,
+    {
+        self.from.push(Box::new(converter));
+    }
+
+    /// Is there a converter for records of `kind`?
+    #[must_use]
+    pub fn handles_kind(&self, kind: Kind) -> bool {
+        self.to.contains_key(&kind)
+    }
+
+    /// Convert a record with the converter registered for its kind
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is flagged `NO_BRIDGE`, if no
+    /// converter is registered for its kind, or if conversion fails
+    pub fn to_foreign(&self, record: &Record) -> Result<F, Error> {
+        if record.flags().contains(RecordFlags::NO_BRIDGE) {
+            return Err(InnerError::NoConverter.into());
+        }
+        let converter = self
+            .to
+            .get(&record.kind())
+            .ok_or_else(|| InnerError::NoConverter.into_err())?;
+        converter.to_foreign(record)
+    }
+
+    /// Convert a foreign item with the first converter that accepts it
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if no converter accepts the item, or if conversion
+    /// fails
+    pub fn to_record(&self, foreign: &F, secret_key: &SecretKey) -> Result<OwnedRecord, Error> {
+        let converter = self
+            .from
+            .iter()
+            .find(|c| c.accepts(foreign))
+            .ok_or_else(|| InnerError::NoConverter.into_err())?;
+        converter.to_record(foreign, secret_key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RecordParts, Timestamp};
+
+    // A toy feed item
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        title: String,
+        published_ms: u64,
+    }
+
+    struct BlogToItem;
+
+    impl ToForeign<Item> for BlogToItem {
+        fn to_foreign(&self, record: &Record) -> Result<Item, Error> {
+            Ok(Item {
+                title: std::str::from_utf8(record.payload_bytes())?.to_owned(),
+                published_ms: record.timestamp().as_millis(),
+            })
+        }
+    }
+
+    struct ItemToBlog;
+
+    impl FromForeign<Item> for ItemToBlog {
+        fn accepts(&self, _foreign: &Item) -> bool {
+            true
+        }
+
+        fn to_record(&self, item: &Item, secret_key: &SecretKey) -> Result<OwnedRecord, Error> {
+            OwnedRecord::new(
+                secret_key,
+                &RecordParts {
+                    kind: Kind::BLOG_POST,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(item.published_ms).unwrap(),
+                    flags: RecordFlags::PRINTABLE | RecordFlags::NO_BRIDGE,
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: item.title.as_bytes(),
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_converter_registry() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let mut registry: ConverterRegistry<Item> = ConverterRegistry::new();
+        registry.register_to(Kind::BLOG_POST, BlogToItem);
+        registry.register_from(ItemToBlog);
+
+        let item = Item {
+            title: "Hello".to_owned(),
+            published_ms: 1000,
+        };
+        let record = registry.to_record(&item, &secret_key).unwrap();
+        assert_eq!(record.payload_bytes(), b"Hello");
+
+        // Bridged-in records are not bridged back out
+        assert!(registry.to_foreign(&record).is_err());
+
+        let native = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::BLOG_POST,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"Hello",
+            },
+        )
+        .unwrap();
+        assert_eq!(registry.to_foreign(&native).unwrap(), item);
+        assert!(!registry.handles_kind(Kind::CHAT_MESSAGE));
+    }
+}
@@ -0,0 +1,399 @@
+//! A conformance suite for other implementations of the Mosaic
+//! specification.
+//!
+//! Implement [`Callbacks`] over the implementation under test (typically by
+//! calling into it over FFI or a subprocess) and pass it to [`run`]. Each
+//! case is computed with this crate and compared with the result of the
+//! callbacks, and every disagreement is reported as a [`Failure`].
+//!
+//! Only records with a deterministic address are signed, so that both
+//! sides produce byte-identical output.
+
+use crate::{
+    test_vectors, Kind, OwnedRecord, OwnedTag, PublicKey, Record, RecordFlags, RecordParts,
+    Reference, SecretKey, Timestamp,
+};
+
+/// The fields of a record as parsed by the implementation under test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedRecord {
+    /// The record `Id`
+    pub id: [u8; 48],
+
+    /// The record `Address`
+    pub address: [u8; 48],
+
+    /// The author's public key
+    pub author_public_key: [u8; 32],
+
+    /// The signing public key
+    pub signing_public_key: [u8; 32],
+
+    /// The kind
+    pub kind: u16,
+
+    /// The timestamp in milliseconds since the UNIX epoch
+    pub timestamp_ms: u64,
+
+    /// The flags
+    pub flags: u16,
+
+    /// The application flags
+    pub app_flags: u16,
+
+    /// The tags, unpadded
+    pub tags: Vec<u8>,
+
+    /// The payload, unpadded
+    pub payload: Vec<u8>,
+}
+
+impl ParsedRecord {
+    fn from_record(record: &Record) -> ParsedRecord {
+        ParsedRecord {
+            id: *record.id().as_bytes(),
+            address: *record.address().as_bytes(),
+            author_public_key: record.author_public_key().to_bytes(),
+            signing_public_key: record.signing_public_key().to_bytes(),
+            kind: record.kind().as_u16(),
+            timestamp_ms: record.timestamp().as_millis(),
+            flags: record.flags().bits(),
+            app_flags: record.app_flags(),
+            tags: record.tags_bytes().to_vec(),
+            payload: record.payload_bytes().to_vec(),
+        }
+    }
+}
+
+/// Hooks into the implementation under test.
+///
+/// Methods return `None` where the implementation refuses the input.
+pub trait Callbacks {
+    /// Derive the public key of a secret key
+    fn public_key(&self, secret_key: &[u8; 32]) -> Option<[u8; 32]>;
+
+    /// Build and sign a record from its parts
+    fn sign(&self, secret_key: &[u8; 32], parts: &RecordParts<'_>) -> Option<Vec<u8>>;
+
+    /// Parse a record without verifying it
+    fn parse(&self, record: &[u8]) -> Option<ParsedRecord>;
+
+    /// Fully verify a record, including its hash and signature
+    fn verify(&self, record: &[u8]) -> bool;
+
+    /// Encode a public key in its printable `mopub0` form
+    fn encode_public_key(&self, public_key: &[u8; 32]) -> String;
+
+    /// Decode a public key from its printable form
+    fn decode_public_key(&self, printable: &str) -> Option<[u8; 32]>;
+
+    /// Encode an `Id` or `Address` in its printable `moref0` form
+    fn encode_reference(&self, reference: &[u8; 48]) -> String;
+
+    /// Decode an `Id` or `Address` from its printable form
+    fn decode_reference(&self, printable: &str) -> Option<[u8; 48]>;
+}
+
+/// A case the implementation under test got wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The name of the case
+    pub case: String,
+
+    /// What was expected and what was received
+    pub detail: String,
+}
+
+/// The outcome of a conformance run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of cases that passed
+    pub passed: usize,
+
+    /// The cases that failed
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Did every case pass?
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn check<T: PartialEq + std::fmt::Debug>(&mut self, case: &str, expected: &T, actual: &T) {
+        if expected == actual {
+            self.passed += 1;
+        } else {
+            self.failures.push(Failure {
+                case: case.to_owned(),
+                detail: format!("expected {expected:?}, got {actual:?}"),
+            });
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} passed, {} failed", self.passed, self.failures.len())?;
+        for failure in &self.failures {
+            writeln!(f, "{}: {}", failure.case, failure.detail)?;
+        }
+        Ok(())
+    }
+}
+
+// Payload lengths that exercise the padding boundaries
+const PAYLOAD_LENGTHS: [usize; 6] = [0, 1, 7, 8, 9, 1000];
+
+/// Run the conformance suite against an implementation
+#[must_use]
+pub fn run<C: Callbacks + ?Sized>(callbacks: &C) -> Report {
+    let mut report = Report::default();
+
+    let secret_keys: Vec<SecretKey> = std::iter::once(test_vectors::SECRET_KEY)
+        .chain((1..4).map(|n| [n; 32]))
+        .map(|bytes| SecretKey::from_bytes(&bytes))
+        .collect();
+
+    for (i, secret_key) in secret_keys.iter().enumerate() {
+        let public_key = secret_key.public();
+        report.check(
+            &format!("public_key[{i}]"),
+            &Some(public_key.to_bytes()),
+            &callbacks.public_key(secret_key.as_bytes()),
+        );
+        run_encoding(callbacks, &mut report, i, public_key);
+    }
+
+    let tag = OwnedTag::new_notify_public_key(&secret_keys[1].public());
+    let mut n = 0;
+    for secret_key in &secret_keys {
+        for len in PAYLOAD_LENGTHS {
+            let Some(timestamp) = Timestamp::from_millis(1_700_000_000_000 + len as u64) else {
+                continue;
+            };
+            let payload: Vec<u8> = (0..len).map(|i| i.to_le_bytes()[0]).collect();
+            let tags_bytes: &[u8] = if len % 2 == 0 { tag.as_bytes() } else { b"" };
+            let parts = RecordParts {
+                kind: if len % 2 == 0 {
+                    Kind::MICROBLOG_ROOT
+                } else {
+                    Kind::BLOG_POST
+                },
+                deterministic_key: Some(b"conformance"),
+                timestamp,
+                flags: RecordFlags::empty(),
+                app_flags: u16::try_from(len).unwrap_or(u16::MAX),
+                tags_bytes,
+                payload: &payload,
+            };
+            let Ok(record) = OwnedRecord::new(secret_key, &parts) else {
+                continue;
+            };
+            run_record(callbacks, &mut report, n, secret_key, &parts, &record);
+            n += 1;
+        }
+    }
+
+    // The published vector
+    report.check(
+        "vector.verify",
+        &true,
+        &callbacks.verify(&test_vectors::RECORD),
+    );
+    // SAFETY: the published vector is a valid record
+    if let Ok(record) = unsafe { Record::from_bytes(&test_vectors::RECORD) } {
+        report.check(
+            "vector.parse",
+            &Some(ParsedRecord::from_record(record)),
+            &callbacks.parse(&test_vectors::RECORD),
+        );
+    }
+
+    report
+}
+
+fn run_encoding<C: Callbacks + ?Sized>(
+    callbacks: &C,
+    report: &mut Report,
+    i: usize,
+    public_key: PublicKey,
+) {
+    let printable = public_key.printable();
+    report.check(
+        &format!("encode_public_key[{i}]"),
+        &printable,
+        &callbacks.encode_public_key(public_key.as_bytes()),
+    );
+    report.check(
+        &format!("decode_public_key[{i}]"),
+        &Some(public_key.to_bytes()),
+        &callbacks.decode_public_key(&printable),
+    );
+    report.check(
+        &format!("decode_public_key[{i}].invalid"),
+        &None,
+        &callbacks.decode_public_key(&printable.replacen("mopub0", "mopub1", 1)),
+    );
+}
+
+fn run_record<C: Callbacks + ?Sized>(
+    callbacks: &C,
+    report: &mut Report,
+    n: usize,
+    secret_key: &SecretKey,
+    parts: &RecordParts<'_>,
+    record: &Record,
+) {
+    let bytes = record.as_bytes();
+
+    report.check(
+        &format!("sign[{n}]"),
+        &Some(bytes.to_vec()),
+        &callbacks.sign(secret_key.as_bytes(), parts),
+    );
+    report.check(&format!("verify[{n}]"), &true, &callbacks.verify(bytes));
+    report.check(
+        &format!("parse[{n}]"),
+        &Some(ParsedRecord::from_record(record)),
+        &callbacks.parse(bytes),
+    );
+
+    // Tampering with the signature, the header or the payload, or
+    // truncating the record, must be detected
+    let tampered_offsets = [("signature", 0), ("header", 180), ("body", bytes.len() - 1)];
+    for (what, offset) in tampered_offsets {
+        let mut tampered = bytes.to_vec();
+        tampered[offset] ^= 0x01;
+        report.check(
+            &format!("verify[{n}].tampered_{what}"),
+            &false,
+            &callbacks.verify(&tampered),
+        );
+    }
+    report.check(
+        &format!("verify[{n}].truncated"),
+        &false,
+        &callbacks.verify(&bytes[..bytes.len() - 8]),
+    );
+
+    for (what, bytes) in [
+        ("id", record.id().as_bytes()),
+        ("address", record.address().as_bytes()),
+    ] {
+        let Ok(reference) = Reference::from_bytes(bytes) else {
+            continue;
+        };
+        let printable = reference.printable();
+        report.check(
+            &format!("encode_reference[{n}].{what}"),
+            &printable,
+            &callbacks.encode_reference(reference.as_bytes()),
+        );
+        report.check(
+            &format!("decode_reference[{n}].{what}"),
+            &Some(*reference.as_bytes()),
+            &callbacks.decode_reference(&printable),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // This crate checked against itself
+    struct Native;
+
+    impl Callbacks for Native {
+        fn public_key(&self, secret_key: &[u8; 32]) -> Option<[u8; 32]> {
+            Some(SecretKey::from_bytes(secret_key).public().to_bytes())
+        }
+
+        fn sign(&self, secret_key: &[u8; 32], parts: &RecordParts<'_>) -> Option<Vec<u8>> {
+            let record = OwnedRecord::new(&SecretKey::from_bytes(secret_key), parts).ok()?;
+            Some(record.as_bytes().to_vec())
+        }
+
+        fn parse(&self, record: &[u8]) -> Option<ParsedRecord> {
+            let record = unsafe { Record::from_bytes(record) }.ok()?;
+            Some(ParsedRecord::from_record(record))
+        }
+
+        fn verify(&self, record: &[u8]) -> bool {
+            Record::from_bytes_verified(record).is_ok()
+        }
+
+        fn encode_public_key(&self, public_key: &[u8; 32]) -> String {
+            PublicKey::from_bytes(public_key).unwrap().printable()
+        }
+
+        fn decode_public_key(&self, printable: &str) -> Option<[u8; 32]> {
+            PublicKey::from_printable(printable)
+                .ok()
+                .map(PublicKey::to_bytes)
+        }
+
+        fn encode_reference(&self, reference: &[u8; 48]) -> String {
+            Reference::from_bytes(reference).unwrap().printable()
+        }
+
+        fn decode_reference(&self, printable: &str) -> Option<[u8; 48]> {
+            Reference::from_printable(printable)
+                .ok()
+                .map(|r| *r.as_bytes())
+        }
+    }
+
+    // An implementation that accepts everything
+    struct Lax;
+
+    impl Callbacks for Lax {
+        fn public_key(&self, secret_key: &[u8; 32]) -> Option<[u8; 32]> {
+            Native.public_key(secret_key)
+        }
+
+        fn sign(&self, secret_key: &[u8; 32], parts: &RecordParts<'_>) -> Option<Vec<u8>> {
+            Native.sign(secret_key, parts)
+        }
+
+        fn parse(&self, record: &[u8]) -> Option<ParsedRecord> {
+            Native.parse(record)
+        }
+
+        fn verify(&self, _record: &[u8]) -> bool {
+            true
+        }
+
+        fn encode_public_key(&self, public_key: &[u8; 32]) -> String {
+            Native.encode_public_key(public_key)
+        }
+
+        fn decode_public_key(&self, printable: &str) -> Option<[u8; 32]> {
+            Native.decode_public_key(printable)
+        }
+
+        fn encode_reference(&self, reference: &[u8; 48]) -> String {
+            Native.encode_reference(reference)
+        }
+
+        fn decode_reference(&self, printable: &str) -> Option<[u8; 48]> {
+            Native.decode_reference(printable)
+        }
+    }
+
+    #[test]
+    fn test_conformance() {
+        let report = run(&Native);
+        assert!(report.is_success(), "{report}");
+        assert!(report.passed > 100);
+
+        let report = run(&Lax);
+        assert!(!report.is_success());
+        assert!(report
+            .failures
+            .iter()
+            .all(|f| f.case.starts_with("verify[") && f.case.contains(".t")));
+    }
+}
@@ -1,6 +1,23 @@
+//! Key agreement and payload encryption between Mosaic identities.
+//!
+//! Identities convert to X25519 keys with `PublicKey::to_encryption_key()`
+//! and `SecretKey::to_encryption_secret()`. With the `seal` feature,
+//! payloads are encrypted from one identity to another with `seal()` and
+//! decrypted with `open()`, or padded to hide their length as well with
+//! `seal_padded()` and `open_padded()`.
+
+use crate::{Error, InnerError};
+use curve25519_dalek::montgomery::MontgomeryPoint;
 use digest::generic_array::typenum::U64;
 use digest::generic_array::GenericArray;
 use digest::{FixedOutput, HashMarker, OutputSizeUser, Reset, Update};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+#[cfg(feature = "seal")]
+use {
+    crate::{unpad_payload, PaddingPolicy, PublicKey, SecretKey},
+    hpke::{Deserializable, OpModeR, OpModeS, Serializable},
+    rand_core::{CryptoRng, OsRng, RngCore},
+};
 
 /// This is a Blake3 Hasher implementing the traits required for use in
 /// the ed25519 construction in place of SHA-512
@@ -44,3 +61,450 @@ impl OutputSizeUser for Blake3 {
 }
 
 impl HashMarker for Blake3 {}
+
+/// A prehash that has already been computed, for signing it with
+/// ed25519-dalek. Updating it does nothing.
+#[derive(Clone)]
+pub(crate) struct Prehash(pub(crate) [u8; 64]);
+
+impl Default for Prehash {
+    #[inline]
+    fn default() -> Self {
+        Prehash([0; 64])
+    }
+}
+
+impl Update for Prehash {
+    #[inline]
+    fn update(&mut self, _data: &[u8]) {}
+}
+
+impl Reset for Prehash {
+    #[inline]
+    fn reset(&mut self) {}
+}
+
+impl FixedOutput for Prehash {
+    #[inline]
+    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        out.copy_from_slice(&self.0);
+    }
+}
+
+impl OutputSizeUser for Prehash {
+    type OutputSize = U64;
+}
+
+impl HashMarker for Prehash {}
+
+/// An X25519 public key for encrypting to a Mosaic identity.
+///
+/// Get one from an identity with `PublicKey::to_encryption_key()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncryptionPublicKey([u8; 32]);
+
+impl EncryptionPublicKey {
+    /// Get as bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Create from bytes. Every 32 bytes are an X25519 public key, though
+    /// agreement with a low order point fails.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8; 32]) -> EncryptionPublicKey {
+        EncryptionPublicKey(*bytes)
+    }
+
+    pub(crate) fn from_montgomery(point: MontgomeryPoint) -> EncryptionPublicKey {
+        EncryptionPublicKey(point.to_bytes())
+    }
+}
+
+/// An X25519 secret for decrypting what was encrypted to a Mosaic
+/// identity, or an ephemeral secret for encrypting.
+///
+/// Get one from an identity with `SecretKey::to_encryption_secret()`, the
+/// counterpart of `PublicKey::to_encryption_key()`. The bytes are zeroed
+/// on drop.
+#[derive(Clone)]
+pub struct EncryptionSecret([u8; 32]);
+
+impl EncryptionSecret {
+    /// Generate a random `EncryptionSecret`, such as an ephemeral one
+    pub fn generate<R: rand_core::CryptoRngCore + ?Sized>(csprng: &mut R) -> EncryptionSecret {
+        let mut bytes: [u8; 32] = [0; 32];
+        csprng.fill_bytes(&mut bytes);
+        EncryptionSecret(bytes)
+    }
+
+    /// Create from bytes. These are clamped when used, as X25519 requires.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8; 32]) -> EncryptionSecret {
+        EncryptionSecret(*bytes)
+    }
+
+    /// View the secret bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The `EncryptionPublicKey` of this secret
+    #[must_use]
+    pub fn public_key(&self) -> EncryptionPublicKey {
+        EncryptionPublicKey::from_montgomery(MontgomeryPoint::mul_base_clamped(self.0))
+    }
+
+    /// Agree on a `SharedSecret` with the holder of `public_key` (X25519)
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `public_key` is a low order point, which would
+    /// make the shared secret all zeroes whatever this secret is
+    pub fn agree(&self, public_key: &EncryptionPublicKey) -> Result<SharedSecret, Error> {
+        let shared = MontgomeryPoint(public_key.0).mul_clamped(self.0).to_bytes();
+        if shared == [0; 32] {
+            return Err(InnerError::WeakSharedSecret.into());
+        }
+        Ok(SharedSecret(shared))
+    }
+}
+
+impl Drop for EncryptionSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for EncryptionSecret {}
+
+impl std::fmt::Debug for EncryptionSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EncryptionSecret")
+            .field(&"<redacted>")
+            .finish()
+    }
+}
+
+/// The secret two parties agree on with `EncryptionSecret::agree()`.
+///
+/// Use it as keying material for a KDF, not directly as a key. The bytes
+/// are zeroed on drop.
+pub struct SharedSecret([u8; 32]);
+
+impl SharedSecret {
+    /// View the secret bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Drop for SharedSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SharedSecret {}
+
+impl std::fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedSecret").field(&"<redacted>").finish()
+    }
+}
+
+#[cfg(feature = "seal")]
+const SEAL_VERSION: u8 = 1;
+#[cfg(feature = "seal")]
+const SEAL_INFO: &[u8] = b"Mosaic 2025 sealed payload v1";
+
+// DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, ChaCha20-Poly1305
+#[cfg(feature = "seal")]
+type SealKem = hpke::kem::X25519HkdfSha256;
+#[cfg(feature = "seal")]
+type SealKdf = hpke::kdf::HkdfSha256;
+#[cfg(feature = "seal")]
+type SealAead = hpke::aead::ChaCha20Poly1305;
+
+#[cfg(feature = "seal")]
+const SEAL_ENCAPPED_LEN: usize = 32;
+#[cfg(feature = "seal")]
+const SEAL_TAG_LEN: usize = 16;
+
+/// The bytes a sealed payload adds to the plaintext: a version byte, an
+/// ephemeral public key and an authentication tag
+#[cfg(feature = "seal")]
+pub const SEAL_OVERHEAD: usize = 1 + SEAL_ENCAPPED_LEN + SEAL_TAG_LEN;
+
+// The HPKE key pair of an identity
+#[cfg(feature = "seal")]
+fn hpke_keypair(
+    secret_key: &SecretKey,
+) -> (
+    <SealKem as hpke::Kem>::PrivateKey,
+    <SealKem as hpke::Kem>::PublicKey,
+) {
+    let secret = secret_key.to_encryption_secret();
+    let private = <SealKem as hpke::Kem>::PrivateKey::from_bytes(secret.as_bytes())
+        .expect("32 bytes is an X25519 private key");
+    let public = <SealKem as hpke::Kem>::sk_to_pk(&private);
+    (private, public)
+}
+
+// The HPKE public key of an identity
+#[cfg(feature = "seal")]
+fn hpke_public_key(public_key: &PublicKey) -> <SealKem as hpke::Kem>::PublicKey {
+    <SealKem as hpke::Kem>::PublicKey::from_bytes(public_key.to_encryption_key().as_bytes())
+        .expect("32 bytes is an X25519 public key")
+}
+
+/// Encrypt a payload from `sender` to `recipient`, producing bytes to use
+/// as a record payload.
+///
+/// This is HPKE (RFC 9180) in auth mode, with the suite
+/// DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 and ChaCha20-Poly1305. Each
+/// payload is sealed with a fresh ephemeral key, and the sender's identity
+/// (converted as by `SecretKey::to_encryption_secret()`) authenticates it,
+/// so only `recipient` can open it and only `sender` could have sealed it.
+///
+/// The layout is a version byte, the 32-byte ephemeral public key, and the
+/// ciphertext with its 16-byte tag. The version byte is authenticated too.
+/// The output is `SEAL_OVERHEAD` bytes longer than the payload, so use
+/// `seal_padded()` instead if its length matters.
+///
+/// # Errors
+///
+/// Returns an `Err` if `recipient` is a low order point
+#[cfg(feature = "seal")]
+pub fn seal(payload: &[u8], sender: &SecretKey, recipient: &PublicKey) -> Result<Vec<u8>, Error> {
+    seal_with_rng(&mut OsRng, payload, sender, recipient)
+}
+
+/// Encrypt a payload as `seal()` does, drawing the ephemeral key from
+/// `rng`
+///
+/// # Errors
+///
+/// Returns an `Err` if `recipient` is a low order point
+#[cfg(feature = "seal")]
+pub fn seal_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    mut rng: &mut R,
+    payload: &[u8],
+    sender: &SecretKey,
+    recipient: &PublicKey,
+) -> Result<Vec<u8>, Error> {
+    let (encapped, ciphertext) = hpke::single_shot_seal::<SealAead, SealKdf, SealKem, _>(
+        &OpModeS::Auth(hpke_keypair(sender)),
+        &hpke_public_key(recipient),
+        SEAL_INFO,
+        payload,
+        &[SEAL_VERSION],
+        &mut rng,
+    )
+    .map_err(|_| InnerError::WeakSharedSecret.into_err())?;
+
+    let mut sealed = Vec::with_capacity(1 + SEAL_ENCAPPED_LEN + ciphertext.len());
+    sealed.push(SEAL_VERSION);
+    sealed.extend_from_slice(&encapped.to_bytes());
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt a payload sealed by `sender` to `recipient` with `seal()`
+///
+/// # Errors
+///
+/// Returns an `Err` if the sealed payload is malformed or of an unknown
+/// version, or if it fails authentication: it was altered, or was not
+/// sealed by `sender` to `recipient`.
+#[cfg(feature = "seal")]
+pub fn open(sealed: &[u8], recipient: &SecretKey, sender: &PublicKey) -> Result<Vec<u8>, Error> {
+    if sealed.len() < SEAL_OVERHEAD {
+        return Err(InnerError::InvalidSealedPayload.into());
+    }
+    if sealed[0] != SEAL_VERSION {
+        return Err(InnerError::UnsupportedSealVersion(sealed[0]).into());
+    }
+    let (encapped, ciphertext) = sealed[1..].split_at(SEAL_ENCAPPED_LEN);
+    let encapped = <SealKem as hpke::Kem>::EncappedKey::from_bytes(encapped)
+        .map_err(|_| InnerError::InvalidSealedPayload.into_err())?;
+
+    let (private, _) = hpke_keypair(recipient);
+    hpke::single_shot_open::<SealAead, SealKdf, SealKem>(
+        &OpModeR::Auth(hpke_public_key(sender)),
+        &private,
+        &encapped,
+        SEAL_INFO,
+        ciphertext,
+        &sealed[..1],
+    )
+    .map_err(|_| InnerError::DecryptionFailed.into())
+}
+
+/// Pad a payload with `padding` and then encrypt it from `sender` to
+/// `recipient` as `seal()` does, so that the record carrying it does not
+/// reveal the length of the plaintext. The padding is inside the
+/// encryption, and is removed by `open_padded()`.
+///
+/// # Errors
+///
+/// Returns an `Err` if `recipient` is a low order point
+#[cfg(feature = "seal")]
+pub fn seal_padded(
+    payload: &[u8],
+    padding: &PaddingPolicy,
+    sender: &SecretKey,
+    recipient: &PublicKey,
+) -> Result<Vec<u8>, Error> {
+    seal_padded_with_rng(&mut OsRng, payload, padding, sender, recipient)
+}
+
+/// Pad and encrypt a payload as `seal_padded()` does, drawing the
+/// ephemeral key from `rng`
+///
+/// # Errors
+///
+/// Returns an `Err` if `recipient` is a low order point
+#[cfg(feature = "seal")]
+pub fn seal_padded_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    payload: &[u8],
+    padding: &PaddingPolicy,
+    sender: &SecretKey,
+    recipient: &PublicKey,
+) -> Result<Vec<u8>, Error> {
+    seal_with_rng(rng, &padding.pad(payload), sender, recipient)
+}
+
+/// Decrypt a payload sealed with `seal_padded()` and remove its padding
+///
+/// # Errors
+///
+/// Returns an `Err` if `open()` would, or if the plaintext is not padded
+#[cfg(feature = "seal")]
+pub fn open_padded(
+    sealed: &[u8],
+    recipient: &SecretKey,
+    sender: &PublicKey,
+) -> Result<Vec<u8>, Error> {
+    let mut payload = open(sealed, recipient, sender)?;
+    let len = unpad_payload(&payload)?.len();
+    payload.truncate(len);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SecretKey;
+
+    #[test]
+    fn test_encryption_keys() {
+        use rand::rngs::OsRng;
+
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+
+        // The converted secret matches the converted public key
+        let alice_secret = alice.to_encryption_secret();
+        let alice_key = alice.public().to_encryption_key();
+        assert_eq!(alice_secret.public_key(), alice_key);
+
+        // Both sides agree, ephemeral secrets included
+        let bob_secret = bob.to_encryption_secret();
+        let bob_key = bob.public().to_encryption_key();
+        let ab = alice_secret.agree(&bob_key).unwrap();
+        let ba = bob_secret.agree(&alice_key).unwrap();
+        assert_eq!(ab.as_bytes(), ba.as_bytes());
+        let ephemeral = EncryptionSecret::generate(&mut OsRng);
+        assert_eq!(
+            ephemeral.agree(&bob_key).unwrap().as_bytes(),
+            bob_secret
+                .agree(&ephemeral.public_key())
+                .unwrap()
+                .as_bytes()
+        );
+
+        // Low order points are refused
+        let err = alice_secret
+            .agree(&EncryptionPublicKey::from_bytes(&[0; 32]))
+            .unwrap_err();
+        assert!(err.is_crypto_failure());
+    }
+
+    #[cfg(feature = "seal")]
+    #[test]
+    fn test_seal() {
+        use rand::rngs::OsRng;
+
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+        let eve = SecretKey::generate(&mut OsRng);
+        let payload = vec![0x5A; 200];
+
+        let sealed = seal(&payload, &alice, &bob.public()).unwrap();
+        assert_eq!(sealed.len(), payload.len() + SEAL_OVERHEAD);
+        assert_eq!(sealed[0], SEAL_VERSION);
+        assert!(!sealed.windows(16).any(|w| w == &payload[..16]));
+        assert_eq!(open(&sealed, &bob, &alice.public()).unwrap(), payload);
+
+        // Ephemeral keys differ, so sealing twice gives different bytes
+        assert_ne!(seal(&payload, &alice, &bob.public()).unwrap(), sealed);
+        assert!(open(
+            &seal(b"", &alice, &bob.public()).unwrap(),
+            &bob,
+            &alice.public()
+        )
+        .unwrap()
+        .is_empty());
+
+        // Wrong parties and tampering are detected
+        let err = open(&sealed, &eve, &alice.public()).unwrap_err();
+        assert!(err.is_crypto_failure());
+        assert!(open(&sealed, &bob, &eve.public()).is_err());
+        for i in [0, 1, 30, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 1;
+            assert!(open(&bad, &bob, &alice.public()).is_err());
+        }
+        assert!(open(&sealed[..SEAL_OVERHEAD - 1], &bob, &alice.public()).is_err());
+        let mut future = sealed.clone();
+        future[0] = 2;
+        assert_eq!(
+            open(&future, &bob, &alice.public()).unwrap_err().kind(),
+            crate::ErrorKind::Unsupported
+        );
+        assert_eq!(err.kind(), crate::ErrorKind::Decryption);
+    }
+
+    #[cfg(feature = "seal")]
+    #[test]
+    fn test_seal_padded() {
+        use rand::rngs::OsRng;
+
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+        let padding = PaddingPolicy::Buckets(vec![64, 256]);
+
+        // Different lengths seal to the same length
+        let short = seal_padded(b"hi", &padding, &alice, &bob.public()).unwrap();
+        let long = seal_padded(&[7; 60], &padding, &alice, &bob.public()).unwrap();
+        assert_eq!(short.len(), 64 + SEAL_OVERHEAD);
+        assert_eq!(long.len(), short.len());
+        assert_eq!(open_padded(&short, &bob, &alice.public()).unwrap(), b"hi");
+        assert_eq!(open_padded(&long, &bob, &alice.public()).unwrap(), [7; 60]);
+
+        // The padding is inside the encryption
+        assert_eq!(
+            open(&short, &bob, &alice.public()).unwrap(),
+            padding.pad(b"hi")
+        );
+
+        // An unpadded plaintext is rejected
+        let unpadded = seal(b"hi", &alice, &bob.public()).unwrap();
+        assert!(open_padded(&unpadded, &bob, &alice.public()).is_err());
+    }
+}
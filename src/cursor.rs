@@ -0,0 +1,143 @@
+use crate::{Filter, Id, OwnedRecord};
+
+/// The order in which paged results are returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Newest first (descending timestamp, then descending `Id`)
+    #[default]
+    NewestFirst,
+
+    /// Oldest first (ascending timestamp, then ascending `Id`)
+    OldestFirst,
+}
+
+/// A position in a paged query.
+///
+/// Results are ordered by (timestamp, `Id`), which is simply `Id` order
+/// since `Id`s begin with their timestamp. A cursor remembers the `Id` of
+/// the last record returned, so a query can be resumed from the next
+/// record even if records were added in the meantime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    direction: Direction,
+    after: Option<Id>,
+}
+
+impl Cursor {
+    /// A cursor at the start of the results in the given direction
+    #[must_use]
+    pub fn start(direction: Direction) -> Cursor {
+        Cursor {
+            direction,
+            after: None,
+        }
+    }
+
+    /// A cursor positioned just past the given `Id` in the given direction
+    #[must_use]
+    pub fn after(direction: Direction, id: Id) -> Cursor {
+        Cursor {
+            direction,
+            after: Some(id),
+        }
+    }
+
+    /// The direction of this cursor
+    #[must_use]
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The `Id` this cursor is positioned after, if any
+    #[must_use]
+    pub fn last_id(&self) -> Option<Id> {
+        self.after
+    }
+
+    /// Is the `Id` beyond this cursor's position (and so not yet returned)?
+    #[must_use]
+    pub fn admits(&self, id: &Id) -> bool {
+        match (self.direction, &self.after) {
+            (_, None) => true,
+            (Direction::NewestFirst, Some(after)) => id < after,
+            (Direction::OldestFirst, Some(after)) => id > after,
+        }
+    }
+}
+
+/// A page of query results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// The records in this page, in the cursor's direction
+    pub records: Vec<OwnedRecord>,
+
+    /// A cursor for the next page, or `None` if there are no more results
+    pub next: Option<Cursor>,
+}
+
+impl Page {
+    // Build a page from records already in cursor order and truncated to the
+    // filter's limit. A full page may be followed by more results; anything
+    // shorter is the last page.
+    pub(crate) fn new(records: Vec<OwnedRecord>, filter: &Filter, direction: Direction) -> Page {
+        let next = match (filter.limit, records.last()) {
+            (Some(limit), Some(last)) if records.len() >= limit as usize => {
+                Some(Cursor::after(direction, last.id()))
+            }
+            _ => None,
+        };
+        Page { records, next }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, MemoryStore, RecordFlags, RecordParts, RecordStore, SecretKey, Timestamp};
+
+    #[test]
+    fn test_cursor_paging() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let mut store = MemoryStore::new();
+        let mut ids: Vec<Id> = vec![];
+        for i in 1..=5 {
+            let record = OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(i * 1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap();
+            ids.push(record.id());
+            let _ = store.put(&record).unwrap();
+        }
+
+        let mut filter = Filter::new();
+        filter.limit = Some(2);
+
+        for direction in [Direction::NewestFirst, Direction::OldestFirst] {
+            let mut seen: Vec<Id> = vec![];
+            let mut cursor = Some(Cursor::start(direction));
+            while let Some(c) = cursor {
+                let page = store.page(&filter, &c).unwrap();
+                seen.extend(page.records.iter().map(|r| r.id()));
+                cursor = page.next;
+            }
+            let mut expected = ids.clone();
+            if direction == Direction::NewestFirst {
+                expected.reverse();
+            }
+            assert_eq!(seen, expected);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{Error, InnerError, Record, RecordFlags};
+#[cfg(feature = "zstd")]
+use std::borrow::Cow;
+use std::io::Read;
+
+/// How many bytes are read from a decoder at a time
+const CHUNK_LEN: usize = 16 * 1024;
+
+/// Read everything from `reader` into `buffer`, failing once more than
+/// `max_len` bytes have been produced.
+///
+/// `buffer` is cleared first and its allocation is reused, so one buffer
+/// can serve many calls. It never grows much beyond `max_len`, however
+/// much the reader would produce.
+///
+/// # Errors
+///
+/// Returns an `Err` if the reader fails, or if it produces more than
+/// `max_len` bytes
+pub fn read_bounded<R: Read>(
+    mut reader: R,
+    max_len: usize,
+    buffer: &mut Vec<u8>,
+) -> Result<(), Error> {
+    buffer.clear();
+    loop {
+        let start = buffer.len();
+        // Read one byte beyond the limit to detect overflow
+        buffer.resize(
+            start + CHUNK_LEN.min((max_len - start).saturating_add(1)),
+            0,
+        );
+        let result = reader.read(&mut buffer[start..]);
+        let n = *result.as_ref().unwrap_or(&0);
+        buffer.truncate(start + n);
+        match result {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+        if buffer.len() > max_len {
+            return Err(InnerError::DecompressedTooLong.into());
+        }
+    }
+}
+
+/// Decompress a record's payload into `buffer`, producing at most
+/// `max_len` bytes, and return the uncompressed payload.
+///
+/// A compressed payload that is small on the wire can expand enormously,
+/// so the limit is enforced while decompressing rather than afterwards.
+///
+/// `decoder` wraps the compressed bytes in a streaming Zstd decoder, for
+/// example `|bytes| zstd::Decoder::new(bytes)`. With the `zstd` feature,
+/// `Record::payload_decompressed()` does this with the bundled decoder. If
+/// the record does not have the `ZSTD` flag, the decoder is not used and
+/// the payload is returned as it is.
+///
+/// # Errors
+///
+/// Returns an `Err` if the decoder fails, or if the uncompressed payload
+/// would be longer than `max_len`
+pub fn decompress_payload<'a, 'r, D, F>(
+    record: &'r Record,
+    max_len: usize,
+    buffer: &'a mut Vec<u8>,
+    decoder: F,
+) -> Result<&'a [u8], Error>
+where
+    'r: 'a,
+    D: Read,
+    F: FnOnce(&'r [u8]) -> std::io::Result<D>,
+{
+    let payload = record.payload_bytes();
+    if !record.flags().contains(RecordFlags::ZSTD) {
+        if payload.len() > max_len {
+            return Err(InnerError::DecompressedTooLong.into());
+        }
+        return Ok(payload);
+    }
+    read_bounded(decoder(payload)?, max_len, buffer)?;
+    Ok(buffer.as_slice())
+}
+
+#[cfg(feature = "zstd")]
+pub(crate) fn payload_decompressed(
+    record: &Record,
+    max_len: usize,
+) -> Result<Cow<'_, [u8]>, Error> {
+    if !record.flags().contains(RecordFlags::ZSTD) {
+        return Ok(Cow::Borrowed(record.payload_bytes()));
+    }
+    let mut buffer = Vec::new();
+    read_bounded(
+        zstd::stream::read::Decoder::new(record.payload_bytes())?,
+        max_len,
+        &mut buffer,
+    )?;
+    Ok(Cow::Owned(buffer))
+}
+
+/// `payload` compressed with Zstd, or `None` if that would not make the
+/// record any shorter.
+///
+/// Records are never compressed behind the caller's back. To send a
+/// compressed payload, put the returned bytes in the `RecordParts` and set
+/// the `ZSTD` flag (or use `RecordBuilder::compress_payload()`), so that
+/// `record_len()`, `predicted_id()` and signing all see the same bytes.
+///
+/// # Errors
+///
+/// Returns an `Err` if compression fails
+#[cfg(feature = "zstd")]
+pub fn compress_payload(payload: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let compressed = zstd::bulk::compress(payload, 0)?;
+    // Only worth it if the record gets shorter after padding
+    if (compressed.len() + 7) & !7 >= (payload.len() + 7) & !7 {
+        return Ok(None);
+    }
+    Ok(Some(compressed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedRecord, RecordParts, SecretKey, Timestamp};
+    #[cfg(feature = "zstd")]
+    use std::borrow::Cow;
+
+    // Stands in for a decoder that expands its input a great deal
+    struct Expand<'a>(&'a [u8], usize);
+
+    impl Read for Expand<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.1);
+            for (i, b) in buf[..n].iter_mut().enumerate() {
+                *b = self.0[i % self.0.len()];
+            }
+            self.1 -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_decompress_payload() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let make = |flags: RecordFlags| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(1000).unwrap(),
+                    flags,
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"ab",
+                },
+            )
+            .unwrap()
+        };
+
+        let mut buffer = Vec::new();
+
+        // Uncompressed payloads are returned as they are
+        let plain = make(RecordFlags::empty());
+        let payload = decompress_payload(&plain, 100, &mut buffer, |_| -> std::io::Result<&[u8]> {
+            panic!("not compressed")
+        })
+        .unwrap();
+        assert_eq!(payload, b"ab");
+
+        // Within the limit
+        let compressed = make(RecordFlags::ZSTD);
+        let payload =
+            decompress_payload(&compressed, 100_000, &mut buffer, |b| Ok(Expand(b, 40_000)))
+                .unwrap();
+        assert_eq!(payload.len(), 40_000);
+        assert_eq!(&payload[..4], b"abab");
+
+        // Exactly at the limit
+        let payload =
+            decompress_payload(&compressed, 40_000, &mut buffer, |b| Ok(Expand(b, 40_000)))
+                .unwrap();
+        assert_eq!(payload.len(), 40_000);
+
+        // A bomb is stopped without producing all of its output
+        let err = decompress_payload(&compressed, 40_000, &mut buffer, |b| {
+            Ok(Expand(b, usize::MAX))
+        })
+        .unwrap_err();
+        assert!(matches!(err.inner, InnerError::DecompressedTooLong));
+        assert!(buffer.len() <= 40_001);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_records() {
+        use crate::{Record, RecordBuilder};
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let mut builder =
+            RecordBuilder::new(Kind::MICROBLOG_ROOT, Timestamp::from_millis(1000).unwrap());
+        let _ = builder
+            .set_deterministic_key(b"compressed")
+            .set_payload(vec![b'x'; 1000]);
+
+        // Not compressed unless asked for
+        let plain = builder.sign(&secret_key).unwrap();
+        assert!(!plain.flags().contains(RecordFlags::ZSTD));
+        assert_eq!(plain.payload_len(), 1000);
+
+        // Once compressed, the predicted length and id match the record,
+        // however it is signed
+        let _ = builder.compress_payload().unwrap();
+        assert!(builder.parts().flags.contains(RecordFlags::ZSTD));
+        let predicted_id = builder
+            .parts()
+            .predicted_id(&secret_key.public())
+            .unwrap()
+            .unwrap();
+        let record = builder.sign(&secret_key).unwrap();
+        assert_eq!(record.as_bytes().len(), builder.record_len());
+        assert_eq!(record.id(), predicted_id);
+        let unsigned = Record::prepare_unsigned(secret_key.public(), &builder.parts()).unwrap();
+        assert_eq!(unsigned.id(), predicted_id);
+        assert!(record.payload_len() < 100);
+        let payload = record.payload_decompressed(1000).unwrap();
+        assert!(matches!(payload, Cow::Owned(_)));
+        assert_eq!(&*payload, &[b'x'; 1000][..]);
+        assert!(record.payload_decompressed(999).is_err());
+
+        // Compressing again leaves the payload alone
+        let _ = builder.compress_payload().unwrap();
+        assert_eq!(builder.sign(&secret_key).unwrap(), record);
+
+        // Left alone when it does not help
+        assert!(compress_payload(b"hello").unwrap().is_none());
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+        let payload = record.payload_decompressed(1000).unwrap();
+        assert!(matches!(payload, Cow::Borrowed(b"hello")));
+    }
+}
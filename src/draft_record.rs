@@ -0,0 +1,103 @@
+use crate::{Error, Kind, OwnedRecord, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+/// The unsigned parts of a record, owning their buffers.
+///
+/// Clients keep drafts and compose queues as `DraftRecord`s and sign them
+/// later with `sign()`, possibly with a different device key than the one
+/// in use when the draft was written.
+///
+/// With the `serde` feature, a `DraftRecord` can be serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DraftRecord {
+    /// The kind of record
+    pub kind: Kind,
+
+    /// Optionally a deterministic key for the Address
+    pub deterministic_key: Option<Vec<u8>>,
+
+    /// The time
+    pub timestamp: Timestamp,
+
+    /// The flags
+    pub flags: RecordFlags,
+
+    /// Application flags
+    pub app_flags: u16,
+
+    /// The tags
+    pub tags_bytes: Vec<u8>,
+
+    /// The payload
+    pub payload: Vec<u8>,
+}
+
+impl DraftRecord {
+    /// Borrow this draft as `RecordParts`
+    #[must_use]
+    pub fn as_parts(&self) -> RecordParts<'_> {
+        RecordParts {
+            kind: self.kind,
+            deterministic_key: self.deterministic_key.as_deref(),
+            timestamp: self.timestamp,
+            flags: self.flags,
+            app_flags: self.app_flags,
+            tags_bytes: &self.tags_bytes,
+            payload: &self.payload,
+        }
+    }
+
+    /// Sign this draft, creating a record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the parts are invalid or if signing fails
+    pub fn sign(&self, secret_key: &SecretKey) -> Result<OwnedRecord, Error> {
+        OwnedRecord::new(secret_key, &self.as_parts())
+    }
+}
+
+impl From<&RecordParts<'_>> for DraftRecord {
+    fn from(parts: &RecordParts<'_>) -> DraftRecord {
+        DraftRecord {
+            kind: parts.kind,
+            deterministic_key: parts.deterministic_key.map(<[u8]>::to_vec),
+            timestamp: parts.timestamp,
+            flags: parts.flags,
+            app_flags: parts.app_flags,
+            tags_bytes: parts.tags_bytes.to_vec(),
+            payload: parts.payload.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draft_record_sign() {
+        use rand::rngs::OsRng;
+
+        let draft = DraftRecord::from(&RecordParts {
+            kind: Kind::MICROBLOG_ROOT,
+            deterministic_key: None,
+            timestamp: Timestamp::from_millis(1000).unwrap(),
+            flags: RecordFlags::PRINTABLE,
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: b"written on the train",
+        });
+
+        // Sign with whichever device key is at hand
+        let laptop = SecretKey::generate(&mut OsRng);
+        let record = draft.sign(&laptop).unwrap();
+        record.verify().unwrap();
+        assert_eq!(record.payload_bytes(), draft.payload.as_slice());
+        assert_eq!(record.timestamp(), draft.timestamp);
+
+        let phone = SecretKey::generate(&mut OsRng);
+        let record = draft.sign(&phone).unwrap();
+        assert_eq!(record.signing_public_key(), phone.public());
+    }
+}
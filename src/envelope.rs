@@ -0,0 +1,100 @@
+use crate::{OwnedRecord, PublicKey, Timestamp};
+use std::ops::Deref;
+
+/// A record together with where and when it was received.
+///
+/// Relays and clients can keep provenance alongside a verified record
+/// without maintaining side tables. An `Envelope` dereferences to the
+/// record it carries.
+///
+/// With the `serde` feature, an `Envelope<OwnedRecord>` can be serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope<T = OwnedRecord> {
+    /// The record
+    pub record: T,
+
+    /// When the record was received
+    pub received_at: Timestamp,
+
+    /// The key of the server the record was received from, if any
+    pub source: Option<PublicKey>,
+
+    /// An application-defined identifier of the connection the record
+    /// arrived on, if any
+    pub connection_id: Option<u64>,
+}
+
+impl<T> Envelope<T> {
+    /// Wrap a record received at `received_at`
+    pub fn new(record: T, received_at: Timestamp) -> Envelope<T> {
+        Envelope {
+            record,
+            received_at,
+            source: None,
+            connection_id: None,
+        }
+    }
+
+    /// Set the server the record was received from
+    #[must_use]
+    pub fn with_source(mut self, source: PublicKey) -> Envelope<T> {
+        self.source = Some(source);
+        self
+    }
+
+    /// Set the connection the record arrived on
+    #[must_use]
+    pub fn with_connection_id(mut self, connection_id: u64) -> Envelope<T> {
+        self.connection_id = Some(connection_id);
+        self
+    }
+
+    /// Unwrap the record, discarding the transport metadata
+    pub fn into_inner(self) -> T {
+        self.record
+    }
+}
+
+impl<T> Deref for Envelope<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.record
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey};
+
+    #[test]
+    fn test_envelope() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let server_key = SecretKey::generate(&mut csprng).public();
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let envelope = Envelope::new(record.clone(), Timestamp::from_millis(2000).unwrap())
+            .with_source(server_key)
+            .with_connection_id(7);
+        assert_eq!(envelope.kind(), Kind::MICROBLOG_ROOT);
+        assert_eq!(envelope.source, Some(server_key));
+        assert_eq!(envelope.into_inner(), record);
+    }
+}
@@ -16,21 +16,225 @@ impl StdError for Error {
     }
 }
 
+impl Error {
+    /// The kind of this error, for callers that need to branch on the cause
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+
+    /// The stable numeric code of this error's kind
+    #[must_use]
+    pub fn code(&self) -> u16 {
+        self.kind().code()
+    }
+
+    /// Might the same operation succeed if tried again later?
+    ///
+    /// True for DHT writes and for transient I/O conditions such as
+    /// timeouts and interruptions.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        use std::io::ErrorKind as IoKind;
+
+        match &self.inner {
+            InnerError::DhtPutError => true,
+            InnerError::Io(e) => matches!(
+                e.kind(),
+                IoKind::Interrupted
+                    | IoKind::TimedOut
+                    | IoKind::WouldBlock
+                    | IoKind::ConnectionReset
+                    | IoKind::ConnectionAborted
+            ),
+            _ => false,
+        }
+    }
+
+    /// Was the input malformed?
+    ///
+    /// Such input will never succeed and should be dropped. This does not
+    /// include well-formed records that fail verification; see
+    /// `is_crypto_failure()`.
+    #[must_use]
+    pub fn is_invalid_input(&self) -> bool {
+        if matches!(
+            self.inner,
+            InnerError::SystemTime(_) | InnerError::TimestampInFuture
+        ) {
+            // The local clock, not the input
+            return false;
+        }
+        matches!(
+            self.kind(),
+            ErrorKind::EndOfInput
+                | ErrorKind::InvalidEncoding
+                | ErrorKind::InvalidFilter
+                | ErrorKind::InvalidTime
+                | ErrorKind::InvalidUri
+                | ErrorKind::LengthMismatch
+                | ErrorKind::ReservedFlags
+                | ErrorKind::ReservedSpace
+                | ErrorKind::TooLong
+                | ErrorKind::TooShort
+        )
+    }
+
+    /// Did a hash or signature fail to verify?
+    ///
+    /// A peer that sends such a record either corrupted it or forged it.
+    #[must_use]
+    pub fn is_crypto_failure(&self) -> bool {
+        matches!(
+            self.inner,
+            InnerError::DecryptionFailed
+                | InnerError::Ed25519(_)
+                | InnerError::HashMismatch
+                | InnerError::WeakSharedSecret
+        )
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}, {}", self.inner, self.location)
     }
 }
 
+/// The broad cause of an `Error`.
+///
+/// Unlike `InnerError`, which may gain variants with any release and carries
+/// details that depend on optional features, these kinds are stable and
+/// meant to be matched on. New kinds may still be added, so matches need a
+/// wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A signature did not verify, or a key is not a valid ed25519 key
+    BadSignature,
+
+    /// A DHT operation failed
+    Dht,
+
+    /// Encrypted data failed to authenticate, or key agreement failed
+    Decryption,
+
+    /// The input ended before a complete item was read
+    EndOfInput,
+
+    /// The output buffer is too small
+    EndOfOutput,
+
+    /// A hash or checksum does not match the data
+    HashMismatch,
+
+    /// Text or binary data is not in the expected encoding
+    InvalidEncoding,
+
+    /// A filter is out of bounds
+    InvalidFilter,
+
+    /// A time is out of range
+    InvalidTime,
+
+    /// A URI is malformed or uses an unsupported scheme
+    InvalidUri,
+
+    /// The lengths of a record's sections disagree with its size, or a key
+    /// or reference has the wrong length
+    LengthMismatch,
+
+    /// Reserved flags are set
+    ReservedFlags,
+
+    /// Reserved bytes are not zero
+    ReservedSpace,
+
+    /// Reading or writing storage failed
+    Storage,
+
+    /// A record or tag is too long
+    TooLong,
+
+    /// A record is too short
+    TooShort,
+
+    /// The operation is not supported for this record or item
+    Unsupported,
+
+    /// A record's signing key is not delegated by its author, has expired,
+    /// or has been revoked
+    UntrustedKey,
+
+    /// Any other error
+    Other,
+}
+
+// Codes are grouped by the tens: lengths and framing, encodings and
+// values, verification, support, and the environment. Codes below 100 are
+// left to transports for their own conditions (the FFI layer uses them for
+// null pointers, short buffers and panics). Never renumber a kind.
+const ERROR_CODES: &[(ErrorKind, u16)] = &[
+    (ErrorKind::Other, 100),
+    (ErrorKind::EndOfInput, 110),
+    (ErrorKind::EndOfOutput, 111),
+    (ErrorKind::TooLong, 112),
+    (ErrorKind::TooShort, 113),
+    (ErrorKind::LengthMismatch, 114),
+    (ErrorKind::InvalidEncoding, 120),
+    (ErrorKind::InvalidTime, 121),
+    (ErrorKind::InvalidUri, 122),
+    (ErrorKind::InvalidFilter, 123),
+    (ErrorKind::ReservedFlags, 124),
+    (ErrorKind::ReservedSpace, 125),
+    (ErrorKind::HashMismatch, 130),
+    (ErrorKind::BadSignature, 131),
+    (ErrorKind::UntrustedKey, 132),
+    (ErrorKind::Decryption, 133),
+    (ErrorKind::Unsupported, 140),
+    (ErrorKind::Storage, 150),
+    (ErrorKind::Dht, 151),
+];
+
+impl ErrorKind {
+    /// The stable numeric code of this kind, for the wire protocol and
+    /// foreign-language bindings. Codes are never reused or renumbered.
+    #[must_use]
+    pub fn code(self) -> u16 {
+        ERROR_CODES
+            .iter()
+            .find(|(kind, _)| *kind == self)
+            .map_or(100, |(_, code)| *code)
+    }
+
+    /// The kind with this numeric code, if any
+    #[must_use]
+    pub fn from_code(code: u16) -> Option<ErrorKind> {
+        ERROR_CODES
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(kind, _)| *kind)
+    }
+}
+
 /// Errors that can occur in this crate
 #[derive(Debug)]
 pub enum InnerError {
+    /// Backup chunk checksum mismatch
+    BackupChecksumMismatch,
+
     /// Unsupported URI scheme
     BadScheme(String),
 
     /// DHT put error
     DhtPutError,
 
+    /// Decompressed payload too long
+    DecompressedTooLong,
+
+    /// A sealed payload failed authentication
+    DecryptionFailed,
+
     /// DHT was shutdown
     DhtWasShutdown,
 
@@ -52,27 +256,116 @@ pub enum InnerError {
     /// General error
     General(String),
 
+    /// The timestamp in a record's ID does not match its timestamp
+    IdTimestampMismatch,
+
     /// ID zeroes are not zero
     IdZerosAreNotZero,
 
+    /// Invalid archive
+    InvalidArchive,
+
+    /// Invalid backup
+    InvalidBackup,
+
+    /// Invalid encrypted secret key
+    InvalidEncryptedKey,
+
+    /// Invalid filter encoding
+    InvalidFilterEncoding,
+
+    /// Invalid filter limit
+    InvalidFilterLimit,
+
+    /// Invalid filter time window
+    InvalidFilterTimeWindow,
+
+    /// Invalid identity claim
+    InvalidIdentityClaim,
+
+    /// Key derivation parameters out of bounds
+    InvalidKdfParams,
+
+    /// Invalid key schedule payload
+    InvalidKeySchedule,
+
+    /// Invalid protocol message
+    InvalidMessage,
+
+    /// Invalid BIP-39 mnemonic
+    #[cfg(feature = "bip39")]
+    InvalidMnemonic,
+
+    /// Invalid `mosaic://` URI
+    InvalidMosaicUri,
+
+    /// Payload padding is invalid
+    InvalidPadding,
+
     /// Invalid printable data
     InvalidPrintable,
 
+    /// Invalid abuse report
+    InvalidReport,
+
+    /// Invalid sealed payload framing
+    InvalidSealedPayload,
+
     /// Invalid `ServerBootstrap` String
     InvalidServerBootstrapString,
 
+    /// Invalid `ServerPolicy` String
+    InvalidServerPolicyString,
+
+    /// Invalid sync state
+    InvalidSyncState,
+
     /// Invalid `UserBootstrap` String
     InvalidUserBootstrapString,
 
     /// Invalid URI
+    #[cfg(feature = "uri")]
     InvalidUri(http::uri::InvalidUri),
 
     /// Invalid URI parts
+    #[cfg(feature = "uri")]
     InvalidUriParts(http::uri::InvalidUriParts),
 
+    /// Invalid zbase32 character
+    InvalidZBase32(u8),
+
+    /// I/O error
+    Io(std::io::Error),
+
+    /// A required tag is missing
+    MissingTag(crate::TagType),
+
     /// Missing scheme
     MissingScheme,
 
+    /// `MessagePack` decoding error
+    #[cfg(feature = "msgpack")]
+    MsgPackDecode(rmp_serde::decode::Error),
+
+    /// `MessagePack` encoding error
+    #[cfg(feature = "msgpack")]
+    MsgPackEncode(rmp_serde::encode::Error),
+
+    /// No converter is registered for this record or foreign item
+    NoConverter,
+
+    /// Cannot map between Mosaic and Nostr
+    #[cfg(feature = "nostr")]
+    NostrUnmappable(&'static str),
+
+    /// redb database error
+    #[cfg(feature = "redb")]
+    Redb(Box<redb::Error>),
+
+    /// LMDB (heed) database error
+    #[cfg(feature = "lmdb")]
+    Lmdb(Box<heed::Error>),
+
     /// Record section length mismatch
     RecordSectionLengthMismatch,
 
@@ -91,30 +384,78 @@ pub enum InnerError {
     /// Reserved space used
     ReservedSpaceUsed,
 
+    /// Seed is not 16 to 64 bytes long
+    SeedLength,
+
+    /// Signing key has expired
+    SigningKeyExpired,
+
+    /// Signing key is not delegated by the author
+    SigningKeyNotDelegated,
+
+    /// Signing key has been revoked
+    SigningKeyRevoked,
+
     /// Time error
     SystemTime(std::time::SystemTimeError),
 
+    /// Tag length byte does not match the tag data length
+    TagLength,
+
     /// Tag too long
     TagTooLong,
 
+    /// Record does not match its template
+    TemplateMismatch(&'static str),
+
+    /// A test vector does not match
+    TestVectorMismatch(&'static str),
+
     /// Time is beyond available leap second data
     TimeIsBeyondLeapSecondData,
 
     /// Time is out of range
     TimeOutOfRange,
 
+    /// Timestamp is too far in the future
+    TimestampInFuture,
+
+    /// Timestamp data length is not 6 bytes
+    TimestampLength,
+
+    /// Timestamp is too far in the past
+    TimestampTooOld,
+
+    /// Bytes remain after the end of a record
+    TrailingBytes(usize),
+
+    /// Unexpected record kind
+    UnexpectedKind(crate::Kind),
+
+    /// No record template by this name
+    UnknownTemplate(String),
+
+    /// Unsupported sealed payload version
+    UnsupportedSealVersion(u8),
+
+    /// Unsupported encrypted secret key version
+    UnsupportedEncryptedKeyVersion(u8),
+
     /// UTF-8 error
     Utf8(std::str::Utf8Error),
 
-    /// Z32 error
-    Z32(z32::Z32Error),
+    /// Key agreement produced an all-zero shared secret
+    WeakSharedSecret,
 }
 
 impl std::fmt::Display for InnerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            InnerError::BackupChecksumMismatch => write!(f, "Backup chunk checksum mismatch"),
             InnerError::BadScheme(s) => write!(f, "Unsupported URI scheme: {s}"),
             InnerError::DhtPutError => write!(f, "DHT put error"),
+            InnerError::DecompressedTooLong => write!(f, "Decompressed payload too long"),
+            InnerError::DecryptionFailed => write!(f, "Sealed payload failed authentication"),
             InnerError::DhtWasShutdown => write!(f, "DHT was shutdown"),
             InnerError::Ed25519(e) => write!(f, "ed25519 Error: {e}"),
             InnerError::EndOfInput => write!(f, "End of input"),
@@ -122,27 +463,89 @@ impl std::fmt::Display for InnerError {
             InnerError::HashMismatch => write!(f, "Hash mismatch"),
             InnerError::KeyLength => write!(f, "Key data length is not 32 bytes"),
             InnerError::General(s) => write!(f, "General Error: {s}"),
+            InnerError::IdTimestampMismatch => {
+                write!(f, "ID timestamp does not match the record timestamp")
+            }
             InnerError::IdZerosAreNotZero => write!(f, "ID zeroes are not zero"),
+            InnerError::InvalidArchive => write!(f, "Invalid archive"),
+            InnerError::InvalidBackup => write!(f, "Invalid backup"),
+            InnerError::InvalidEncryptedKey => write!(f, "Invalid encrypted secret key"),
+            InnerError::InvalidFilterEncoding => write!(f, "Invalid filter encoding"),
+            InnerError::InvalidFilterLimit => write!(f, "Invalid filter limit"),
+            InnerError::InvalidFilterTimeWindow => {
+                write!(f, "Invalid filter time window (since must be before until)")
+            }
+            InnerError::InvalidIdentityClaim => write!(f, "Invalid identity claim"),
+            InnerError::InvalidKdfParams => write!(f, "Key derivation parameters out of bounds"),
+            InnerError::InvalidKeySchedule => write!(f, "Invalid key schedule"),
+            InnerError::InvalidMessage => write!(f, "Invalid protocol message"),
+            #[cfg(feature = "bip39")]
+            InnerError::InvalidMnemonic => write!(f, "Invalid BIP-39 mnemonic"),
+            InnerError::InvalidMosaicUri => write!(f, "Invalid mosaic URI"),
+            InnerError::InvalidPadding => write!(f, "Payload padding is invalid"),
             InnerError::InvalidPrintable => write!(f, "Printable data is invalid"),
+            InnerError::InvalidReport => write!(f, "Invalid abuse report"),
+            InnerError::InvalidSealedPayload => write!(f, "Invalid sealed payload"),
             InnerError::InvalidServerBootstrapString => write!(f, "Invalid ServerBootstrap String"),
+            InnerError::InvalidServerPolicyString => write!(f, "Invalid ServerPolicy String"),
+            InnerError::InvalidSyncState => write!(f, "Invalid sync state"),
             InnerError::InvalidUserBootstrapString => write!(f, "Invalid UserBootstrap String"),
+            #[cfg(feature = "uri")]
             InnerError::InvalidUri(e) => write!(f, "Invalid URI: {e}"),
+            #[cfg(feature = "uri")]
             InnerError::InvalidUriParts(e) => write!(f, "Invalid URI parts: {e}"),
+            InnerError::InvalidZBase32(c) => write!(f, "Invalid zbase32 character: {c:#04x}"),
+            InnerError::Io(e) => write!(f, "I/O error: {e}"),
+            InnerError::MissingTag(t) => write!(f, "Missing tag of type {}", t.0),
             InnerError::MissingScheme => write!(f, "Missing scheme"),
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackDecode(e) => write!(f, "MessagePack decode error: {e}"),
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackEncode(e) => write!(f, "MessagePack encode error: {e}"),
+            InnerError::NoConverter => write!(f, "No converter for this record or item"),
+            #[cfg(feature = "nostr")]
+            InnerError::NostrUnmappable(s) => write!(f, "Cannot map between Mosaic and Nostr: {s}"),
+            #[cfg(feature = "redb")]
+            InnerError::Redb(e) => write!(f, "redb error: {e}"),
+            #[cfg(feature = "lmdb")]
+            InnerError::Lmdb(e) => write!(f, "LMDB error: {e}"),
             InnerError::RecordSectionLengthMismatch => write!(f, "Record section length mismatch"),
             InnerError::RecordTooLong => write!(f, "Record too long"),
             InnerError::RecordTooShort => write!(f, "Record too short"),
             InnerError::ReferenceLength => write!(f, "Reference data length is not 48 bytes"),
             InnerError::ReservedFlagsUsed => write!(f, "Reserved flags used"),
             InnerError::ReservedSpaceUsed => write!(f, "Reserved space used"),
+            InnerError::SeedLength => write!(f, "Seed is not 16 to 64 bytes long"),
+            InnerError::SigningKeyExpired => write!(f, "Signing key has expired"),
+            InnerError::SigningKeyNotDelegated => {
+                write!(f, "Signing key is not delegated by the author")
+            }
+            InnerError::SigningKeyRevoked => write!(f, "Signing key has been revoked"),
             InnerError::SystemTime(e) => write!(f, "Time Error: {e}"),
+            InnerError::TagLength => {
+                write!(f, "Tag length byte does not match the tag data length")
+            }
             InnerError::TagTooLong => write!(f, "Tag too long"),
+            InnerError::TemplateMismatch(s) => write!(f, "Record does not match template: {s}"),
+            InnerError::TestVectorMismatch(s) => write!(f, "Test vector does not match: {s}"),
             InnerError::TimeIsBeyondLeapSecondData => {
                 write!(f, "Time is beyond available leap second data")
             }
             InnerError::TimeOutOfRange => write!(f, "Time is out of range"),
+            InnerError::TimestampInFuture => write!(f, "Timestamp is too far in the future"),
+            InnerError::TimestampLength => write!(f, "Timestamp data length is not 6 bytes"),
+            InnerError::TimestampTooOld => write!(f, "Timestamp is too far in the past"),
+            InnerError::TrailingBytes(n) => write!(f, "{n} bytes remain after the record"),
+            InnerError::UnexpectedKind(k) => write!(f, "Unexpected record kind: {k}"),
+            InnerError::UnknownTemplate(s) => write!(f, "Unknown record template: {s}"),
+            InnerError::UnsupportedEncryptedKeyVersion(v) => {
+                write!(f, "Unsupported encrypted secret key version: {v}")
+            }
+            InnerError::UnsupportedSealVersion(v) => {
+                write!(f, "Unsupported sealed payload version: {v}")
+            }
             InnerError::Utf8(e) => write!(f, "UTF-8 error: {e}"),
-            InnerError::Z32(e) => write!(f, "zbase32 error: {e}"),
+            InnerError::WeakSharedSecret => write!(f, "Key agreement with a low order point"),
         }
     }
 }
@@ -151,8 +554,19 @@ impl StdError for InnerError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
             InnerError::Ed25519(e) => Some(e),
+            #[cfg(feature = "uri")]
             InnerError::InvalidUri(e) => Some(e),
+            InnerError::Io(e) => Some(e),
+            #[cfg(feature = "uri")]
             InnerError::InvalidUriParts(e) => Some(e),
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackDecode(e) => Some(e),
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackEncode(e) => Some(e),
+            #[cfg(feature = "redb")]
+            InnerError::Redb(e) => Some(e.as_ref()),
+            #[cfg(feature = "lmdb")]
+            InnerError::Lmdb(e) => Some(e.as_ref()),
             InnerError::SystemTime(e) => Some(e),
             InnerError::Utf8(e) => Some(e),
             _ => None,
@@ -160,6 +574,94 @@ impl StdError for InnerError {
     }
 }
 
+impl InnerError {
+    /// The kind of this error
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            InnerError::BackupChecksumMismatch | InnerError::HashMismatch => {
+                ErrorKind::HashMismatch
+            }
+            InnerError::BadScheme(_) | InnerError::InvalidMosaicUri | InnerError::MissingScheme => {
+                ErrorKind::InvalidUri
+            }
+            #[cfg(feature = "uri")]
+            InnerError::InvalidUri(_) | InnerError::InvalidUriParts(_) => ErrorKind::InvalidUri,
+            InnerError::DhtPutError | InnerError::DhtWasShutdown => ErrorKind::Dht,
+            InnerError::DecryptionFailed | InnerError::WeakSharedSecret => ErrorKind::Decryption,
+            InnerError::Ed25519(_) => ErrorKind::BadSignature,
+            InnerError::EndOfInput => ErrorKind::EndOfInput,
+            InnerError::EndOfOutput => ErrorKind::EndOfOutput,
+            InnerError::KeyLength
+            | InnerError::RecordSectionLengthMismatch
+            | InnerError::ReferenceLength
+            | InnerError::SeedLength
+            | InnerError::TagLength
+            | InnerError::TimestampLength
+            | InnerError::TrailingBytes(_) => ErrorKind::LengthMismatch,
+            InnerError::IdZerosAreNotZero | InnerError::ReservedSpaceUsed => {
+                ErrorKind::ReservedSpace
+            }
+            InnerError::InvalidArchive
+            | InnerError::InvalidBackup
+            | InnerError::InvalidFilterEncoding
+            | InnerError::InvalidEncryptedKey
+            | InnerError::InvalidIdentityClaim
+            | InnerError::InvalidKdfParams
+            | InnerError::InvalidKeySchedule
+            | InnerError::InvalidMessage
+            | InnerError::InvalidPadding
+            | InnerError::InvalidPrintable
+            | InnerError::InvalidReport
+            | InnerError::InvalidSealedPayload
+            | InnerError::MissingTag(_)
+            | InnerError::TemplateMismatch(_)
+            | InnerError::InvalidServerBootstrapString
+            | InnerError::InvalidServerPolicyString
+            | InnerError::InvalidSyncState
+            | InnerError::InvalidUserBootstrapString
+            | InnerError::Utf8(_)
+            | InnerError::InvalidZBase32(_) => ErrorKind::InvalidEncoding,
+            #[cfg(feature = "bip39")]
+            InnerError::InvalidMnemonic => ErrorKind::InvalidEncoding,
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackDecode(_) => ErrorKind::InvalidEncoding,
+            InnerError::InvalidFilterLimit | InnerError::InvalidFilterTimeWindow => {
+                ErrorKind::InvalidFilter
+            }
+            InnerError::Io(_) => ErrorKind::Storage,
+            #[cfg(feature = "redb")]
+            InnerError::Redb(_) => ErrorKind::Storage,
+            #[cfg(feature = "lmdb")]
+            InnerError::Lmdb(_) => ErrorKind::Storage,
+            InnerError::NoConverter
+            | InnerError::UnexpectedKind(_)
+            | InnerError::UnknownTemplate(_)
+            | InnerError::UnsupportedEncryptedKeyVersion(_)
+            | InnerError::UnsupportedSealVersion(_) => ErrorKind::Unsupported,
+            #[cfg(feature = "nostr")]
+            InnerError::NostrUnmappable(_) => ErrorKind::Unsupported,
+            InnerError::DecompressedTooLong
+            | InnerError::RecordTooLong
+            | InnerError::TagTooLong => ErrorKind::TooLong,
+            InnerError::RecordTooShort => ErrorKind::TooShort,
+            InnerError::ReservedFlagsUsed => ErrorKind::ReservedFlags,
+            InnerError::IdTimestampMismatch
+            | InnerError::SystemTime(_)
+            | InnerError::TimeIsBeyondLeapSecondData
+            | InnerError::TimeOutOfRange
+            | InnerError::TimestampInFuture
+            | InnerError::TimestampTooOld => ErrorKind::InvalidTime,
+            InnerError::SigningKeyExpired
+            | InnerError::SigningKeyNotDelegated
+            | InnerError::SigningKeyRevoked => ErrorKind::UntrustedKey,
+            InnerError::General(_) | InnerError::TestVectorMismatch(_) => ErrorKind::Other,
+            #[cfg(feature = "msgpack")]
+            InnerError::MsgPackEncode(_) => ErrorKind::Other,
+        }
+    }
+}
+
 // Note: we impl Into because our typical pattern is InnerError::Variant.into()
 //       when we tried implementing From, the location was deep in rust code's
 //       blanket into implementation, which wasn't the line number we wanted.
@@ -223,6 +725,7 @@ impl From<ed25519_dalek::ed25519::Error> for Error {
     }
 }
 
+#[cfg(feature = "uri")]
 impl From<http::uri::InvalidUri> for Error {
     #[track_caller]
     fn from(e: http::uri::InvalidUri) -> Error {
@@ -233,6 +736,7 @@ impl From<http::uri::InvalidUri> for Error {
     }
 }
 
+#[cfg(feature = "uri")]
 impl From<http::uri::InvalidUriParts> for Error {
     #[track_caller]
     fn from(e: http::uri::InvalidUriParts) -> Error {
@@ -243,6 +747,79 @@ impl From<http::uri::InvalidUriParts> for Error {
     }
 }
 
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::decode::Error> for Error {
+    #[track_caller]
+    fn from(e: rmp_serde::decode::Error) -> Error {
+        Error {
+            inner: InnerError::MsgPackDecode(e),
+            location: Location::caller(),
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for Error {
+    #[track_caller]
+    fn from(e: rmp_serde::encode::Error) -> Error {
+        Error {
+            inner: InnerError::MsgPackEncode(e),
+            location: Location::caller(),
+        }
+    }
+}
+
+#[cfg(feature = "redb")]
+macro_rules! impl_from_redb_error {
+    ($t:ty) => {
+        impl From<$t> for Error {
+            #[track_caller]
+            fn from(e: $t) -> Error {
+                Error {
+                    inner: InnerError::Redb(Box::new(e.into())),
+                    location: Location::caller(),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::Error);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::DatabaseError);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::TransactionError);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::TableError);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::StorageError);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::CommitError);
+#[cfg(feature = "redb")]
+impl_from_redb_error!(redb::CompactionError);
+
+#[cfg(feature = "lmdb")]
+impl From<heed::Error> for Error {
+    #[track_caller]
+    fn from(e: heed::Error) -> Error {
+        Error {
+            inner: InnerError::Lmdb(Box::new(e)),
+            location: Location::caller(),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[track_caller]
+    fn from(e: std::io::Error) -> Error {
+        Error {
+            inner: InnerError::Io(e),
+            location: Location::caller(),
+        }
+    }
+}
+
 impl From<std::time::SystemTimeError> for Error {
     #[track_caller]
     fn from(e: std::time::SystemTimeError) -> Error {
@@ -263,12 +840,46 @@ impl From<std::str::Utf8Error> for Error {
     }
 }
 
-impl From<z32::Z32Error> for Error {
-    #[track_caller]
-    fn from(e: z32::Z32Error) -> Error {
-        Error {
-            inner: InnerError::Z32(e),
-            location: Location::caller(),
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_vectors, Record};
+
+    #[test]
+    fn test_error_kind() {
+        let bytes = test_vectors::RECORD;
+        let err = unsafe { Record::from_bytes(&bytes[..100]) }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EndOfInput);
+
+        let mut tampered = bytes;
+        tampered[200] ^= 1;
+        let record = unsafe { Record::from_bytes(&tampered) }.unwrap();
+        assert_eq!(record.verify().unwrap_err().kind(), ErrorKind::HashMismatch);
+
+        let mut tampered = bytes;
+        tampered[0] ^= 1;
+        let record = unsafe { Record::from_bytes(&tampered) }.unwrap();
+        let err = record.verify().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadSignature);
+        assert!(err.is_crypto_failure());
+        assert!(!err.is_invalid_input());
+        assert!(!err.is_retryable());
+
+        let err: Error = std::io::Error::from(std::io::ErrorKind::TimedOut).into();
+        assert!(err.is_retryable());
+        assert!(InnerError::RecordTooLong.into_err().is_invalid_input());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut codes = std::collections::HashSet::new();
+        for (kind, code) in ERROR_CODES {
+            assert!(codes.insert(*code));
+            assert!(*code >= 100);
+            assert_eq!(kind.code(), *code);
+            assert_eq!(ErrorKind::from_code(*code), Some(*kind));
         }
+        assert_eq!(ErrorKind::HashMismatch.code(), 130);
+        assert_eq!(ErrorKind::from_code(0), None);
     }
 }
@@ -0,0 +1,509 @@
+//! A C ABI for embedding mosaic-core in C, C++, Swift and other languages.
+//!
+//! Build the crate with the `ffi` feature and a `cdylib` or `staticlib`
+//! crate type to get a library exporting these functions.
+//!
+//! # Errors
+//!
+//! Every fallible function returns a status code: `MOSAIC_OK` (zero) on
+//! success, one of the other `MOSAIC_*` constants (all below 100) for
+//! problems at the ABI boundary, or otherwise the stable code of the
+//! library error (see `Error::code()` and `ErrorKind::from_code()`), which
+//! is the same code used on the wire. Panics never cross the ABI; they are
+//! reported as `MOSAIC_ERR_PANIC`.
+//!
+//! # Ownership
+//!
+//! - Keys, ids and addresses are fixed-size byte arrays owned by the
+//!   caller: 32 bytes for keys, 48 bytes for ids and addresses.
+//! - Records are opaque `MosaicRecord` handles created by this library.
+//!   The caller owns each handle it is given and must release it exactly
+//!   once with `mosaic_record_free()`.
+//! - Pointers returned by field accessors (the record bytes, tags and
+//!   payload) borrow from the record handle and are valid until it is
+//!   freed.
+//! - Strings are written NUL-terminated into caller-provided buffers.
+//!
+//! Pointers passed in must be valid for the documented length and
+//! non-null unless stated otherwise.
+
+use crate::{
+    Error, ErrorKind, Kind, OwnedRecord, PublicKey, RecordFlags, RecordParts, SecretKey, Timestamp,
+};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Success
+pub const MOSAIC_OK: i32 = 0;
+
+/// A required pointer was null
+pub const MOSAIC_ERR_NULL_POINTER: i32 = 1;
+
+/// The caller's output buffer is too small
+pub const MOSAIC_ERR_BUFFER_TOO_SMALL: i32 = 4;
+
+/// A panic was caught at the ABI boundary
+pub const MOSAIC_ERR_PANIC: i32 = 6;
+
+/// An opaque record handle
+#[derive(Debug)]
+pub struct MosaicRecord(OwnedRecord);
+
+fn status(e: &Error) -> i32 {
+    i32::from(e.code())
+}
+
+fn kind_status(kind: ErrorKind) -> i32 {
+    i32::from(kind.code())
+}
+
+// Run `f`, converting its result and any panic into a status code
+fn guard<F: FnOnce() -> Result<(), i32>>(f: F) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => MOSAIC_OK,
+        Ok(Err(code)) => code,
+        Err(_) => MOSAIC_ERR_PANIC,
+    }
+}
+
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], i32> {
+    if len == 0 {
+        Ok(&[])
+    } else if ptr.is_null() {
+        Err(MOSAIC_ERR_NULL_POINTER)
+    } else {
+        Ok(std::slice::from_raw_parts(ptr, len))
+    }
+}
+
+unsafe fn array<'a, const N: usize>(ptr: *const u8) -> Result<&'a [u8; N], i32> {
+    if ptr.is_null() {
+        Err(MOSAIC_ERR_NULL_POINTER)
+    } else {
+        Ok(&*ptr.cast::<[u8; N]>())
+    }
+}
+
+unsafe fn write_array<const N: usize>(ptr: *mut u8, bytes: &[u8; N]) -> Result<(), i32> {
+    if ptr.is_null() {
+        return Err(MOSAIC_ERR_NULL_POINTER);
+    }
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, N);
+    Ok(())
+}
+
+unsafe fn get_record<'a>(handle: *const MosaicRecord) -> Result<&'a OwnedRecord, i32> {
+    if handle.is_null() {
+        Err(MOSAIC_ERR_NULL_POINTER)
+    } else {
+        Ok(&(*handle).0)
+    }
+}
+
+/// Generate a new secret key into the 32-byte buffer `out_secret_key`.
+///
+/// # Safety
+///
+/// `out_secret_key` must be valid for writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_secret_key_generate(out_secret_key: *mut u8) -> i32 {
+    guard(|| {
+        let secret_key = SecretKey::generate(&mut rand_core::OsRng);
+        write_array(out_secret_key, secret_key.as_bytes())
+    })
+}
+
+/// Compute the 32-byte public key of a 32-byte secret key.
+///
+/// # Safety
+///
+/// `secret_key` must be valid for reads of 32 bytes and `out_public_key`
+/// for writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_secret_key_public(
+    secret_key: *const u8,
+    out_public_key: *mut u8,
+) -> i32 {
+    guard(|| {
+        let secret_key = SecretKey::from_bytes(array(secret_key)?);
+        write_array(out_public_key, secret_key.public().as_bytes())
+    })
+}
+
+/// Write the printable form of a 32-byte public key, NUL-terminated, into
+/// `out` of capacity `out_len`. Returns `MOSAIC_ERR_BUFFER_TOO_SMALL` if it
+/// does not fit.
+///
+/// # Safety
+///
+/// `public_key` must be valid for reads of 32 bytes and `out` for writes of
+/// `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_public_key_printable(
+    public_key: *const u8,
+    out: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    guard(|| {
+        let public_key = PublicKey::from_bytes(array(public_key)?).map_err(|e| status(&e))?;
+        let printable = public_key.printable();
+        if out.is_null() {
+            return Err(MOSAIC_ERR_NULL_POINTER);
+        }
+        if printable.len() >= out_len {
+            return Err(MOSAIC_ERR_BUFFER_TOO_SMALL);
+        }
+        std::ptr::copy_nonoverlapping(printable.as_ptr(), out.cast::<u8>(), printable.len());
+        *out.add(printable.len()) = 0;
+        Ok(())
+    })
+}
+
+/// Create and sign a new record, storing a new handle in `out_record`.
+///
+/// `deterministic_key` may be null (with a zero length) for a random
+/// address. `tags` and `payload` may be null if their length is zero.
+///
+/// # Safety
+///
+/// `secret_key` must be valid for reads of 32 bytes, each buffer for reads
+/// of its given length, and `out_record` for a pointer write.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn mosaic_record_new(
+    secret_key: *const u8,
+    kind: u16,
+    deterministic_key: *const u8,
+    deterministic_key_len: usize,
+    timestamp_ms: u64,
+    flags: u16,
+    app_flags: u16,
+    tags: *const u8,
+    tags_len: usize,
+    payload: *const u8,
+    payload_len: usize,
+    out_record: *mut *mut MosaicRecord,
+) -> i32 {
+    guard(|| {
+        if out_record.is_null() {
+            return Err(MOSAIC_ERR_NULL_POINTER);
+        }
+        let secret_key = SecretKey::from_bytes(array(secret_key)?);
+        let deterministic_key = if deterministic_key.is_null() {
+            None
+        } else {
+            Some(slice(deterministic_key, deterministic_key_len)?)
+        };
+        let parts = RecordParts {
+            kind: Kind(kind),
+            deterministic_key,
+            timestamp: Timestamp::from_millis(timestamp_ms)
+                .ok_or_else(|| kind_status(ErrorKind::InvalidTime))?,
+            flags: RecordFlags::from_bits(flags)
+                .ok_or_else(|| kind_status(ErrorKind::ReservedFlags))?,
+            app_flags,
+            tags_bytes: slice(tags, tags_len)?,
+            payload: slice(payload, payload_len)?,
+        };
+        let record = OwnedRecord::new(&secret_key, &parts).map_err(|e| status(&e))?;
+        *out_record = Box::into_raw(Box::new(MosaicRecord(record)));
+        Ok(())
+    })
+}
+
+/// Parse and fully verify a record from bytes, storing a new handle in
+/// `out_record`. The bytes are copied.
+///
+/// # Safety
+///
+/// `bytes` must be valid for reads of `len` bytes and `out_record` for a
+/// pointer write.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_from_bytes(
+    bytes: *const u8,
+    len: usize,
+    out_record: *mut *mut MosaicRecord,
+) -> i32 {
+    guard(|| {
+        if out_record.is_null() {
+            return Err(MOSAIC_ERR_NULL_POINTER);
+        }
+        let record = OwnedRecord::from_vec(slice(bytes, len)?.to_vec()).map_err(|e| status(&e))?;
+        *out_record = Box::into_raw(Box::new(MosaicRecord(record)));
+        Ok(())
+    })
+}
+
+/// Free a record handle. Null is ignored.
+///
+/// # Safety
+///
+/// `record` must be null or a handle from this library that has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_free(record: *mut MosaicRecord) {
+    if !record.is_null() {
+        drop(Box::from_raw(record));
+    }
+}
+
+/// Verify a record
+///
+/// # Safety
+///
+/// `record` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_verify(record: *const MosaicRecord) -> i32 {
+    guard(|| get_record(record)?.verify().map_err(|e| status(&e)))
+}
+
+/// Borrow the serialized bytes of a record. They are valid until the record
+/// is freed.
+///
+/// # Safety
+///
+/// `record` must be a live handle, and `out_ptr` and `out_len` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_bytes(
+    record: *const MosaicRecord,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> i32 {
+    guard(|| borrow(get_record(record)?.as_bytes(), out_ptr, out_len))
+}
+
+/// Borrow the tag bytes of a record. They are valid until the record is
+/// freed.
+///
+/// # Safety
+///
+/// `record` must be a live handle, and `out_ptr` and `out_len` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_tags(
+    record: *const MosaicRecord,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> i32 {
+    guard(|| borrow(get_record(record)?.tags_bytes(), out_ptr, out_len))
+}
+
+/// Borrow the payload bytes of a record. They are valid until the record is
+/// freed.
+///
+/// # Safety
+///
+/// `record` must be a live handle, and `out_ptr` and `out_len` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_payload(
+    record: *const MosaicRecord,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> i32 {
+    guard(|| borrow(get_record(record)?.payload_bytes(), out_ptr, out_len))
+}
+
+unsafe fn borrow(bytes: &[u8], out_ptr: *mut *const u8, out_len: *mut usize) -> Result<(), i32> {
+    if out_ptr.is_null() || out_len.is_null() {
+        return Err(MOSAIC_ERR_NULL_POINTER);
+    }
+    *out_ptr = bytes.as_ptr();
+    *out_len = bytes.len();
+    Ok(())
+}
+
+/// Copy the 48-byte id of a record into `out_id`
+///
+/// # Safety
+///
+/// `record` must be a live handle and `out_id` valid for writes of 48
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_id(record: *const MosaicRecord, out_id: *mut u8) -> i32 {
+    guard(|| write_array(out_id, get_record(record)?.id().as_bytes()))
+}
+
+/// Copy the 48-byte address of a record into `out_address`
+///
+/// # Safety
+///
+/// `record` must be a live handle and `out_address` valid for writes of 48
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_address(
+    record: *const MosaicRecord,
+    out_address: *mut u8,
+) -> i32 {
+    guard(|| write_array(out_address, get_record(record)?.address().as_bytes()))
+}
+
+/// Copy the 32-byte author public key of a record into `out_public_key`
+///
+/// # Safety
+///
+/// `record` must be a live handle and `out_public_key` valid for writes of
+/// 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_author_public_key(
+    record: *const MosaicRecord,
+    out_public_key: *mut u8,
+) -> i32 {
+    guard(|| {
+        write_array(
+            out_public_key,
+            get_record(record)?.author_public_key().as_bytes(),
+        )
+    })
+}
+
+/// Copy the 32-byte signing public key of a record into `out_public_key`
+///
+/// # Safety
+///
+/// `record` must be a live handle and `out_public_key` valid for writes of
+/// 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_signing_public_key(
+    record: *const MosaicRecord,
+    out_public_key: *mut u8,
+) -> i32 {
+    guard(|| {
+        write_array(
+            out_public_key,
+            get_record(record)?.signing_public_key().as_bytes(),
+        )
+    })
+}
+
+/// The kind of a record, or 0 if `record` is null
+///
+/// # Safety
+///
+/// `record` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_kind(record: *const MosaicRecord) -> u16 {
+    get_record(record).map_or(0, |r| r.kind().0)
+}
+
+/// The timestamp of a record in milliseconds, or 0 if `record` is null
+///
+/// # Safety
+///
+/// `record` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_timestamp(record: *const MosaicRecord) -> u64 {
+    get_record(record).map_or(0, |r| r.timestamp().as_millis())
+}
+
+/// The flags of a record, or 0 if `record` is null
+///
+/// # Safety
+///
+/// `record` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_flags(record: *const MosaicRecord) -> u16 {
+    get_record(record).map_or(0, |r| r.flags().bits())
+}
+
+/// The application flags of a record, or 0 if `record` is null
+///
+/// # Safety
+///
+/// `record` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_record_app_flags(record: *const MosaicRecord) -> u16 {
+    get_record(record).map_or(0, |r| r.app_flags())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let mut secret_key = [0_u8; 32];
+            assert_eq!(
+                mosaic_secret_key_generate(secret_key.as_mut_ptr()),
+                MOSAIC_OK
+            );
+            let mut public_key = [0_u8; 32];
+            assert_eq!(
+                mosaic_secret_key_public(secret_key.as_ptr(), public_key.as_mut_ptr()),
+                MOSAIC_OK
+            );
+
+            let mut printable: [c_char; 64] = [0; 64];
+            assert_eq!(
+                mosaic_public_key_printable(public_key.as_ptr(), printable.as_mut_ptr(), 8),
+                MOSAIC_ERR_BUFFER_TOO_SMALL
+            );
+            assert_eq!(
+                mosaic_public_key_printable(public_key.as_ptr(), printable.as_mut_ptr(), 64),
+                MOSAIC_OK
+            );
+
+            let payload = b"hello";
+            let mut handle: *mut MosaicRecord = ptr::null_mut();
+            assert_eq!(
+                mosaic_record_new(
+                    secret_key.as_ptr(),
+                    Kind::MICROBLOG_ROOT.0,
+                    ptr::null(),
+                    0,
+                    1000,
+                    0,
+                    0,
+                    ptr::null(),
+                    0,
+                    payload.as_ptr(),
+                    payload.len(),
+                    &raw mut handle,
+                ),
+                MOSAIC_OK
+            );
+            assert_eq!(mosaic_record_verify(handle), MOSAIC_OK);
+            assert_eq!(mosaic_record_kind(handle), Kind::MICROBLOG_ROOT.0);
+            assert_eq!(mosaic_record_timestamp(handle), 1000);
+
+            let mut author = [0_u8; 32];
+            assert_eq!(
+                mosaic_record_author_public_key(handle, author.as_mut_ptr()),
+                MOSAIC_OK
+            );
+            assert_eq!(author, public_key);
+
+            let (mut ptr, mut len) = (ptr::null(), 0);
+            assert_eq!(
+                mosaic_record_payload(handle, &raw mut ptr, &raw mut len),
+                MOSAIC_OK
+            );
+            assert_eq!(std::slice::from_raw_parts(ptr, len), payload);
+
+            // Round trip through bytes; a corrupted copy fails
+            assert_eq!(
+                mosaic_record_bytes(handle, &raw mut ptr, &raw mut len),
+                MOSAIC_OK
+            );
+            let mut bytes = std::slice::from_raw_parts(ptr, len).to_vec();
+            let mut copy: *mut MosaicRecord = ptr::null_mut();
+            assert_eq!(
+                mosaic_record_from_bytes(bytes.as_ptr(), bytes.len(), &raw mut copy),
+                MOSAIC_OK
+            );
+            mosaic_record_free(copy);
+            let payload_start = bytes.len() - 8;
+            bytes[payload_start] ^= 1;
+            assert_eq!(
+                mosaic_record_from_bytes(bytes.as_ptr(), bytes.len(), &raw mut copy),
+                i32::from(ErrorKind::HashMismatch.code())
+            );
+
+            assert_eq!(mosaic_record_verify(ptr::null()), MOSAIC_ERR_NULL_POINTER);
+            mosaic_record_free(handle);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use crate::tag::iter_tags;
+use crate::{
+    Address, Error, Id, InnerError, Kind, PublicKey, Record, RecordFlags, TagType, Timestamp,
+    Tokenizer, UnicodeTokenizer,
+};
+
+/// The largest `limit` a `Filter` may specify
+pub const MAX_FILTER_LIMIT: u32 = 10_000;
+
+/// A `Filter` selects records.
+///
+/// Every field that is set must match for a record to match. Empty lists
+/// and `None` values do not constrain the match.
+///
+/// # Time windows
+///
+/// `since` is inclusive and `until` is exclusive, so a record matches if
+/// `since <= timestamp < until`. Adjacent windows therefore never overlap
+/// and never leave gaps. If both are set, `since` must be strictly before
+/// `until`.
+///
+/// # Limits
+///
+/// `limit` is the maximum number of records a query returns. Results are
+/// ordered newest first, so the limit keeps the newest matching records.
+/// `limit` does not affect `matches()`. It must be between 1 and
+/// `MAX_FILTER_LIMIT`.
+///
+/// # Cursors
+///
+/// When a query is resumed from a cursor, `since` and `until` still apply
+/// and the cursor only narrows the window further. `limit` applies to each
+/// page, not to the query as a whole.
+///
+/// # Encoding
+///
+/// `to_bytes()` gives a canonical binary encoding, in which lists are
+/// sorted and free of duplicates, so two filters that select the same
+/// records encode to the same bytes. `from_bytes()` accepts only canonical
+/// encodings.
+///
+/// With the `serde` feature, a `Filter` can be serialized. Fields that are
+/// missing when deserializing take their default (unconstrained) value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Filter {
+    /// Match only records of these kinds
+    pub kinds: Vec<Kind>,
+
+    /// Match only records by these authors
+    pub author_keys: Vec<PublicKey>,
+
+    /// Match only records signed by these keys
+    pub signing_keys: Vec<PublicKey>,
+
+    /// Match only records with these ids
+    pub ids: Vec<Id>,
+
+    /// Match only records at these addresses
+    pub addresses: Vec<Address>,
+
+    /// Match only records carrying at least one of these tags, given as a
+    /// tag type and tag value (see `Tag::get_value()`). Stores answer this
+    /// from their tag index.
+    pub tags: Vec<(TagType, Vec<u8>)>,
+
+    /// Match only records carrying at least one tag of the given type whose
+    /// value starts with the given prefix, such as a geohash prefix or the
+    /// top of a topic hierarchy. Stores with a tag value index answer this
+    /// with a range scan.
+    pub tag_prefixes: Vec<(TagType, Vec<u8>)>,
+
+    /// Match only records whose payload text contains every token of this
+    /// search term.
+    ///
+    /// Records with compressed or non-UTF-8 payloads never match a search.
+    /// Servers that do not index payload text may ignore this field.
+    pub search: Option<String>,
+
+    /// Match only records with a timestamp at or after this time (inclusive)
+    pub since: Option<Timestamp>,
+
+    /// Match only records with a timestamp before this time (exclusive)
+    pub until: Option<Timestamp>,
+
+    /// Return at most this many records
+    pub limit: Option<u32>,
+
+    /// Also return records that were deleted but retained by a store in
+    /// `DeletionMode::Hide`. This does not affect `matches()`.
+    ///
+    /// This is a local query option for the server, so it is neither encoded
+    /// nor serialized and a filter received from a peer never has it set.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub include_deleted: bool,
+}
+
+impl Filter {
+    /// Create a new empty `Filter` which matches every record
+    #[must_use]
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    /// Check that the time window and limit are within bounds
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `since` is not before `until`, or if `limit` is
+    /// zero or larger than `MAX_FILTER_LIMIT`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since >= until {
+                return Err(InnerError::InvalidFilterTimeWindow.into());
+            }
+        }
+        if let Some(limit) = self.limit {
+            if limit == 0 || limit > MAX_FILTER_LIMIT {
+                return Err(InnerError::InvalidFilterLimit.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Is the timestamp inside this filter's time window?
+    #[must_use]
+    pub fn in_time_window(&self, timestamp: Timestamp) -> bool {
+        if let Some(since) = self.since {
+            if timestamp < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if timestamp >= until {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Does the record match this filter?
+    ///
+    /// Searches are tokenized with the default `UnicodeTokenizer`.
+    #[must_use]
+    pub fn matches(&self, record: &Record) -> bool {
+        self.matches_with_tokenizer(record, &UnicodeTokenizer)
+    }
+
+    /// Does the record match this filter, using the given `Tokenizer` for
+    /// any search term?
+    #[must_use]
+    pub fn matches_with_tokenizer<T: Tokenizer + ?Sized>(
+        &self,
+        record: &Record,
+        tokenizer: &T,
+    ) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&record.kind()) {
+            return false;
+        }
+
+        if !self.in_time_window(record.timestamp()) {
+            return false;
+        }
+
+        if !self.author_keys.is_empty() && !self.author_keys.contains(&record.author_public_key()) {
+            return false;
+        }
+
+        if !self.signing_keys.is_empty()
+            && !self.signing_keys.contains(&record.signing_public_key())
+        {
+            return false;
+        }
+
+        if !self.ids.is_empty() && !self.ids.contains(&record.id()) {
+            return false;
+        }
+
+        if !self.addresses.is_empty() && !self.addresses.contains(&record.address()) {
+            return false;
+        }
+
+        if !self.tags.is_empty()
+            && !iter_tags(record.tags_bytes()).any(|tag| {
+                self.tags
+                    .iter()
+                    .any(|(ty, value)| tag.get_type() == *ty && tag.get_value() == value.as_slice())
+            })
+        {
+            return false;
+        }
+
+        if !self.tag_prefixes.is_empty()
+            && !iter_tags(record.tags_bytes()).any(|tag| {
+                self.tag_prefixes.iter().any(|(ty, prefix)| {
+                    tag.get_type() == *ty && tag.get_value().starts_with(prefix)
+                })
+            })
+        {
+            return false;
+        }
+
+        if let Some(ref term) = self.search {
+            if record.flags().contains(RecordFlags::ZSTD) {
+                return false;
+            }
+            let Ok(text) = std::str::from_utf8(record.payload_bytes()) else {
+                return false;
+            };
+            if !crate::search::text_matches(tokenizer, term, text) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Sort every list and remove duplicates. This does not change which
+    /// records the filter matches.
+    pub fn canonicalize(&mut self) {
+        self.kinds.sort_by_key(|k| k.0);
+        self.kinds.dedup();
+        self.author_keys.sort();
+        self.author_keys.dedup();
+        self.signing_keys.sort();
+        self.signing_keys.dedup();
+        self.ids.sort();
+        self.ids.dedup();
+        self.addresses.sort();
+        self.addresses.dedup();
+        for tags in [&mut self.tags, &mut self.tag_prefixes] {
+            tags.sort_by(|a, b| (a.0 .0, &a.1).cmp(&(b.0 .0, &b.1)));
+            tags.dedup();
+        }
+    }
+
+    /// Encode the filter canonically
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if a list has more than 65535 entries, or a tag
+    /// value or search term is longer than 65535 bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decode a canonically encoded filter
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the encoding is truncated, malformed or not
+    /// canonical, or if any bytes follow it
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Filter, Error> {
+        let filter = Filter::read(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(InnerError::InvalidFilterEncoding.into());
+        }
+        Ok(filter)
+    }
+
+    // Append the canonical encoding to `bytes`
+    pub(crate) fn write(&self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        fn write_len(len: usize, bytes: &mut Vec<u8>) -> Result<(), Error> {
+            let len =
+                u16::try_from(len).map_err(|_| InnerError::InvalidFilterEncoding.into_err())?;
+            bytes.extend_from_slice(&len.to_le_bytes());
+            Ok(())
+        }
+
+        let mut filter = self.clone();
+        filter.canonicalize();
+
+        write_len(filter.kinds.len(), bytes)?;
+        for kind in &filter.kinds {
+            bytes.extend_from_slice(&kind.0.to_le_bytes());
+        }
+        for keys in [&filter.author_keys, &filter.signing_keys] {
+            write_len(keys.len(), bytes)?;
+            for key in keys {
+                bytes.extend_from_slice(key.as_bytes());
+            }
+        }
+        write_len(filter.ids.len(), bytes)?;
+        for id in &filter.ids {
+            bytes.extend_from_slice(id.as_bytes());
+        }
+        write_len(filter.addresses.len(), bytes)?;
+        for address in &filter.addresses {
+            bytes.extend_from_slice(address.as_bytes());
+        }
+        for tags in [&filter.tags, &filter.tag_prefixes] {
+            write_len(tags.len(), bytes)?;
+            for (tag_type, value) in tags {
+                bytes.extend_from_slice(&tag_type.0.to_le_bytes());
+                write_len(value.len(), bytes)?;
+                bytes.extend_from_slice(value);
+            }
+        }
+        match &filter.search {
+            Some(search) => {
+                bytes.push(1);
+                write_len(search.len(), bytes)?;
+                bytes.extend_from_slice(search.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+        for time in [filter.since, filter.until] {
+            match time {
+                Some(time) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&time.to_bytes());
+                }
+                None => bytes.push(0),
+            }
+        }
+        match filter.limit {
+            Some(limit) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&limit.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        Ok(())
+    }
+
+    // Read a canonical encoding from the front of `bytes`
+    #[allow(clippy::missing_panics_doc)]
+    pub(crate) fn read(bytes: &mut &[u8]) -> Result<Filter, Error> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+            if bytes.len() < len {
+                return Err(InnerError::InvalidFilterEncoding.into());
+            }
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            Ok(head)
+        }
+        fn read_u16(bytes: &mut &[u8]) -> Result<u16, Error> {
+            Ok(u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()))
+        }
+        fn present(bytes: &mut &[u8]) -> Result<bool, Error> {
+            match take(bytes, 1)?[0] {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(InnerError::InvalidFilterEncoding.into()),
+            }
+        }
+
+        let mut filter = Filter::new();
+        for _ in 0..read_u16(bytes)? {
+            filter.kinds.push(Kind(read_u16(bytes)?));
+        }
+        for keys in [&mut filter.author_keys, &mut filter.signing_keys] {
+            for _ in 0..read_u16(bytes)? {
+                keys.push(PublicKey::from_bytes(take(bytes, 32)?.try_into().unwrap())?);
+            }
+        }
+        for _ in 0..read_u16(bytes)? {
+            filter
+                .ids
+                .push(Id::from_bytes(take(bytes, 48)?.try_into().unwrap())?);
+        }
+        for _ in 0..read_u16(bytes)? {
+            filter
+                .addresses
+                .push(Address::from_bytes(take(bytes, 48)?.try_into().unwrap())?);
+        }
+        for tags in [&mut filter.tags, &mut filter.tag_prefixes] {
+            for _ in 0..read_u16(bytes)? {
+                let tag_type = TagType(read_u16(bytes)?);
+                let len = read_u16(bytes)? as usize;
+                tags.push((tag_type, take(bytes, len)?.to_vec()));
+            }
+        }
+        if present(bytes)? {
+            let len = read_u16(bytes)? as usize;
+            filter.search = Some(std::str::from_utf8(take(bytes, len)?)?.to_owned());
+        }
+        for time in [&mut filter.since, &mut filter.until] {
+            if present(bytes)? {
+                *time = Some(Timestamp::from_bytes(take(bytes, 6)?.try_into().unwrap())?);
+            }
+        }
+        if present(bytes)? {
+            filter.limit = Some(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()));
+        }
+
+        let mut canonical = filter.clone();
+        canonical.canonicalize();
+        if canonical != filter {
+            return Err(InnerError::InvalidFilterEncoding.into());
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedRecord, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_filter_search() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1_746_051_282_390).unwrap(),
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"The quick brown fox",
+            },
+        )
+        .unwrap();
+
+        let mut filter = Filter::new();
+        assert!(filter.matches(&record));
+
+        filter.search = Some("FOX quick".to_owned());
+        assert!(filter.matches(&record));
+
+        filter.search = Some("dog".to_owned());
+        assert!(!filter.matches(&record));
+
+        filter.search = None;
+        filter.kinds = vec![Kind::BLOG_POST];
+        assert!(!filter.matches(&record));
+
+        filter.kinds = vec![Kind::MICROBLOG_ROOT];
+        filter.author_keys = vec![secret_key.public()];
+        assert!(filter.matches(&record));
+    }
+
+    #[test]
+    fn test_filter_tag_prefixes() {
+        use crate::OwnedTag;
+        use rand::rngs::OsRng;
+
+        const GEOHASH: TagType = TagType::new(0x8001);
+        let tag = OwnedTag::new(GEOHASH, b"u4pruyd").unwrap();
+        let record = OwnedRecord::new(
+            &SecretKey::generate(&mut OsRng),
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1_746_051_282_390).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: tag.as_bytes(),
+                payload: b"",
+            },
+        )
+        .unwrap();
+
+        let mut filter = Filter::new();
+        filter.tag_prefixes = vec![(GEOHASH, b"u4p".to_vec())];
+        assert!(filter.matches(&record));
+        filter.tag_prefixes = vec![(GEOHASH, b"u4q".to_vec())];
+        assert!(!filter.matches(&record));
+        filter.tag_prefixes = vec![(TagType::new(0x8002), b"u4p".to_vec())];
+        assert!(!filter.matches(&record));
+    }
+
+    #[test]
+    fn test_filter_time_window() {
+        let ts = |ms| Timestamp::from_millis(ms).unwrap();
+
+        let mut filter = Filter::new();
+        filter.since = Some(ts(100));
+        filter.until = Some(ts(200));
+        filter.validate().unwrap();
+        assert!(!filter.in_time_window(ts(99)));
+        assert!(filter.in_time_window(ts(100)));
+        assert!(filter.in_time_window(ts(199)));
+        assert!(!filter.in_time_window(ts(200)));
+
+        filter.until = Some(ts(100));
+        assert!(filter.validate().is_err());
+        filter.until = None;
+
+        filter.limit = Some(0);
+        assert!(filter.validate().is_err());
+        filter.limit = Some(MAX_FILTER_LIMIT + 1);
+        assert!(filter.validate().is_err());
+        filter.limit = Some(MAX_FILTER_LIMIT);
+        filter.validate().unwrap();
+    }
+
+    #[test]
+    fn test_filter_encoding() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let other_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut filter = Filter::new();
+        filter.signing_keys = vec![other_key.public()];
+        assert!(!filter.matches(&record));
+        filter.signing_keys.push(secret_key.public());
+        assert!(filter.matches(&record));
+        filter.ids = vec![record.id()];
+        filter.addresses = vec![record.address()];
+        assert!(filter.matches(&record));
+        filter.addresses = vec![Address::new_random(secret_key.public(), Kind::BLOG_POST)];
+        assert!(!filter.matches(&record));
+
+        filter.kinds = vec![Kind::BLOG_POST, Kind::MICROBLOG_ROOT, Kind::BLOG_POST];
+        filter.tags = vec![
+            (TagType::NOTIFY_PUBLIC_KEY, vec![2]),
+            (TagType::NOTIFY_PUBLIC_KEY, vec![1]),
+        ];
+        filter.search = Some("hello".to_owned());
+        filter.until = Some(Timestamp::from_millis(2000).unwrap());
+        filter.limit = Some(5);
+
+        // Filters selecting the same records encode the same way
+        let bytes = filter.to_bytes().unwrap();
+        let mut reordered = filter.clone();
+        reordered.kinds.reverse();
+        reordered.signing_keys.reverse();
+        reordered.tags.reverse();
+        assert_eq!(reordered.to_bytes().unwrap(), bytes);
+
+        let decoded = Filter::from_bytes(&bytes).unwrap();
+        filter.canonicalize();
+        assert_eq!(decoded, filter);
+        assert_eq!(decoded.kinds, vec![Kind::MICROBLOG_ROOT, Kind::BLOG_POST]);
+
+        // include_deleted is a local option and does not survive encoding
+        filter.include_deleted = true;
+        assert_eq!(filter.to_bytes().unwrap(), bytes);
+        assert!(!Filter::from_bytes(&bytes).unwrap().include_deleted);
+
+        assert!(Filter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(Filter::from_bytes(&trailing).is_err());
+
+        // Non-canonical order is rejected
+        let mut swapped = bytes;
+        swapped.swap(2, 4);
+        swapped.swap(3, 5);
+        let err = Filter::from_bytes(&swapped).unwrap_err();
+        assert!(matches!(err.inner, InnerError::InvalidFilterEncoding));
+    }
+}
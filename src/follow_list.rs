@@ -0,0 +1,207 @@
+use crate::{
+    Error, InnerError, Kind, OwnedRecord, PublicKey, Record, RecordFlags, RecordParts, SecretKey,
+    Timestamp,
+};
+use std::collections::BTreeSet;
+
+/// The deterministic key of follow list records, so that each new list
+/// replaces the last
+pub const FOLLOW_LIST_KEY: &[u8] = b"follow-list";
+
+/// The set of keys a user follows, as carried by a `FOLLOW_LIST` record.
+///
+/// The payload of a follow list record is the followed public keys, 32
+/// bytes each, in ascending order.
+///
+/// Clients on several devices can each edit the list and reconcile with
+/// `FollowList::merge()` against the last list they had in common, rather
+/// than letting the latest record silently drop the other device's
+/// follows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FollowList(BTreeSet<PublicKey>);
+
+/// The changes between two `FollowList`s
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FollowListDiff {
+    /// Keys followed in the new list but not the old, in ascending order
+    pub added: Vec<PublicKey>,
+
+    /// Keys followed in the old list but not the new, in ascending order
+    pub removed: Vec<PublicKey>,
+}
+
+impl FollowListDiff {
+    /// Are the lists the same?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl FollowList {
+    /// Create an empty `FollowList`
+    #[must_use]
+    pub fn new() -> FollowList {
+        FollowList::default()
+    }
+
+    /// Read a `FollowList` from a follow list record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `FOLLOW_LIST`, if its
+    /// payload is not a whole number of keys, or if a key is invalid
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_record(record: &Record) -> Result<FollowList, Error> {
+        if record.kind() != Kind::FOLLOW_LIST {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let payload = record.payload_bytes();
+        if !payload.len().is_multiple_of(32) {
+            return Err(InnerError::KeyLength.into());
+        }
+        let mut keys = BTreeSet::new();
+        for chunk in payload.chunks_exact(32) {
+            let _ = keys.insert(PublicKey::from_bytes(chunk.try_into().unwrap())?);
+        }
+        Ok(FollowList(keys))
+    }
+
+    /// Create a follow list record of this list
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the list is too long for a record, or if
+    /// signing fails
+    pub fn to_record(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        let payload: Vec<u8> = self.0.iter().flat_map(|k| *k.as_bytes()).collect();
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::FOLLOW_LIST,
+                deterministic_key: Some(FOLLOW_LIST_KEY),
+                timestamp,
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: &payload,
+            },
+        )
+    }
+
+    /// Follow a key. Returns false if it was already followed.
+    pub fn insert(&mut self, public_key: PublicKey) -> bool {
+        self.0.insert(public_key)
+    }
+
+    /// Unfollow a key. Returns false if it was not followed.
+    pub fn remove(&mut self, public_key: &PublicKey) -> bool {
+        self.0.remove(public_key)
+    }
+
+    /// Is this key followed?
+    #[must_use]
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.0.contains(public_key)
+    }
+
+    /// The number of keys followed
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Is nobody followed?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the followed keys in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = &PublicKey> {
+        self.0.iter()
+    }
+
+    /// The changes that turn this list into `newer`
+    #[must_use]
+    pub fn diff(&self, newer: &FollowList) -> FollowListDiff {
+        FollowListDiff {
+            added: newer.0.difference(&self.0).copied().collect(),
+            removed: self.0.difference(&newer.0).copied().collect(),
+        }
+    }
+
+    /// Apply changes made elsewhere to this list
+    pub fn apply(&mut self, diff: &FollowListDiff) {
+        for key in &diff.removed {
+            let _ = self.0.remove(key);
+        }
+        self.0.extend(diff.added.iter().copied());
+    }
+
+    /// Merge two lists that diverged from a common `base`.
+    ///
+    /// A key is followed in the result if either side followed it since
+    /// `base`, or if it was in `base` and neither side unfollowed it.
+    #[must_use]
+    pub fn merge(base: &FollowList, local: &FollowList, remote: &FollowList) -> FollowList {
+        let mut merged = local.clone();
+        merged.apply(&base.diff(remote));
+        merged
+    }
+}
+
+impl FromIterator<PublicKey> for FollowList {
+    fn from_iter<I: IntoIterator<Item = PublicKey>>(iter: I) -> Self {
+        FollowList(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_follow_list_merge() {
+        use rand::rngs::OsRng;
+
+        let keys: Vec<PublicKey> = (0..5)
+            .map(|_| SecretKey::generate(&mut OsRng).public())
+            .collect();
+        let base: FollowList = keys[0..3].iter().copied().collect();
+
+        // Local unfollows 0 and follows 3; remote unfollows 1 and follows 4
+        let mut local = base.clone();
+        assert!(local.remove(&keys[0]));
+        assert!(local.insert(keys[3]));
+        let mut remote = base.clone();
+        assert!(remote.remove(&keys[1]));
+        assert!(remote.insert(keys[4]));
+
+        let diff = base.diff(&local);
+        assert_eq!(diff.removed, vec![keys[0]]);
+        assert_eq!(diff.added, vec![keys[3]]);
+        assert!(base.diff(&base).is_empty());
+
+        let merged = FollowList::merge(&base, &local, &remote);
+        let expected: FollowList = [keys[2], keys[3], keys[4]].into_iter().collect();
+        assert_eq!(merged, expected);
+        assert_eq!(FollowList::merge(&base, &remote, &local), expected);
+
+        // Round trip through a record
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = merged
+            .to_record(&secret_key, Timestamp::from_millis(1000).unwrap())
+            .unwrap();
+        assert_eq!(FollowList::from_record(&record).unwrap(), merged);
+
+        let later = local
+            .to_record(&secret_key, Timestamp::from_millis(2000).unwrap())
+            .unwrap();
+        assert!(later.supersedes(&record));
+    }
+}
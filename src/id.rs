@@ -1,4 +1,4 @@
-use crate::{Error, InnerError, Timestamp};
+use crate::{zbase32, Error, InnerError, Timestamp};
 
 /// An Id uniquely identifies a record.
 ///
@@ -31,10 +31,18 @@ impl Id {
         Id(bytes)
     }
 
+    /// The smallest possible `Id` at the given timestamp. Since `Id`s sort
+    /// in time order, this is useful as a range bound.
+    pub(crate) fn min_for_timestamp(timestamp: Timestamp) -> Id {
+        let mut bytes: [u8; 48] = [0; 48];
+        bytes[0..6].copy_from_slice(timestamp.to_be_bytes().as_slice());
+        Id(bytes)
+    }
+
     /// Convert an `Id` into a human printable `moref0` form.
     #[must_use]
     pub fn printable(&self) -> String {
-        format!("moref0{}", z32::encode(self.as_ref()))
+        format!("moref0{}", zbase32::encode(self.as_ref()))
     }
 
     /// Import an `Id` from its printable form
@@ -47,7 +55,11 @@ impl Id {
         if !s.starts_with("moref0") {
             return Err(InnerError::InvalidPrintable.into_err());
         }
-        let bytes = z32::decode(&s.as_bytes()[6..])?;
+        let bytes = zbase32::decode(&s.as_bytes()[6..])?;
+        // Reject strings that only decode because spare bits are ignored
+        if zbase32::encode(&bytes).as_bytes() != &s.as_bytes()[6..] {
+            return Err(InnerError::InvalidPrintable.into_err());
+        }
         let bytes: [u8; 48] = bytes
             .try_into()
             .map_err(|_| InnerError::ReferenceLength.into_err())?;
@@ -69,6 +81,20 @@ impl Id {
         self.0[8..48].try_into().unwrap()
     }
 
+    /// Proof-of-work difficulty, as the number of leading zero bits in the
+    /// hash prefix
+    #[must_use]
+    pub fn pow_difficulty(&self) -> u32 {
+        let mut bits: u32 = 0;
+        for byte in self.hash_prefix() {
+            bits += byte.leading_zeros();
+            if *byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+
     pub(crate) fn verify(bytes: &[u8; 48]) -> Result<(), Error> {
         // Verify zeros
         if bytes[6] != 0 || bytes[7] != 0 {
@@ -88,12 +114,39 @@ impl AsRef<[u8]> for Id {
     }
 }
 
+impl TryFrom<&[u8]> for Id {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Id, Error> {
+        let bytes: &[u8; 48] = bytes
+            .try_into()
+            .map_err(|_| InnerError::ReferenceLength.into_err())?;
+        Id::from_bytes(bytes)
+    }
+}
+
+impl TryFrom<[u8; 48]> for Id {
+    type Error = Error;
+
+    fn try_from(bytes: [u8; 48]) -> Result<Id, Error> {
+        Id::from_bytes(&bytes)
+    }
+}
+
 impl std::fmt::Display for Id {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.printable())
     }
 }
 
+impl std::str::FromStr for Id {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Id, Error> {
+        Id::from_printable(s)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -105,5 +158,18 @@ mod test {
         let id = Id::from_printable(printable).unwrap();
         let timestamp = id.timestamp();
         assert_eq!(format!("{timestamp}"), "1746051282390");
+
+        assert_eq!(Id::try_from(id.as_ref()).unwrap(), id);
+        assert_eq!(Id::try_from(*id.as_bytes()).unwrap(), id);
+        assert!(Id::try_from(&id.as_ref()[1..]).is_err());
+
+        assert_eq!(printable.parse::<Id>().unwrap(), id);
+        // A spare bit set in the last character, a character outside the
+        // alphabet, or a truncation
+        let mut spare = printable.to_owned();
+        spare.replace_range(spare.len() - 1.., "u");
+        assert!(spare.parse::<Id>().is_err());
+        assert!(printable.replacen('y', "l", 1).parse::<Id>().is_err());
+        assert!(printable.trim_end_matches('1').parse::<Id>().is_err());
     }
 }
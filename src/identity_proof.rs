@@ -0,0 +1,232 @@
+use crate::{
+    Error, Id, InnerError, Kind, OwnedRecord, PublicKey, Record, RecordFlags, RecordParts,
+    SecretKey, Timestamp,
+};
+
+/// An external identity that a user claims to control
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IdentityClaim {
+    /// A DNS domain, such as `example.com`
+    Domain(String),
+
+    /// An account on a git forge, such as `alice` on `github.com`
+    Git {
+        /// The forge's domain
+        forge: String,
+
+        /// The account name
+        handle: String,
+    },
+
+    /// A web page, by its `https` URL
+    WebPage(String),
+}
+
+impl IdentityClaim {
+    /// Parse the canonical form written by `Display`: `domain:<domain>`,
+    /// `git:<forge>/<handle>` or `web:<https url>`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the string is not a valid claim
+    pub fn parse(s: &str) -> Result<IdentityClaim, Error> {
+        let invalid = || InnerError::InvalidIdentityClaim.into_err();
+        let claim = if let Some(domain) = s.strip_prefix("domain:") {
+            IdentityClaim::Domain(domain.to_owned())
+        } else if let Some(rest) = s.strip_prefix("git:") {
+            let (forge, handle) = rest.split_once('/').ok_or_else(invalid)?;
+            IdentityClaim::Git {
+                forge: forge.to_owned(),
+                handle: handle.to_owned(),
+            }
+        } else if let Some(url) = s.strip_prefix("web:") {
+            IdentityClaim::WebPage(url.to_owned())
+        } else {
+            return Err(invalid());
+        };
+        if !claim.is_valid() {
+            return Err(invalid());
+        }
+        Ok(claim)
+    }
+
+    fn is_valid(&self) -> bool {
+        fn is_domain(s: &str) -> bool {
+            !s.is_empty()
+                && s.split('.').all(|label| {
+                    !label.is_empty()
+                        && label
+                            .bytes()
+                            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                })
+        }
+        match self {
+            IdentityClaim::Domain(domain) => is_domain(domain),
+            IdentityClaim::Git { forge, handle } => {
+                is_domain(forge)
+                    && !handle.is_empty()
+                    && handle
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+            }
+            IdentityClaim::WebPage(url) => url.strip_prefix("https://").is_some_and(|rest| {
+                !rest.is_empty() && !rest.bytes().any(|b| b.is_ascii_whitespace())
+            }),
+        }
+    }
+
+    /// The text the user publishes at this claim's location to bind it to
+    /// `author`. It can be shown before the proof record is created.
+    #[must_use]
+    pub fn challenge(&self, author: &PublicKey) -> String {
+        format!("mosaic-proof {self} {}", author.printable())
+    }
+
+    /// Where the user must publish the challenge for this claim, and
+    /// where a verifier should fetch it from:
+    ///
+    /// - a domain: the DNS TXT record at `_mosaic.<domain>`
+    /// - a git account: a public repository named `mosaic-proof` owned by
+    ///   the account, whose `README` holds the challenge
+    /// - a web page: the page itself
+    #[must_use]
+    pub fn location(&self) -> String {
+        match self {
+            IdentityClaim::Domain(domain) => format!("_mosaic.{domain}"),
+            IdentityClaim::Git { forge, handle } => {
+                format!("https://{forge}/{handle}/mosaic-proof")
+            }
+            IdentityClaim::WebPage(url) => url.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for IdentityClaim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentityClaim::Domain(domain) => write!(f, "domain:{domain}"),
+            IdentityClaim::Git { forge, handle } => write!(f, "git:{forge}/{handle}"),
+            IdentityClaim::WebPage(url) => write!(f, "web:{url}"),
+        }
+    }
+}
+
+/// A user's claim to control an external identity, as carried by an
+/// `IDENTITY_PROOF` record.
+///
+/// The binding is proven in both directions: the record, signed by the
+/// user, names the external identity, and the user publishes the
+/// `challenge()` (which names their key) at the claim's `location()`.
+/// Fetching that location is left to the caller; pass what was fetched to
+/// `is_confirmed_by()`.
+///
+/// The record's payload is the claim in its canonical form, and the claim
+/// is also its deterministic key, so a newer proof of the same claim
+/// replaces an older one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityProof {
+    /// What is claimed
+    pub claim: IdentityClaim,
+
+    /// The user claiming it
+    pub author: PublicKey,
+
+    /// The proof record
+    pub id: Id,
+}
+
+impl IdentityProof {
+    /// Create an identity proof record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the claim is invalid or if signing fails
+    pub fn create(
+        secret_key: &SecretKey,
+        claim: &IdentityClaim,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        if !claim.is_valid() {
+            return Err(InnerError::InvalidIdentityClaim.into());
+        }
+        let canonical = claim.to_string();
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::IDENTITY_PROOF,
+                deterministic_key: Some(canonical.as_bytes()),
+                timestamp,
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: canonical.as_bytes(),
+            },
+        )
+    }
+
+    /// Read and verify an identity proof record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record does not verify, is not an
+    /// `IDENTITY_PROOF`, or does not carry a valid claim
+    pub fn from_record(record: &Record) -> Result<IdentityProof, Error> {
+        record.verify()?;
+        if record.kind() != Kind::IDENTITY_PROOF {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let claim = IdentityClaim::parse(std::str::from_utf8(record.payload_bytes())?)?;
+        Ok(IdentityProof {
+            claim,
+            author: record.author_public_key(),
+            id: record.id(),
+        })
+    }
+
+    /// The text the user publishes at the claim's location
+    #[must_use]
+    pub fn challenge(&self) -> String {
+        self.claim.challenge(&self.author)
+    }
+
+    /// Does the content fetched from the claim's location contain the
+    /// challenge on a line of its own?
+    #[must_use]
+    pub fn is_confirmed_by(&self, published: &str) -> bool {
+        let challenge = self.challenge();
+        published.lines().any(|line| line.trim() == challenge)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_identity_proof() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let claim = IdentityClaim::Git {
+            forge: "github.com".to_owned(),
+            handle: "alice".to_owned(),
+        };
+        assert_eq!(IdentityClaim::parse(&claim.to_string()).unwrap(), claim);
+        assert!(IdentityClaim::parse("domain:Example.com").is_err());
+        assert!(IdentityClaim::parse("web:http://example.com").is_err());
+
+        let record =
+            IdentityProof::create(&secret_key, &claim, Timestamp::from_millis(1000).unwrap())
+                .unwrap();
+        let proof = IdentityProof::from_record(&record).unwrap();
+        assert_eq!(proof.claim, claim);
+        assert_eq!(proof.author, secret_key.public());
+
+        let page = format!("# My proof\n\n{}\n", claim.challenge(&secret_key.public()));
+        assert!(proof.is_confirmed_by(&page));
+
+        // Someone else's challenge does not confirm it
+        let other = SecretKey::generate(&mut OsRng).public();
+        assert!(!proof.is_confirmed_by(&claim.challenge(&other)));
+    }
+}
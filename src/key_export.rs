@@ -0,0 +1,264 @@
+use crate::{zbase32, Error, InnerError, SecretKey};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand_core::{CryptoRng, OsRng, RngCore};
+use zeroize::Zeroizing;
+
+const ENCRYPTED_KEY_PREFIX: &str = "moenc0";
+const ENCRYPTED_KEY_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+// version, three u32 costs, salt, nonce
+const HEADER_LEN: usize = 1 + 12 + SALT_LEN + NONCE_LEN;
+const ENCRYPTED_KEY_LEN: usize = HEADER_LEN + 32 + TAG_LEN;
+
+/// The most memory an encrypted key may ask Argon2id for when it is
+/// imported, so that a crafted export cannot exhaust memory (256 MiB,
+/// which a phone can allocate)
+pub const MAX_KDF_MEMORY_KIB: u32 = 256 * 1024;
+
+/// The most passes over memory an encrypted key may ask Argon2id for, so
+/// that a crafted export cannot hang the importer
+pub const MAX_KDF_ITERATIONS: u32 = 16;
+
+/// The most lanes an encrypted key may ask Argon2id for
+pub const MAX_KDF_PARALLELISM: u32 = 16;
+
+/// The cost parameters of the Argon2id key derivation that protects an
+/// exported `SecretKey`.
+///
+/// They are stored in the export, so keys exported with any parameters
+/// can be imported. The default is the OWASP minimum (19 MiB, two passes,
+/// one lane); raise them where unlocking can take longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KdfParams {
+    /// Memory to use, in KiB
+    pub memory_kib: u32,
+
+    /// Number of passes over the memory
+    pub iterations: u32,
+
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> KdfParams {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    // Derive the encryption key from a passphrase
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, Error> {
+        // The parameters may come from an untrusted export, so they are
+        // bounded before any memory is allocated or time spent
+        if self.memory_kib > MAX_KDF_MEMORY_KIB
+            || self.iterations > MAX_KDF_ITERATIONS
+            || self.parallelism > MAX_KDF_PARALLELISM
+        {
+            return Err(InnerError::InvalidKdfParams.into());
+        }
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|_| InnerError::InvalidKdfParams.into_err())?;
+        let mut key = Zeroizing::new([0; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+            .map_err(|_| InnerError::InvalidKdfParams.into_err())?;
+        Ok(key)
+    }
+}
+
+impl SecretKey {
+    /// Export this key encrypted under a passphrase, in the printable
+    /// `moenc0` form, with the default `KdfParams`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if encryption fails
+    pub fn export_encrypted(&self, passphrase: &str) -> Result<String, Error> {
+        self.export_encrypted_with_params(passphrase, &KdfParams::default(), &mut OsRng)
+    }
+
+    /// Export this key encrypted under a passphrase, in the printable
+    /// `moenc0` form.
+    ///
+    /// The encryption key is derived from the passphrase and a random salt
+    /// with Argon2id, and the secret key is encrypted with
+    /// XChaCha20-Poly1305. The version, cost parameters, salt and nonce
+    /// are stored ahead of the ciphertext and are authenticated with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the parameters are out of Argon2id's bounds or
+    /// above `MAX_KDF_MEMORY_KIB`, `MAX_KDF_ITERATIONS` or
+    /// `MAX_KDF_PARALLELISM`
+    pub fn export_encrypted_with_params<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        passphrase: &str,
+        params: &KdfParams,
+        rng: &mut R,
+    ) -> Result<String, Error> {
+        let mut salt: [u8; SALT_LEN] = [0; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        let mut nonce: [u8; NONCE_LEN] = [0; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let mut bytes = Vec::with_capacity(ENCRYPTED_KEY_LEN);
+        bytes.push(ENCRYPTED_KEY_VERSION);
+        bytes.extend_from_slice(params.memory_kib.to_le_bytes().as_slice());
+        bytes.extend_from_slice(params.iterations.to_le_bytes().as_slice());
+        bytes.extend_from_slice(params.parallelism.to_le_bytes().as_slice());
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+
+        let key = params.derive_key(passphrase, &salt)?;
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: self.as_bytes(),
+                    aad: &bytes,
+                },
+            )
+            .map_err(|_| InnerError::InvalidEncryptedKey.into_err())?;
+        bytes.extend_from_slice(&ciphertext);
+
+        Ok(format!("{ENCRYPTED_KEY_PREFIX}{}", zbase32::encode(&bytes)))
+    }
+
+    /// Import a key exported with `export_encrypted()`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the input is not an encrypted key, if it is of
+    /// an unknown version or asks for more than `MAX_KDF_MEMORY_KIB`,
+    /// `MAX_KDF_ITERATIONS` or `MAX_KDF_PARALLELISM`, or if the passphrase
+    /// is wrong or the input was altered.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn import_encrypted(s: &str, passphrase: &str) -> Result<SecretKey, Error> {
+        if !s.starts_with(ENCRYPTED_KEY_PREFIX) {
+            return Err(InnerError::InvalidPrintable.into_err());
+        }
+        let bytes = zbase32::decode(&s.as_bytes()[ENCRYPTED_KEY_PREFIX.len()..])?;
+        if bytes.len() != ENCRYPTED_KEY_LEN {
+            return Err(InnerError::InvalidEncryptedKey.into());
+        }
+        if bytes[0] != ENCRYPTED_KEY_VERSION {
+            return Err(InnerError::UnsupportedEncryptedKeyVersion(bytes[0]).into());
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let params = KdfParams {
+            memory_kib: u32_at(1),
+            iterations: u32_at(5),
+            parallelism: u32_at(9),
+        };
+        let salt = &bytes[13..13 + SALT_LEN];
+        let nonce = &bytes[13 + SALT_LEN..HEADER_LEN];
+
+        let key = params.derive_key(passphrase, salt)?;
+        let secret = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: &bytes[HEADER_LEN..],
+                        aad: &bytes[..HEADER_LEN],
+                    },
+                )
+                .map_err(|_| InnerError::DecryptionFailed.into_err())?,
+        );
+        Ok(SecretKey::from_bytes(secret.as_slice().try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_export_encrypted() {
+        use rand::rngs::OsRng;
+
+        // Cheap parameters, so that the test is quick
+        let params = KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let exported = secret_key
+            .export_encrypted_with_params("correct horse", &params, &mut OsRng)
+            .unwrap();
+        assert!(exported.starts_with(ENCRYPTED_KEY_PREFIX));
+        let encoded = secret_key.printable();
+        assert!(!exported.contains(encoded.trim_start_matches("mosec0")));
+
+        let imported = SecretKey::import_encrypted(&exported, "correct horse").unwrap();
+        assert_eq!(imported.as_bytes(), secret_key.as_bytes());
+
+        // Salts and nonces differ between exports
+        let again = secret_key
+            .export_encrypted_with_params("correct horse", &params, &mut OsRng)
+            .unwrap();
+        assert_ne!(again, exported);
+
+        let err = SecretKey::import_encrypted(&exported, "battery staple").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Decryption);
+        assert!(SecretKey::import_encrypted(&encoded, "correct horse").is_err());
+
+        // The parameters are authenticated
+        let mut bytes = zbase32::decode(&exported.as_bytes()[6..]).unwrap();
+        bytes[5] = 2;
+        let altered = format!("{ENCRYPTED_KEY_PREFIX}{}", zbase32::encode(&bytes));
+        assert!(SecretKey::import_encrypted(&altered, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_import_encrypted_limits() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let params = KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let exported = secret_key
+            .export_encrypted_with_params("correct horse", &params, &mut OsRng)
+            .unwrap();
+        let bytes = zbase32::decode(&exported.as_bytes()[6..]).unwrap();
+
+        // Excessive costs are refused before any hashing is done; a
+        // billion passes would never finish
+        for (at, cost) in [
+            (1, MAX_KDF_MEMORY_KIB + 1),
+            (1, u32::MAX),
+            (5, MAX_KDF_ITERATIONS + 1),
+            (5, u32::MAX),
+            (9, MAX_KDF_PARALLELISM + 1),
+        ] {
+            let mut bytes = bytes.clone();
+            bytes[at..at + 4].copy_from_slice(cost.to_le_bytes().as_slice());
+            let greedy = format!("{ENCRYPTED_KEY_PREFIX}{}", zbase32::encode(&bytes));
+            let err = SecretKey::import_encrypted(&greedy, "correct horse").unwrap_err();
+            assert!(matches!(err.inner, InnerError::InvalidKdfParams));
+        }
+
+        // and are not written either
+        let greedy = KdfParams {
+            iterations: MAX_KDF_ITERATIONS + 1,
+            ..params
+        };
+        assert!(secret_key
+            .export_encrypted_with_params("correct horse", &greedy, &mut OsRng)
+            .is_err());
+    }
+}
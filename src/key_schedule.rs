@@ -0,0 +1,312 @@
+use crate::{
+    Error, InnerError, Kind, OwnedRecord, OwnedTag, OwnedTagSet, PublicKey, Record, RecordFlags,
+    RecordParts, SecretKey, Timestamp,
+};
+use std::collections::BTreeMap;
+
+/// The deterministic key of key schedule records, so that each new
+/// schedule replaces the last
+pub const KEY_SCHEDULE_KEY: &[u8] = b"key-schedule";
+
+/// When a delegated subkey may sign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubkeyWindow {
+    /// The earliest record timestamp the subkey may sign
+    pub valid_from: Timestamp,
+
+    /// The latest record timestamp the subkey may sign, if it expires
+    pub valid_until: Option<Timestamp>,
+}
+
+impl SubkeyWindow {
+    /// Does this window include `at`?
+    #[must_use]
+    pub fn contains(&self, at: Timestamp) -> bool {
+        at >= self.valid_from && self.valid_until.is_none_or(|until| at <= until)
+    }
+}
+
+/// The subkeys a user has delegated signing to, as carried by a
+/// `KEY_SCHEDULE` record signed by the user's master key.
+///
+/// The payload is a little-endian `u32` count of subkeys, then for each
+/// subkey in ascending order its 32-byte public key and its window as two
+/// little-endian `u64` millisecond timestamps (an end of zero meaning it
+/// does not expire), then for each revoked key in ascending order its
+/// 32-byte public key and the little-endian `u64` millisecond timestamp
+/// it was revoked from. Each subkey that is not revoked is also listed in
+/// a `SUBKEY` tag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeySchedule {
+    subkeys: BTreeMap<PublicKey, SubkeyWindow>,
+    revoked: BTreeMap<PublicKey, Timestamp>,
+}
+
+impl KeySchedule {
+    /// Create an empty `KeySchedule`
+    #[must_use]
+    pub fn new() -> KeySchedule {
+        KeySchedule::default()
+    }
+
+    /// Delegate signing to `subkey` for records timestamped within
+    /// `window`, replacing any earlier window for it
+    pub fn add_subkey(&mut self, subkey: PublicKey, window: SubkeyWindow) {
+        let _ = self.subkeys.insert(subkey, window);
+    }
+
+    /// Revoke `key` from `at`. Records it signed that are timestamped at
+    /// or after `at` are no longer trusted, while earlier ones still are.
+    /// Revoking a key again replaces the earlier timestamp.
+    pub fn revoke(&mut self, key: PublicKey, at: Timestamp) {
+        let _ = self.revoked.insert(key, at);
+    }
+
+    /// Has `key` been revoked, from any time?
+    #[must_use]
+    pub fn is_revoked(&self, key: &PublicKey) -> bool {
+        self.revoked.contains_key(key)
+    }
+
+    /// When `key` was revoked from, if it was
+    #[must_use]
+    pub fn revoked_at(&self, key: &PublicKey) -> Option<Timestamp> {
+        self.revoked.get(key).copied()
+    }
+
+    /// Was `key` revoked as of `at`?
+    #[must_use]
+    pub fn is_revoked_at(&self, key: &PublicKey, at: Timestamp) -> bool {
+        self.revoked_at(key).is_some_and(|revoked| at >= revoked)
+    }
+
+    /// The window of a delegated subkey, revoked or not
+    #[must_use]
+    pub fn window(&self, subkey: &PublicKey) -> Option<SubkeyWindow> {
+        self.subkeys.get(subkey).copied()
+    }
+
+    /// Iterate over the delegated subkeys and their windows, in ascending
+    /// order of key. Revoked subkeys are included.
+    pub fn subkeys(&self) -> impl Iterator<Item = (&PublicKey, &SubkeyWindow)> {
+        self.subkeys.iter()
+    }
+
+    /// Iterate over the revoked keys and when they were revoked from, in
+    /// ascending order of key
+    pub fn revoked(&self) -> impl Iterator<Item = (&PublicKey, &Timestamp)> {
+        self.revoked.iter()
+    }
+
+    /// May `signing_key` sign records timestamped `at` under this
+    /// schedule?
+    #[must_use]
+    pub fn validates(&self, signing_key: &PublicKey, at: Timestamp) -> bool {
+        !self.is_revoked_at(signing_key, at)
+            && self
+                .subkeys
+                .get(signing_key)
+                .is_some_and(|window| window.contains(at))
+    }
+
+    /// Read a `KeySchedule` from a key schedule record, after verifying it
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `KEY_SCHEDULE`, is not
+    /// signed by its author, fails to verify, or if its payload is invalid
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_record(record: &Record) -> Result<KeySchedule, Error> {
+        if record.kind() != Kind::KEY_SCHEDULE {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        if record.signing_public_key() != record.author_public_key() {
+            return Err(InnerError::SigningKeyNotDelegated.into());
+        }
+        record.verify()?;
+
+        let payload = record.payload_bytes();
+        if payload.len() < 4 {
+            return Err(InnerError::InvalidKeySchedule.into());
+        }
+        let count = u32::from_le_bytes(payload[..4].try_into().unwrap()) as usize;
+        let rest = &payload[4..];
+        let subkeys_len = count
+            .checked_mul(48)
+            .filter(|len| *len <= rest.len())
+            .ok_or_else(|| InnerError::InvalidKeySchedule.into_err())?;
+        let (subkeys_bytes, revoked_bytes) = rest.split_at(subkeys_len);
+        if !revoked_bytes.len().is_multiple_of(40) {
+            return Err(InnerError::InvalidKeySchedule.into());
+        }
+
+        let timestamp = |bytes: &[u8]| {
+            Timestamp::from_millis(u64::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(|| InnerError::InvalidKeySchedule.into_err())
+        };
+        let mut schedule = KeySchedule::new();
+        for entry in subkeys_bytes.chunks_exact(48) {
+            let subkey = PublicKey::from_bytes(entry[..32].try_into().unwrap())?;
+            let valid_from = timestamp(&entry[32..40])?;
+            let valid_until = match timestamp(&entry[40..48])? {
+                until if until.as_millis() == 0 => None,
+                until if until < valid_from => {
+                    return Err(InnerError::InvalidKeySchedule.into());
+                }
+                until => Some(until),
+            };
+            let window = SubkeyWindow {
+                valid_from,
+                valid_until,
+            };
+            if schedule.subkeys.insert(subkey, window).is_some() {
+                return Err(InnerError::InvalidKeySchedule.into());
+            }
+        }
+        for entry in revoked_bytes.chunks_exact(40) {
+            let key = PublicKey::from_bytes(entry[..32].try_into().unwrap())?;
+            schedule.revoke(key, timestamp(&entry[32..40])?);
+        }
+        Ok(schedule)
+    }
+
+    /// The payload of a key schedule record of this schedule
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(4 + self.subkeys.len() * 48 + self.revoked.len() * 40);
+        let count = u32::try_from(self.subkeys.len()).unwrap();
+        payload.extend_from_slice(count.to_le_bytes().as_slice());
+        for (subkey, window) in &self.subkeys {
+            payload.extend_from_slice(subkey.as_bytes());
+            payload.extend_from_slice(window.valid_from.as_millis().to_le_bytes().as_slice());
+            let until = window.valid_until.map_or(0, |t| t.as_millis());
+            payload.extend_from_slice(until.to_le_bytes().as_slice());
+        }
+        for (key, at) in &self.revoked {
+            payload.extend_from_slice(key.as_bytes());
+            payload.extend_from_slice(at.as_millis().to_le_bytes().as_slice());
+        }
+        payload
+    }
+
+    /// Create a key schedule record of this schedule, signed by the
+    /// master key
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the schedule is too long for a record, or if
+    /// signing fails
+    pub fn to_record(
+        &self,
+        master_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        let mut tags = OwnedTagSet::new();
+        for subkey in self.subkeys.keys().filter(|k| !self.is_revoked(k)) {
+            tags.push(&OwnedTag::new_subkey(subkey))?;
+        }
+        OwnedRecord::new(
+            master_key,
+            &RecordParts {
+                kind: Kind::KEY_SCHEDULE,
+                deterministic_key: Some(KEY_SCHEDULE_KEY),
+                timestamp,
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: tags.as_bytes(),
+                payload: &self.to_payload(),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tag::iter_tags;
+
+    #[test]
+    fn test_key_schedule() {
+        use rand::rngs::OsRng;
+
+        let master = SecretKey::generate(&mut OsRng);
+        let [phone, laptop, lost] =
+            std::array::from_fn(|_| SecretKey::generate(&mut OsRng).public());
+        let at = |millis: u64| Timestamp::from_millis(millis).unwrap();
+
+        let mut schedule = KeySchedule::new();
+        schedule.add_subkey(
+            phone,
+            SubkeyWindow {
+                valid_from: at(1000),
+                valid_until: Some(at(2000)),
+            },
+        );
+        schedule.add_subkey(
+            laptop,
+            SubkeyWindow {
+                valid_from: at(1500),
+                valid_until: None,
+            },
+        );
+        schedule.add_subkey(
+            lost,
+            SubkeyWindow {
+                valid_from: at(0),
+                valid_until: None,
+            },
+        );
+        schedule.revoke(lost, at(1000));
+
+        assert!(schedule.validates(&phone, at(1000)));
+        assert!(schedule.validates(&phone, at(2000)));
+        assert!(!schedule.validates(&phone, at(2001)));
+        assert!(!schedule.validates(&laptop, at(1499)));
+        assert!(schedule.validates(&laptop, at(u64::from(u32::MAX))));
+        assert!(schedule.validates(&lost, at(999)));
+        assert!(!schedule.validates(&lost, at(1000)));
+        assert_eq!(schedule.revoked_at(&lost), Some(at(1000)));
+        assert!(!schedule.validates(&master.public(), at(1000)));
+
+        let record = schedule.to_record(&master, at(3000)).unwrap();
+        assert_eq!(KeySchedule::from_record(&record).unwrap(), schedule);
+        let tagged: Vec<_> = iter_tags(record.tags_bytes())
+            .filter_map(|t| t.get_public_key().ok().flatten())
+            .collect();
+        assert_eq!(tagged.len(), 2);
+        assert!(!tagged.contains(&lost));
+
+        // Only the master key may sign a schedule
+        let subkey_secret = SecretKey::generate(&mut OsRng);
+        let forged = OwnedRecord::new_replacement(
+            &subkey_secret,
+            record.address(),
+            at(4000),
+            RecordFlags::empty(),
+            0,
+            b"",
+            &schedule.to_payload(),
+        )
+        .unwrap();
+        assert!(KeySchedule::from_record(&forged).is_err());
+
+        // Malformed payloads are rejected
+        let mut payload = schedule.to_payload();
+        payload[0] = 9;
+        let bad = OwnedRecord::new_replacement(
+            &master,
+            record.address(),
+            at(4000),
+            RecordFlags::empty(),
+            0,
+            b"",
+            &payload,
+        )
+        .unwrap();
+        assert_eq!(
+            KeySchedule::from_record(&bad).unwrap_err().kind(),
+            crate::ErrorKind::InvalidEncoding
+        );
+    }
+}
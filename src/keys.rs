@@ -1,9 +1,10 @@
-use crate::{DalekSigningKey, DalekVerifyingKey};
-use crate::{Error, InnerError};
+use crate::{zbase32, Error, InnerError};
+use crate::{DalekSigningKey, DalekVerifyingKey, EncryptionPublicKey, EncryptionSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// A public signing key representing a server or user,
 /// whether a master key or subkey.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PublicKey([u8; 32]);
 
 impl PublicKey {
@@ -47,10 +48,17 @@ impl PublicKey {
         Ok(Self::from_verifying_key(&vk))
     }
 
+    /// The X25519 `EncryptionPublicKey` corresponding to this identity, for
+    /// encrypting to its holder
+    #[must_use]
+    pub fn to_encryption_key(&self) -> EncryptionPublicKey {
+        EncryptionPublicKey::from_montgomery(self.to_verifying_key().to_montgomery())
+    }
+
     /// Convert a `PublicKey` into the human printable `mopub0` form.
     #[must_use]
     pub fn printable(&self) -> String {
-        format!("mopub0{}", z32::encode(&self.0))
+        format!("mopub0{}", zbase32::encode(&self.0))
     }
 
     /// Import a `PublicKey` from its printable form
@@ -62,7 +70,11 @@ impl PublicKey {
         if !s.starts_with("mopub0") {
             return Err(InnerError::InvalidPrintable.into_err());
         }
-        let bytes = z32::decode(&s.as_bytes()[6..])?;
+        let bytes = zbase32::decode(&s.as_bytes()[6..])?;
+        // Reject strings that only decode because spare bits are ignored
+        if zbase32::encode(&bytes).as_bytes() != &s.as_bytes()[6..] {
+            return Err(InnerError::InvalidPrintable.into_err());
+        }
         let bytes: [u8; 32] = bytes
             .try_into()
             .map_err(|_| InnerError::KeyLength.into_err())?;
@@ -70,16 +82,52 @@ impl PublicKey {
     }
 }
 
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<PublicKey, Error> {
+        let bytes: &[u8; 32] = bytes
+            .try_into()
+            .map_err(|_| InnerError::KeyLength.into_err())?;
+        PublicKey::from_bytes(bytes)
+    }
+}
+
+impl TryFrom<[u8; 32]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(bytes: [u8; 32]) -> Result<PublicKey, Error> {
+        PublicKey::from_bytes(&bytes)
+    }
+}
+
 impl std::fmt::Display for PublicKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.printable())
     }
 }
 
+impl std::str::FromStr for PublicKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PublicKey, Error> {
+        PublicKey::from_printable(s)
+    }
+}
+
 /// A secret signing key
+///
+/// The key bytes are zeroed when a `SecretKey` is dropped, and `Debug` and
+/// `Display` do not print them; use `printable()` to export a key.
 // WARNING: do not implement Eq or PartialEq without a constant time algorithm
 #[allow(missing_copy_implementations)]
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct SecretKey([u8; 32]);
 
 impl SecretKey {
@@ -128,10 +176,18 @@ impl SecretKey {
         PublicKey::from_verifying_key(&self.to_signing_key().verifying_key())
     }
 
+    /// The X25519 `EncryptionSecret` corresponding to this identity, for
+    /// decrypting what was encrypted to `PublicKey::to_encryption_key()`
+    #[must_use]
+    pub fn to_encryption_secret(&self) -> EncryptionSecret {
+        let scalar_bytes = Zeroizing::new(self.to_signing_key().to_scalar_bytes());
+        EncryptionSecret::from_bytes(&scalar_bytes)
+    }
+
     /// Convert a `SecretKey` into the human printable `mosec0` form.
     #[must_use]
     pub fn printable(&self) -> String {
-        format!("mosec0{}", z32::encode(&self.0))
+        format!("mosec0{}", zbase32::encode(&self.0))
     }
 
     /// Import a `SecretKey` from its printable form
@@ -143,17 +199,32 @@ impl SecretKey {
         if !s.starts_with("mosec0") {
             return Err(InnerError::InvalidPrintable.into_err());
         }
-        let bytes = z32::decode(&s.as_bytes()[6..])?;
-        let bytes: [u8; 32] = bytes
+        let bytes = Zeroizing::new(zbase32::decode(&s.as_bytes()[6..])?);
+        let bytes: &[u8; 32] = bytes
+            .as_slice()
             .try_into()
             .map_err(|_| InnerError::KeyLength.into_err())?;
-        Ok(Self::from_bytes(&bytes))
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKey {}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SecretKey").field(&"<redacted>").finish()
     }
 }
 
 impl std::fmt::Display for SecretKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.printable())
+        write!(f, "mosec0<redacted>")
     }
 }
 
@@ -161,7 +232,7 @@ impl std::fmt::Display for SecretKey {
 mod test {
     #[test]
     fn test_generate() {
-        use crate::SecretKey;
+        use crate::{PublicKey, SecretKey};
         use rand::rngs::OsRng;
 
         let mut csprng = OsRng;
@@ -171,5 +242,42 @@ mod test {
 
         println!("public: {public_key}");
         println!("secret: {secret_key}");
+
+        // Secret key bytes are never formatted
+        let printable = secret_key.printable();
+        let encoded = printable.trim_start_matches("mosec0");
+        assert!(!format!("{secret_key}").contains(encoded));
+        assert!(!format!("{secret_key:?}").contains(encoded));
+        assert_eq!(
+            SecretKey::from_printable(&printable).unwrap().public(),
+            public_key
+        );
+
+        assert_eq!(
+            PublicKey::try_from(public_key.as_ref()).unwrap(),
+            public_key
+        );
+        assert_eq!(
+            PublicKey::try_from(public_key.to_bytes()).unwrap(),
+            public_key
+        );
+        assert!(PublicKey::try_from(&public_key.as_ref()[1..]).is_err());
+
+        let printable = public_key.printable();
+        assert_eq!(printable.parse::<PublicKey>().unwrap(), public_key);
+        // Only one data bit is in the last character; setting a spare bit
+        // is rejected, as are other prefixes and truncations
+        let mut spare = printable.clone();
+        let last = if spare.ends_with('y') { "b" } else { "t" };
+        spare.replace_range(spare.len() - 1.., last);
+        assert!(spare.parse::<PublicKey>().is_err());
+        assert!(printable
+            .replacen("mopub0", "mosec0", 1)
+            .parse::<PublicKey>()
+            .is_err());
+        assert!(printable
+            .trim_end_matches(['y', 'o'])
+            .parse::<PublicKey>()
+            .is_err());
     }
 }
@@ -1,8 +1,45 @@
 /// A record kind
+///
+/// Applications can define their own kinds as constants with
+/// `Kind::new()`, and match on them:
+///
+/// ```
+/// # use mosaic_core::Kind;
+/// const MY_KIND: Kind = Kind::new(0x8001);
+///
+/// fn describe(kind: Kind) -> &'static str {
+///     match kind {
+///         Kind::BLOG_POST => "blog post",
+///         MY_KIND => "my kind",
+///         _ => "other",
+///     }
+/// }
+/// # assert_eq!(describe(Kind::new(0x8001)), "my kind");
+/// ```
+///
+/// Kinds below `0x8000` are allocated by the Mosaic specification, and
+/// kinds from `0x8000` are application-specific. The kinds this crate adds
+/// beyond the specification's (follow lists, identity proofs, reports,
+/// reactions, sync state and read markers) have no allocation yet, so
+/// they use the application-specific range from `0xF001`; applications
+/// should pick their own kinds below that.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kind(pub u16);
 
 impl Kind {
+    /// Create a `Kind` from its numeric value
+    #[must_use]
+    pub const fn new(kind: u16) -> Kind {
+        Kind(kind)
+    }
+
+    /// The numeric value of this `Kind`
+    #[must_use]
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
     /// Key Schedule Record
     pub const KEY_SCHEDULE: Kind = Kind(0x1);
 
@@ -20,6 +57,30 @@ impl Kind {
 
     /// Chat Message Record
     pub const CHAT_MESSAGE: Kind = Kind(0x6);
+
+    /// Follow List Record
+    pub const FOLLOW_LIST: Kind = Kind(0xF001);
+
+    /// Identity Proof Record
+    pub const IDENTITY_PROOF: Kind = Kind(0xF002);
+
+    /// Abuse Report Record
+    pub const REPORT: Kind = Kind(0xF003);
+
+    /// Reaction Record
+    pub const REACTION: Kind = Kind(0xF004);
+
+    /// Device Sync State Record
+    pub const SYNC_STATE: Kind = Kind(0xF005);
+
+    /// Read Marker Record
+    pub const READ_MARKER: Kind = Kind(0xF006);
+
+    /// Is this one of the kinds defined by this crate?
+    #[must_use]
+    pub const fn is_known(self) -> bool {
+        matches!(self.0, 0x1..=0x6 | 0xF001..=0xF006)
+    }
 }
 
 impl std::fmt::Display for Kind {
@@ -31,6 +92,12 @@ impl std::fmt::Display for Kind {
             0x4 => write!(f, "Reply Comment"),
             0x5 => write!(f, "Blog Post"),
             0x6 => write!(f, "Chat Message"),
+            0xF001 => write!(f, "Follow List"),
+            0xF002 => write!(f, "Identity Proof"),
+            0xF003 => write!(f, "Abuse Report"),
+            0xF004 => write!(f, "Reaction"),
+            0xF005 => write!(f, "Sync State"),
+            0xF006 => write!(f, "Read Marker"),
             u => write!(f, "Kind({u})"),
         }
     }
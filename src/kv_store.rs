@@ -0,0 +1,973 @@
+// The parts of the persistent stores that do not depend on the database
+// engine: the table layout, the index keys, candidate selection and index
+// maintenance. `RedbStore` and `LmdbStore` only adapt their transactions to
+// `KvRead` and `KvWrite`.
+
+use crate::tag_index::{value_key, value_keys};
+use crate::{
+    Address, Counter, Cursor, DeletionMode, Direction, Error, Filter, Histogram, Id, Kind, Metrics,
+    OwnedRecord, Page, PublicKey, PutOutcome, Record, RecordStore, Snapshot, TagKey, WriteBatch,
+    WriteOp, WriteResult, MAX_RECORD_LEN,
+};
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+// The tables of a persistent store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KvTable {
+    // Id -> record bytes. Since Ids sort in time order this is also the
+    // timestamp index.
+    Records,
+
+    // Address -> Id of the current record at that address
+    Addresses,
+
+    // Deleted Ids, with empty values
+    Deleted,
+
+    // Author key -> Ids (many values per key)
+    Authors,
+
+    // Kind (little-endian) -> Ids (many values per key)
+    Kinds,
+
+    // TagKey -> Ids (many values per key)
+    Tags,
+
+    // Tag type (big-endian) and value -> Ids (many values per key). Each
+    // type's values are contiguous and in order, so a value prefix is a
+    // range scan.
+    TagValues,
+}
+
+// A range of keys
+pub(crate) type KeyRange<'a> = (Bound<&'a [u8]>, Bound<&'a [u8]>);
+
+// A visitor of keys and values, returning false to stop
+pub(crate) type ScanFn<'f> = dyn FnMut(&[u8], &[u8]) -> Result<bool, Error> + 'f;
+
+// Read access to the tables, within one transaction
+pub(crate) trait KvRead {
+    // Call `f` with the (first) value under `key`. Returns false if there
+    // is none.
+    fn get(
+        &self,
+        table: KvTable,
+        key: &[u8],
+        f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error>;
+
+    // Call `f` with each key and value in the range, in key order (then
+    // value order), or the reverse, until it returns false
+    fn scan(
+        &self,
+        table: KvTable,
+        range: KeyRange<'_>,
+        reverse: bool,
+        f: &mut ScanFn<'_>,
+    ) -> Result<(), Error>;
+}
+
+impl<R: KvRead + ?Sized> KvRead for &R {
+    fn get(
+        &self,
+        table: KvTable,
+        key: &[u8],
+        f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        (**self).get(table, key, f)
+    }
+
+    fn scan(
+        &self,
+        table: KvTable,
+        range: KeyRange<'_>,
+        reverse: bool,
+        f: &mut ScanFn<'_>,
+    ) -> Result<(), Error> {
+        (**self).scan(table, range, reverse, f)
+    }
+}
+
+// Write access to the tables, within one transaction
+pub(crate) trait KvWrite: KvRead {
+    // Set the value under `key`, or add it to the values under `key` in a
+    // table with many values per key
+    fn insert(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error>;
+
+    // Remove `key`, or only `value` from under `key` in a table with many
+    // values per key
+    fn remove(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error>;
+}
+
+// A database engine holding the tables
+pub(crate) trait KvBackend {
+    // A read transaction
+    type Reader: KvRead + Send + 'static;
+
+    // The `Snapshot` type handed out for a read transaction
+    type Snapshot: Snapshot + Send + 'static;
+
+    fn path(&self) -> &Path;
+
+    fn read(&self) -> Result<Self::Reader, Error>;
+
+    fn snapshot(&self) -> Result<Self::Snapshot, Error>;
+
+    // Run `f` in a write transaction, committing it if `f` succeeds and
+    // wrote anything
+    fn write<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn KvWrite) -> Result<T, Error>;
+
+    // Give space freed by removed records back, if the engine can
+    fn reclaim(&mut self) -> Result<(), Error>;
+}
+
+// Keys in our tables are always written from 48-byte Ids
+pub(crate) fn id_from_slice(slice: &[u8]) -> Id {
+    let mut bytes: [u8; 48] = [0; 48];
+    bytes.copy_from_slice(slice);
+    Id::from_owned_bytes_no_verify(bytes)
+}
+
+// View stored record bytes
+fn record_from_slice(slice: &[u8]) -> Result<&Record, Error> {
+    // SAFETY: only verified records are written to the database
+    unsafe { Record::from_bytes(slice) }
+}
+
+fn kind_key(kind: Kind) -> [u8; 2] {
+    kind.0.to_le_bytes()
+}
+
+fn index_keys(record: &Record) -> (PublicKey, [u8; 2]) {
+    (record.author_public_key(), kind_key(record.kind()))
+}
+
+fn contains<R: KvRead + ?Sized>(r: &R, table: KvTable, key: &[u8]) -> Result<bool, Error> {
+    r.get(table, key, &mut |_| Ok(()))
+}
+
+fn get_id<R: KvRead + ?Sized>(r: &R, table: KvTable, key: &[u8]) -> Result<Option<Id>, Error> {
+    let mut output: Option<Id> = None;
+    let _ = r.get(table, key, &mut |value| {
+        output = Some(id_from_slice(value));
+        Ok(())
+    })?;
+    Ok(output)
+}
+
+// Call `f` with a stored record, including one hidden by `DeletionMode::Hide`
+pub(crate) fn with_record<R, F, T>(r: &R, id: &Id, f: F) -> Result<Option<T>, Error>
+where
+    R: KvRead + ?Sized,
+    F: FnOnce(&Record) -> T,
+{
+    let mut f = Some(f);
+    let mut output: Option<T> = None;
+    let _ = r.get(KvTable::Records, id.as_ref(), &mut |bytes| {
+        let record = record_from_slice(bytes)?;
+        output = f.take().map(|f| f(record));
+        Ok(())
+    })?;
+    Ok(output)
+}
+
+fn is_deleted<R: KvRead + ?Sized>(r: &R, id: &Id) -> Result<bool, Error> {
+    contains(r, KvTable::Deleted, id.as_ref())
+}
+
+fn get_by_id<R: KvRead + ?Sized>(r: &R, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+    if is_deleted(r, id)? {
+        return Ok(None);
+    }
+    with_record(r, id, Record::to_owned)
+}
+
+fn get_by_address<R: KvRead + ?Sized>(
+    r: &R,
+    address: &Address,
+) -> Result<Option<OwnedRecord>, Error> {
+    match get_id(r, KvTable::Addresses, address.as_ref())? {
+        Some(id) => get_by_id(r, &id),
+        None => Ok(None),
+    }
+}
+
+// How many records `Records` reads at a time
+const RECORDS_BATCH: usize = 256;
+
+// Every record, in Id order, read lazily a batch at a time within one read
+// transaction. A backend error reading a later batch is yielded, and ends
+// the iteration.
+pub(crate) struct Records<R> {
+    reader: R,
+    batch: std::vec::IntoIter<OwnedRecord>,
+    last: Option<Id>,
+    done: bool,
+}
+
+impl<R: KvRead> Records<R> {
+    fn new(reader: R) -> Result<Records<R>, Error> {
+        let mut records = Records {
+            reader,
+            batch: Vec::new().into_iter(),
+            last: None,
+            done: false,
+        };
+        records.fill()?;
+        Ok(records)
+    }
+
+    fn fill(&mut self) -> Result<(), Error> {
+        let mut batch: Vec<OwnedRecord> = Vec::with_capacity(RECORDS_BATCH);
+        let start = self
+            .last
+            .as_ref()
+            .map_or(Bound::Unbounded, |id| Bound::Excluded(id.as_ref()));
+        self.reader.scan(
+            KvTable::Records,
+            (start, Bound::Unbounded),
+            false,
+            &mut |_, bytes| {
+                batch.push(record_from_slice(bytes)?.to_owned());
+                Ok(batch.len() < RECORDS_BATCH)
+            },
+        )?;
+        self.done = batch.len() < RECORDS_BATCH;
+        self.last = batch.last().map(|record| record.id());
+        self.batch = batch.into_iter();
+        Ok(())
+    }
+}
+
+impl<R: KvRead> Iterator for Records<R> {
+    type Item = Result<OwnedRecord, Error>;
+
+    fn next(&mut self) -> Option<Result<OwnedRecord, Error>> {
+        loop {
+            if let Some(record) = self.batch.next() {
+                return Some(Ok(record));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+// Add the Ids stored under `key` in an index
+fn add_ids<R: KvRead + ?Sized>(
+    r: &R,
+    table: KvTable,
+    key: &[u8],
+    output: &mut BTreeSet<Id>,
+) -> Result<(), Error> {
+    r.scan(
+        table,
+        (Bound::Included(key), Bound::Included(key)),
+        false,
+        &mut |_, id| {
+            let _ = output.insert(id_from_slice(id));
+            Ok(true)
+        },
+    )
+}
+
+// Ids from the most selective index available for a filter, or `None` if
+// the filter constrains nothing but time
+fn index_candidates<R: KvRead + ?Sized>(
+    r: &R,
+    filter: &Filter,
+) -> Result<Option<BTreeSet<Id>>, Error> {
+    let mut output: BTreeSet<Id> = BTreeSet::new();
+    if !filter.ids.is_empty() {
+        for id in &filter.ids {
+            if contains(r, KvTable::Records, id.as_ref())? {
+                let _ = output.insert(*id);
+            }
+        }
+    } else if !filter.addresses.is_empty() {
+        for address in &filter.addresses {
+            if let Some(id) = get_id(r, KvTable::Addresses, address.as_ref())? {
+                let _ = output.insert(id);
+            }
+        }
+    } else if !filter.tags.is_empty() {
+        for (ty, value) in &filter.tags {
+            let key = TagKey::new(*ty, value);
+            add_ids(r, KvTable::Tags, key.as_bytes(), &mut output)?;
+        }
+    } else if !filter.tag_prefixes.is_empty() {
+        for (ty, prefix) in &filter.tag_prefixes {
+            let start = value_key(*ty, prefix);
+            r.scan(
+                KvTable::TagValues,
+                (Bound::Included(&start), Bound::Unbounded),
+                false,
+                &mut |key, id| {
+                    if !key.starts_with(&start) {
+                        return Ok(false);
+                    }
+                    let _ = output.insert(id_from_slice(id));
+                    Ok(true)
+                },
+            )?;
+        }
+    } else if !filter.author_keys.is_empty() {
+        for pk in &filter.author_keys {
+            add_ids(r, KvTable::Authors, pk.as_bytes(), &mut output)?;
+        }
+    } else if !filter.kinds.is_empty() {
+        for kind in &filter.kinds {
+            add_ids(r, KvTable::Kinds, &kind_key(*kind), &mut output)?;
+        }
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(output))
+}
+
+// The range of Ids within the filter's time window and beyond the cursor,
+// or `None` if it is empty
+fn id_range(filter: &Filter, cursor: &Cursor) -> Option<(Bound<Id>, Bound<Id>)> {
+    let mut start = filter.since.map_or(Bound::Unbounded, |t| {
+        Bound::Included(Id::min_for_timestamp(t))
+    });
+    let mut end = filter.until.map_or(Bound::Unbounded, |t| {
+        Bound::Excluded(Id::min_for_timestamp(t))
+    });
+    match (cursor.direction(), cursor.last_id()) {
+        (_, None) => {}
+        (Direction::OldestFirst, Some(after)) => {
+            if !matches!(start, Bound::Included(s) if s > after) {
+                start = Bound::Excluded(after);
+            }
+        }
+        (Direction::NewestFirst, Some(after)) => {
+            if !matches!(end, Bound::Excluded(e) if e <= after) {
+                end = Bound::Excluded(after);
+            }
+        }
+    }
+    // The end is never inclusive
+    match (start, end) {
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e)) if s >= e => None,
+        _ => Some((start, end)),
+    }
+}
+
+fn key_range(range: &(Bound<Id>, Bound<Id>)) -> KeyRange<'_> {
+    (
+        range.0.as_ref().map(AsRef::as_ref),
+        range.1.as_ref().map(AsRef::as_ref),
+    )
+}
+
+// One page of matching records, reading only as far past the cursor as
+// it takes to fill the page
+fn page<R: KvRead + ?Sized>(r: &R, filter: &Filter, cursor: &Cursor) -> Result<Page, Error> {
+    filter.validate()?;
+    let reverse = cursor.direction() == Direction::NewestFirst;
+    let limit = filter.limit.map_or(usize::MAX, |limit| limit as usize);
+    let mut output: Vec<OwnedRecord> = vec![];
+    let Some(range) = id_range(filter, cursor).filter(|_| limit > 0) else {
+        return Ok(Page::new(output, filter, cursor.direction()));
+    };
+    let mut visit = |bytes: &[u8]| -> Result<bool, Error> {
+        let record = record_from_slice(bytes)?;
+        if filter.matches(record) && (filter.include_deleted || !is_deleted(r, &record.id())?) {
+            output.push(record.to_owned());
+        }
+        Ok(output.len() < limit)
+    };
+    if let Some(ids) = index_candidates(r, filter)? {
+        let mut ids = ids.range(range);
+        while let Some(id) = if reverse { ids.next_back() } else { ids.next() } {
+            let mut more = true;
+            let _ = r.get(KvTable::Records, id.as_ref(), &mut |bytes| {
+                more = visit(bytes)?;
+                Ok(())
+            })?;
+            if !more {
+                break;
+            }
+        }
+    } else {
+        r.scan(
+            KvTable::Records,
+            key_range(&range),
+            reverse,
+            &mut |_, bytes| visit(bytes),
+        )?;
+    }
+    Ok(Page::new(output, filter, cursor.direction()))
+}
+
+// Matching records, newest first. Ids sort in time order, so this is the
+// first page from the newest end, and reading stops once the limit is met.
+fn query<R: KvRead + ?Sized>(r: &R, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+    Ok(page(r, filter, &Cursor::start(Direction::NewestFirst))?.records)
+}
+
+fn put<W: KvWrite + ?Sized>(
+    w: &mut W,
+    record: &Record,
+    max_record_len: usize,
+) -> Result<PutOutcome, Error> {
+    if record.is_ephemeral() {
+        return Ok(PutOutcome::Ephemeral);
+    }
+    if record.as_bytes().len() > max_record_len {
+        return Ok(PutOutcome::TooLarge);
+    }
+    let id = record.id();
+    let address = record.address();
+
+    if is_deleted(w, &id)? {
+        return Ok(PutOutcome::Deleted);
+    }
+    if contains(w, KvTable::Records, id.as_ref())? {
+        return Ok(PutOutcome::Duplicate);
+    }
+
+    if let Some(current_id) = get_id(w, KvTable::Addresses, address.as_ref())? {
+        if let Some(current) = with_record(w, &current_id, Record::to_owned)? {
+            if !record.supersedes(&current) {
+                return Ok(PutOutcome::Superseded);
+            }
+            w.remove(KvTable::Records, current_id.as_ref(), &[])?;
+            unindex(w, &current)?;
+        }
+    }
+
+    let (author, kind) = index_keys(record);
+    w.insert(KvTable::Records, id.as_ref(), record.as_bytes())?;
+    w.insert(KvTable::Addresses, address.as_ref(), id.as_ref())?;
+    w.insert(KvTable::Authors, author.as_bytes(), id.as_ref())?;
+    w.insert(KvTable::Kinds, &kind, id.as_ref())?;
+    for key in TagKey::for_record(record) {
+        w.insert(KvTable::Tags, key.as_bytes(), id.as_ref())?;
+    }
+    for key in value_keys(record) {
+        w.insert(KvTable::TagValues, &key, id.as_ref())?;
+    }
+
+    Ok(PutOutcome::Stored)
+}
+
+fn delete<W: KvWrite + ?Sized>(w: &mut W, id: &Id, mode: DeletionMode) -> Result<bool, Error> {
+    let newly = !is_deleted(w, id)?;
+    w.insert(KvTable::Deleted, id.as_ref(), &[])?;
+
+    if mode == DeletionMode::Hide {
+        return Ok(newly && contains(w, KvTable::Records, id.as_ref())?);
+    }
+    evict(w, id)
+}
+
+// Remove a record and its index entries, leaving the deleted table alone
+fn evict<W: KvWrite + ?Sized>(w: &mut W, id: &Id) -> Result<bool, Error> {
+    let Some(record) = with_record(w, id, Record::to_owned)? else {
+        return Ok(false);
+    };
+    w.remove(KvTable::Records, id.as_ref(), &[])?;
+    unindex(w, &record)?;
+    let address = record.address();
+    if get_id(w, KvTable::Addresses, address.as_ref())? == Some(*id) {
+        w.remove(KvTable::Addresses, address.as_ref(), &[])?;
+    }
+    Ok(true)
+}
+
+// Remove a record from the author, kind, tag and tag value indexes
+fn unindex<W: KvWrite + ?Sized>(w: &mut W, record: &Record) -> Result<(), Error> {
+    let id = record.id();
+    let (author, kind) = index_keys(record);
+    w.remove(KvTable::Authors, author.as_bytes(), id.as_ref())?;
+    w.remove(KvTable::Kinds, &kind, id.as_ref())?;
+    for key in TagKey::for_record(record) {
+        w.remove(KvTable::Tags, key.as_bytes(), id.as_ref())?;
+    }
+    for key in value_keys(record) {
+        w.remove(KvTable::TagValues, &key, id.as_ref())?;
+    }
+    Ok(())
+}
+
+// A `RecordStore` over any `KvBackend`
+pub(crate) struct KvStore<B> {
+    backend: B,
+    deletion_mode: DeletionMode,
+    max_record_len: usize,
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl<B: KvBackend> KvStore<B> {
+    pub(crate) fn new(backend: B) -> KvStore<B> {
+        KvStore {
+            backend,
+            deletion_mode: DeletionMode::default(),
+            max_record_len: MAX_RECORD_LEN,
+            metrics: None,
+        }
+    }
+
+    pub(crate) fn fmt_debug(
+        &self,
+        name: &str,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct(name)
+            .field("path", &self.backend.path())
+            .field("deletion_mode", &self.deletion_mode)
+            .field("max_record_len", &self.max_record_len)
+            .field("metrics", &self.metrics)
+            .finish_non_exhaustive()
+    }
+
+    pub(crate) fn set_deletion_mode(&mut self, mode: DeletionMode) {
+        self.deletion_mode = mode;
+    }
+
+    pub(crate) fn set_max_record_len(&mut self, max_len: usize) {
+        self.max_record_len = max_len.min(MAX_RECORD_LEN);
+    }
+
+    pub(crate) fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    pub(crate) fn with_record<F, T>(&self, id: &Id, f: F) -> Result<Option<T>, Error>
+    where
+        F: FnOnce(&Record) -> T,
+    {
+        with_record(&self.backend.read()?, id, f)
+    }
+
+    fn metric(&self, counter: Counter) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.increment(counter, 1);
+        }
+    }
+}
+
+impl<B: KvBackend> RecordStore for KvStore<B> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(id = %record.id(), kind = %record.kind(), len = record.as_bytes().len()),
+            ret,
+            err
+        )
+    )]
+    fn put(&mut self, record: &Record) -> Result<PutOutcome, Error> {
+        let max_record_len = self.max_record_len;
+        let outcome = self.backend.write(|w| put(w, record, max_record_len))?;
+        if outcome == PutOutcome::Stored {
+            self.metric(Counter::StorePuts);
+        }
+        Ok(outcome)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(id = %id), err)
+    )]
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+        get_by_id(&self.backend.read()?, id)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(address = %address), err)
+    )]
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+        get_by_address(&self.backend.read()?, address)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(id = %id), ret, err)
+    )]
+    fn delete(&mut self, id: &Id) -> Result<bool, Error> {
+        let mode = self.deletion_mode;
+        let present = self.backend.write(|w| delete(w, id, mode))?;
+        if present {
+            self.metric(Counter::StoreDeletes);
+        }
+        Ok(present)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(id = %id), ret, err)
+    )]
+    fn evict(&mut self, id: &Id) -> Result<bool, Error> {
+        self.backend.write(|w| evict(w, id))
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<Vec<WriteResult>, Error> {
+        let (max_record_len, mode) = (self.max_record_len, self.deletion_mode);
+        let results = self.backend.write(|w| {
+            let mut results: Vec<WriteResult> = Vec::with_capacity(batch.len());
+            for op in batch {
+                results.push(match op {
+                    WriteOp::Put(record) => WriteResult::Put(put(w, &record, max_record_len)?),
+                    WriteOp::Delete(id) => WriteResult::Delete(delete(w, &id, mode)?),
+                });
+            }
+            Ok(results)
+        })?;
+        for result in &results {
+            match result {
+                WriteResult::Put(PutOutcome::Stored) => self.metric(Counter::StorePuts),
+                WriteResult::Delete(true) => self.metric(Counter::StoreDeletes),
+                _ => {}
+            }
+        }
+        Ok(results)
+    }
+
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+        is_deleted(&self.backend.read()?, id)
+    }
+
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+        Ok(Box::new(Records::new(self.backend.read()?)?))
+    }
+
+    fn page(&self, filter: &Filter, cursor: &Cursor) -> Result<Page, Error> {
+        page(&self.backend.read()?, filter, cursor)
+    }
+
+    fn snapshot(&self) -> Result<Box<dyn Snapshot + Send>, Error> {
+        Ok(Box::new(self.backend.snapshot()?))
+    }
+
+    fn compact(&mut self) -> Result<usize, Error> {
+        let superseded = crate::record_store::superseded_ids(self.records()?)?;
+        let mut count: usize = 0;
+        for id in superseded {
+            if self.evict(&id)? {
+                count += 1;
+            }
+        }
+        self.backend.reclaim()?;
+        Ok(count)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(filter = ?filter, results = tracing::field::Empty),
+            err
+        )
+    )]
+    fn query(&self, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+        // One read transaction for the whole query, so that it sees a
+        // single consistent state of the store
+        let output = query(&self.backend.read()?, filter)?;
+        #[cfg(feature = "tracing")]
+        let _ = tracing::Span::current().record("results", output.len());
+        if let Some(ref metrics) = self.metrics {
+            metrics.increment(Counter::StoreQueries, 1);
+            metrics.observe(Histogram::QueryResults, output.len() as u64);
+        }
+        Ok(output)
+    }
+}
+
+// A `Snapshot` over a read transaction
+pub(crate) struct KvSnapshot<R>(R);
+
+impl<R: KvRead> KvSnapshot<R> {
+    pub(crate) fn new(reader: R) -> KvSnapshot<R> {
+        KvSnapshot(reader)
+    }
+}
+
+impl<R: KvRead> Snapshot for KvSnapshot<R> {
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+        get_by_id(&self.0, id)
+    }
+
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+        get_by_address(&self.0, address)
+    }
+
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+        is_deleted(&self.0, id)
+    }
+
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+        Ok(Box::new(Records::new(&self.0)?))
+    }
+
+    fn query(&self, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+        query(&self.0, filter)
+    }
+}
+
+// Implement `Debug`, the settings and `RecordStore` for a newtype over a
+// `KvStore`, and `Debug` and `Snapshot` for a newtype over a `KvSnapshot`
+macro_rules! impl_kv_store {
+    ($store:ident, $snapshot:ident) => {
+        impl std::fmt::Debug for $store {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt_debug(stringify!($store), f)
+            }
+        }
+
+        impl $store {
+            /// Set what happens to records when they are deleted. This does
+            /// not affect records already deleted, and is not persisted.
+            pub fn set_deletion_mode(&mut self, mode: $crate::DeletionMode) {
+                self.0.set_deletion_mode(mode);
+            }
+
+            /// Refuse to store records longer than `max_len` bytes
+            /// (`MAX_RECORD_LEN` by default). Values above `MAX_RECORD_LEN`
+            /// have no effect. This does not affect records already stored,
+            /// and is not persisted.
+            pub fn set_max_record_len(&mut self, max_len: usize) {
+                self.0.set_max_record_len(max_len);
+            }
+
+            /// Report to the given `Metrics`
+            pub fn set_metrics(&mut self, metrics: std::sync::Arc<dyn $crate::Metrics>) {
+                self.0.set_metrics(metrics);
+            }
+        }
+
+        impl $crate::RecordStore for $store {
+            fn put(&mut self, record: &$crate::Record) -> Result<$crate::PutOutcome, Error> {
+                self.0.put(record)
+            }
+
+            fn get_by_id(&self, id: &$crate::Id) -> Result<Option<$crate::OwnedRecord>, Error> {
+                self.0.get_by_id(id)
+            }
+
+            fn get_by_address(
+                &self,
+                address: &$crate::Address,
+            ) -> Result<Option<$crate::OwnedRecord>, Error> {
+                self.0.get_by_address(address)
+            }
+
+            fn delete(&mut self, id: &$crate::Id) -> Result<bool, Error> {
+                self.0.delete(id)
+            }
+
+            fn evict(&mut self, id: &$crate::Id) -> Result<bool, Error> {
+                self.0.evict(id)
+            }
+
+            fn write_batch(
+                &mut self,
+                batch: $crate::WriteBatch,
+            ) -> Result<Vec<$crate::WriteResult>, Error> {
+                self.0.write_batch(batch)
+            }
+
+            fn is_deleted(&self, id: &$crate::Id) -> Result<bool, Error> {
+                self.0.is_deleted(id)
+            }
+
+            fn records(
+                &self,
+            ) -> Result<Box<dyn Iterator<Item = Result<$crate::OwnedRecord, Error>> + '_>, Error>
+            {
+                self.0.records()
+            }
+
+            fn query(&self, filter: &$crate::Filter) -> Result<Vec<$crate::OwnedRecord>, Error> {
+                self.0.query(filter)
+            }
+
+            fn page(
+                &self,
+                filter: &$crate::Filter,
+                cursor: &$crate::Cursor,
+            ) -> Result<$crate::Page, Error> {
+                self.0.page(filter, cursor)
+            }
+
+            fn snapshot(&self) -> Result<Box<dyn $crate::Snapshot + Send>, Error> {
+                self.0.snapshot()
+            }
+
+            fn compact(&mut self) -> Result<usize, Error> {
+                self.0.compact()
+            }
+        }
+
+        impl std::fmt::Debug for $snapshot {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($snapshot))
+                    .finish_non_exhaustive()
+            }
+        }
+
+        impl $crate::Snapshot for $snapshot {
+            fn get_by_id(&self, id: &$crate::Id) -> Result<Option<$crate::OwnedRecord>, Error> {
+                self.0.get_by_id(id)
+            }
+
+            fn get_by_address(
+                &self,
+                address: &$crate::Address,
+            ) -> Result<Option<$crate::OwnedRecord>, Error> {
+                self.0.get_by_address(address)
+            }
+
+            fn is_deleted(&self, id: &$crate::Id) -> Result<bool, Error> {
+                self.0.is_deleted(id)
+            }
+
+            fn records(
+                &self,
+            ) -> Result<Box<dyn Iterator<Item = Result<$crate::OwnedRecord, Error>> + '_>, Error>
+            {
+                self.0.records()
+            }
+
+            fn query(&self, filter: &$crate::Filter) -> Result<Vec<$crate::OwnedRecord>, Error> {
+                self.0.query(filter)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_kv_store;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, Timestamp};
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+
+    // The records table in memory, counting the records visited and
+    // failing every scan after the first `max_scans`
+    struct TestRead {
+        records: BTreeMap<Vec<u8>, Vec<u8>>,
+        scans: Cell<usize>,
+        max_scans: usize,
+        visited: Cell<usize>,
+    }
+
+    impl TestRead {
+        fn new(count: u64, max_scans: usize) -> TestRead {
+            use rand::rngs::OsRng;
+
+            let secret_key = SecretKey::generate(&mut OsRng);
+            let mut records: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+            for millis in 1..=count {
+                let record = OwnedRecord::new(
+                    &secret_key,
+                    &RecordParts {
+                        kind: Kind::MICROBLOG_ROOT,
+                        deterministic_key: None,
+                        timestamp: Timestamp::from_millis(millis).unwrap(),
+                        flags: RecordFlags::empty(),
+                        app_flags: 0,
+                        tags_bytes: b"",
+                        payload: b"hello",
+                    },
+                )
+                .unwrap();
+                let _ = records.insert(record.id().as_ref().to_vec(), record.into_vec());
+            }
+            TestRead {
+                records,
+                scans: Cell::new(0),
+                max_scans,
+                visited: Cell::new(0),
+            }
+        }
+    }
+
+    impl KvRead for TestRead {
+        fn get(
+            &self,
+            _table: KvTable,
+            _key: &[u8],
+            _f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+        ) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        fn scan(
+            &self,
+            _table: KvTable,
+            range: KeyRange<'_>,
+            reverse: bool,
+            f: &mut ScanFn<'_>,
+        ) -> Result<(), Error> {
+            self.scans.set(self.scans.get() + 1);
+            if self.scans.get() > self.max_scans {
+                return Err(std::io::Error::other("backend failed").into());
+            }
+            let mut entries = self.records.range::<[u8], _>(range);
+            while let Some((key, value)) = if reverse {
+                entries.next_back()
+            } else {
+                entries.next()
+            } {
+                self.visited.set(self.visited.get() + 1);
+                if !f(key, value)? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_records_backend_error() {
+        let reader = TestRead::new(RECORDS_BATCH as u64 + 1, 1);
+
+        // The first batch is read, then the failure of the second is
+        // yielded rather than ending the iteration quietly
+        let results: Vec<_> = Records::new(&reader).unwrap().collect();
+        assert_eq!(results.len(), RECORDS_BATCH + 1);
+        assert!(results[..RECORDS_BATCH].iter().all(Result::is_ok));
+        assert!(results[RECORDS_BATCH].is_err());
+
+        // and reaches whoever consumes the records
+        reader.scans.set(0);
+        let records = Records::new(&reader).unwrap();
+        assert!(crate::record_store::superseded_ids(records).is_err());
+    }
+
+    #[test]
+    fn test_query_stops_at_limit() {
+        let reader = TestRead::new(100, usize::MAX);
+        let newest: Vec<Id> = reader
+            .records
+            .keys()
+            .rev()
+            .take(3)
+            .map(|key| id_from_slice(key))
+            .collect();
+
+        let mut filter = Filter::new();
+        filter.limit = Some(3);
+        let output = query(&reader, &filter).unwrap();
+        assert_eq!(output.iter().map(|r| r.id()).collect::<Vec<_>>(), newest);
+        assert_eq!(reader.visited.get(), 3);
+    }
+}
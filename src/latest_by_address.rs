@@ -0,0 +1,175 @@
+use crate::{Address, OwnedRecord, Record};
+use std::collections::HashMap;
+
+type ChangeListener = Box<dyn FnMut(&Record, Option<&Record>)>;
+
+/// `LatestByAddress` ingests records and retains only the winning record at
+/// each `Address`, as determined by `Record::supersedes()`.
+///
+/// Records can be collected into a `LatestByAddress` from an iterator,
+/// and it iterates into the winning records.
+///
+/// Listeners registered with `on_change()` are called whenever the winning
+/// record at an address changes, with the new record and the record it
+/// replaced (if any).
+#[derive(Default)]
+pub struct LatestByAddress {
+    latest: HashMap<Address, OwnedRecord>,
+    listeners: Vec<ChangeListener>,
+}
+
+impl std::fmt::Debug for LatestByAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LatestByAddress")
+            .field("latest", &self.latest)
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+impl LatestByAddress {
+    /// Create a new empty `LatestByAddress`
+    #[must_use]
+    pub fn new() -> LatestByAddress {
+        LatestByAddress::default()
+    }
+
+    /// Register a listener to be called when the winning record at an
+    /// address changes
+    pub fn on_change<F: FnMut(&Record, Option<&Record>) + 'static>(&mut self, f: F) {
+        self.listeners.push(Box::new(f));
+    }
+
+    /// Ingest a record. Returns true if it is now the winning record at
+    /// its address.
+    pub fn ingest(&mut self, record: &Record) -> bool {
+        let address = record.address();
+        if let Some(current) = self.latest.get(&address) {
+            if !record.supersedes(current) {
+                return false;
+            }
+        }
+
+        let old = self.latest.insert(address, record.to_owned());
+        for listener in &mut self.listeners {
+            listener(record, old.as_deref());
+        }
+        true
+    }
+
+    /// Get the winning record at an address
+    #[must_use]
+    pub fn get(&self, address: &Address) -> Option<&OwnedRecord> {
+        self.latest.get(address)
+    }
+
+    /// Remove the record at an address, returning it
+    pub fn remove(&mut self, address: &Address) -> Option<OwnedRecord> {
+        self.latest.remove(address)
+    }
+
+    /// Number of addresses held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.latest.len()
+    }
+
+    /// Is this empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_empty()
+    }
+
+    /// Iterate over the winning records, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &OwnedRecord> {
+        self.latest.values()
+    }
+}
+
+impl<'a, R: AsRef<Record> + ?Sized + 'a> Extend<&'a R> for LatestByAddress {
+    fn extend<I: IntoIterator<Item = &'a R>>(&mut self, iter: I) {
+        for record in iter {
+            let _ = self.ingest(record.as_ref());
+        }
+    }
+}
+
+impl<'a, R: AsRef<Record> + ?Sized + 'a> FromIterator<&'a R> for LatestByAddress {
+    fn from_iter<I: IntoIterator<Item = &'a R>>(iter: I) -> LatestByAddress {
+        let mut latest = LatestByAddress::new();
+        latest.extend(iter);
+        latest
+    }
+}
+
+impl IntoIterator for LatestByAddress {
+    type Item = OwnedRecord;
+    type IntoIter = std::collections::hash_map::IntoValues<Address, OwnedRecord>;
+
+    /// The winning records, in no particular order
+    fn into_iter(self) -> Self::IntoIter {
+        self.latest.into_values()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, Timestamp};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_latest_by_address() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::PROFILE,
+                    deterministic_key: Some(b"profile"),
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let v1 = make(1000);
+        let v2 = make(2000);
+        let v3 = make(3000);
+
+        let changes = Rc::new(RefCell::new(Vec::<(u64, Option<u64>)>::new()));
+        let mut latest = LatestByAddress::new();
+        let c = changes.clone();
+        latest.on_change(move |new, old| {
+            c.borrow_mut().push((
+                new.timestamp().as_millis(),
+                old.map(|r| r.timestamp().as_millis()),
+            ));
+        });
+
+        assert!(latest.ingest(&v2));
+        assert!(!latest.ingest(&v1));
+        assert!(!latest.ingest(&v2));
+        assert!(latest.ingest(&v3));
+
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest.get(&v1.address()), Some(&v3));
+        assert_eq!(*changes.borrow(), vec![(2000, None), (3000, Some(2000))]);
+
+        // In any order, only the newest survives
+        let latest: LatestByAddress = [&v2, &v3, &v1].into_iter().collect();
+        assert_eq!(latest.into_iter().collect::<Vec<_>>(), vec![v3.clone()]);
+        let mut latest = LatestByAddress::new();
+        latest.extend([&*v1, &*v3, &*v2]);
+        assert_eq!(latest.get(&v1.address()), Some(&v3));
+    }
+}
@@ -6,20 +6,76 @@
 //! Users and Servers are known by their [`PublicKey`] proven by their
 //! [`SecretKey`]. These are 32-byte packed data, and have to be unpacked
 //! into their [`DalekVerifyingKey`] or [`DalekSigningKey`] respectively in
-//! order to do cryptographic operations.
+//! order to do cryptographic operations. With the `seed` feature, a single
+//! `Seed` can derive a user's master key, device subkeys and throwaway
+//! identities. With the `key-export` feature, a secret key is backed up
+//! under a passphrase with `SecretKey::export_encrypted()`, whose Argon2id
+//! cost is set by `KdfParams`. With the `bip39` feature, keys and seeds are
+//! also written down as BIP-39 mnemonics (see `SecretKey::to_mnemonic()`
+//! and `Seed::from_mnemonic()`). Identities convert to X25519
+//! [`EncryptionPublicKey`]s and [`EncryptionSecret`]s for key agreement, on
+//! which, with the `seal` feature, `crypto::seal()` encrypts record
+//! payloads from one identity to another. A [`PaddingPolicy`] hides their
+//! lengths.
+//!
+//! A master key delegates signing to subkeys, each within a
+//! [`SubkeyWindow`], and revokes lost keys with a [`KeySchedule`] record.
+//!
+//! Users can prove control of a domain, a git account or a web page with
+//! an [`IdentityProof`] record and a challenge published at the
+//! [`IdentityClaim`]'s location.
+//!
+//! With the `uri` feature, servers, users and records are linked to with a
+//! `ServerUri`, `UserUri` or `RecordUri`, of the form
+//! `mosaic://server/mopub0...`.
 //!
 //! # Bootstrap
 //!
 //! Server endpoints (URLs) are bootstrapped from Mainline DHT with
-//! a [`ServerBootstrap`] record.
+//! a `ServerBootstrap` record (with the `uri` feature).
 //!
 //! The servers that a user uses are bootstrapped from Mainline DHT
 //! with a [`UserBootstrap`] record.
 //!
+//! The crate builds for `wasm32-unknown-unknown` so that browser clients
+//! can use it directly. There the DHT functions are unavailable (browsers
+//! cannot speak UDP), but the bootstrap records themselves still are.
+//!
+//! With the `uri` feature, clients look bootstraps up through a `Resolver`,
+//! whose futures are runtime-agnostic; the DHT client is one, and a
+//! `MemoryResolver` serves tests and simulations. A `BootstrapCache` in
+//! front of a resolver keeps repeated lookups off the DHT.
+//!
+//! The DHT functions are behind the default `dht` feature, and the other
+//! default features, `key-export`, `rand`, `seal`, `seed` and `uri`, bring
+//! in the crates they are built on (Argon2 and XChaCha20-Poly1305, `rand`,
+//! HPKE, HMAC, and `http`). Embedded and WASM consumers can build with
+//! `default-features = false`, leaving parsing, signing and verification
+//! depending only on the ed25519 and BLAKE3 implementations.
+//!
 //! # Records
 //!
 //! [`Record`]s are of various [`Kind`]s and have [`Timestamp`]s and
-//! [`RecordFlags`].
+//! [`RecordFlags`]. Untrusted bytes are parsed as an [`UnverifiedRecord`],
+//! which only yields a [`Record`] once verified. With the `rayon` feature,
+//! large records are hashed on multiple threads. Short records can be built
+//! without heap allocation as a [`SmallRecord`]. Records can be printed for
+//! tools and logs in several layouts with a [`RecordFormatter`]. Key
+//! generation and record creation take a caller-provided random number
+//! generator (see [`OwnedRecord::new_with_rng`]) so that simulations and
+//! tests can be reproduced from a seed. Records can be signed by a
+//! [`Signer`] or [`AsyncSigner`] that keeps the secret key elsewhere, or
+//! prepared as an [`UnsignedRecord`] whose signature is obtained out of
+//! band.
+//! Unsigned records can be kept (and, with the `serde` feature, saved) as a
+//! [`DraftRecord`] and signed later. When a record has a deterministic key,
+//! its `Address` and `Id` can be known before it is signed (see
+//! [`RecordParts::predicted_address`]). Records can also be assembled piece
+//! by piece with a [`RecordBuilder`]. Applications register the shapes of
+//! the records they make as [`RecordTemplate`]s in a [`TemplateRegistry`].
+//! With the `zstd` feature, `RecordBuilder::compress_payload()` compresses
+//! a payload when that makes the record shorter, and
+//! `Record::payload_decompressed()` undoes it.
 //!
 //! [`Record`]s may have `Tags` (TBD) of varying [`TagType`]s.
 //!
@@ -28,10 +84,102 @@
 //! and so a [`Reference`] type can be used when it is unknown which kind
 //! of reference is specified.
 //!
+//! Other implementations of the specification can check their keys,
+//! records and encodings against the known-answer vectors in
+//! [`test_vectors`], or run the [`conformance`] suite against themselves.
+//!
+//! Bridges to other protocols plug their mappings into a
+//! [`ConverterRegistry`] by implementing [`ToForeign`] and [`FromForeign`].
+//! With the `nostr` feature, records can be converted to and from Nostr
+//! events for bridges and dual-protocol clients.
+//!
+//! Clients assemble the replies to a record into a tree with a
+//! [`ThreadBuilder`]. Follow lists edited on several devices are
+//! reconciled with a three-way [`FollowList::merge()`], and the reactions
+//! under each post are counted with a [`ReactionSummary`]. With the `seal`
+//! feature, a user's devices share what each has fetched through a
+//! `SyncState`, and how far they have read in each conversation through
+//! `ReadMarker`s.
+//!
+//! Records at the same [`Address`] replace one another. A
+//! [`LatestByAddress`] keeps only the winning record at each address.
+//!
 //! # Protocol
 //!
-//! Protocol `Messages` (TBD) are sent between client and server over some
-//! transport. Many client-initiated messages include a `Filter` (TBD)
+//! Protocol messages, a [`ClientMessage`] or a [`ServerMessage`], are sent
+//! between client and server over some transport. Queries and
+//! subscriptions carry a [`Filter`].
+//! Records sent back to back on a byte stream are framed with a
+//! [`RecordCodec`], which with the `tokio-util` feature is a
+//! `tokio_util` codec for `Framed` streams.
+//! Received records can be kept in an [`Envelope`] recording where and
+//! when they arrived; with the `serde` feature these can be serialized,
+//! as can records, keys, ids, addresses and bootstraps (as text in
+//! human-readable formats and as bytes in binary ones), and with the
+//! `msgpack` feature records, filters and envelopes can be encoded as
+//! `MessagePack` with `to_msgpack()`.
+//! Servers deliver newly accepted records to live subscriptions with a
+//! [`Matcher`], which indexes many subscription filters at once.
+//!
+//! # Acceptance
+//!
+//! Servers decide which incoming records to accept with an
+//! [`AcceptancePipeline`], which can be extended with a [`Policy`] such as
+//! an operator-configured [`ServerPolicy`], and with a [`SpamScorer`] that
+//! also sees the [`ConnectionContext`]. The pipeline and the stores
+//! report to a [`Metrics`] implementation if one is set, and can feed a
+//! [`SizeStats`] with per-kind histograms of record sizes. Relays can drop
+//! re-broadcast duplicates cheaply with [`RecentIds`], and skip re-verifying
+//! records seen on several connections with a [`VerificationCache`].
+//! Users report abusive records with an [`AbuseReport`], and moderation
+//! tools tally them per record with a [`ReportIndex`].
+//! With the `tracing` feature, verification, signing, store operations and
+//! DHT lookups are instrumented with `tracing` spans carrying record ids,
+//! kinds and sizes.
+//!
+//! # Storage
+//!
+//! Records are stored in a [`RecordStore`], a trait implemented by each
+//! storage backend. A [`MemoryStore`] is provided as a reference backend.
+//! With the `redb` feature, a persistent `RedbStore` is also available,
+//! and with the `lmdb` feature an LMDB-backed `LmdbStore`.
+//! Stores keep a [`TagIndex`] so that queries on tags are lookups rather
+//! than scans, and queries on tag value prefixes (such as geohash areas)
+//! are range scans. Deleted records are purged or retained-but-hidden per
+//! [`DeletionMode`]. Ephemeral records, such as typing indicators and
+//! presence, are delivered to live subscriptions but never stored.
+//!
+//! Results can be paged through in either [`Direction`] with a [`Cursor`].
+//! A [`Snapshot`] gives a consistent view of a store while writes proceed.
+//! Writes can be grouped into a [`WriteBatch`] so that transactional
+//! backends commit them together. Stores can be exported to and imported
+//! from archives in either [`ArchiveFormat`], and large stores can be
+//! streamed to a resumable, checksummed backup. With the `mmap` feature,
+//! a binary archive can be memory-mapped as an `MmapRecordFile` to serve
+//! records without copying them. Expired records are pruned according to
+//! a [`RetentionPlan`].
+//!
+//! One physical store can serve several logical servers through a
+//! [`NamespacedStore`], with isolated queries and a [`Quota`] each.
+//! Deployments that spread records over several nodes can agree on which
+//! node owns a record with [`shard_for`] and [`shard_for_address`]. Caches
+//! account their memory against a shared [`MemoryBudget`] so that
+//! embedding applications can bound memory use.
+//!
+//! # Search
+//!
+//! A [`Filter`] may include a full-text search term. Servers that index
+//! payload text split both the payload and the search term into tokens
+//! with a [`Tokenizer`] such as the default [`UnicodeTokenizer`].
+//!
+//! # Embedding
+//!
+//! With the `ffi` feature, the `ffi` module exposes a C ABI for key
+//! generation, record creation, verification and field access, so that
+//! C, C++ and Swift applications can embed this library. With the
+//! `uniffi` feature, wrappers such as [`MosaicSecretKey`], [`MosaicRecord`]
+//! and [`MosaicFilter`] are annotated for `UniFFI` so that Kotlin and Swift
+//! bindings can be generated.
 
 #![warn(clippy::pedantic)]
 #![deny(
@@ -55,45 +203,278 @@
 
 pub use ed25519_dalek::SigningKey as DalekSigningKey;
 pub use ed25519_dalek::VerifyingKey as DalekVerifyingKey;
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
 pub use mainline;
+#[cfg(feature = "rand")]
 pub use rand;
+pub use rand_core;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+mod abuse_report;
+pub use abuse_report::{AbuseReport, ReportIndex, ReportReason, Severity};
+
+mod acceptance;
+pub use acceptance::{
+    AcceptancePipeline, ConnectionContext, Outcome, Policy, RejectReason, SpamAction, SpamScorer,
+    SpamVerdict,
+};
 
 mod address;
 pub use address::Address;
 
-mod crypto;
+mod archive;
+pub use archive::{ArchiveFormat, VerifyLevel};
+
+mod backup;
+pub use backup::{BackupProgress, BACKUP_CHUNK_RECORDS};
+
+#[cfg(feature = "uri")]
+mod bootstrap_cache;
+#[cfg(feature = "uri")]
+pub use bootstrap_cache::BootstrapCache;
+
+mod bridge;
+pub use bridge::{ConverterRegistry, FromForeign, ToForeign};
+
+pub mod conformance;
+
+pub mod crypto;
+pub use crypto::{EncryptionPublicKey, EncryptionSecret, SharedSecret};
+
+mod cursor;
+pub use cursor::{Cursor, Direction, Page};
+
+mod decompress;
+#[cfg(feature = "zstd")]
+pub use decompress::compress_payload;
+pub use decompress::{decompress_payload, read_bounded};
+
+mod draft_record;
+pub use draft_record::DraftRecord;
+
+mod envelope;
+pub use envelope::Envelope;
 
 mod error;
-pub use error::{Error, InnerError};
+pub use error::{Error, ErrorKind, InnerError};
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+mod filter;
+pub use filter::{Filter, MAX_FILTER_LIMIT};
+
+mod follow_list;
+pub use follow_list::{FollowList, FollowListDiff, FOLLOW_LIST_KEY};
 
 mod id;
 pub use id::Id;
 
+mod identity_proof;
+pub use identity_proof::{IdentityClaim, IdentityProof};
+
+#[cfg(feature = "key-export")]
+mod key_export;
+#[cfg(feature = "key-export")]
+pub use key_export::{KdfParams, MAX_KDF_ITERATIONS, MAX_KDF_MEMORY_KIB, MAX_KDF_PARALLELISM};
+
+mod key_schedule;
+pub use key_schedule::{KeySchedule, SubkeyWindow, KEY_SCHEDULE_KEY};
+
 mod kind;
 pub use kind::Kind;
 
 mod keys;
 pub use keys::{PublicKey, SecretKey};
 
+#[cfg(any(feature = "lmdb", feature = "redb"))]
+mod kv_store;
+
+mod latest_by_address;
+pub use latest_by_address::LatestByAddress;
+
+mod lint;
+pub use lint::Lint;
+
+#[cfg(feature = "lmdb")]
+mod lmdb_store;
+#[cfg(feature = "lmdb")]
+pub use lmdb_store::{LmdbSnapshot, LmdbStore, DEFAULT_LMDB_MAP_SIZE};
+
+mod matcher;
+pub use matcher::Matcher;
+
+mod memory_budget;
+pub use memory_budget::{BudgetHandle, MemoryBudget};
+
+mod memory_store;
+pub use memory_store::MemoryStore;
+
+mod message;
+pub use message::{ClientMessage, CloseReason, QueryId, ServerMessage, SubmissionResult};
+
+mod metrics;
+pub use metrics::{Counter, Histogram, Metrics};
+
+#[cfg(feature = "mmap")]
+mod mmap_record_file;
+#[cfg(feature = "mmap")]
+pub use mmap_record_file::MmapRecordFile;
+
+#[cfg(feature = "bip39")]
+mod mnemonic;
+
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "msgpack")]
+pub use msgpack::{from_msgpack, to_msgpack};
+
+mod namespace;
+pub use namespace::{Namespace, NamespaceView, NamespacedStore, Quota};
+
+#[cfg(feature = "nostr")]
+mod nostr;
+#[cfg(feature = "nostr")]
+pub use nostr::{
+    nostr_kind, nostr_sister_id, nostr_to_record, record_to_nostr, NostrEvent, UnsignedNostrEvent,
+    NOSTR_KIND_MAP,
+};
+
+mod padding;
+pub use padding::{unpad_payload, PaddingPolicy};
+
+mod reaction;
+pub use reaction::{Reaction, ReactionSummary};
+
+#[cfg(feature = "seal")]
+mod read_marker;
+#[cfg(feature = "seal")]
+pub use read_marker::ReadMarker;
+
+mod recent_ids;
+pub use recent_ids::RecentIds;
+
 mod record;
-pub use record::{OwnedRecord, Record, RecordParts};
+pub use record::{OwnedRecord, Record, RecordBuilder, RecordParts, UnsignedRecord, MAX_RECORD_LEN};
+
+mod record_codec;
+pub use record_codec::RecordCodec;
+
+mod record_formatter;
+pub use record_formatter::{FormatMode, PayloadFormat, RecordFormatter};
+
+mod record_set;
+pub use record_set::RecordSet;
+
+mod record_store;
+pub use record_store::{DeletionMode, PutOutcome, RecordStore, IMPORT_BATCH_SIZE};
+
+mod record_template;
+pub use record_template::{PayloadSchema, RecordTemplate, TemplateRegistry};
 
 mod record_flags;
 pub use record_flags::RecordFlags;
 
+#[cfg(feature = "redb")]
+mod redb_store;
+#[cfg(feature = "redb")]
+pub use redb_store::{RedbSnapshot, RedbStore};
+
 mod reference;
 pub use reference::Reference;
 
+mod resolver;
+pub use resolver::{author_server_hints, server_hints};
+#[cfg(feature = "uri")]
+pub use resolver::{resolve_user_servers, MemoryResolver, Resolver};
+
+mod retention;
+pub use retention::{ExpiredBatches, KindRetention, RetentionPlan};
+
+mod search;
+pub use search::{Tokenizer, UnicodeTokenizer};
+
+#[cfg(feature = "seed")]
+mod seed;
+#[cfg(feature = "seed")]
+pub use seed::{Seed, DEVICE_KEY_INDEX, MASTER_KEY_INDEX, THROWAWAY_KEY_INDEX};
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+#[cfg(feature = "uri")]
 mod server_bootstrap;
+#[cfg(feature = "uri")]
 pub use server_bootstrap::ServerBootstrap;
 
+mod server_policy;
+pub use server_policy::ServerPolicy;
+
+mod shard;
+pub use shard::{shard_for, shard_for_address};
+
+mod signer;
+pub use signer::{AsyncSigner, Signer};
+
+mod size_stats;
+pub use size_stats::{KindSizes, SizeHistogram, SizeStats, SIZE_CLASSES};
+
+mod small_record;
+pub use small_record::SmallRecord;
+
+mod sniff;
+pub use sniff::sniff_content_type;
+
+mod snapshot;
+pub use snapshot::{MemorySnapshot, Snapshot};
+
+#[cfg(feature = "seal")]
+mod sync_state;
+#[cfg(feature = "seal")]
+pub use sync_state::{DeviceSyncState, SyncState, SYNC_STATE_KEY};
+
 mod tag;
-pub use tag::{OwnedTag, Tag, TagType};
+pub use tag::{OwnedTag, OwnedTagSet, Tag, TagIter, TagType};
+
+mod tag_index;
+pub use tag_index::{TagIndex, TagKey};
+
+pub mod test_vectors;
+
+mod thread;
+pub use thread::{Thread, ThreadBuilder, ThreadNode};
 
 mod timestamp;
 pub use timestamp::Timestamp;
 
+#[cfg(feature = "uri")]
 mod uri;
+#[cfg(feature = "uri")]
+pub use uri::{RecordUri, ServerUri, UserUri};
+
+#[cfg(feature = "uniffi")]
+mod uniffi_bindings;
+#[cfg(feature = "uniffi")]
+pub use uniffi_bindings::{
+    filter_matches, MosaicError, MosaicFilter, MosaicPublicKey, MosaicRecord, MosaicSecretKey,
+    MosaicServerBootstrap, MosaicUserBootstrap, MosaicUserServer,
+};
+
+mod unverified_record;
+pub use unverified_record::UnverifiedRecord;
 
 mod user_bootstrap;
 pub use user_bootstrap::UserBootstrap;
+
+mod verification_cache;
+pub use verification_cache::VerificationCache;
+
+mod verify_policy;
+pub use verify_policy::VerifyPolicy;
+
+mod write_batch;
+pub use write_batch::{WriteBatch, WriteOp, WriteResult};
+
+mod zbase32;
@@ -0,0 +1,164 @@
+use crate::tag::iter_tags;
+use crate::{sniff_content_type, Kind, Record, RecordFlags, TagType, Timestamp};
+
+/// Timestamps more than this far (in milliseconds) in the future are
+/// linted, as with the `AcceptancePipeline` default
+const MAX_FUTURE_MS: u64 = 15 * 60 * 1000;
+
+/// A quality problem with a record that verification does not reject.
+///
+/// See `Record::lint()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// The timestamp is this many milliseconds in the future, so many
+    /// servers will reject the record
+    TimestampInFuture(u64),
+
+    /// The kind is not one defined by this crate
+    UnknownKind(Kind),
+
+    /// A tag of this type appears more than once with the same value
+    DuplicateTag(TagType),
+
+    /// The tags section has bytes after its last complete tag
+    TrailingTagBytes(usize),
+
+    /// The padding after the tags or the payload is not zero
+    NonZeroPadding,
+
+    /// The payload does not suit the kind or flags. The payload's sniffed
+    /// content type is given.
+    PayloadMismatch(&'static str),
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lint::TimestampInFuture(ms) => write!(f, "Timestamp is {ms}ms in the future"),
+            Lint::UnknownKind(k) => write!(f, "Unknown kind: {k}"),
+            Lint::DuplicateTag(t) => write!(f, "Duplicate tag: {t}"),
+            Lint::TrailingTagBytes(n) => write!(f, "{n} bytes after the last tag"),
+            Lint::NonZeroPadding => write!(f, "Padding is not zero"),
+            Lint::PayloadMismatch(c) => write!(f, "Payload looks like {c}"),
+        }
+    }
+}
+
+// Kinds whose payload is text
+const TEXT_KINDS: &[Kind] = &[
+    Kind::MICROBLOG_ROOT,
+    Kind::REPLY_COMMENT,
+    Kind::BLOG_POST,
+    Kind::CHAT_MESSAGE,
+];
+
+pub(crate) fn lint(record: &Record, now: Timestamp) -> Vec<Lint> {
+    let mut lints: Vec<Lint> = vec![];
+
+    let ahead = record
+        .timestamp()
+        .as_millis()
+        .saturating_sub(now.as_millis());
+    if ahead > MAX_FUTURE_MS {
+        lints.push(Lint::TimestampInFuture(ahead));
+    }
+
+    let kind = record.kind();
+    if !kind.is_known() {
+        lints.push(Lint::UnknownKind(kind));
+    }
+
+    let tags_bytes = record.tags_bytes();
+    let tags: Vec<_> = iter_tags(tags_bytes).collect();
+    for (i, tag) in tags.iter().enumerate() {
+        let duplicate = tags[..i].contains(tag);
+        let reported = lints.contains(&Lint::DuplicateTag(tag.get_type()));
+        if duplicate && !reported {
+            lints.push(Lint::DuplicateTag(tag.get_type()));
+        }
+    }
+    let parsed: usize = tags.iter().map(|t| t.as_bytes().len()).sum();
+    if parsed < tags_bytes.len() {
+        lints.push(Lint::TrailingTagBytes(tags_bytes.len() - parsed));
+    }
+
+    let bytes = record.as_bytes();
+    let tags_end = bytes.len() - record.payload_padded_len();
+    let tags_padding = &bytes[tags_end - (record.tags_padded_len() - record.tags_len())..tags_end];
+    let payload_padding = &bytes[tags_end + record.payload_len()..];
+    if tags_padding.iter().chain(payload_padding).any(|b| *b != 0) {
+        lints.push(Lint::NonZeroPadding);
+    }
+
+    let flags = record.flags();
+    if !flags.contains(RecordFlags::ZSTD)
+        && (TEXT_KINDS.contains(&kind) || flags.contains(RecordFlags::PRINTABLE))
+    {
+        let content_type = sniff_content_type(record.payload_bytes());
+        if !content_type.starts_with("text/") {
+            lints.push(Lint::PayloadMismatch(content_type));
+        }
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedRecord, OwnedTag, RecordParts, SecretKey};
+
+    #[test]
+    fn test_lint() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let make = |kind: Kind, millis: u64, tags_bytes: &[u8], payload: &[u8]| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes,
+                    payload,
+                },
+            )
+            .unwrap()
+        };
+        let now = Timestamp::from_millis(10_000_000).unwrap();
+
+        let good = make(Kind::MICROBLOG_ROOT, 9_000_000, b"", b"hello");
+        assert!(good.lint(now).is_empty());
+
+        let r = make(Kind::MICROBLOG_ROOT, 20_000_000, b"", b"hello");
+        assert_eq!(r.lint(now), vec![Lint::TimestampInFuture(10_000_000)]);
+
+        let r = make(Kind::new(0x8001), 9_000_000, b"", b"\0\x01");
+        assert_eq!(r.lint(now), vec![Lint::UnknownKind(Kind::new(0x8001))]);
+
+        let tag = OwnedTag::new_notify_public_key(&secret_key.public());
+        let mut tags = [tag.as_bytes(), tag.as_bytes()].concat();
+        let r = make(Kind::MICROBLOG_ROOT, 9_000_000, &tags, b"hello");
+        assert_eq!(
+            r.lint(now),
+            vec![Lint::DuplicateTag(TagType::NOTIFY_PUBLIC_KEY)]
+        );
+        tags.truncate(tag.as_bytes().len() + 2);
+        let r = make(Kind::MICROBLOG_ROOT, 9_000_000, &tags, b"hello");
+        assert_eq!(r.lint(now), vec![Lint::TrailingTagBytes(2)]);
+
+        let r = make(Kind::CHAT_MESSAGE, 9_000_000, b"", b"\x89PNG\r\n\x1a\n");
+        assert_eq!(r.lint(now), vec![Lint::PayloadMismatch("image/png")]);
+
+        let mut bytes = good.as_bytes().to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] = 1;
+        // SAFETY: only the padding was changed, and the record is not used
+        // beyond linting
+        let r = unsafe { Record::from_bytes(&bytes) }.unwrap();
+        assert_eq!(r.lint(now), vec![Lint::NonZeroPadding]);
+    }
+}
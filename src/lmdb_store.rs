@@ -0,0 +1,447 @@
+use crate::kv_store::{
+    impl_kv_store, KeyRange, KvBackend, KvRead, KvSnapshot, KvStore, KvTable, KvWrite, ScanFn,
+};
+use crate::{Error, Id, Record};
+use heed::types::Bytes;
+use heed::{Database, DatabaseFlags, Env, EnvOpenOptions, RoTxn, RwTxn};
+use std::path::{Path, PathBuf};
+
+/// The default LMDB map size of a `LmdbStore`: the most the database may
+/// grow to (1 TiB of address space, not of memory or disk)
+pub const DEFAULT_LMDB_MAP_SIZE: usize = 1 << 40;
+
+// The handles of the databases within the environment, laid out as
+// described on `KvTable`. Tables with many values per key are
+// duplicate-sorted databases.
+#[derive(Clone, Copy)]
+struct Databases {
+    records: Database<Bytes, Bytes>,
+    addresses: Database<Bytes, Bytes>,
+    deleted: Database<Bytes, Bytes>,
+    authors: Database<Bytes, Bytes>,
+    kinds: Database<Bytes, Bytes>,
+    tags: Database<Bytes, Bytes>,
+    tag_values: Database<Bytes, Bytes>,
+}
+
+impl Databases {
+    // The database of a table, and whether it is duplicate-sorted
+    fn get(&self, table: KvTable) -> (Database<Bytes, Bytes>, bool) {
+        match table {
+            KvTable::Records => (self.records, false),
+            KvTable::Addresses => (self.addresses, false),
+            KvTable::Deleted => (self.deleted, false),
+            KvTable::Authors => (self.authors, true),
+            KvTable::Kinds => (self.kinds, true),
+            KvTable::Tags => (self.tags, true),
+            KvTable::TagValues => (self.tag_values, true),
+        }
+    }
+}
+
+/// A `RecordStore` persisted in an [LMDB](http://www.lmdb.tech/doc/)
+/// environment, through [heed](https://docs.rs/heed).
+///
+/// Writes are transactional and crash safe. Records are indexed by author,
+/// kind, tag, tag value prefix, timestamp and address, and replacement and
+/// deletion are handled the same way as in `MemoryStore`.
+///
+/// Record bytes are stored as they are and read straight from the memory
+/// map: use `with_record()` to view them without copying. LMDB allows an
+/// environment to be opened only once per process, so open each directory
+/// with one `LmdbStore`.
+pub struct LmdbStore(KvStore<LmdbBackend>);
+
+impl LmdbStore {
+    /// Open (or create) a `LmdbStore` in the given directory, with the
+    /// `DEFAULT_LMDB_MAP_SIZE`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the directory cannot be created or the
+    /// environment cannot be opened
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<LmdbStore, Error> {
+        Self::open_with_map_size(path, DEFAULT_LMDB_MAP_SIZE)
+    }
+
+    /// Open (or create) a `LmdbStore` in the given directory. The database
+    /// cannot grow beyond `map_size` bytes, which should be a multiple of
+    /// the page size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the directory cannot be created or the
+    /// environment cannot be opened
+    pub fn open_with_map_size<P: AsRef<Path>>(
+        path: P,
+        map_size: usize,
+    ) -> Result<LmdbStore, Error> {
+        std::fs::create_dir_all(path.as_ref())?;
+
+        // SAFETY: LMDB environments must not be opened twice in one
+        // process, nor their files modified while mapped. This is
+        // documented on LmdbStore.
+        let env = unsafe {
+            EnvOpenOptions::new()
+                .map_size(map_size)
+                .max_dbs(7)
+                .open(path.as_ref())?
+        };
+
+        // Create all databases so that read transactions can always open them
+        let mut txn = env.write_txn()?;
+        let dup = |txn: &mut RwTxn, name| {
+            env.database_options()
+                .types::<Bytes, Bytes>()
+                .name(name)
+                .flags(DatabaseFlags::DUP_SORT)
+                .create(txn)
+        };
+        let dbs = Databases {
+            records: env.create_database(&mut txn, Some("records"))?,
+            addresses: env.create_database(&mut txn, Some("addresses"))?,
+            deleted: env.create_database(&mut txn, Some("deleted"))?,
+            authors: dup(&mut txn, "authors")?,
+            kinds: dup(&mut txn, "kinds")?,
+            tags: dup(&mut txn, "tags")?,
+            tag_values: dup(&mut txn, "tag_values")?,
+        };
+        txn.commit()?;
+
+        Ok(LmdbStore(KvStore::new(LmdbBackend {
+            env,
+            dbs,
+            path: path.as_ref().to_owned(),
+        })))
+    }
+
+    /// View a stored record in place in the memory map, without copying
+    /// it. Records hidden by `DeletionMode::Hide` are included.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the database fails
+    pub fn with_record<F, R>(&self, id: &Id, f: F) -> Result<Option<R>, Error>
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        self.0.with_record(id, f)
+    }
+}
+
+/// A `Snapshot` of a `LmdbStore`.
+///
+/// This holds an LMDB read transaction, so it costs nothing to take and
+/// does not block writers. Pages freed by later writes are not reused
+/// until the snapshot is dropped, so do not hold one for long.
+pub struct LmdbSnapshot(KvSnapshot<LmdbTxn<RoTxn<'static>>>);
+
+impl_kv_store!(LmdbStore, LmdbSnapshot);
+
+pub(crate) struct LmdbBackend {
+    env: Env,
+    dbs: Databases,
+    path: PathBuf,
+}
+
+impl KvBackend for LmdbBackend {
+    type Reader = LmdbTxn<RoTxn<'static>>;
+    type Snapshot = LmdbSnapshot;
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<Self::Reader, Error> {
+        Ok(LmdbTxn {
+            txn: self.env.clone().static_read_txn()?,
+            dbs: self.dbs,
+            dirty: false,
+        })
+    }
+
+    fn snapshot(&self) -> Result<LmdbSnapshot, Error> {
+        Ok(LmdbSnapshot(KvSnapshot::new(self.read()?)))
+    }
+
+    fn write<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn KvWrite) -> Result<T, Error>,
+    {
+        let mut writer = LmdbTxn {
+            txn: self.env.write_txn()?,
+            dbs: self.dbs,
+            dirty: false,
+        };
+        let output = f(&mut writer)?;
+        if writer.dirty {
+            writer.txn.commit()?;
+        }
+        Ok(output)
+    }
+
+    fn reclaim(&mut self) -> Result<(), Error> {
+        // LMDB reuses freed pages itself, and never shrinks its file
+        Ok(())
+    }
+}
+
+// A read or write transaction with the databases
+pub(crate) struct LmdbTxn<T> {
+    txn: T,
+    dbs: Databases,
+    dirty: bool,
+}
+
+// Read and write transactions both read
+trait AsRoTxn {
+    fn as_ro_txn(&self) -> &RoTxn<'_>;
+}
+
+impl AsRoTxn for RoTxn<'_> {
+    fn as_ro_txn(&self) -> &RoTxn<'_> {
+        self
+    }
+}
+
+impl AsRoTxn for RwTxn<'_> {
+    fn as_ro_txn(&self) -> &RoTxn<'_> {
+        self
+    }
+}
+
+impl<T: AsRoTxn> KvRead for LmdbTxn<T> {
+    fn get(
+        &self,
+        table: KvTable,
+        key: &[u8],
+        f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        let (db, _) = self.dbs.get(table);
+        let Some(value) = db.get(self.txn.as_ro_txn(), key)? else {
+            return Ok(false);
+        };
+        f(value)?;
+        Ok(true)
+    }
+
+    fn scan(
+        &self,
+        table: KvTable,
+        range: KeyRange<'_>,
+        reverse: bool,
+        f: &mut ScanFn<'_>,
+    ) -> Result<(), Error> {
+        let (db, _) = self.dbs.get(table);
+        let mut step = |item: heed::Result<(&[u8], &[u8])>| -> Result<bool, Error> {
+            let (key, value) = item?;
+            f(key, value)
+        };
+        if reverse {
+            for item in db.rev_range(self.txn.as_ro_txn(), &range)? {
+                if !step(item)? {
+                    break;
+                }
+            }
+        } else {
+            for item in db.range(self.txn.as_ro_txn(), &range)? {
+                if !step(item)? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KvWrite for LmdbTxn<RwTxn<'_>> {
+    fn insert(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.dirty = true;
+        let (db, _) = self.dbs.get(table);
+        db.put(&mut self.txn, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.dirty = true;
+        let _ = match self.dbs.get(table) {
+            (db, true) => db.delete_one_duplicate(&mut self.txn, key, value)?,
+            (db, false) => db.delete(&mut self.txn, key)?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Filter, Kind, OwnedRecord, OwnedTag, OwnedTagSet, PutOutcome, RecordFlags, RecordParts,
+        RecordStore, SecretKey, Timestamp, WriteBatch, WriteResult,
+    };
+
+    #[test]
+    fn test_lmdb_store() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::PROFILE,
+                    deterministic_key: Some(b"profile"),
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let path = std::env::temp_dir().join(format!("mosaic-lmdb-{}", rand::random::<u64>()));
+        let mut store = LmdbStore::open_with_map_size(&path, 16 * 1024 * 1024).unwrap();
+
+        let profile1 = make(1000);
+        let profile2 = make(2000);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Duplicate);
+        assert_eq!(store.put(&profile2).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Superseded);
+        assert!(store.get_by_id(&profile1.id()).unwrap().is_none());
+
+        // Reopen and check persistence
+        drop(store);
+        let mut store = LmdbStore::open_with_map_size(&path, 16 * 1024 * 1024).unwrap();
+        assert_eq!(
+            store.get_by_address(&profile2.address()).unwrap(),
+            Some(profile2.clone())
+        );
+        assert_eq!(
+            store
+                .with_record(&profile2.id(), |r| r.payload_bytes().to_vec())
+                .unwrap(),
+            Some(b"hello".to_vec())
+        );
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::PROFILE];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        filter.until = Some(Timestamp::from_millis(2000).unwrap());
+        assert!(store.query(&filter).unwrap().is_empty());
+        filter.since = Some(Timestamp::from_millis(1000).unwrap());
+        filter.until = Some(Timestamp::from_millis(2001).unwrap());
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        let mut filter = Filter::new();
+        filter.addresses = vec![profile2.address()];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        let mut filter = Filter::new();
+        filter.ids = vec![profile1.id(), profile2.id()];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+
+        let snapshot = store.snapshot().unwrap();
+        assert!(store.delete(&profile2.id()).unwrap());
+        assert!(store.get_by_address(&profile2.address()).unwrap().is_none());
+        assert!(store.is_deleted(&profile2.id()).unwrap());
+        assert_eq!(
+            snapshot.get_by_address(&profile2.address()).unwrap(),
+            Some(profile2.clone())
+        );
+        drop(snapshot);
+
+        let mut batch = WriteBatch::new();
+        batch.put(profile1.clone());
+        batch.delete(profile1.id());
+        assert_eq!(
+            store.write_batch(batch).unwrap(),
+            vec![
+                WriteResult::Put(PutOutcome::Stored),
+                WriteResult::Delete(true)
+            ]
+        );
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_lmdb_store_indexes() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let path = std::env::temp_dir().join(format!("mosaic-lmdb-{}", rand::random::<u64>()));
+        let mut store = LmdbStore::open_with_map_size(&path, 16 * 1024 * 1024).unwrap();
+
+        let profile = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::PROFILE,
+                deterministic_key: Some(b"profile"),
+                timestamp: Timestamp::from_millis(2000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+        let mut tags = OwnedTagSet::new();
+        tags.push(&OwnedTag::new_notify_public_key(&secret_key.public()))
+            .unwrap();
+        let tagged = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(3000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: tags.as_bytes(),
+                payload: b"tagged",
+            },
+        )
+        .unwrap();
+        assert_eq!(store.put(&profile).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&tagged).unwrap(), PutOutcome::Stored);
+
+        let mut filter = Filter::new();
+        filter.author_keys = vec![secret_key.public()];
+        assert_eq!(
+            store.query(&filter).unwrap(),
+            vec![tagged.clone(), profile.clone()]
+        );
+        let mut filter = Filter::new();
+        filter.since = Some(Timestamp::from_millis(2500).unwrap());
+        assert_eq!(store.query(&filter).unwrap(), vec![tagged.clone()]);
+
+        // Tagged records are found through the tag index
+        let mut filter = Filter::new();
+        for tag in &tags {
+            filter.tags.push((tag.get_type(), tag.get_value().to_vec()));
+        }
+        assert_eq!(store.query(&filter).unwrap(), vec![tagged.clone()]);
+
+        // and by value prefix through the tag value index
+        let tag = tags.iter().next().unwrap();
+        let mut prefix_filter = Filter::new();
+        prefix_filter.tag_prefixes = vec![(tag.get_type(), tag.get_value()[..4].to_vec())];
+        assert_eq!(store.query(&prefix_filter).unwrap(), vec![tagged.clone()]);
+        prefix_filter.tag_prefixes = vec![(tag.get_type(), vec![0xFF; 33])];
+        assert!(store.query(&prefix_filter).unwrap().is_empty());
+
+        assert!(store.evict(&tagged.id()).unwrap());
+        assert!(!store.is_deleted(&tagged.id()).unwrap());
+        assert!(store.query(&filter).unwrap().is_empty());
+        prefix_filter.tag_prefixes = vec![(tag.get_type(), tag.get_value()[..4].to_vec())];
+        assert!(store.query(&prefix_filter).unwrap().is_empty());
+        assert_eq!(store.put(&tagged).unwrap(), PutOutcome::Stored);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{Filter, Kind, PublicKey, Record, TagKey};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// The index bucket a subscription is filed under. Each subscription is
+// filed under its most selective constraint only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Bucket {
+    Author(PublicKey),
+    Tag(TagKey),
+    Kind(Kind),
+    Any,
+}
+
+fn buckets(filter: &Filter) -> Vec<Bucket> {
+    if !filter.author_keys.is_empty() {
+        filter
+            .author_keys
+            .iter()
+            .map(|pk| Bucket::Author(*pk))
+            .collect()
+    } else if !filter.tags.is_empty() {
+        filter
+            .tags
+            .iter()
+            .map(|(ty, value)| Bucket::Tag(TagKey::new(*ty, value)))
+            .collect()
+    } else if !filter.kinds.is_empty() {
+        filter.kinds.iter().map(|k| Bucket::Kind(*k)).collect()
+    } else {
+        vec![Bucket::Any]
+    }
+}
+
+/// A `Matcher` matches newly accepted records against many live
+/// subscriptions at once.
+///
+/// Each subscription is a `Filter` under a caller-chosen subscription id
+/// `S`. Subscriptions are kept in an inverted index keyed by their most
+/// selective constraint (author, then tag, then kind), so matching a
+/// record only checks the subscriptions that could possibly match it
+/// rather than all of them.
+///
+/// A filter's `limit` and `include_deleted` are ignored here; they only
+/// apply to stored queries. Ephemeral records, which stores refuse, are
+/// matched like any other, so subscribers still receive them.
+#[derive(Debug, Clone)]
+pub struct Matcher<S> {
+    filters: HashMap<S, Filter>,
+    index: HashMap<Bucket, HashSet<S>>,
+}
+
+impl<S> Default for Matcher<S> {
+    fn default() -> Self {
+        Matcher {
+            filters: HashMap::new(),
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Clone + Eq + Hash> Matcher<S> {
+    /// Create a new empty `Matcher`
+    #[must_use]
+    pub fn new() -> Matcher<S> {
+        Matcher::default()
+    }
+
+    /// Add a subscription, replacing (and returning) any existing filter
+    /// with the same subscription id
+    pub fn insert(&mut self, subscription: S, filter: Filter) -> Option<Filter> {
+        let old = self.remove(&subscription);
+        for bucket in buckets(&filter) {
+            let _ = self
+                .index
+                .entry(bucket)
+                .or_default()
+                .insert(subscription.clone());
+        }
+        let _ = self.filters.insert(subscription, filter);
+        old
+    }
+
+    /// Remove a subscription, returning its filter
+    pub fn remove(&mut self, subscription: &S) -> Option<Filter> {
+        let filter = self.filters.remove(subscription)?;
+        for bucket in buckets(&filter) {
+            if let Some(subscriptions) = self.index.get_mut(&bucket) {
+                let _ = subscriptions.remove(subscription);
+                if subscriptions.is_empty() {
+                    let _ = self.index.remove(&bucket);
+                }
+            }
+        }
+        Some(filter)
+    }
+
+    /// Get the filter of a subscription
+    #[must_use]
+    pub fn get(&self, subscription: &S) -> Option<&Filter> {
+        self.filters.get(subscription)
+    }
+
+    /// Number of subscriptions
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Are there no subscriptions?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Remove all subscriptions
+    pub fn clear(&mut self) {
+        self.filters.clear();
+        self.index.clear();
+    }
+
+    /// The ids of all subscriptions whose filter matches the record, in no
+    /// particular order
+    #[must_use]
+    pub fn matches(&self, record: &Record) -> Vec<S> {
+        let mut candidates: HashSet<&S> = HashSet::new();
+        let mut gather = |bucket: Bucket| {
+            if let Some(subscriptions) = self.index.get(&bucket) {
+                candidates.extend(subscriptions);
+            }
+        };
+        gather(Bucket::Author(record.author_public_key()));
+        for key in TagKey::for_record(record) {
+            gather(Bucket::Tag(key));
+        }
+        gather(Bucket::Kind(record.kind()));
+        gather(Bucket::Any);
+
+        candidates
+            .into_iter()
+            .filter(|s| self.filters[*s].matches(record))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedRecord, OwnedTag, RecordFlags, RecordParts, SecretKey, TagType, Timestamp};
+
+    #[test]
+    fn test_matcher() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let alice = SecretKey::generate(&mut csprng);
+        let bob = SecretKey::generate(&mut csprng);
+
+        let notify = OwnedTag::new_notify_public_key(&bob.public());
+        let record = OwnedRecord::new(
+            &alice,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: notify.as_bytes(),
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut matcher: Matcher<u32> = Matcher::new();
+        for n in 0..1000 {
+            let mut filter = Filter::new();
+            filter.author_keys = vec![bob.public()];
+            filter.kinds = vec![Kind::MICROBLOG_ROOT];
+            let _ = matcher.insert(n, filter);
+        }
+
+        let _ = matcher.insert(1000, Filter::new());
+        let mut filter = Filter::new();
+        filter.author_keys = vec![bob.public(), alice.public()];
+        let _ = matcher.insert(1001, filter);
+        let mut filter = Filter::new();
+        filter.tags = vec![(TagType::NOTIFY_PUBLIC_KEY, bob.public().as_bytes().to_vec())];
+        let _ = matcher.insert(1002, filter);
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::BLOG_POST];
+        let _ = matcher.insert(1003, filter);
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::MICROBLOG_ROOT];
+        filter.since = Some(Timestamp::from_millis(2000).unwrap());
+        let _ = matcher.insert(1004, filter);
+
+        let mut ids = matcher.matches(&record);
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1000, 1001, 1002]);
+
+        assert!(matcher.remove(&1001).is_some());
+        let _ = matcher.insert(1000, {
+            let mut filter = Filter::new();
+            filter.author_keys = vec![bob.public()];
+            filter
+        });
+        let mut ids = matcher.matches(&record);
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1002]);
+        assert_eq!(matcher.len(), 1004);
+    }
+}
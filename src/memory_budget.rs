@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type Evictor = Box<dyn FnMut(usize) -> usize + Send>;
+
+#[derive(Default)]
+struct Component {
+    quota: usize,
+    used: usize,
+    evictor: Option<Evictor>,
+}
+
+#[derive(Default)]
+struct Inner {
+    total: usize,
+    components: HashMap<String, Component>,
+}
+
+impl Inner {
+    fn used(&self) -> usize {
+        self.components.values().map(|c| c.used).sum()
+    }
+
+    // How many bytes must be freed, by the component itself and by the
+    // budget as a whole, before `bytes` more can be reserved
+    fn shortfall(&self, name: &str, bytes: usize) -> (usize, usize) {
+        let component = &self.components[name];
+        let own = (component.used + bytes).saturating_sub(component.quota);
+        let total = (self.used() + bytes).saturating_sub(self.total);
+        (own, total)
+    }
+}
+
+/// A `MemoryBudget` bounds the memory used by the caches in this crate.
+///
+/// The budget has a total size in bytes and is divided among named
+/// components (for example the bootstrap cache, the verifier cache and a
+/// record cache), each with its own quota. Components reserve bytes
+/// before they grow and release them when they shrink.
+///
+/// When a reservation would exceed the component's quota or the total,
+/// eviction callbacks are asked to free memory: first the component's
+/// own, then those of other components, largest first. An eviction
+/// callback is given the number of bytes wanted and returns the number of
+/// bytes it actually freed. It must not call back into the budget.
+///
+/// `MemoryBudget` is cheap to clone; clones share the same accounting.
+#[derive(Clone, Default)]
+pub struct MemoryBudget(Arc<Mutex<Inner>>);
+
+impl std::fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.lock();
+        let mut components: Vec<(&String, usize, usize)> = inner
+            .components
+            .iter()
+            .map(|(name, c)| (name, c.used, c.quota))
+            .collect();
+        components.sort();
+        f.debug_struct("MemoryBudget")
+            .field("total", &inner.total)
+            .field("components", &components)
+            .finish()
+    }
+}
+
+impl MemoryBudget {
+    /// Create a new `MemoryBudget` of `total` bytes
+    #[must_use]
+    pub fn new(total: usize) -> MemoryBudget {
+        MemoryBudget(Arc::new(Mutex::new(Inner {
+            total,
+            components: HashMap::new(),
+        })))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a component with a quota of `quota` bytes, returning its
+    /// `BudgetHandle`. Registering an existing name changes its quota.
+    #[must_use]
+    pub fn register(&self, name: &str, quota: usize) -> BudgetHandle {
+        self.lock()
+            .components
+            .entry(name.to_owned())
+            .or_default()
+            .quota = quota;
+        BudgetHandle {
+            budget: self.clone(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// The total size of the budget in bytes
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.lock().total
+    }
+
+    /// The bytes currently reserved by all components
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.lock().used()
+    }
+
+    // Ask the named component to free `bytes`, returning what it freed
+    fn evict(&self, name: &str, bytes: usize) -> usize {
+        let Some(mut evictor) = self
+            .lock()
+            .components
+            .get_mut(name)
+            .and_then(|c| c.evictor.take())
+        else {
+            return 0;
+        };
+
+        // Run the callback without holding the lock
+        let freed = evictor(bytes);
+
+        let mut inner = self.lock();
+        if let Some(component) = inner.components.get_mut(name) {
+            component.used = component.used.saturating_sub(freed);
+            if component.evictor.is_none() {
+                component.evictor = Some(evictor);
+            }
+        }
+        freed
+    }
+}
+
+/// A component's share of a `MemoryBudget`
+#[derive(Debug)]
+pub struct BudgetHandle {
+    budget: MemoryBudget,
+    name: String,
+}
+
+impl BudgetHandle {
+    /// The name of the component
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the callback asked to free memory when this component or the
+    /// budget as a whole is over its limit
+    pub fn set_evictor<F>(&self, evictor: F)
+    where
+        F: FnMut(usize) -> usize + Send + 'static,
+    {
+        if let Some(component) = self.budget.lock().components.get_mut(&self.name) {
+            component.evictor = Some(Box::new(evictor));
+        }
+    }
+
+    /// Reserve `bytes`, evicting if needed. Returns false (and reserves
+    /// nothing) if enough memory could not be freed.
+    #[must_use]
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        let (own, total) = self.budget.lock().shortfall(&self.name, bytes);
+        if own > 0 || total > 0 {
+            let _ = self.budget.evict(&self.name, own.max(total));
+        }
+
+        let (_, total) = self.budget.lock().shortfall(&self.name, bytes);
+        if total > 0 {
+            let mut others: Vec<(String, usize)> = self
+                .budget
+                .lock()
+                .components
+                .iter()
+                .filter(|(name, _)| **name != self.name)
+                .map(|(name, c)| (name.clone(), c.used))
+                .collect();
+            others.sort_by_key(|(_, used)| std::cmp::Reverse(*used));
+            let mut needed = total;
+            for (name, _) in others {
+                needed = needed.saturating_sub(self.budget.evict(&name, needed));
+                if needed == 0 {
+                    break;
+                }
+            }
+        }
+
+        let mut inner = self.budget.lock();
+        let (own, total) = inner.shortfall(&self.name, bytes);
+        if own > 0 || total > 0 {
+            return false;
+        }
+        if let Some(component) = inner.components.get_mut(&self.name) {
+            component.used += bytes;
+        }
+        true
+    }
+
+    /// Release `bytes` previously reserved
+    pub fn release(&self, bytes: usize) {
+        if let Some(component) = self.budget.lock().components.get_mut(&self.name) {
+            component.used = component.used.saturating_sub(bytes);
+        }
+    }
+
+    /// The bytes currently reserved by this component
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.budget
+            .lock()
+            .components
+            .get(&self.name)
+            .map_or(0, |c| c.used)
+    }
+
+    /// This component's quota in bytes
+    #[must_use]
+    pub fn quota(&self) -> usize {
+        self.budget
+            .lock()
+            .components
+            .get(&self.name)
+            .map_or(0, |c| c.quota)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(100);
+        let records = budget.register("records", 80);
+        let verifier = budget.register("verifier", 50);
+
+        assert!(records.try_reserve(60));
+        assert!(!records.try_reserve(30)); // over quota, nothing to evict
+        assert_eq!(records.used(), 60);
+
+        // The records cache frees what it is asked for
+        let evicted = Arc::new(AtomicUsize::new(0));
+        let evicted2 = evicted.clone();
+        records.set_evictor(move |bytes| {
+            let _ = evicted2.fetch_add(bytes, Ordering::SeqCst);
+            bytes
+        });
+
+        // Over the total: the records cache is asked to make room
+        assert!(verifier.try_reserve(50));
+        assert_eq!(evicted.load(Ordering::SeqCst), 10);
+        assert_eq!(records.used(), 50);
+        assert_eq!(budget.used(), 100);
+
+        verifier.release(50);
+        assert_eq!(budget.used(), 50);
+    }
+}
@@ -0,0 +1,476 @@
+use crate::record_store::sort_and_limit;
+use crate::{
+    Address, Counter, Cursor, DeletionMode, Direction, Error, Filter, Histogram, Id, Kind,
+    MemorySnapshot, Metrics, OwnedRecord, Page, PublicKey, PutOutcome, Record, RecordStore,
+    SizeStats, Snapshot, TagIndex, MAX_RECORD_LEN,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// A `RecordStore` held entirely in memory.
+///
+/// Records are indexed by author, kind, tag, timestamp and address. When a
+/// record supersedes another at the same address, the older record is
+/// dropped. Deleted `Id`s are remembered so that deleted records are not
+/// stored again, and deleted records are purged or hidden according to
+/// the `DeletionMode`.
+///
+/// This is a reference implementation, useful for tests and small clients.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    // Records by Id. Since Ids sort in time order this also serves as the
+    // timestamp index.
+    records: BTreeMap<Id, OwnedRecord>,
+    by_author: HashMap<PublicKey, BTreeSet<Id>>,
+    by_kind: HashMap<Kind, BTreeSet<Id>>,
+    by_address: HashMap<Address, Id>,
+    by_tag: TagIndex,
+    deleted: HashSet<Id>,
+    deletion_mode: DeletionMode,
+    metrics: Option<Arc<dyn Metrics>>,
+    size_stats: Option<SizeStats>,
+    max_record_len: Option<usize>,
+}
+
+impl MemoryStore {
+    /// Create a new empty `MemoryStore`
+    #[must_use]
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// Report to the given `Metrics`
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Feed the sizes of stored records to the given `SizeStats`
+    pub fn set_size_stats(&mut self, size_stats: SizeStats) {
+        self.size_stats = Some(size_stats);
+    }
+
+    /// Set what happens to records when they are deleted. This does not
+    /// affect records already deleted.
+    pub fn set_deletion_mode(&mut self, mode: DeletionMode) {
+        self.deletion_mode = mode;
+    }
+
+    /// Refuse to store records longer than `max_len` bytes
+    /// (`MAX_RECORD_LEN` by default). Values above `MAX_RECORD_LEN` have
+    /// no effect. This does not affect records already stored.
+    pub fn set_max_record_len(&mut self, max_len: usize) {
+        self.max_record_len = Some(max_len.min(MAX_RECORD_LEN));
+    }
+
+    fn metric(&self, counter: Counter) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.increment(counter, 1);
+        }
+    }
+
+    fn observe_query(&self, results: usize) {
+        #[cfg(feature = "tracing")]
+        let _ = tracing::Span::current().record("results", results);
+        if let Some(ref metrics) = self.metrics {
+            metrics.increment(Counter::StoreQueries, 1);
+            metrics.observe(Histogram::QueryResults, results as u64);
+        }
+    }
+
+    /// Number of records stored, including hidden ones
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Is the store empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn visible(&self, id: &Id, filter: &Filter) -> Option<&OwnedRecord> {
+        if !filter.include_deleted && self.deleted.contains(id) {
+            return None;
+        }
+        self.records.get(id)
+    }
+
+    fn insert(&mut self, record: OwnedRecord) {
+        let id = record.id();
+        let _ = self
+            .by_author
+            .entry(record.author_public_key())
+            .or_default()
+            .insert(id);
+        let _ = self.by_kind.entry(record.kind()).or_default().insert(id);
+        let _ = self.by_address.insert(record.address(), id);
+        self.by_tag.insert(&record);
+        let _ = self.records.insert(id, record);
+    }
+
+    fn remove(&mut self, id: &Id) -> Option<OwnedRecord> {
+        let record = self.records.remove(id)?;
+        if let Some(set) = self.by_author.get_mut(&record.author_public_key()) {
+            let _ = set.remove(id);
+        }
+        if let Some(set) = self.by_kind.get_mut(&record.kind()) {
+            let _ = set.remove(id);
+        }
+        if self.by_address.get(&record.address()) == Some(id) {
+            let _ = self.by_address.remove(&record.address());
+        }
+        self.by_tag.remove(&record);
+        Some(record)
+    }
+
+    // Candidate Ids for a filter, from the most selective index available
+    fn candidates(&self, filter: &Filter) -> BTreeSet<Id> {
+        if !filter.ids.is_empty() {
+            filter
+                .ids
+                .iter()
+                .filter(|id| self.records.contains_key(id))
+                .copied()
+                .collect()
+        } else if !filter.addresses.is_empty() {
+            filter
+                .addresses
+                .iter()
+                .filter_map(|address| self.by_address.get(address))
+                .copied()
+                .collect()
+        } else if !filter.tags.is_empty() {
+            filter
+                .tags
+                .iter()
+                .flat_map(|(ty, value)| self.by_tag.get(*ty, value))
+                .copied()
+                .collect()
+        } else if !filter.tag_prefixes.is_empty() {
+            filter
+                .tag_prefixes
+                .iter()
+                .flat_map(|(ty, prefix)| self.by_tag.get_prefix(*ty, prefix))
+                .copied()
+                .collect()
+        } else if !filter.author_keys.is_empty() {
+            filter
+                .author_keys
+                .iter()
+                .filter_map(|pk| self.by_author.get(pk))
+                .flatten()
+                .copied()
+                .collect()
+        } else if !filter.kinds.is_empty() {
+            filter
+                .kinds
+                .iter()
+                .filter_map(|k| self.by_kind.get(k))
+                .flatten()
+                .copied()
+                .collect()
+        } else {
+            let start = filter.since.map(Id::min_for_timestamp);
+            let end = filter.until.map(Id::min_for_timestamp);
+            let range = match (start, end) {
+                (Some(s), Some(e)) => self.records.range(s..e),
+                (Some(s), None) => self.records.range(s..),
+                (None, Some(e)) => self.records.range(..e),
+                (None, None) => self.records.range(..),
+            };
+            range.map(|(id, _)| *id).collect()
+        }
+    }
+}
+
+impl RecordStore for MemoryStore {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(id = %record.id(), kind = %record.kind(), len = record.as_bytes().len()),
+            ret,
+            err
+        )
+    )]
+    fn put(&mut self, record: &Record) -> Result<PutOutcome, Error> {
+        if record.is_ephemeral() {
+            return Ok(PutOutcome::Ephemeral);
+        }
+        if record.as_bytes().len() > self.max_record_len.unwrap_or(MAX_RECORD_LEN) {
+            return Ok(PutOutcome::TooLarge);
+        }
+        let id = record.id();
+        if self.deleted.contains(&id) {
+            return Ok(PutOutcome::Deleted);
+        }
+        if self.records.contains_key(&id) {
+            return Ok(PutOutcome::Duplicate);
+        }
+
+        if let Some(current_id) = self.by_address.get(&record.address()).copied() {
+            if let Some(current) = self.records.get(&current_id) {
+                if !record.supersedes(current) {
+                    return Ok(PutOutcome::Superseded);
+                }
+            }
+            let _ = self.remove(&current_id);
+        }
+
+        self.insert(record.to_owned());
+        self.metric(Counter::StorePuts);
+        if let Some(ref size_stats) = self.size_stats {
+            size_stats.observe(record);
+        }
+        Ok(PutOutcome::Stored)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(id = %id), err)
+    )]
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+        Ok(self.visible(id, &Filter::new()).cloned())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(address = %address), err)
+    )]
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+        Ok(self
+            .by_address
+            .get(address)
+            .and_then(|id| self.visible(id, &Filter::new()))
+            .cloned())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(id = %id), ret, err)
+    )]
+    fn delete(&mut self, id: &Id) -> Result<bool, Error> {
+        let newly = self.deleted.insert(*id);
+        let present = match self.deletion_mode {
+            DeletionMode::Purge => self.remove(id).is_some(),
+            DeletionMode::Hide => newly && self.records.contains_key(id),
+        };
+        if present {
+            self.metric(Counter::StoreDeletes);
+        }
+        Ok(present)
+    }
+
+    fn evict(&mut self, id: &Id) -> Result<bool, Error> {
+        Ok(self.remove(id).is_some())
+    }
+
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+        Ok(self.deleted.contains(id))
+    }
+
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+        Ok(Box::new(self.records.values().cloned().map(Ok)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(filter = ?filter, results = tracing::field::Empty),
+            err
+        )
+    )]
+    fn query(&self, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+        filter.validate()?;
+        let mut output: Vec<OwnedRecord> = self
+            .candidates(filter)
+            .iter()
+            .filter_map(|id| self.visible(id, filter))
+            .filter(|r| filter.matches(r))
+            .cloned()
+            .collect();
+        sort_and_limit(&mut output, filter);
+        self.observe_query(output.len());
+        Ok(output)
+    }
+
+    fn snapshot(&self) -> Result<Box<dyn Snapshot + Send>, Error> {
+        Ok(Box::new(MemorySnapshot::new(
+            self.records.clone(),
+            self.by_address.clone(),
+            self.deleted.clone(),
+        )))
+    }
+
+    fn compact(&mut self) -> Result<usize, Error> {
+        // Superseded versions are dropped on put(), so there is nothing to
+        // remove; just release memory held by the indexes.
+        self.by_author.retain(|_, set| !set.is_empty());
+        self.by_kind.retain(|_, set| !set.is_empty());
+        self.by_author.shrink_to_fit();
+        self.by_kind.shrink_to_fit();
+        self.by_address.shrink_to_fit();
+        self.deleted.shrink_to_fit();
+        Ok(0)
+    }
+
+    fn page(&self, filter: &Filter, cursor: &Cursor) -> Result<Page, Error> {
+        filter.validate()?;
+        let candidates = self.candidates(filter);
+        let ids: Box<dyn Iterator<Item = &Id>> = match (cursor.direction(), cursor.last_id()) {
+            (Direction::NewestFirst, None) => Box::new(candidates.iter().rev()),
+            (Direction::NewestFirst, Some(id)) => Box::new(candidates.range(..id).rev()),
+            (Direction::OldestFirst, None) => Box::new(candidates.iter()),
+            (Direction::OldestFirst, Some(id)) => {
+                Box::new(candidates.range((Bound::Excluded(id), Bound::Unbounded)))
+            }
+        };
+        let limit = filter.limit.map_or(usize::MAX, |l| l as usize);
+        let output: Vec<OwnedRecord> = ids
+            .filter_map(|id| self.visible(id, filter))
+            .filter(|r| filter.matches(r))
+            .take(limit)
+            .cloned()
+            .collect();
+        self.observe_query(output.len());
+        Ok(Page::new(output, filter, cursor.direction()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedTag, RecordFlags, RecordParts, Reference, SecretKey, TagType, Timestamp};
+
+    #[test]
+    fn test_memory_store() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |kind: Kind, millis: u64, key: Option<&[u8]>| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: key,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let mut store = MemoryStore::new();
+
+        let post1 = make(Kind::MICROBLOG_ROOT, 1000, None);
+        let post2 = make(Kind::MICROBLOG_ROOT, 2000, None);
+        let chat = make(Kind::CHAT_MESSAGE, 3000, None);
+        assert_eq!(store.put(&post1).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&post2).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&chat).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&post1).unwrap(), PutOutcome::Duplicate);
+        assert_eq!(store.len(), 3);
+
+        // Records over the configured maximum length are refused
+        let mut small = MemoryStore::new();
+        small.set_max_record_len(200);
+        assert_eq!(small.put(&post1).unwrap(), PutOutcome::TooLarge);
+        assert!(small.is_empty());
+
+        // Query by kind, newest first
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::MICROBLOG_ROOT];
+        let found = store.query(&filter).unwrap();
+        assert_eq!(found, vec![post2.clone(), post1.clone()]);
+
+        // Query by author with a limit
+        let mut filter = Filter::new();
+        filter.author_keys = vec![secret_key.public()];
+        filter.limit = Some(1);
+        assert_eq!(store.query(&filter).unwrap(), vec![chat.clone()]);
+
+        // Query by time window
+        let mut filter = Filter::new();
+        filter.since = Some(Timestamp::from_millis(1000).unwrap());
+        filter.until = Some(Timestamp::from_millis(3000).unwrap());
+        assert_eq!(
+            store.query(&filter).unwrap(),
+            vec![post2.clone(), post1.clone()]
+        );
+
+        // Query by id and address
+        let mut filter = Filter::new();
+        filter.ids = vec![post1.id(), post2.id()];
+        filter.addresses = vec![post2.address()];
+        assert_eq!(store.query(&filter).unwrap(), vec![post2.clone()]);
+
+        // Query by tag
+        let post1_ref = Reference::from_bytes(post1.id().as_bytes()).unwrap();
+        let reply = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::REPLY_COMMENT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(3500).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: OwnedTag::new_reply(&post1_ref, Kind::MICROBLOG_ROOT).as_bytes(),
+                payload: b"hi",
+            },
+        )
+        .unwrap();
+        assert_eq!(store.put(&reply).unwrap(), PutOutcome::Stored);
+        let mut filter = Filter::new();
+        filter.tags = vec![(TagType::REPLY, post1_ref.as_bytes().to_vec())];
+        assert_eq!(store.query(&filter).unwrap(), vec![reply.clone()]);
+        assert!(store.delete(&reply.id()).unwrap());
+
+        // Replacement
+        let profile1 = make(Kind::PROFILE, 1000, Some(b"profile"));
+        let profile2 = make(Kind::PROFILE, 2000, Some(b"profile"));
+        assert_eq!(store.put(&profile2).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Superseded);
+        assert_eq!(
+            store.get_by_address(&profile2.address()).unwrap(),
+            Some(profile2.clone())
+        );
+
+        // Deletion
+        assert!(store.delete(&post2.id()).unwrap());
+        assert!(store.get_by_id(&post2.id()).unwrap().is_none());
+        assert_eq!(store.put(&post2).unwrap(), PutOutcome::Deleted);
+        assert_eq!(store.len(), 3);
+
+        // Retain-but-hide deletion
+        store.set_deletion_mode(DeletionMode::Hide);
+        assert!(store.delete(&chat.id()).unwrap());
+        assert!(store.is_deleted(&chat.id()).unwrap());
+        assert!(store.get_by_id(&chat.id()).unwrap().is_none());
+        assert_eq!(store.len(), 3);
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::CHAT_MESSAGE];
+        assert!(store.query(&filter).unwrap().is_empty());
+        filter.include_deleted = true;
+        assert_eq!(store.query(&filter).unwrap(), vec![chat.clone()]);
+
+        assert_eq!(store.compact().unwrap(), 0);
+        assert_eq!(store.len(), 3);
+
+        // Snapshots do not see later writes
+        let snapshot = store.snapshot().unwrap();
+        assert!(store.delete(&post1.id()).unwrap());
+        assert!(store.get_by_id(&post1.id()).unwrap().is_none());
+        assert_eq!(snapshot.get_by_id(&post1.id()).unwrap(), Some(post1));
+        assert!(snapshot.get_by_id(&chat.id()).unwrap().is_none());
+    }
+}
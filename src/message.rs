@@ -0,0 +1,414 @@
+use crate::{Error, Filter, Id, InnerError, Outcome, OwnedRecord, RejectReason};
+
+/// Identifies a query or subscription on a connection. The client chooses
+/// it, and the server echoes it in the messages that answer the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QueryId(pub u16);
+
+/// A message from a client to a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    /// Submit a record to be stored
+    Submit(OwnedRecord),
+
+    /// Ask for the stored records matching a filter. The server answers
+    /// with `ServerMessage::Record`s and then `ServerMessage::QueryClosed`.
+    Query {
+        /// The id of this query
+        query_id: QueryId,
+
+        /// The records wanted
+        filter: Filter,
+    },
+
+    /// Ask for the stored records matching a filter, and then for matching
+    /// records as they arrive, until unsubscribed
+    Subscribe {
+        /// The id of this subscription
+        query_id: QueryId,
+
+        /// The records wanted
+        filter: Filter,
+    },
+
+    /// End a subscription
+    Unsubscribe {
+        /// The id of the subscription
+        query_id: QueryId,
+    },
+}
+
+/// Why a query or subscription was closed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CloseReason {
+    /// All matching records have been sent
+    Complete,
+
+    /// The client unsubscribed
+    Unsubscribed,
+
+    /// The server refused the query
+    Rejected(String),
+}
+
+/// The result of a submission
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SubmissionResult {
+    /// The record was accepted
+    Accepted,
+
+    /// The record was already stored
+    Duplicate,
+
+    /// The record was rejected
+    Rejected(String),
+}
+
+impl From<&Outcome> for SubmissionResult {
+    fn from(outcome: &Outcome) -> SubmissionResult {
+        match outcome {
+            Outcome::Accepted(_) => SubmissionResult::Accepted,
+            Outcome::Rejected(RejectReason::Duplicate) => SubmissionResult::Duplicate,
+            Outcome::Rejected(reason) => SubmissionResult::Rejected(reason.to_string()),
+        }
+    }
+}
+
+/// A message from a server to a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    /// A record matching a query or subscription
+    Record {
+        /// The query it matches
+        query_id: QueryId,
+
+        /// The record
+        record: OwnedRecord,
+    },
+
+    /// A query or subscription has ended
+    QueryClosed {
+        /// The query
+        query_id: QueryId,
+
+        /// Why it ended
+        reason: CloseReason,
+    },
+
+    /// The result of a `ClientMessage::Submit`
+    SubmissionResult {
+        /// The `Id` of the submitted record
+        id: Id,
+
+        /// What became of it
+        result: SubmissionResult,
+    },
+
+    /// A human-readable notice from the server
+    Notice(String),
+}
+
+// Message types. Client messages have the high bit clear and server
+// messages have it set.
+const SUBMIT: u8 = 0x01;
+const QUERY: u8 = 0x02;
+const SUBSCRIBE: u8 = 0x03;
+const UNSUBSCRIBE: u8 = 0x04;
+const RECORD: u8 = 0x81;
+const QUERY_CLOSED: u8 = 0x82;
+const SUBMISSION_RESULT: u8 = 0x83;
+const NOTICE: u8 = 0x84;
+
+impl ClientMessage {
+    /// Encode the message
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the filter cannot be encoded. See
+    /// `Filter::to_bytes()`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        match self {
+            ClientMessage::Submit(record) => {
+                bytes.push(SUBMIT);
+                bytes.extend_from_slice(record.as_bytes());
+            }
+            ClientMessage::Query { query_id, filter } => {
+                bytes.push(QUERY);
+                bytes.extend_from_slice(&query_id.0.to_le_bytes());
+                filter.write(&mut bytes)?;
+            }
+            ClientMessage::Subscribe { query_id, filter } => {
+                bytes.push(SUBSCRIBE);
+                bytes.extend_from_slice(&query_id.0.to_le_bytes());
+                filter.write(&mut bytes)?;
+            }
+            ClientMessage::Unsubscribe { query_id } => {
+                bytes.push(UNSUBSCRIBE);
+                bytes.extend_from_slice(&query_id.0.to_le_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a message. Records are fully verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the message is truncated or malformed, or if a
+    /// record fails to verify
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<ClientMessage, Error> {
+        let message = match take(&mut bytes, 1)?[0] {
+            SUBMIT => return Ok(ClientMessage::Submit(read_record(bytes)?)),
+            QUERY => ClientMessage::Query {
+                query_id: read_query_id(&mut bytes)?,
+                filter: Filter::read(&mut bytes)?,
+            },
+            SUBSCRIBE => ClientMessage::Subscribe {
+                query_id: read_query_id(&mut bytes)?,
+                filter: Filter::read(&mut bytes)?,
+            },
+            UNSUBSCRIBE => ClientMessage::Unsubscribe {
+                query_id: read_query_id(&mut bytes)?,
+            },
+            _ => return Err(InnerError::InvalidMessage.into()),
+        };
+        finish(bytes, message)
+    }
+}
+
+impl ServerMessage {
+    /// Encode the message
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if a text field is longer than 65535 bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        match self {
+            ServerMessage::Record { query_id, record } => {
+                bytes.push(RECORD);
+                bytes.extend_from_slice(&query_id.0.to_le_bytes());
+                bytes.extend_from_slice(record.as_bytes());
+            }
+            ServerMessage::QueryClosed { query_id, reason } => {
+                bytes.push(QUERY_CLOSED);
+                bytes.extend_from_slice(&query_id.0.to_le_bytes());
+                match reason {
+                    CloseReason::Complete => bytes.push(0),
+                    CloseReason::Unsubscribed => bytes.push(1),
+                    CloseReason::Rejected(s) => {
+                        bytes.push(2);
+                        write_bytes(s.as_bytes(), &mut bytes)?;
+                    }
+                }
+            }
+            ServerMessage::SubmissionResult { id, result } => {
+                bytes.push(SUBMISSION_RESULT);
+                bytes.extend_from_slice(id.as_bytes());
+                match result {
+                    SubmissionResult::Accepted => bytes.push(0),
+                    SubmissionResult::Duplicate => bytes.push(1),
+                    SubmissionResult::Rejected(s) => {
+                        bytes.push(2);
+                        write_bytes(s.as_bytes(), &mut bytes)?;
+                    }
+                }
+            }
+            ServerMessage::Notice(s) => {
+                bytes.push(NOTICE);
+                write_bytes(s.as_bytes(), &mut bytes)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a message. Records are fully verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the message is truncated or malformed, or if a
+    /// record fails to verify
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<ServerMessage, Error> {
+        let message = match take(&mut bytes, 1)?[0] {
+            RECORD => {
+                let query_id = read_query_id(&mut bytes)?;
+                let record = read_record(bytes)?;
+                return Ok(ServerMessage::Record { query_id, record });
+            }
+            QUERY_CLOSED => {
+                let query_id = read_query_id(&mut bytes)?;
+                let reason = match take(&mut bytes, 1)?[0] {
+                    0 => CloseReason::Complete,
+                    1 => CloseReason::Unsubscribed,
+                    2 => CloseReason::Rejected(read_string(&mut bytes)?),
+                    _ => return Err(InnerError::InvalidMessage.into()),
+                };
+                ServerMessage::QueryClosed { query_id, reason }
+            }
+            SUBMISSION_RESULT => {
+                let id = Id::from_bytes(take(&mut bytes, 48)?.try_into().unwrap())?;
+                let result = match take(&mut bytes, 1)?[0] {
+                    0 => SubmissionResult::Accepted,
+                    1 => SubmissionResult::Duplicate,
+                    2 => SubmissionResult::Rejected(read_string(&mut bytes)?),
+                    _ => return Err(InnerError::InvalidMessage.into()),
+                };
+                ServerMessage::SubmissionResult { id, result }
+            }
+            NOTICE => ServerMessage::Notice(read_string(&mut bytes)?),
+            _ => return Err(InnerError::InvalidMessage.into()),
+        };
+        finish(bytes, message)
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < len {
+        return Err(InnerError::InvalidMessage.into());
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+// Nothing may follow the last field
+fn finish<T>(bytes: &[u8], message: T) -> Result<T, Error> {
+    if bytes.is_empty() {
+        Ok(message)
+    } else {
+        Err(InnerError::InvalidMessage.into())
+    }
+}
+
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, Error> {
+    let b = take(bytes, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_query_id(bytes: &mut &[u8]) -> Result<QueryId, Error> {
+    Ok(QueryId(read_u16(bytes)?))
+}
+
+// A record takes up the rest of the message
+fn read_record(bytes: &[u8]) -> Result<OwnedRecord, Error> {
+    OwnedRecord::from_vec(bytes.to_vec())
+}
+
+fn write_len(len: usize, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    let len = u16::try_from(len).map_err(|_| InnerError::InvalidMessage.into_err())?;
+    bytes.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn write_bytes(value: &[u8], bytes: &mut Vec<u8>) -> Result<(), Error> {
+    write_len(value.len(), bytes)?;
+    bytes.extend_from_slice(value);
+    Ok(())
+}
+
+fn read_bytes<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = read_u16(bytes)? as usize;
+    take(bytes, len)
+}
+
+fn read_string(bytes: &mut &[u8]) -> Result<String, Error> {
+    Ok(std::str::from_utf8(read_bytes(bytes)?)?.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, TagType, Timestamp};
+
+    #[test]
+    fn test_message_round_trip() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::MICROBLOG_ROOT, Kind::BLOG_POST];
+        filter.author_keys = vec![secret_key.public()];
+        filter.tags = vec![(TagType::NOTIFY_PUBLIC_KEY, vec![1, 2, 3])];
+        filter.tag_prefixes = vec![(TagType(0x40), b"u4pr".to_vec())];
+        filter.search = Some("rust".to_owned());
+        filter.since = Some(Timestamp::from_millis(1000).unwrap());
+        filter.limit = Some(10);
+
+        let query_id = QueryId(7);
+        let client_messages = vec![
+            ClientMessage::Submit(record.clone()),
+            ClientMessage::Query {
+                query_id,
+                filter: filter.clone(),
+            },
+            ClientMessage::Subscribe {
+                query_id,
+                filter: Filter::new(),
+            },
+            ClientMessage::Unsubscribe { query_id },
+        ];
+        for message in client_messages {
+            let bytes = message.to_bytes().unwrap();
+            assert_eq!(ClientMessage::from_bytes(&bytes).unwrap(), message);
+            assert!(ServerMessage::from_bytes(&bytes).is_err());
+            assert!(ClientMessage::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        }
+
+        let server_messages = vec![
+            ServerMessage::Record {
+                query_id,
+                record: record.clone(),
+            },
+            ServerMessage::QueryClosed {
+                query_id,
+                reason: CloseReason::Complete,
+            },
+            ServerMessage::QueryClosed {
+                query_id,
+                reason: CloseReason::Rejected("too broad".to_owned()),
+            },
+            ServerMessage::SubmissionResult {
+                id: record.id(),
+                result: SubmissionResult::from(&Outcome::Accepted(record.id())),
+            },
+            ServerMessage::SubmissionResult {
+                id: record.id(),
+                result: SubmissionResult::from(&Outcome::Rejected(RejectReason::Duplicate)),
+            },
+            ServerMessage::SubmissionResult {
+                id: record.id(),
+                result: SubmissionResult::Rejected("spam".to_owned()),
+            },
+            ServerMessage::Notice("maintenance at noon".to_owned()),
+        ];
+        for message in server_messages {
+            let bytes = message.to_bytes().unwrap();
+            assert_eq!(ServerMessage::from_bytes(&bytes).unwrap(), message);
+            assert!(ClientMessage::from_bytes(&bytes).is_err());
+            assert!(ServerMessage::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        }
+
+        // Trailing bytes are rejected
+        let mut bytes = ClientMessage::Unsubscribe { query_id }.to_bytes().unwrap();
+        bytes.push(0);
+        let err = ClientMessage::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(err.inner, InnerError::InvalidMessage));
+        assert!(ClientMessage::from_bytes(&[]).is_err());
+    }
+}
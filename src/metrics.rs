@@ -0,0 +1,126 @@
+use crate::RejectReason;
+
+/// Counters reported through `Metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// Records fully verified (hash and signature)
+    Verifications,
+
+    /// Records that failed verification
+    VerificationFailures,
+
+    /// Records accepted
+    Accepts,
+
+    /// Records rejected
+    Rejects,
+
+    /// Bytes of records accepted
+    BytesAccepted,
+
+    /// Records stored
+    StorePuts,
+
+    /// Records deleted from a store
+    StoreDeletes,
+
+    /// Queries run against a store
+    StoreQueries,
+}
+
+impl Counter {
+    /// A name suitable for a metrics system such as Prometheus
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::Verifications => "mosaic_verifications_total",
+            Counter::VerificationFailures => "mosaic_verification_failures_total",
+            Counter::Accepts => "mosaic_accepts_total",
+            Counter::Rejects => "mosaic_rejects_total",
+            Counter::BytesAccepted => "mosaic_accepted_bytes_total",
+            Counter::StorePuts => "mosaic_store_puts_total",
+            Counter::StoreDeletes => "mosaic_store_deletes_total",
+            Counter::StoreQueries => "mosaic_store_queries_total",
+        }
+    }
+}
+
+/// Histograms reported through `Metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Histogram {
+    /// Length of each record accepted, in bytes
+    RecordBytes,
+
+    /// Number of records returned by each query
+    QueryResults,
+
+    /// Score given by each `SpamScorer` to each record it scored
+    SpamScore,
+}
+
+impl Histogram {
+    /// A name suitable for a metrics system such as Prometheus
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Histogram::RecordBytes => "mosaic_record_bytes",
+            Histogram::QueryResults => "mosaic_query_results",
+            Histogram::SpamScore => "mosaic_spam_score",
+        }
+    }
+}
+
+/// A sink for operational metrics.
+///
+/// The `AcceptancePipeline` and the stores report to a `Metrics` if one is
+/// set. Every method has a no-op default, so implementors only need to
+/// handle what they care about. Implementations are typically thin
+/// wrappers around a metrics library such as Prometheus.
+pub trait Metrics: std::fmt::Debug {
+    /// Increase a counter
+    fn increment(&self, _counter: Counter, _amount: u64) {}
+
+    /// Record an observation in a histogram
+    fn observe(&self, _histogram: Histogram, _value: u64) {}
+
+    /// A record was rejected. By default this increments `Counter::Rejects`.
+    fn rejected(&self, _reason: &RejectReason) {
+        self.increment(Counter::Rejects, 1);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Metrics that are simply counted, for tests
+    #[derive(Debug, Default)]
+    pub(crate) struct TestMetrics {
+        pub(crate) counters: Mutex<HashMap<Counter, u64>>,
+    }
+
+    impl TestMetrics {
+        pub(crate) fn get(&self, counter: Counter) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .get(&counter)
+                .copied()
+                .unwrap_or(0)
+        }
+    }
+
+    impl Metrics for TestMetrics {
+        fn increment(&self, counter: Counter, amount: u64) {
+            *self.counters.lock().unwrap().entry(counter).or_insert(0) += amount;
+        }
+    }
+
+    #[test]
+    fn test_metrics_names() {
+        assert_eq!(Counter::Accepts.name(), "mosaic_accepts_total");
+        assert_eq!(Histogram::RecordBytes.name(), "mosaic_record_bytes");
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{Error, InnerError, Record, VerifyLevel, MAX_RECORD_LEN};
+use memmap2::Mmap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+/// A memory-mapped log of records, in the `ArchiveFormat::Binary` layout
+/// (each record preceded by its length as a little-endian `u32`).
+///
+/// Every record is bounds-checked (and optionally fully verified) once when
+/// the file is opened. Afterwards `&Record` views are handed out directly
+/// over the mapping without copying. A `Record` is a byte slice with an
+/// alignment of one, so records need no alignment within the file.
+#[derive(Debug)]
+pub struct MmapRecordFile {
+    mmap: Mmap,
+    ranges: Vec<Range<usize>>,
+}
+
+impl MmapRecordFile {
+    /// Map and validate a record log
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be mapped, if a length prefix
+    /// runs past the end of the file, or if any record is invalid at the
+    /// given `VerifyLevel`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped;
+    /// otherwise records handed out may change underneath the caller or
+    /// the process may fault.
+    pub unsafe fn open<P: AsRef<Path>>(path: P, verify: VerifyLevel) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        let ranges = Self::validate(&mmap, verify)?;
+        Ok(MmapRecordFile { mmap, ranges })
+    }
+
+    fn validate(bytes: &[u8], verify: VerifyLevel) -> Result<Vec<Range<usize>>, Error> {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let Some(len_bytes) = bytes.get(offset..offset + 4) else {
+                return Err(InnerError::EndOfInput.into());
+            };
+            let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            if len > MAX_RECORD_LEN {
+                return Err(InnerError::RecordTooLong.into());
+            }
+            let start = offset + 4;
+            let Some(record_bytes) = bytes.get(start..start + len) else {
+                return Err(InnerError::EndOfInput.into());
+            };
+
+            // SAFETY: the record is checked before any view of it is used
+            let record = unsafe { Record::from_bytes_strict(record_bytes)? };
+            if verify == VerifyLevel::Full {
+                record.verify()?;
+            }
+
+            ranges.push(start..start + len);
+            offset = start + len;
+        }
+        Ok(ranges)
+    }
+
+    /// The number of records
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Is the file empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The record at `index`, in file order
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Record> {
+        let range = self.ranges.get(index)?.clone();
+        // SAFETY: validated in open()
+        Some(unsafe { Record::from_bytes(&self.mmap[range]).ok()? })
+    }
+
+    /// Iterate over the records in file order
+    pub fn iter(&self) -> impl Iterator<Item = &Record> + '_ {
+        (0..self.ranges.len()).filter_map(|i| self.get(i))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ArchiveFormat, Kind, OwnedRecord, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_mmap_record_file() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let records: Vec<OwnedRecord> = (0..3_u64)
+            .map(|n| {
+                OwnedRecord::new(
+                    &secret_key,
+                    &RecordParts {
+                        kind: Kind::MICROBLOG_ROOT,
+                        deterministic_key: None,
+                        timestamp: Timestamp::from_millis(1000 + n).unwrap(),
+                        flags: RecordFlags::empty(),
+                        app_flags: 0,
+                        tags_bytes: b"",
+                        payload: b"hello",
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let mut log = Vec::new();
+        for record in &records {
+            crate::archive::write_record(&mut log, ArchiveFormat::Binary, record).unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("mosaic-mmap-{}", rand::random::<u64>()));
+        std::fs::write(&path, &log).unwrap();
+
+        let file = unsafe { MmapRecordFile::open(&path, VerifyLevel::Full) }.unwrap();
+        assert_eq!(file.len(), 3);
+        assert!(file.iter().zip(&records).all(|(a, b)| a == &**b));
+
+        // A truncated log is rejected
+        std::fs::write(&path, &log[..log.len() - 1]).unwrap();
+        assert!(unsafe { MmapRecordFile::open(&path, VerifyLevel::LengthsOnly) }.is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Error, InnerError, SecretKey, Seed};
+use bip39::Mnemonic;
+use zeroize::Zeroizing;
+
+impl SecretKey {
+    /// The 24-word BIP-39 (English) mnemonic of this key, for writing an
+    /// identity down.
+    ///
+    /// The words encode the key bytes themselves as BIP-39 entropy, so
+    /// `from_mnemonic()` restores exactly this key. They carry no
+    /// passphrase; to protect a written backup with one, keep a `Seed`
+    /// mnemonic and derive keys with `Seed::from_mnemonic()` instead.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn to_mnemonic(&self) -> String {
+        Mnemonic::from_entropy(self.as_bytes())
+            .expect("32 bytes is valid BIP-39 entropy")
+            .to_string()
+    }
+
+    /// Restore a key from the 24 words of `to_mnemonic()`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the words are not a valid 24-word BIP-39
+    /// mnemonic (unknown words, or a bad checksum)
+    pub fn from_mnemonic(words: &str) -> Result<SecretKey, Error> {
+        let mnemonic =
+            Mnemonic::parse(words).map_err(|_| InnerError::InvalidMnemonic.into_err())?;
+        let entropy = Zeroizing::new(mnemonic.to_entropy());
+        let bytes: &[u8; 32] = entropy
+            .as_slice()
+            .try_into()
+            .map_err(|_| InnerError::InvalidMnemonic.into_err())?;
+        Ok(SecretKey::from_bytes(bytes))
+    }
+}
+
+impl Seed {
+    /// The `Seed` of a BIP-39 mnemonic of any standard length and an
+    /// optional passphrase (pass `""` for none).
+    ///
+    /// This is the standard BIP-39 seed, so together with the SLIP-0010
+    /// derivation of `Seed` the same words give the same keys in other
+    /// wallets. A different passphrase gives a different, equally valid
+    /// seed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the words are not a valid BIP-39 mnemonic
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_mnemonic(words: &str, passphrase: &str) -> Result<Seed, Error> {
+        let mnemonic =
+            Mnemonic::parse(words).map_err(|_| InnerError::InvalidMnemonic.into_err())?;
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+        Ok(Seed::from_bytes(seed.as_slice()).expect("BIP-39 seeds are 64 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_mnemonic() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let words = secret_key.to_mnemonic();
+        assert_eq!(words.split(' ').count(), 24);
+        let restored = SecretKey::from_mnemonic(&words).unwrap();
+        assert_eq!(restored.as_bytes(), secret_key.as_bytes());
+
+        // Pinned: BIP-39 entropy of all zeroes, and of all 0x7f
+        let zero = SecretKey::from_bytes(&[0; 32]);
+        assert_eq!(zero.to_mnemonic(), ZERO_WORDS);
+        assert_eq!(
+            SecretKey::from_mnemonic(ZERO_WORDS).unwrap().as_bytes(),
+            &[0; 32]
+        );
+        assert_eq!(
+            SecretKey::from_bytes(&[0x7f; 32]).to_mnemonic(),
+            LEGAL_WORDS
+        );
+
+        // Bad checksums, unknown words and short mnemonics are refused
+        let bad_checksum = ZERO_WORDS.replace(" art", " abandon");
+        assert!(SecretKey::from_mnemonic(&bad_checksum).is_err());
+        assert!(SecretKey::from_mnemonic(&ZERO_WORDS.replace("art", "mosaic")).is_err());
+        let twelve = "abandon abandon abandon abandon abandon abandon \
+                      abandon abandon abandon abandon abandon about";
+        assert_eq!(
+            SecretKey::from_mnemonic(twelve).unwrap_err().kind(),
+            crate::ErrorKind::InvalidEncoding
+        );
+    }
+
+    #[test]
+    fn test_seed_mnemonic() {
+        // BIP-39 test vectors, with the passphrase "TREZOR"
+        let seed = Seed::from_mnemonic(ZERO_WORDS, "TREZOR").unwrap();
+        assert_eq!(seed.as_bytes(), &ZERO_SEED);
+        let seed = Seed::from_mnemonic(LEGAL_WORDS, "TREZOR").unwrap();
+        assert_eq!(seed.as_bytes(), &LEGAL_SEED);
+
+        // The passphrase changes every key
+        let plain = Seed::from_mnemonic(LEGAL_WORDS, "").unwrap();
+        assert_ne!(plain.master_key().public(), seed.master_key().public());
+        assert!(Seed::from_mnemonic("legal winner", "").is_err());
+    }
+
+    const ZERO_WORDS: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                              abandon abandon abandon abandon abandon abandon abandon abandon \
+                              abandon abandon abandon abandon abandon abandon abandon art";
+
+    const ZERO_SEED: [u8; 64] = [
+        0xbd, 0xa8, 0x54, 0x46, 0xc6, 0x84, 0x13, 0x70, 0x70, 0x90, 0xa5, 0x20, 0x22, 0xed, 0xd2,
+        0x6a, 0x1c, 0x94, 0x62, 0x29, 0x50, 0x29, 0xf2, 0xe6, 0x0c, 0xd7, 0xc4, 0xf2, 0xbb, 0xd3,
+        0x09, 0x71, 0x70, 0xaf, 0x7a, 0x4d, 0x73, 0x24, 0x5c, 0xaf, 0xa9, 0xc3, 0xcc, 0xa8, 0xd5,
+        0x61, 0xa7, 0xc3, 0xde, 0x6f, 0x5d, 0x4a, 0x10, 0xbe, 0x8e, 0xd2, 0xa5, 0xe6, 0x08, 0xd6,
+        0x8f, 0x92, 0xfc, 0xc8,
+    ];
+
+    const LEGAL_WORDS: &str = "legal winner thank year wave sausage worth useful legal winner \
+                               thank year wave sausage worth useful legal winner thank year \
+                               wave sausage worth title";
+
+    const LEGAL_SEED: [u8; 64] = [
+        0xbc, 0x09, 0xfc, 0xa1, 0x80, 0x4f, 0x7e, 0x69, 0xda, 0x93, 0xc2, 0xf2, 0x02, 0x8e, 0xb2,
+        0x38, 0xc2, 0x27, 0xf2, 0xe9, 0xdd, 0xa3, 0x0c, 0xd6, 0x36, 0x99, 0x23, 0x25, 0x78, 0x48,
+        0x0a, 0x40, 0x21, 0xb1, 0x46, 0xad, 0x71, 0x7f, 0xbb, 0x7e, 0x45, 0x1c, 0xe9, 0xeb, 0x83,
+        0x5f, 0x43, 0x62, 0x0b, 0xf5, 0xc5, 0x14, 0xdb, 0x0f, 0x8a, 0xdd, 0x49, 0xf5, 0xd1, 0x21,
+        0x44, 0x9d, 0x3e, 0x87,
+    ];
+}
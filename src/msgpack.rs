@@ -0,0 +1,110 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encode a value as `MessagePack`.
+///
+/// This works for anything with a `serde` implementation in this crate,
+/// such as `OwnedRecord`, `Filter` and `Envelope`. Structs are encoded as
+/// maps keyed by field name so that JavaScript and Python tooling can
+/// decode them without a schema. Records and keys are encoded as `bin`.
+///
+/// # Errors
+///
+/// Returns an `Err` if the value cannot be encoded
+pub fn to_msgpack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    Ok(rmp_serde::to_vec_named(value)?)
+}
+
+/// Decode a value from `MessagePack`.
+///
+/// Records are checked for well-formedness as they are decoded, but their
+/// signatures are not verified.
+///
+/// # Errors
+///
+/// Returns an `Err` if the bytes are not valid `MessagePack` for `T`
+pub fn from_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    Ok(rmp_serde::from_slice(bytes)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::user_bootstrap::ServerUsage;
+    use crate::{
+        Address, Envelope, Filter, Id, Kind, OwnedRecord, RecordFlags, RecordParts, SecretKey,
+        TagType, Timestamp, UserBootstrap,
+    };
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let bytes = to_msgpack(&record).unwrap();
+        let decoded: OwnedRecord = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, record);
+
+        let bytes = to_msgpack(&*record).unwrap();
+        assert_eq!(from_msgpack::<OwnedRecord>(&bytes).unwrap(), record);
+        let bytes = to_msgpack(&(record.id(), record.address())).unwrap();
+        let decoded: (Id, Address) = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, (record.id(), record.address()));
+
+        #[cfg(feature = "uri")]
+        {
+            let mut server_bootstrap = crate::ServerBootstrap::new();
+            server_bootstrap
+                .append_uri("wss://example.com".parse().unwrap())
+                .unwrap();
+            let bytes = to_msgpack(&server_bootstrap).unwrap();
+            let decoded: crate::ServerBootstrap = from_msgpack(&bytes).unwrap();
+            assert_eq!(decoded.to_dht_string(), server_bootstrap.to_dht_string());
+        }
+
+        let mut user_bootstrap = UserBootstrap::new();
+        user_bootstrap.append_server(ServerUsage::OUTBOX, secret_key.public());
+        let bytes = to_msgpack(&user_bootstrap).unwrap();
+        let decoded: UserBootstrap = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded.inner(), user_bootstrap.inner());
+
+        let mut envelope = Envelope::new(record, Timestamp::from_millis(2000).unwrap());
+        envelope.source = Some(secret_key.public());
+        let bytes = to_msgpack(&envelope).unwrap();
+        let decoded: Envelope = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, envelope);
+
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::MICROBLOG_ROOT];
+        filter.author_keys = vec![secret_key.public()];
+        filter.tags = vec![(TagType::NOTIFY_PUBLIC_KEY, vec![1, 2, 3])];
+        filter.since = Some(Timestamp::from_millis(1000).unwrap());
+        filter.limit = Some(10);
+        let bytes = to_msgpack(&filter).unwrap();
+        let decoded: Filter = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, filter);
+        filter.include_deleted = true;
+        assert_eq!(to_msgpack(&filter).unwrap(), bytes);
+        let decoded: Filter = from_msgpack(&bytes).unwrap();
+        assert!(!decoded.include_deleted);
+
+        // Garbage is rejected
+        assert!(from_msgpack::<OwnedRecord>(&[0xc1]).is_err());
+        assert!(from_msgpack::<Id>(&[0xc4, 1, 0]).is_err());
+    }
+}
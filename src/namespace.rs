@@ -0,0 +1,338 @@
+use crate::{Address, Error, Id, OwnedRecord, PublicKey, PutOutcome, Record, RecordStore};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The name of a logical store within a `NamespacedStore`, for example a
+/// hosting customer or a server key
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Create a `Namespace` with the given name
+    #[must_use]
+    pub fn new(name: &str) -> Namespace {
+        Namespace(name.to_owned())
+    }
+
+    /// The name of the namespace
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Namespace {
+    fn from(name: &str) -> Namespace {
+        Namespace::new(name)
+    }
+}
+
+impl From<PublicKey> for Namespace {
+    fn from(key: PublicKey) -> Namespace {
+        Namespace(key.printable())
+    }
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Limits on what one namespace may store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// Maximum number of records
+    pub max_records: Option<usize>,
+
+    /// Maximum total length of records, in bytes
+    pub max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct NamespaceState {
+    // Member Ids and their record lengths
+    ids: BTreeMap<Id, usize>,
+    deleted: HashSet<Id>,
+    bytes: usize,
+    quota: Quota,
+}
+
+impl NamespaceState {
+    fn remove(&mut self, id: &Id) -> bool {
+        match self.ids.remove(id) {
+            Some(len) => {
+                self.bytes -= len;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A `NamespacedStore` lets one physical `RecordStore` serve several
+/// logical stores.
+///
+/// Each `Namespace` sees only the records put into it, has its own
+/// deletions, and may have a `Quota`. Access a namespace through
+/// `namespace()`, which returns a `NamespaceView` implementing
+/// `RecordStore`.
+///
+/// A record put into several namespaces is stored once. It is deleted
+/// from the underlying store only once no namespace holds it. Versions
+/// of a replaceable record are shared: a newer version put into one
+/// namespace replaces the older version in every namespace.
+///
+/// Namespace membership, quotas and deletions are held in memory only;
+/// they are not saved in the underlying store. Wrapping a persistent store
+/// such as a `RedbStore` keeps the records across a restart but not which
+/// namespaces hold them, so every namespace starts out empty. Put each
+/// namespace's records again at startup to rebuild membership (a record
+/// the underlying store already holds is not written again).
+#[derive(Debug, Default)]
+pub struct NamespacedStore<S> {
+    inner: S,
+    namespaces: HashMap<Namespace, NamespaceState>,
+}
+
+impl<S: RecordStore> NamespacedStore<S> {
+    /// Wrap a physical store
+    pub fn new(inner: S) -> NamespacedStore<S> {
+        NamespacedStore {
+            inner,
+            namespaces: HashMap::new(),
+        }
+    }
+
+    /// Set the quota for a namespace. Records already stored are kept even
+    /// if they exceed the new quota.
+    pub fn set_quota(&mut self, namespace: &Namespace, quota: Quota) {
+        self.namespaces.entry(namespace.clone()).or_default().quota = quota;
+    }
+
+    /// The number of records and bytes a namespace holds
+    #[must_use]
+    pub fn usage(&self, namespace: &Namespace) -> (usize, usize) {
+        self.namespaces
+            .get(namespace)
+            .map_or((0, 0), |state| (state.ids.len(), state.bytes))
+    }
+
+    /// Access one namespace as a `RecordStore`
+    pub fn namespace(&mut self, namespace: &Namespace) -> NamespaceView<'_, S> {
+        let _ = self.namespaces.entry(namespace.clone()).or_default();
+        NamespaceView {
+            store: self,
+            namespace: namespace.clone(),
+        }
+    }
+
+    /// The underlying physical store
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwrap the underlying physical store
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn referenced(&self, id: &Id) -> bool {
+        self.namespaces
+            .values()
+            .any(|state| state.ids.contains_key(id))
+    }
+}
+
+/// One namespace of a `NamespacedStore`
+#[derive(Debug)]
+pub struct NamespaceView<'a, S> {
+    store: &'a mut NamespacedStore<S>,
+    namespace: Namespace,
+}
+
+impl<S: RecordStore> NamespaceView<'_, S> {
+    fn state(&self) -> &NamespaceState {
+        &self.store.namespaces[&self.namespace]
+    }
+
+    fn state_mut(&mut self) -> &mut NamespaceState {
+        self.store
+            .namespaces
+            .get_mut(&self.namespace)
+            .expect("namespace state is created with the view")
+    }
+
+    fn contains(&self, id: &Id) -> bool {
+        self.state().ids.contains_key(id)
+    }
+}
+
+impl<S: RecordStore> RecordStore for NamespaceView<'_, S> {
+    fn put(&mut self, record: &Record) -> Result<PutOutcome, Error> {
+        let id = record.id();
+        let len = record.as_bytes().len();
+        let state = self.state();
+        if state.deleted.contains(&id) {
+            return Ok(PutOutcome::Deleted);
+        }
+        if state.ids.contains_key(&id) {
+            return Ok(PutOutcome::Duplicate);
+        }
+        if state
+            .quota
+            .max_records
+            .is_some_and(|max| state.ids.len() >= max)
+            || state
+                .quota
+                .max_bytes
+                .is_some_and(|max| state.bytes + len > max)
+        {
+            return Ok(PutOutcome::QuotaExceeded);
+        }
+
+        let previous = self.store.inner.get_by_address(&record.address())?;
+        match self.store.inner.put(record)? {
+            PutOutcome::Stored | PutOutcome::Duplicate => {}
+            outcome => return Ok(outcome),
+        }
+
+        // The previous version was replaced in the physical store
+        if let Some(previous) = previous {
+            if previous.id() != id {
+                for state in self.store.namespaces.values_mut() {
+                    let _ = state.remove(&previous.id());
+                }
+            }
+        }
+
+        let state = self.state_mut();
+        let _ = state.ids.insert(id, len);
+        state.bytes += len;
+        Ok(PutOutcome::Stored)
+    }
+
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+        if !self.contains(id) {
+            return Ok(None);
+        }
+        self.store.inner.get_by_id(id)
+    }
+
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+        Ok(self
+            .store
+            .inner
+            .get_by_address(address)?
+            .filter(|r| self.contains(&r.id())))
+    }
+
+    fn delete(&mut self, id: &Id) -> Result<bool, Error> {
+        let state = self.state_mut();
+        let _ = state.deleted.insert(*id);
+        // The deletion is this namespace's alone, so the physical store only
+        // drops the bytes; recording it there would refuse the record to
+        // every other namespace
+        self.evict(id)
+    }
+
+    fn evict(&mut self, id: &Id) -> Result<bool, Error> {
+        if !self.state_mut().remove(id) {
+            return Ok(false);
+        }
+        if !self.store.referenced(id) {
+            let _ = self.store.inner.evict(id)?;
+        }
+        Ok(true)
+    }
+
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+        Ok(self.state().deleted.contains(id))
+    }
+
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+        let mut output: Vec<OwnedRecord> = Vec::with_capacity(self.state().ids.len());
+        for id in self.state().ids.keys() {
+            if let Some(record) = self.store.inner.get_by_id(id)? {
+                output.push(record);
+            }
+        }
+        Ok(Box::new(output.into_iter().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Filter, Kind, MemoryStore, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_namespaced_store() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+        let shared = make(1000);
+        let only_a = make(2000);
+
+        let a = Namespace::new("a");
+        let b = Namespace::new("b");
+        let mut store = NamespacedStore::new(MemoryStore::new());
+        store.set_quota(
+            &b,
+            Quota {
+                max_records: Some(1),
+                max_bytes: None,
+            },
+        );
+
+        let mut view = store.namespace(&a);
+        assert_eq!(view.put(&shared).unwrap(), PutOutcome::Stored);
+        assert_eq!(view.put(&only_a).unwrap(), PutOutcome::Stored);
+        assert_eq!(view.query(&Filter::new()).unwrap().len(), 2);
+
+        let mut view = store.namespace(&b);
+        assert_eq!(view.put(&shared).unwrap(), PutOutcome::Stored);
+        assert_eq!(view.put(&only_a).unwrap(), PutOutcome::QuotaExceeded);
+        assert_eq!(view.query(&Filter::new()).unwrap(), vec![shared.clone()]);
+        assert!(view.get_by_id(&only_a.id()).unwrap().is_none());
+
+        // Deleting from one namespace leaves the record in the other
+        assert!(view.delete(&shared.id()).unwrap());
+        assert_eq!(view.put(&shared).unwrap(), PutOutcome::Deleted);
+        assert!(store
+            .namespace(&a)
+            .get_by_id(&shared.id())
+            .unwrap()
+            .is_some());
+        assert_eq!(store.inner().len(), 2);
+        assert_eq!(store.usage(&b), (0, 0));
+
+        // Deleting the last reference drops the bytes but leaves no
+        // tombstone, so other namespaces may still store the record
+        let mut view = store.namespace(&a);
+        assert!(view.delete(&only_a.id()).unwrap());
+        assert_eq!(store.inner().len(), 1);
+        assert!(!store.inner().is_deleted(&only_a.id()).unwrap());
+        let mut view = store.namespace(&Namespace::new("c"));
+        assert_eq!(view.put(&only_a).unwrap(), PutOutcome::Stored);
+        assert_eq!(
+            store.namespace(&a).put(&only_a).unwrap(),
+            PutOutcome::Deleted
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::tag::iter_tags;
+use crate::{
+    Error, InnerError, Kind, OwnedRecord, OwnedTag, Record, RecordFlags, RecordParts, SecretKey,
+    TagType, Timestamp,
+};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// How Mosaic kinds map to Nostr kinds.
+///
+/// Nostr kind 1 maps to `MICROBLOG_ROOT`, or to `REPLY_COMMENT` if it has
+/// an `e` tag.
+pub const NOSTR_KIND_MAP: &[(Kind, u32)] = &[
+    (Kind::MICROBLOG_ROOT, 1),
+    (Kind::REPLY_COMMENT, 1),
+    (Kind::CHAT_MESSAGE, 9),
+    (Kind::BLOG_POST, 30023),
+];
+
+/// The Nostr kind for a Mosaic kind, if there is one
+#[must_use]
+pub fn nostr_kind(kind: Kind) -> Option<u32> {
+    NOSTR_KIND_MAP
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, n)| *n)
+}
+
+/// A Nostr event without its id and signature, as produced from a Mosaic
+/// record.
+///
+/// Mosaic keys are ed25519 and Nostr keys are secp256k1, so a key can
+/// never be reused across the two protocols. The bridge supplies the Nostr
+/// public key of the author (or its own) and must sign the event with the
+/// matching secp256k1 key over `id()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedNostrEvent {
+    /// The author's x-only secp256k1 public key
+    pub pubkey: [u8; 32],
+
+    /// Seconds since the UNIX epoch
+    pub created_at: u64,
+
+    /// The Nostr kind
+    pub kind: u32,
+
+    /// The tags
+    pub tags: Vec<Vec<String>>,
+
+    /// The content
+    pub content: String,
+}
+
+impl UnsignedNostrEvent {
+    /// The NIP-01 serialization that the event id is the hash of
+    #[must_use]
+    pub fn serialize_for_id(&self) -> String {
+        let mut s = format!(
+            "[0,\"{}\",{},{},[",
+            to_hex(&self.pubkey),
+            self.created_at,
+            self.kind
+        );
+        for (i, tag) in self.tags.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            s.push('[');
+            for (j, value) in tag.iter().enumerate() {
+                if j > 0 {
+                    s.push(',');
+                }
+                push_json_string(&mut s, value);
+            }
+            s.push(']');
+        }
+        s.push_str("],");
+        push_json_string(&mut s, &self.content);
+        s.push(']');
+        s
+    }
+
+    /// The NIP-01 event id (the SHA-256 of `serialize_for_id()`)
+    #[must_use]
+    pub fn id(&self) -> [u8; 32] {
+        Sha256::digest(self.serialize_for_id().as_bytes()).into()
+    }
+}
+
+/// A signed Nostr event, as received by a bridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NostrEvent {
+    /// The event id
+    pub id: [u8; 32],
+
+    /// The event contents
+    pub event: UnsignedNostrEvent,
+
+    /// The Schnorr signature
+    pub sig: [u8; 64],
+}
+
+/// Convert a Mosaic record into an unsigned Nostr event.
+///
+/// - The payload becomes the content, so it must be uncompressed UTF-8.
+/// - Milliseconds are truncated from the timestamp.
+/// - A NIP-48 `proxy` tag carries the printable record `Id`, so that the
+///   event can be traced back to the record.
+/// - Blog posts get a `d` tag of the printable `Address`, so replacements
+///   of the record replace the Nostr event too.
+/// - Mosaic tags reference Mosaic keys and records, which have no Nostr
+///   equivalent, and are dropped.
+///
+/// # Errors
+///
+/// Returns an `Err` if the kind has no Nostr equivalent, if the payload is
+/// not text, or if the record is marked `NO_BRIDGE` (which also covers
+/// records that were themselves bridged from Nostr).
+pub fn record_to_nostr(record: &Record, pubkey: [u8; 32]) -> Result<UnsignedNostrEvent, Error> {
+    let kind =
+        nostr_kind(record.kind()).ok_or_else(|| InnerError::NostrUnmappable("kind").into_err())?;
+    if record.flags().contains(RecordFlags::NO_BRIDGE) {
+        return Err(InnerError::NostrUnmappable("record is marked NO_BRIDGE").into());
+    }
+    if record.flags().contains(RecordFlags::ZSTD) {
+        return Err(InnerError::NostrUnmappable("payload is compressed").into());
+    }
+    let content = std::str::from_utf8(record.payload_bytes())?.to_owned();
+
+    let mut tags = vec![vec![
+        "proxy".to_owned(),
+        record.id().printable(),
+        "mosaic".to_owned(),
+    ]];
+    if record.kind() == Kind::BLOG_POST {
+        tags.push(vec!["d".to_owned(), record.address().printable()]);
+    }
+
+    Ok(UnsignedNostrEvent {
+        pubkey,
+        created_at: record.timestamp().to_unixtime().0,
+        kind,
+        tags,
+        content,
+    })
+}
+
+/// Convert a Nostr event into a Mosaic record signed by `secret_key`.
+///
+/// - The content becomes a printable payload.
+/// - A `NOSTR_SISTER` tag carries the event id.
+/// - The record is flagged `NO_BRIDGE` so it is never bridged back.
+/// - Addressable events (such as long-form posts) use their `d` tag as the
+///   deterministic key, so that later versions replace earlier ones.
+/// - Nostr tags reference Nostr keys and events, which have no Mosaic
+///   equivalent, and are dropped.
+///
+/// The event's id and signature are not checked here; check them with a
+/// Nostr library before bridging.
+///
+/// # Errors
+///
+/// Returns an `Err` if the kind has no Mosaic equivalent, if the event was
+/// itself bridged from Mosaic, or if the record cannot be built.
+pub fn nostr_to_record(event: &NostrEvent, secret_key: &SecretKey) -> Result<OwnedRecord, Error> {
+    let e = &event.event;
+    let tag = |name: &str| {
+        e.tags
+            .iter()
+            .find(|t| t.first().map(String::as_str) == Some(name))
+    };
+
+    if tag("proxy").is_some_and(|t| t.get(2).map(String::as_str) == Some("mosaic")) {
+        return Err(InnerError::NostrUnmappable("event was bridged from Mosaic").into());
+    }
+
+    let kind = match e.kind {
+        1 if tag("e").is_some() => Kind::REPLY_COMMENT,
+        1 => Kind::MICROBLOG_ROOT,
+        9 => Kind::CHAT_MESSAGE,
+        30023 => Kind::BLOG_POST,
+        _ => return Err(InnerError::NostrUnmappable("kind").into()),
+    };
+
+    // Nostr times are Unix times; Mosaic's count leap seconds
+    let timestamp = Timestamp::from_unixtime(e.created_at, 0)?;
+
+    let deterministic_key = if (30000..40000).contains(&e.kind) {
+        Some(tag("d").and_then(|t| t.get(1)).map_or("", String::as_str))
+    } else {
+        None
+    };
+
+    let sister = OwnedTag::new_nostr_sister(&event.id);
+    OwnedRecord::new(
+        secret_key,
+        &RecordParts {
+            kind,
+            deterministic_key: deterministic_key.map(str::as_bytes),
+            timestamp,
+            flags: RecordFlags::PRINTABLE | RecordFlags::NO_BRIDGE,
+            app_flags: 0,
+            tags_bytes: sister.as_bytes(),
+            payload: e.content.as_bytes(),
+        },
+    )
+}
+
+/// The Nostr event id a record was bridged from, if it has a
+/// `NOSTR_SISTER` tag
+#[must_use]
+pub fn nostr_sister_id(record: &Record) -> Option<[u8; 32]> {
+    iter_tags(record.tags_bytes())
+        .find(|t| t.get_type() == TagType::NOSTR_SISTER)
+        .and_then(|t| t.get_value().try_into().ok())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+// Append a JSON string literal with the escaping NIP-01 requires: only
+// these seven characters are escaped, and every other character (other
+// control characters included) is written as is
+fn push_json_string(s: &mut String, value: &str) {
+    s.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            '\u{8}' => s.push_str("\\b"),
+            '\u{c}' => s.push_str("\\f"),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nostr_round_trip() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_unixtime(1_700_000_000, 123).unwrap(),
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"Hello \"Nostr\"\n\x01",
+            },
+        )
+        .unwrap();
+
+        let event = record_to_nostr(&record, [7; 32]).unwrap();
+        assert_eq!(event.kind, 1);
+        assert_eq!(event.created_at, 1_700_000_000);
+        assert_eq!(
+            event.serialize_for_id(),
+            format!(
+                "[0,\"{}\",1700000000,1,[[\"proxy\",\"{}\",\"mosaic\"]],\"Hello \\\"Nostr\\\"\\n\u{1}\"]",
+                "07".repeat(32),
+                record.id().printable()
+            )
+        );
+
+        // An event that came from Mosaic is not bridged back
+        let signed = NostrEvent {
+            id: event.id(),
+            event,
+            sig: [0; 64],
+        };
+        assert!(nostr_to_record(&signed, &secret_key).is_err());
+
+        // A native Nostr event is
+        let mut native = signed.clone();
+        native.event.tags.clear();
+        native.id = native.event.id();
+        let bridged = nostr_to_record(&native, &secret_key).unwrap();
+        assert_eq!(bridged.kind(), Kind::MICROBLOG_ROOT);
+        assert_eq!(bridged.payload_bytes(), record.payload_bytes());
+        assert_eq!(nostr_sister_id(&bridged), Some(native.id));
+        assert_eq!(
+            bridged.timestamp(),
+            Timestamp::from_unixtime(1_700_000_000, 0).unwrap()
+        );
+        assert!(record_to_nostr(&bridged, [7; 32]).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{Error, InnerError};
+
+const PADDING_MARKER: u8 = 0x80;
+
+/// How to pad a payload so that its length says less about its contents.
+///
+/// Encrypted payloads are as long as their plaintext, so the lengths of
+/// the records carrying them leak the lengths of the messages. Padding
+/// the plaintext to one of a few sizes before it is encrypted hides that;
+/// with the `seal` feature, `crypto::seal_padded()` does both.
+///
+/// Padding is a `0x80` byte followed by zero bytes (ISO/IEC 7816-4), so
+/// it is removed with `unpad_payload()` without knowing the policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum PaddingPolicy {
+    /// Do not pad
+    #[default]
+    None,
+
+    /// Pad to the next power of two
+    PowerOfTwo,
+
+    /// Pad to the smallest of these sizes that fits. Payloads that fit in
+    /// none of them are padded to a multiple of the largest.
+    Buckets(Vec<usize>),
+}
+
+impl PaddingPolicy {
+    /// The length a payload of `len` bytes has once padded
+    #[must_use]
+    pub fn padded_len(&self, len: usize) -> usize {
+        let min = len + 1;
+        match self {
+            PaddingPolicy::None => len,
+            PaddingPolicy::PowerOfTwo => min.next_power_of_two(),
+            PaddingPolicy::Buckets(sizes) => {
+                if let Some(size) = sizes.iter().filter(|&&size| size >= min).min() {
+                    *size
+                } else {
+                    match sizes.iter().max() {
+                        Some(&largest) if largest > 0 => min.div_ceil(largest) * largest,
+                        _ => min,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pad a payload
+    #[must_use]
+    pub fn pad(&self, payload: &[u8]) -> Vec<u8> {
+        let mut padded = payload.to_vec();
+        self.pad_in_place(&mut padded);
+        padded
+    }
+
+    pub(crate) fn pad_in_place(&self, payload: &mut Vec<u8>) {
+        if *self == PaddingPolicy::None {
+            return;
+        }
+        let padded_len = self.padded_len(payload.len());
+        payload.push(PADDING_MARKER);
+        payload.resize(padded_len, 0);
+    }
+}
+
+/// Remove the padding added by `PaddingPolicy::pad()`
+///
+/// # Errors
+///
+/// Returns an `Err` if the payload is not padded
+pub fn unpad_payload(padded: &[u8]) -> Result<&[u8], Error> {
+    let Some(end) = padded.iter().rposition(|&b| b != 0) else {
+        return Err(InnerError::InvalidPadding.into());
+    };
+    if padded[end] != PADDING_MARKER {
+        return Err(InnerError::InvalidPadding.into());
+    }
+    Ok(&padded[..end])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_padding_policies() {
+        let buckets = PaddingPolicy::Buckets(vec![256, 64, 1024]);
+        for (len, none, power, bucket) in [
+            (0, 0, 1, 64),
+            (1, 1, 2, 64),
+            (63, 63, 64, 64),
+            (64, 64, 128, 256),
+            (1023, 1023, 1024, 1024),
+            (1024, 1024, 2048, 2048),
+            (3000, 3000, 4096, 3072),
+        ] {
+            assert_eq!(PaddingPolicy::None.padded_len(len), none);
+            assert_eq!(PaddingPolicy::PowerOfTwo.padded_len(len), power);
+            assert_eq!(buckets.padded_len(len), bucket);
+        }
+        assert_eq!(PaddingPolicy::Buckets(vec![]).padded_len(5), 6);
+
+        for payload in [&b""[..], b"hello", b"trailing zeroes\0\0", &[0x80; 3]] {
+            for policy in [PaddingPolicy::PowerOfTwo, buckets.clone()] {
+                let padded = policy.pad(payload);
+                assert_eq!(padded.len(), policy.padded_len(payload.len()));
+                assert_eq!(unpad_payload(&padded).unwrap(), payload);
+            }
+        }
+        assert_eq!(PaddingPolicy::None.pad(b"hello"), b"hello");
+
+        assert!(unpad_payload(b"").is_err());
+        assert!(unpad_payload(&[0; 8]).is_err());
+        assert!(unpad_payload(b"hello\0\0").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::tag::iter_tags;
+use crate::{
+    Error, Id, InnerError, Kind, OwnedRecord, OwnedTag, PublicKey, Record, RecordFlags,
+    RecordParts, Reference, SecretKey, TagType, Timestamp,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A reaction (such as `+` or an emoji) to a record, as carried by a
+/// `REACTION` record.
+///
+/// The reaction record has a `REPLY` tag referencing the target and the
+/// reaction text as its payload. Its deterministic key is the target
+/// reference followed by the text, so reacting the same way twice replaces
+/// the earlier reaction. A reaction is withdrawn by deleting its record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reaction {
+    /// The record reacted to
+    pub target: Reference,
+
+    /// The kind of the record reacted to
+    pub target_kind: Kind,
+
+    /// The reaction text
+    pub content: String,
+}
+
+impl Reaction {
+    /// Create a reaction record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the content is too long for a record, or if
+    /// signing fails
+    pub fn to_record(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        let tag = OwnedTag::new_reply(&self.target, self.target_kind);
+        let mut key = self.target.as_bytes().to_vec();
+        key.extend_from_slice(self.content.as_bytes());
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::REACTION,
+                deterministic_key: Some(&key),
+                timestamp,
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: tag.as_bytes(),
+                payload: self.content.as_bytes(),
+            },
+        )
+    }
+
+    /// Read a reaction from a reaction record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `REACTION`, has no valid
+    /// `REPLY` tag, or its payload is not UTF-8
+    pub fn from_record(record: &Record) -> Result<Reaction, Error> {
+        if record.kind() != Kind::REACTION {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let tag = iter_tags(record.tags_bytes())
+            .find(|t| t.get_type() == TagType::REPLY)
+            .ok_or_else(|| InnerError::MissingTag(TagType::REPLY).into_err())?;
+        let target = tag
+            .get_reference()?
+            .ok_or_else(|| InnerError::MissingTag(TagType::REPLY).into_err())?;
+        let target_kind = tag
+            .get_kind()
+            .ok_or_else(|| InnerError::MissingTag(TagType::REPLY).into_err())?;
+        Ok(Reaction {
+            target,
+            target_kind,
+            content: std::str::from_utf8(record.payload_bytes())?.to_owned(),
+        })
+    }
+}
+
+/// Reaction counts per target record, as displayed under each post.
+///
+/// Each author counts once per target and reaction text, however many
+/// reaction records they published.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReactionSummary {
+    by_target: HashMap<Reference, BTreeMap<String, BTreeSet<PublicKey>>>,
+}
+
+impl ReactionSummary {
+    /// Tally reaction records, skipping deleted ones and records that are
+    /// not valid reactions.
+    ///
+    /// The records are assumed to be verified already, as they are when
+    /// taken from a `RecordStore`. `is_deleted` is usually a closure over
+    /// `RecordStore::is_deleted()`.
+    pub fn from_records<'a, I, F>(records: I, is_deleted: F) -> ReactionSummary
+    where
+        I: IntoIterator<Item = &'a Record>,
+        F: Fn(&Id) -> bool,
+    {
+        let mut summary = ReactionSummary::default();
+        for record in records {
+            if is_deleted(&record.id()) {
+                continue;
+            }
+            let Ok(reaction) = Reaction::from_record(record) else {
+                continue;
+            };
+            let _ = summary
+                .by_target
+                .entry(reaction.target)
+                .or_default()
+                .entry(reaction.content)
+                .or_default()
+                .insert(record.author_public_key());
+        }
+        summary
+    }
+
+    /// The reactions to a record and how many authors gave each, most
+    /// popular first (ties in text order)
+    #[must_use]
+    pub fn counts(&self, target: &Reference) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self
+            .by_target
+            .get(target)
+            .into_iter()
+            .flatten()
+            .map(|(content, authors)| (content.as_str(), authors.len()))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// The number of reactions to a record, counting each author once per
+    /// reaction text
+    #[must_use]
+    pub fn total(&self, target: &Reference) -> usize {
+        self.by_target
+            .get(target)
+            .map_or(0, |r| r.values().map(BTreeSet::len).sum())
+    }
+
+    /// Did this author react to the record with this text? Clients use
+    /// this to highlight the user's own reactions.
+    #[must_use]
+    pub fn has_reacted(&self, target: &Reference, author: &PublicKey, content: &str) -> bool {
+        self.by_target
+            .get(target)
+            .and_then(|r| r.get(content))
+            .is_some_and(|authors| authors.contains(author))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reaction_summary() {
+        use rand::rngs::OsRng;
+
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+        let address = crate::Address::new_random(alice.public(), Kind::MICROBLOG_ROOT);
+        let post = Reference::from_bytes(address.as_bytes()).unwrap();
+        let like = |content: &str| Reaction {
+            target: post,
+            target_kind: Kind::MICROBLOG_ROOT,
+            content: content.to_owned(),
+        };
+        let ts = |ms| Timestamp::from_millis(ms).unwrap();
+
+        let records = [
+            like("+").to_record(&alice, ts(1000)).unwrap(),
+            like("+").to_record(&alice, ts(2000)).unwrap(), // counted once
+            like("+").to_record(&bob, ts(1000)).unwrap(),
+            like("🔥").to_record(&alice, ts(1000)).unwrap(),
+            like("🎉").to_record(&bob, ts(1000)).unwrap(), // deleted below
+        ];
+        assert_eq!(Reaction::from_record(&records[3]).unwrap(), like("🔥"));
+        let deleted = records[4].id();
+
+        let summary =
+            ReactionSummary::from_records(records.iter().map(|r| &**r), |id| *id == deleted);
+        assert_eq!(summary.counts(&post), vec![("+", 2), ("🔥", 1)]);
+        assert_eq!(summary.total(&post), 3);
+        assert!(summary.has_reacted(&post, &alice.public(), "🔥"));
+        assert!(!summary.has_reacted(&post, &bob.public(), "🎉"));
+    }
+}
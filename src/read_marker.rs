@@ -0,0 +1,182 @@
+use crate::{
+    Error, Id, InnerError, Kind, OwnedRecord, Record, RecordFlags, RecordParts, Reference,
+    SecretKey, Timestamp,
+};
+
+/// How far a user has read in a conversation or thread, as carried by a
+/// `READ_MARKER` record.
+///
+/// The payload of a read marker record is the 48-byte `Reference` of the
+/// conversation followed by the 48-byte `Id` of the last record read,
+/// sealed with `crypto::seal()` from the user's key to itself so that only
+/// the user can read it. The conversation is also the deterministic key,
+/// so each marker replaces the user's last marker for that conversation.
+/// The conversation is not tagged, so servers cannot index who reads what.
+///
+/// Read marker records are flagged `FROM_AUTHOR`, `TO_RECIPIENTS` and
+/// `NO_BRIDGE` so that servers accept them only from the user and serve
+/// them only to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadMarker {
+    /// The conversation or thread
+    pub conversation: Reference,
+
+    /// The last record read
+    pub read_up_to: Id,
+}
+
+impl ReadMarker {
+    /// Create a read marker record, sealed to the user's own key
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if sealing or signing fails
+    pub fn to_record(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        let mut payload = self.conversation.as_bytes().to_vec();
+        payload.extend_from_slice(self.read_up_to.as_bytes());
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::READ_MARKER,
+                deterministic_key: Some(self.conversation.as_bytes()),
+                timestamp,
+                flags: RecordFlags::FROM_AUTHOR
+                    | RecordFlags::TO_RECIPIENTS
+                    | RecordFlags::NO_BRIDGE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: &crate::crypto::seal(&payload, secret_key, &secret_key.public())?,
+            },
+        )
+    }
+
+    /// Read a read marker from a read marker record sealed to the user's
+    /// key
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `READ_MARKER`, if its payload
+    /// was not sealed by and to `secret_key`, or if it is malformed
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_record(record: &Record, secret_key: &SecretKey) -> Result<ReadMarker, Error> {
+        if record.kind() != Kind::READ_MARKER {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let payload =
+            crate::crypto::open(record.payload_bytes(), secret_key, &secret_key.public())?;
+        if payload.len() != 96 {
+            return Err(InnerError::RecordSectionLengthMismatch.into());
+        }
+        Ok(ReadMarker {
+            conversation: Reference::from_bytes(payload[..48].try_into().unwrap())?,
+            read_up_to: Id::from_bytes(payload[48..].try_into().unwrap())?,
+        })
+    }
+
+    /// How far `reader` has read in `conversation`, from their read marker
+    /// records.
+    ///
+    /// Markers only move forward: the furthest marker wins even if a
+    /// device that was behind published later. Records by other authors,
+    /// for other conversations, that are not read markers, or that do not
+    /// open with `reader` are ignored.
+    pub fn latest_read_up_to<'a, I>(
+        records: I,
+        reader: &SecretKey,
+        conversation: &Reference,
+    ) -> Option<Id>
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let reader_public = reader.public();
+        records
+            .into_iter()
+            .filter(|r| r.author_public_key() == reader_public)
+            .filter_map(|r| ReadMarker::from_record(r, reader).ok())
+            .filter(|m| m.conversation == *conversation)
+            .map(|m| m.read_up_to)
+            .max()
+    }
+
+    /// The number of records in `ids` after `read_up_to`, for unread
+    /// badges. With no marker, every record is unread.
+    pub fn unread_count<'a, I>(read_up_to: Option<Id>, ids: I) -> usize
+    where
+        I: IntoIterator<Item = &'a Id>,
+    {
+        ids.into_iter()
+            .filter(|id| read_up_to.is_none_or(|r| **id > r))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latest_read_up_to() {
+        use rand::rngs::OsRng;
+
+        let alice = SecretKey::generate(&mut OsRng);
+        let bob = SecretKey::generate(&mut OsRng);
+        let ts = |ms| Timestamp::from_millis(ms).unwrap();
+        let messages: Vec<OwnedRecord> = (1..=4)
+            .map(|n| {
+                OwnedRecord::new(
+                    &bob,
+                    &RecordParts {
+                        kind: Kind::CHAT_MESSAGE,
+                        deterministic_key: None,
+                        timestamp: ts(n * 1000),
+                        flags: RecordFlags::empty(),
+                        app_flags: 0,
+                        tags_bytes: b"",
+                        payload: b"hi",
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+        let ids: Vec<Id> = messages.iter().map(|m| m.id()).collect();
+        let address = crate::Address::new_random(bob.public(), Kind::CHAT_MESSAGE);
+        let chat = Reference::from_bytes(address.as_bytes()).unwrap();
+        let other = Reference::from_bytes(ids[0].as_bytes()).unwrap();
+
+        let marker = |conversation, read_up_to, key: &SecretKey, ms| {
+            ReadMarker {
+                conversation,
+                read_up_to,
+            }
+            .to_record(key, ts(ms))
+            .unwrap()
+        };
+        let markers = [
+            marker(chat, ids[2], &alice, 5000),
+            marker(chat, ids[1], &alice, 6000), // a device that was behind
+            marker(chat, ids[3], &bob, 5000),
+            marker(other, ids[3], &alice, 5000),
+        ];
+        assert_eq!(
+            ReadMarker::from_record(&markers[0], &alice)
+                .unwrap()
+                .read_up_to,
+            ids[2]
+        );
+        assert_eq!(
+            ReadMarker::from_record(&markers[0], &bob)
+                .unwrap_err()
+                .kind(),
+            crate::ErrorKind::Decryption
+        );
+
+        let read = ReadMarker::latest_read_up_to(markers.iter().map(|r| &**r), &alice, &chat);
+        assert_eq!(read, Some(ids[2]));
+        assert_eq!(ReadMarker::unread_count(read, &ids), 1);
+        assert_eq!(ReadMarker::unread_count(None, &ids), 4);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::Id;
+use std::collections::{HashSet, VecDeque};
+
+/// A fixed-capacity set of recently seen `Id`s.
+///
+/// Relays use this to drop re-broadcast duplicates at ingress, before
+/// verifying or touching storage. Once full, inserting a new `Id` evicts
+/// the oldest one, so memory use is bounded by the capacity.
+#[derive(Debug, Clone)]
+pub struct RecentIds {
+    order: VecDeque<Id>,
+    set: HashSet<Id>,
+    capacity: usize,
+}
+
+impl RecentIds {
+    /// Create a `RecentIds` holding at most `capacity` ids. A capacity of
+    /// zero is treated as one.
+    #[must_use]
+    pub fn new(capacity: usize) -> RecentIds {
+        let capacity = capacity.max(1);
+        RecentIds {
+            order: VecDeque::with_capacity(capacity),
+            set: HashSet::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Check whether the `Id` was seen recently, and remember it.
+    ///
+    /// Returns true if it was already present.
+    pub fn seen(&mut self, id: &Id) -> bool {
+        if self.set.contains(id) {
+            return true;
+        }
+        self.insert(*id);
+        false
+    }
+
+    /// Check whether the `Id` was seen recently, without remembering it
+    #[must_use]
+    pub fn contains(&self, id: &Id) -> bool {
+        self.set.contains(id)
+    }
+
+    /// Remember an `Id`, evicting the oldest if full
+    pub fn insert(&mut self, id: Id) {
+        if !self.set.insert(id) {
+            return;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                let _ = self.set.remove(&old);
+            }
+        }
+        self.order.push_back(id);
+    }
+
+    /// The maximum number of ids held
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of ids held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Is it empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Forget every id
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.set.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recent_ids() {
+        let id = |n: u8| {
+            let mut bytes = [0; 48];
+            bytes[47] = n;
+            Id::from_bytes(&bytes).unwrap()
+        };
+
+        let mut recent = RecentIds::new(2);
+        assert!(!recent.seen(&id(1)));
+        assert!(recent.seen(&id(1)));
+        assert!(!recent.seen(&id(2)));
+        assert!(!recent.seen(&id(3)));
+        assert_eq!(recent.len(), 2);
+        assert!(!recent.contains(&id(1)));
+        assert!(recent.contains(&id(2)));
+        assert!(recent.seen(&id(3)));
+    }
+}
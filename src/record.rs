@@ -1,6 +1,19 @@
-use crate::{Address, Error, Id, InnerError, Kind, PublicKey, RecordFlags, SecretKey, Timestamp};
+use crate::{
+    crypto, Address, AsyncSigner, DalekSigningKey, DalekVerifyingKey, Error, Id, InnerError,
+    KeySchedule, Kind, Lint, OwnedTagSet, PublicKey, RecordFlags, RecordFormatter, SecretKey,
+    Signer, Tag, TagIter, Timestamp, VerifyPolicy,
+};
 use ed25519_dalek::Signature;
-use std::ops::{Deref, DerefMut, Range, RangeFrom};
+use rand_core::{OsRng, RngCore};
+#[cfg(feature = "zstd")]
+use std::borrow::Cow;
+use std::ops::{Deref, Range, RangeFrom};
+
+/// The largest a record may be, in bytes, as set by the specification.
+///
+/// Stores, pipelines and parsers may be configured with a smaller limit,
+/// but never a larger one.
+pub const MAX_RECORD_LEN: usize = 1_048_576;
 
 macro_rules! padded_len {
     ($len:expr) => {
@@ -8,6 +21,24 @@ macro_rules! padded_len {
     };
 }
 
+// Records at least this long are hashed on multiple threads when the
+// `rayon` feature is enabled. Below this, thread overhead outweighs the gain.
+#[cfg(feature = "rayon")]
+const PARALLEL_HASH_THRESHOLD: usize = 128 * 1024;
+
+// Hash the hashable contents of a record, returning the hasher so that its
+// state can be reused for the prehashed signature
+fn hash_contents(contents: &[u8]) -> blake3::Hasher {
+    let mut hasher = blake3::Hasher::new();
+    #[cfg(feature = "rayon")]
+    if contents.len() >= PARALLEL_HASH_THRESHOLD {
+        let _ = hasher.update_rayon(contents);
+        return hasher;
+    }
+    let _ = hasher.update(contents);
+    hasher
+}
+
 /// A `Record` is a digitally signed datum generated by a user,
 /// stored in and retrieed from a server, and used by an application,
 /// and unsized (borrowed).
@@ -15,7 +46,7 @@ macro_rules! padded_len {
 /// See also `OwnedRecord` for the owned variant.
 // INVARIANTS:
 //   at least 208 bytes long
-//   no more than 1_048_576 bytes long
+//   no more than MAX_RECORD_LEN bytes long
 //   hash is correct
 //   signature is correct
 //   reserved flags are zero
@@ -25,31 +56,80 @@ pub struct Record([u8]);
 
 impl Record {
     // View a slice of bytes as a Record
-    fn from_inner<S: AsRef<[u8]> + ?Sized>(s: &S) -> &Record {
+    pub(crate) fn from_inner<S: AsRef<[u8]> + ?Sized>(s: &S) -> &Record {
         unsafe { &*(std::ptr::from_ref::<[u8]>(s.as_ref()) as *const Record) }
     }
 
-    // View a mutable slice of bytes as a Record
-    fn from_inner_mut(inner: &mut [u8]) -> &mut Record {
-        // SAFETY: Record is just a wrapper around [u8],
-        // therefore converting &mut [u8] to &mut Record is safe.
-        unsafe { &mut *(std::ptr::from_mut::<[u8]>(inner) as *mut Record) }
+    /// Interpret a sequence of bytes as a `Record`. Checks validity of the length
+    /// only. Any bytes after the record are ignored; see `from_bytes_strict()`
+    /// and `from_bytes_prefix()`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the input is shorter than the lengths in its header say, or if
+    /// the length is more than `MAX_RECORD_LEN` bytes.
+    ///
+    /// # Safety
+    ///
+    /// Be sure the input is a valid Record. Consider calling `verify()` afterwards to
+    /// be sure, or use `from_bytes_verified()` instead.
+    pub unsafe fn from_bytes(input: &[u8]) -> Result<&Record, Error> {
+        Self::from_bytes_with_max_len(input, MAX_RECORD_LEN)
     }
 
-    /// Interpret a sequence of bytes as a `Record`. Checks validity of the length
-    /// only.
+    /// Interpret a sequence of bytes as a `Record`, requiring the record to
+    /// take up all of the input. Checks validity of the length only.
+    ///
+    /// # Errors
+    ///
+    /// Errors as with `from_bytes()`, or if any bytes remain after the
+    /// record.
+    ///
+    /// # Safety
+    ///
+    /// Be sure the input is a valid Record. Consider calling `verify()` afterwards to
+    /// be sure.
+    pub unsafe fn from_bytes_strict(input: &[u8]) -> Result<&Record, Error> {
+        let (record, consumed) = Self::from_bytes_prefix(input)?;
+        if consumed != input.len() {
+            return Err(InnerError::TrailingBytes(input.len() - consumed).into());
+        }
+        Ok(record)
+    }
+
+    /// Interpret the start of a sequence of bytes as a `Record`, returning it
+    /// along with the number of bytes it takes up. Framing code can use this
+    /// to find where the next item begins. Checks validity of the length only.
+    ///
+    /// # Errors
+    ///
+    /// Errors as with `from_bytes()`.
+    ///
+    /// # Safety
+    ///
+    /// Be sure the input is a valid Record. Consider calling `verify()` afterwards to
+    /// be sure.
+    pub unsafe fn from_bytes_prefix(input: &[u8]) -> Result<(&Record, usize), Error> {
+        let record = Self::from_bytes(input)?;
+        Ok((record, record.0.len()))
+    }
+
+    /// Interpret a sequence of bytes as a `Record` no longer than `max_len`
+    /// bytes. Checks validity of the length only.
+    ///
+    /// `max_len` is capped at `MAX_RECORD_LEN`.
     ///
     /// # Errors
     ///
-    /// Errors if the input is not long enough or if the length is more than `1_048_576`
-    /// bytes.
+    /// Errors if the input is shorter than the lengths in its header say, or if
+    /// the length is more than `max_len` bytes.
     ///
     /// # Safety
     ///
     /// Be sure the input is a valid Record. Consider calling `verify()` afterwards to
     /// be sure.
     #[allow(clippy::missing_panics_doc)]
-    pub unsafe fn from_bytes(input: &[u8]) -> Result<&Record, Error> {
+    pub unsafe fn from_bytes_with_max_len(input: &[u8], max_len: usize) -> Result<&Record, Error> {
         if input.len() < HEADER_LEN {
             return Err(InnerError::EndOfInput.into());
         }
@@ -60,14 +140,34 @@ impl Record {
         let padded_payload_len = padded_len!(unpadded_payload_len);
 
         let len = HEADER_LEN + padded_tag_len + padded_payload_len;
-        if len > 1_048_576 {
+        if len > max_len.min(MAX_RECORD_LEN) {
             return Err(InnerError::RecordTooLong.into());
         }
+        if input.len() < len {
+            return Err(InnerError::EndOfInput.into());
+        }
 
         let unverified = Self::from_inner(&input[..len]);
         Ok(unverified)
     }
 
+    /// Interpret a sequence of bytes as a `Record`, checking its lengths and
+    /// then fully verifying it.
+    ///
+    /// Prefer this to `from_bytes()` unless the bytes are already trusted.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the lengths are invalid as with `from_bytes()`, if any
+    /// bytes remain after the record, or if any verification fails. See
+    /// `verify()`
+    pub fn from_bytes_verified(input: &[u8]) -> Result<&Record, Error> {
+        // SAFETY: verified before it is returned
+        let record = unsafe { Self::from_bytes_strict(input)? };
+        record.verify()?;
+        Ok(record)
+    }
+
     /// Write a new `Record` to the buffer
     ///
     /// # Errors
@@ -79,14 +179,38 @@ impl Record {
         signing_secret_key: &SecretKey,
         parts: &RecordParts,
     ) -> Result<&'a Record, Error> {
+        Self::write_record_with_rng(&mut OsRng, buffer, signing_secret_key, parts)
+    }
+
+    /// Write a new `Record` to the buffer, drawing the address nonce (if
+    /// there is no deterministic key) from `rng`.
+    ///
+    /// Signing is deterministic, so with a seeded `rng` the same parts
+    /// always produce the same record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if the buffer is too small,
+    /// if reserved flags are set, or if signing fails.
+    pub fn write_record_with_rng<'a, R: RngCore + ?Sized>(
+        rng: &mut R,
+        buffer: &'a mut [u8],
+        signing_secret_key: &SecretKey,
+        parts: &RecordParts,
+    ) -> Result<&'a Record, Error> {
+        // Expand the secret key once; deriving the public key is a scalar
+        // multiplication
+        let signing_key = signing_secret_key.to_signing_key();
+        let public_key = PublicKey::from_verifying_key(&signing_key.verifying_key());
         let address = match parts.deterministic_key {
-            Some(key) => Address::new_deterministic(signing_secret_key.public(), parts.kind, key),
-            None => Address::new_random(signing_secret_key.public(), parts.kind),
+            Some(key) => Address::new_deterministic(public_key, parts.kind, key),
+            None => Address::new_random_with_rng(rng, public_key, parts.kind),
         };
 
-        Self::write_replacement_record(
+        Self::write_signed(
             buffer,
-            signing_secret_key,
+            &signing_key,
+            public_key,
             address,
             parts.timestamp,
             parts.flags,
@@ -113,18 +237,141 @@ impl Record {
         tags_bytes: &[u8],
         payload: &[u8],
     ) -> Result<&'a Record, Error> {
+        let signing_key = signing_secret_key.to_signing_key();
+        let public_key = PublicKey::from_verifying_key(&signing_key.verifying_key());
+        Self::write_signed(
+            buffer,
+            &signing_key,
+            public_key,
+            address,
+            timestamp,
+            flags,
+            app_flags,
+            tags_bytes,
+            payload,
+        )
+    }
+
+    /// Prepare a record to be signed by the secret key of `public_key`
+    /// elsewhere, for example on another device.
+    ///
+    /// Everything but the signature is written. Send the prehash from
+    /// `UnsignedRecord::prehash()` to be signed, and attach the signature
+    /// with `UnsignedRecord::attach_signature()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long or if reserved flags are
+    /// set
+    pub fn prepare_unsigned(
+        public_key: PublicKey,
+        parts: &RecordParts,
+    ) -> Result<UnsignedRecord, Error> {
+        Self::prepare_unsigned_with_rng(&mut OsRng, public_key, parts)
+    }
+
+    /// Prepare a record to be signed elsewhere as `prepare_unsigned()`
+    /// does, drawing the address nonce (if there is no deterministic key)
+    /// from `rng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long or if reserved flags are
+    /// set
+    pub fn prepare_unsigned_with_rng<R: RngCore + ?Sized>(
+        rng: &mut R,
+        public_key: PublicKey,
+        parts: &RecordParts,
+    ) -> Result<UnsignedRecord, Error> {
+        let address = match parts.deterministic_key {
+            Some(key) => Address::new_deterministic(public_key, parts.kind, key),
+            None => Address::new_random_with_rng(rng, public_key, parts.kind),
+        };
+        let mut buffer = vec![0; parts.record_len()];
+        let (_, hasher) = Self::write_unsigned(
+            &mut buffer,
+            public_key,
+            address,
+            parts.timestamp,
+            parts.flags,
+            parts.app_flags,
+            parts.tags_bytes,
+            parts.payload,
+        )?;
+        let mut prehash = [0; 64];
+        hasher.finalize_xof().fill(&mut prehash);
+        Ok(UnsignedRecord { buffer, prehash })
+    }
+
+    // Write and sign a record. `public_key` must be the public key of
+    // `signing_key`.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                kind = %address.kind(),
+                tags_len = tags_bytes.len(),
+                payload_len = payload.len(),
+                id = tracing::field::Empty
+            )
+        )
+    )]
+    fn write_signed<'a>(
+        buffer: &'a mut [u8],
+        signing_key: &DalekSigningKey,
+        public_key: PublicKey,
+        address: Address,
+        timestamp: Timestamp,
+        flags: RecordFlags,
+        app_flags: u16,
+        tags_bytes: &[u8],
+        payload: &[u8],
+    ) -> Result<&'a Record, Error> {
+        let (buffer, hasher) = Self::write_unsigned(
+            buffer, public_key, address, timestamp, flags, app_flags, tags_bytes, payload,
+        )?;
+
+        // Sign, reusing the hasher state rather than hashing again
+        let digest = crate::crypto::Blake3 { h: hasher };
+        let sig = signing_key.sign_prehashed(digest, Some(b"Mosaic"))?;
+        buffer[SIG_RANGE].copy_from_slice(sig.to_bytes().as_slice());
+
+        let record = Record::from_inner(buffer);
+        #[cfg(feature = "tracing")]
+        let _ = tracing::Span::current().record("id", tracing::field::display(record.id()));
+        Ok(record)
+    }
+
+    // Write everything but the signature, returning the record's bytes
+    // and the hasher state over its hashable range
+    #[allow(clippy::too_many_arguments)]
+    fn write_unsigned<'a>(
+        buffer: &'a mut [u8],
+        public_key: PublicKey,
+        address: Address,
+        timestamp: Timestamp,
+        flags: RecordFlags,
+        app_flags: u16,
+        tags_bytes: &[u8],
+        payload: &[u8],
+    ) -> Result<(&'a mut [u8], blake3::Hasher), Error> {
         if tags_bytes.len() > 65_536 {
             return Err(InnerError::RecordTooLong.into());
         }
         let padded_tags_len = padded_len!(tags_bytes.len());
         let padded_payload_len = padded_len!(payload.len());
         let len = HEADER_LEN + padded_tags_len + padded_payload_len;
-        if len > 1_048_576 {
+        if len > MAX_RECORD_LEN {
             return Err(InnerError::RecordTooLong.into());
         }
         if buffer.len() < len {
             return Err(InnerError::EndOfOutput.into());
         }
+        // Only the record's own bytes are hashed, even if the buffer is larger
+        let buffer = &mut buffer[..len];
 
         if flags | RecordFlags::all() != RecordFlags::all() {
             return Err(InnerError::ReservedFlagsUsed.into());
@@ -151,30 +398,16 @@ impl Record {
 
         buffer[ADDRESS_RANGE].copy_from_slice(address.as_bytes().as_slice());
 
-        let public_key = signing_secret_key.public();
         buffer[SIGNING_KEY_RANGE].copy_from_slice(public_key.as_bytes().as_slice());
 
         let mut truehash: [u8; 64] = [0; 64];
-        let mut hasher = blake3::Hasher::new();
-        let _ = hasher.update(&buffer[HASHABLE_RANGE]);
+        let hasher = hash_contents(&buffer[HASHABLE_RANGE]);
         hasher.finalize_xof().fill(&mut truehash[..]);
         buffer[HASH_RANGE].copy_from_slice(&truehash[..40]);
 
         buffer[BE_TIMESTAMP_RANGE].copy_from_slice(timestamp.to_be_bytes().as_slice());
 
-        // Sign
-        let digest = crate::crypto::Blake3 { h: hasher };
-        let sig = signing_secret_key
-            .to_signing_key()
-            .sign_prehashed(digest, Some(b"Mosaic"))?;
-        buffer[SIG_RANGE].copy_from_slice(sig.to_bytes().as_slice());
-
-        let record = Record::from_inner(&buffer[..len]);
-        if cfg!(debug_assertions) {
-            record.verify()?;
-        }
-
-        Ok(record)
+        Ok((buffer, hasher))
     }
 
     /// Verify invariants. You should not normally need to call this; all code paths
@@ -182,15 +415,40 @@ impl Record {
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if the length is too short (<`216`) too long (>`1_048_576`),
+    /// Returns an `Err` if the length is too short (<`216`) too long (>`MAX_RECORD_LEN`),
     /// if the sum of the sections (header, tags, and payload) doesn't equal the
     /// length, if either public key is invalid, if the hash is wrong, if the
     /// signature is wrong, if the timestamp is out of range, or if any reserved
     /// area is not zeroed.
-    #[allow(clippy::missing_panics_doc)]
+    ///
+    /// Verifying a valid record does not allocate, so it can be used per
+    /// packet. (Rejecting one may, as ed25519-dalek boxes the cause of its
+    /// errors, and with the `tracing` feature an active subscriber's span
+    /// does.)
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(id = %self.id(), kind = %self.kind(), len = self.0.len()),
+            err
+        )
+    )]
     pub fn verify(&self) -> Result<(), Error> {
+        self.verify_inner(true)
+    }
+
+    /// Verify everything `verify()` does except the signature. Only use
+    /// this when this exact signature is already known to be valid for
+    /// this `Id`.
+    pub(crate) fn verify_except_signature(&self) -> Result<(), Error> {
+        self.verify_inner(false)
+    }
+
+    #[allow(clippy::missing_panics_doc)]
+    fn verify_inner(&self, check_signature: bool) -> Result<(), Error> {
         // Verify all lengths
-        if self.0.len() > 1_048_576 {
+        if self.0.len() > MAX_RECORD_LEN {
             return Err(InnerError::RecordTooLong.into());
         }
         if self.0.len() < HEADER_LEN {
@@ -202,7 +460,7 @@ impl Record {
 
         // Verify PublicKey validity
         let signing_public_key =
-            PublicKey::from_bytes(self.0[SIGNING_KEY_RANGE].try_into().unwrap())?;
+            DalekVerifyingKey::from_bytes(self.0[SIGNING_KEY_RANGE].try_into().unwrap())?;
         let _author_public_key =
             PublicKey::from_bytes(self.0[AUTHOR_KEY_RANGE].try_into().unwrap())?;
 
@@ -210,8 +468,7 @@ impl Record {
         // (note we don't use fn full_hash() because we need to
         //  reuse the hasher to verify the signature)
         let mut truehash: [u8; 64] = [0; 64];
-        let mut hasher = blake3::Hasher::new();
-        let _ = hasher.update(&self.0[HASHABLE_RANGE]);
+        let hasher = hash_contents(&self.0[HASHABLE_RANGE]);
         hasher.finalize_xof().fill(&mut truehash[..]);
 
         // Compare the start of the true hash to the claimed hash
@@ -220,14 +477,18 @@ impl Record {
         }
 
         // Verify the signature
-        let signature = Signature::from_slice(&self.0[SIG_RANGE])?;
-        let digest = crate::crypto::Blake3 { h: hasher };
-        signing_public_key
-            .to_verifying_key()
-            .verify_prehashed_strict(digest, Some(b"Mosaic"), &signature)?;
+        if check_signature {
+            let signature = Signature::from_slice(&self.0[SIG_RANGE])?;
+            let digest = crypto::Blake3 { h: hasher };
+            signing_public_key.verify_prehashed_strict(digest, Some(b"Mosaic"), &signature)?;
+        }
 
-        // Verify the timestamp
-        let _timestamp = Timestamp::from_bytes(self.0[TIMESTAMP_RANGE].try_into().unwrap())?;
+        // Verify the timestamp, and that the copy at the start of the Id
+        // (which is neither hashed nor signed) matches it
+        let timestamp = Timestamp::from_bytes(self.0[TIMESTAMP_RANGE].try_into().unwrap())?;
+        if self.0[BE_TIMESTAMP_RANGE] != timestamp.to_be_bytes() {
+            return Err(InnerError::IdTimestampMismatch.into());
+        }
 
         // Verify reserved flags are 0
         let flags = self.flags();
@@ -242,6 +503,12 @@ impl Record {
         Ok(())
     }
 
+    /// Copy to an allocated owned data type
+    #[must_use]
+    pub fn to_owned(&self) -> OwnedRecord {
+        OwnedRecord(self.0.to_owned())
+    }
+
     /// View a `Record` as a slice of bytes
     #[must_use]
     pub fn as_bytes(&self) -> &[u8] {
@@ -259,8 +526,7 @@ impl Record {
     #[must_use]
     pub fn full_hash(&self) -> [u8; 64] {
         let mut truehash: [u8; 64] = [0; 64];
-        let mut hasher = blake3::Hasher::new();
-        let _ = hasher.update(&self.0[HASHABLE_RANGE]);
+        let hasher = hash_contents(&self.0[HASHABLE_RANGE]);
         hasher.finalize_xof().fill(&mut truehash[..]);
         truehash
     }
@@ -314,6 +580,66 @@ impl Record {
         RecordFlags::from_bits_retain(u16::from_le_bytes(self.0[FLAGS_RANGE].try_into().unwrap()))
     }
 
+    /// Is the record ephemeral? Ephemeral records (typing indicators,
+    /// presence) are delivered to current subscribers but never stored.
+    #[must_use]
+    pub fn is_ephemeral(&self) -> bool {
+        self.flags().contains(RecordFlags::EPHEMERAL)
+    }
+
+    /// Verify this record and check it against a `VerifyPolicy`, taking
+    /// `now` as the current time. See `VerifyPolicy::check()`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record fails to verify or the policy does not
+    /// trust it
+    pub fn verify_with(&self, policy: &VerifyPolicy, now: Timestamp) -> Result<(), Error> {
+        policy.check(self, now)
+    }
+
+    /// Check that the signing key was authorized to sign for the author at
+    /// the record's timestamp: either it is the author's key, or
+    /// `schedule` delegates to it at that time and had not revoked it by
+    /// then.
+    ///
+    /// `schedule` must be the author's, as read with
+    /// `KeySchedule::from_record()` from a record whose
+    /// `author_public_key()` is this record's. This does not verify the
+    /// record itself; see `verify()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the signing key was revoked, its window had
+    /// closed, or it was not delegated at the record's timestamp
+    pub fn verify_delegation(&self, schedule: &KeySchedule) -> Result<(), Error> {
+        let signer = self.signing_public_key();
+        if signer == self.author_public_key() {
+            return Ok(());
+        }
+        let timestamp = self.timestamp();
+        if schedule.is_revoked_at(&signer, timestamp) {
+            return Err(InnerError::SigningKeyRevoked.into());
+        }
+        match schedule.window(&signer) {
+            Some(window) if window.contains(timestamp) => Ok(()),
+            Some(window) if timestamp > window.valid_from => {
+                Err(InnerError::SigningKeyExpired.into())
+            }
+            _ => Err(InnerError::SigningKeyNotDelegated.into()),
+        }
+    }
+
+    /// Check for quality problems that verification does not reject, such
+    /// as a timestamp far in the future of `now`, an unknown kind, or
+    /// duplicate tags. An empty result means no problems were found.
+    ///
+    /// This does not verify the record.
+    #[must_use]
+    pub fn lint(&self, now: Timestamp) -> Vec<Lint> {
+        crate::lint::lint(self, now)
+    }
+
     /// App Flags
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
@@ -348,6 +674,12 @@ impl Record {
         &self.0[HEADER_LEN..HEADER_LEN + self.tags_len()]
     }
 
+    /// Iterate over the tags. Iteration stops at the first malformed tag.
+    #[must_use]
+    pub fn tags(&self) -> TagIter<'_> {
+        crate::tag::iter_tags(self.tags_bytes())
+    }
+
     /// Payload length
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
@@ -365,12 +697,40 @@ impl Record {
     /// Payload area bytes
     ///
     /// These are the raw bytes. If Zstd is used, the caller is responsible for
-    /// decompressing them.
+    /// decompressing them, for example with `decompress_payload()` (or
+    /// `payload_decompressed()` with the `zstd` feature).
     #[must_use]
     pub fn payload_bytes(&self) -> &[u8] {
         let start = HEADER_LEN + self.tags_padded_len();
         &self.0[start..start + self.payload_len()]
     }
+
+    /// The payload, decompressed if the record has the `ZSTD` flag,
+    /// producing at most `max_len` bytes. Uncompressed payloads are
+    /// borrowed without copying.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if decompression fails, or if the uncompressed
+    /// payload would be longer than `max_len`
+    #[cfg(feature = "zstd")]
+    pub fn payload_decompressed(&self, max_len: usize) -> Result<Cow<'_, [u8]>, Error> {
+        crate::decompress::payload_decompressed(self, max_len)
+    }
+
+    /// Does this record supersede the other record?
+    ///
+    /// A record supersedes another at the same `Address` if it has a later
+    /// timestamp. If the timestamps are equal, the record with the greater
+    /// `Id` wins so that every party resolves the tie the same way.
+    /// Records at different addresses never supersede each other.
+    #[must_use]
+    pub fn supersedes(&self, other: &Record) -> bool {
+        if self.address() != other.address() {
+            return false;
+        }
+        (self.timestamp(), self.id()) > (other.timestamp(), other.id())
+    }
 }
 
 const SIG_RANGE: Range<usize> = 0..64;
@@ -398,33 +758,7 @@ const HASHABLE_RANGE: RangeFrom<usize> = 112..;
 
 impl std::fmt::Display for Record {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "id: {}", self.id().printable())?;
-        writeln!(f, "  address: {}", self.address().printable())?;
-        writeln!(f, "  author key: {}", self.author_public_key().printable())?;
-        writeln!(
-            f,
-            "  signing key: {}",
-            self.signing_public_key().printable()
-        )?;
-        writeln!(f, "  timestamp: {}", self.timestamp())?;
-        writeln!(f, "  kind: {}", self.kind())?;
-        writeln!(f, "  flags: {} {}", self.flags(), self.app_flags())?;
-        writeln!(f, "  tags (zbase32): {}", z32::encode(self.tags_bytes()))?;
-        if self.flags().contains(RecordFlags::PRINTABLE) {
-            writeln!(
-                f,
-                "  payload: {}",
-                String::from_utf8_lossy(self.payload_bytes())
-            )?;
-        } else {
-            writeln!(
-                f,
-                "  payload (zbase32): {}",
-                z32::encode(self.payload_bytes())
-            )?;
-        }
-
-        Ok(())
+        RecordFormatter::new().write(f, self)
     }
 }
 
@@ -435,7 +769,7 @@ impl std::fmt::Display for Record {
 /// See also `Record` for the borrowed variant.
 // INVARIANTS:
 //   at least 208 bytes long
-//   no more than 1_048_576 bytes long
+//   no more than MAX_RECORD_LEN bytes long
 //   hash is correct
 //   signature is correct
 //   reserved flags are zero
@@ -455,28 +789,114 @@ impl OwnedRecord {
         Ok(unverified)
     }
 
+    /// Take the bytes of this `OwnedRecord`.
+    ///
+    /// There is no mutable access to the bytes of a record, since any
+    /// change would break its hash and signature. To edit the bytes anyway,
+    /// take them with this and pass them back through `from_vec()`, which
+    /// verifies them again.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+
     /// Create a new `OwnedRecord` from component parts.
     ///
     /// # Errors
     ///
     /// Returns an `Err` if any data is too long, if reserved flags are set,
     /// or if signing fails.
-    #[allow(clippy::missing_panics_doc)]
     pub fn new(signing_secret_key: &SecretKey, parts: &RecordParts) -> Result<OwnedRecord, Error> {
-        let address = match parts.deterministic_key {
-            Some(key) => Address::new_deterministic(signing_secret_key.public(), parts.kind, key),
-            None => Address::new_random(signing_secret_key.public(), parts.kind),
-        };
+        Self::new_with_rng(&mut OsRng, signing_secret_key, parts)
+    }
 
-        Self::new_replacement(
-            signing_secret_key,
-            address,
-            parts.timestamp,
-            parts.flags,
-            parts.app_flags,
-            parts.tags_bytes,
-            parts.payload,
-        )
+    /// Create a new `OwnedRecord` from component parts, drawing the address
+    /// nonce (if there is no deterministic key) from `rng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if reserved flags are set,
+    /// or if signing fails.
+    pub fn new_with_rng<R: RngCore + ?Sized>(
+        rng: &mut R,
+        signing_secret_key: &SecretKey,
+        parts: &RecordParts,
+    ) -> Result<OwnedRecord, Error> {
+        if parts.tags_bytes.len() > 65_536 || parts.record_len() > MAX_RECORD_LEN {
+            return Err(InnerError::RecordTooLong.into());
+        }
+        let mut buffer = vec![0; parts.record_len()];
+        let _ = Record::write_record_with_rng(rng, &mut buffer, signing_secret_key, parts)?;
+        Ok(OwnedRecord(buffer))
+    }
+
+    /// Create a new `OwnedRecord` from component parts, signed by `signer`.
+    ///
+    /// The record is verified before it is returned, so a signer that
+    /// produces a bad signature is caught here.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if reserved flags are set,
+    /// or if signing or verification fails.
+    pub fn new_with_signer<S: Signer + ?Sized>(
+        signer: &S,
+        parts: &RecordParts,
+    ) -> Result<OwnedRecord, Error> {
+        Self::new_with_signer_with_rng(&mut OsRng, signer, parts)
+    }
+
+    /// Create a new `OwnedRecord` from component parts, signed by `signer`,
+    /// drawing the address nonce (if there is no deterministic key) from
+    /// `rng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if reserved flags are set,
+    /// or if signing or verification fails.
+    pub fn new_with_signer_with_rng<R: RngCore + ?Sized, S: Signer + ?Sized>(
+        rng: &mut R,
+        signer: &S,
+        parts: &RecordParts,
+    ) -> Result<OwnedRecord, Error> {
+        let unsigned = Record::prepare_unsigned_with_rng(rng, signer.public_key(), parts)?;
+        let signature = signer.sign_prehash(unsigned.prehash())?;
+        unsigned.attach_signature(&signature)
+    }
+
+    /// Create a new `OwnedRecord` from component parts, signed by an
+    /// `AsyncSigner`.
+    ///
+    /// The record is verified before it is returned, so a signer that
+    /// produces a bad signature is caught here.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if reserved flags are set,
+    /// or if signing or verification fails.
+    pub async fn new_with_async_signer<S: AsyncSigner + ?Sized>(
+        signer: &S,
+        parts: &RecordParts<'_>,
+    ) -> Result<OwnedRecord, Error> {
+        Self::new_with_async_signer_with_rng(&mut OsRng, signer, parts).await
+    }
+
+    /// Create a new `OwnedRecord` from component parts, signed by an
+    /// `AsyncSigner`, drawing the address nonce (if there is no
+    /// deterministic key) from `rng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any data is too long, if reserved flags are set,
+    /// or if signing or verification fails.
+    pub async fn new_with_async_signer_with_rng<R: RngCore + ?Sized, S: AsyncSigner + ?Sized>(
+        rng: &mut R,
+        signer: &S,
+        parts: &RecordParts<'_>,
+    ) -> Result<OwnedRecord, Error> {
+        let unsigned = Record::prepare_unsigned_with_rng(rng, signer.public_key(), parts)?;
+        let signature = signer.sign_prehash(unsigned.prehash()).await?;
+        unsigned.attach_signature(&signature)
     }
 
     /// Create a new `OwnedRecord` from component parts, replacing an existing record
@@ -502,7 +922,7 @@ impl OwnedRecord {
         let padded_tags_len = padded_len!(tags_bytes.len());
         let padded_payload_len = padded_len!(payload.len());
         let len = HEADER_LEN + padded_tags_len + padded_payload_len;
-        if len > 1_048_576 {
+        if len > MAX_RECORD_LEN {
             return Err(InnerError::RecordTooLong.into());
         }
         let mut buffer = vec![0; len];
@@ -528,9 +948,15 @@ impl Deref for OwnedRecord {
     }
 }
 
-impl DerefMut for OwnedRecord {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        Record::from_inner_mut(&mut self.0)
+impl AsRef<Record> for OwnedRecord {
+    fn as_ref(&self) -> &Record {
+        self
+    }
+}
+
+impl AsRef<Record> for Record {
+    fn as_ref(&self) -> &Record {
+        self
     }
 }
 
@@ -540,6 +966,56 @@ impl std::fmt::Display for OwnedRecord {
     }
 }
 
+/// A record that has been written but not yet signed.
+///
+/// See `Record::prepare_unsigned()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedRecord {
+    // The record's bytes with a zeroed signature
+    buffer: Vec<u8>,
+    prehash: [u8; 64],
+}
+
+impl UnsignedRecord {
+    /// The 64-byte prehash to sign with Ed25519ph under the context
+    /// `b"Mosaic"`. See `Signer`.
+    #[must_use]
+    pub fn prehash(&self) -> &[u8; 64] {
+        &self.prehash
+    }
+
+    /// The public key that must sign the record
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn signing_public_key(&self) -> PublicKey {
+        PublicKey::from_bytes(self.buffer[SIGNING_KEY_RANGE].try_into().unwrap()).unwrap()
+    }
+
+    /// The `Id` the record will have. It does not depend on the signature.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn id(&self) -> Id {
+        Id::from_bytes_no_verify(self.buffer[ID_RANGE].try_into().unwrap())
+    }
+
+    /// The `Address` the record will have
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn address(&self) -> Address {
+        Address::from_bytes_no_verify(self.buffer[ADDRESS_RANGE].try_into().unwrap())
+    }
+
+    /// Attach a signature obtained out of band, creating the record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the signature does not verify
+    pub fn attach_signature(mut self, signature: &[u8; 64]) -> Result<OwnedRecord, Error> {
+        self.buffer[SIG_RANGE].copy_from_slice(signature);
+        OwnedRecord::from_vec(self.buffer)
+    }
+}
+
 /// The parts of a Record
 #[derive(Debug)]
 pub struct RecordParts<'a> {
@@ -573,6 +1049,196 @@ impl RecordParts<'_> {
         let padded_payload_len = padded_len!(self.payload.len());
         HEADER_LEN + padded_tags_len + padded_payload_len
     }
+
+    /// The `Address` that signing these parts with the secret key of
+    /// `public_key` will give the record, so that other records can refer
+    /// to it before it is published.
+    ///
+    /// This is only known in advance when there is a deterministic key;
+    /// otherwise the address nonce is random and `None` is returned.
+    /// (Callers wanting a forward reference to such a record can choose
+    /// its `Address` themselves and use `Record::write_replacement_record()`.)
+    #[must_use]
+    pub fn predicted_address(&self, public_key: &PublicKey) -> Option<Address> {
+        self.deterministic_key
+            .map(|key| Address::new_deterministic(*public_key, self.kind, key))
+    }
+
+    /// The `Id` that signing these parts with the secret key of
+    /// `public_key` will give the record, when there is a deterministic key.
+    ///
+    /// The `Id` covers everything but the signature, so it is known before
+    /// signing; this computes the record's hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the parts could not be written as a record
+    #[allow(clippy::missing_panics_doc)]
+    pub fn predicted_id(&self, public_key: &PublicKey) -> Result<Option<Id>, Error> {
+        let Some(address) = self.predicted_address(public_key) else {
+            return Ok(None);
+        };
+        let mut buffer = vec![0; self.record_len()];
+        let (bytes, _) = Record::write_unsigned(
+            &mut buffer,
+            *public_key,
+            address,
+            self.timestamp,
+            self.flags,
+            self.app_flags,
+            self.tags_bytes,
+            self.payload,
+        )?;
+        Ok(Some(Id::from_bytes_no_verify(
+            bytes[ID_RANGE].try_into().unwrap(),
+        )))
+    }
+}
+
+/// A `RecordBuilder` assembles the parts of a record one at a time, for
+/// when they are not all at hand as slices the way `RecordParts` needs.
+///
+/// The length of the record can be checked with `record_len()` at any
+/// point before it is signed.
+#[derive(Debug, Clone)]
+pub struct RecordBuilder {
+    kind: Kind,
+    deterministic_key: Option<Vec<u8>>,
+    timestamp: Timestamp,
+    flags: RecordFlags,
+    app_flags: u16,
+    tags: OwnedTagSet,
+    payload: Vec<u8>,
+}
+
+impl RecordBuilder {
+    /// Start building a record of `kind` at `timestamp`, with no flags,
+    /// tags or payload and a random address nonce
+    #[must_use]
+    pub fn new(kind: Kind, timestamp: Timestamp) -> RecordBuilder {
+        RecordBuilder {
+            kind,
+            deterministic_key: None,
+            timestamp,
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags: OwnedTagSet::new(),
+            payload: Vec::new(),
+        }
+    }
+
+    /// Use a deterministic key for the address instead of a random nonce
+    pub fn set_deterministic_key(&mut self, key: &[u8]) -> &mut RecordBuilder {
+        self.deterministic_key = Some(key.to_vec());
+        self
+    }
+
+    /// Set the timestamp
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) -> &mut RecordBuilder {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Set the flags
+    pub fn set_flags(&mut self, flags: RecordFlags) -> &mut RecordBuilder {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the application flags
+    pub fn set_app_flags(&mut self, app_flags: u16) -> &mut RecordBuilder {
+        self.app_flags = app_flags;
+        self
+    }
+
+    /// Append a tag
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the tags would become too long
+    pub fn add_tag(&mut self, tag: &Tag) -> Result<&mut RecordBuilder, Error> {
+        self.tags.push(tag)?;
+        Ok(self)
+    }
+
+    /// Set the payload, replacing any previous payload
+    pub fn set_payload(&mut self, payload: impl Into<Vec<u8>>) -> &mut RecordBuilder {
+        self.payload = payload.into();
+        self
+    }
+
+    /// Compress the payload with Zstd and set the `ZSTD` flag, if that
+    /// makes the record shorter and the payload is not compressed already.
+    ///
+    /// This replaces the payload, so call it once the payload is set.
+    /// `record_len()` and the signed record then agree on the compressed
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if compression fails
+    #[cfg(feature = "zstd")]
+    pub fn compress_payload(&mut self) -> Result<&mut RecordBuilder, Error> {
+        if self.flags.contains(RecordFlags::ZSTD) {
+            return Ok(self);
+        }
+        if let Some(compressed) = crate::decompress::compress_payload(&self.payload)? {
+            self.payload = compressed;
+            self.flags |= RecordFlags::ZSTD;
+        }
+        Ok(self)
+    }
+
+    /// The tags added so far
+    #[must_use]
+    pub fn tags(&self) -> &OwnedTagSet {
+        &self.tags
+    }
+
+    /// The parts of the record as they stand
+    #[must_use]
+    pub fn parts(&self) -> RecordParts<'_> {
+        RecordParts {
+            kind: self.kind,
+            deterministic_key: self.deterministic_key.as_deref(),
+            timestamp: self.timestamp,
+            flags: self.flags,
+            app_flags: self.app_flags,
+            tags_bytes: self.tags.as_bytes(),
+            payload: &self.payload,
+        }
+    }
+
+    /// The length the record would have if it were signed now
+    #[must_use]
+    pub fn record_len(&self) -> usize {
+        self.parts().record_len()
+    }
+
+    /// Sign the record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record would be too long, if reserved flags
+    /// are set, or if signing fails.
+    pub fn sign(&self, signing_secret_key: &SecretKey) -> Result<OwnedRecord, Error> {
+        self.sign_with_rng(&mut OsRng, signing_secret_key)
+    }
+
+    /// Sign the record, drawing the address nonce (if there is no
+    /// deterministic key) from `rng`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record would be too long, if reserved flags
+    /// are set, or if signing fails.
+    pub fn sign_with_rng<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+        signing_secret_key: &SecretKey,
+    ) -> Result<OwnedRecord, Error> {
+        OwnedRecord::new_with_rng(rng, signing_secret_key, &self.parts())
+    }
 }
 
 #[cfg(test)]
@@ -618,4 +1284,326 @@ mod test {
 
         assert_eq!(*r1, *r2);
     }
+
+    #[test]
+    fn test_owned_record_into_vec() {
+        use rand::rngs::OsRng;
+
+        let signing_secret_key = SecretKey::generate(&mut OsRng);
+        let r1 = OwnedRecord::new(
+            &signing_secret_key,
+            &RecordParts {
+                kind: Kind::KEY_SCHEDULE,
+                deterministic_key: None,
+                timestamp: Timestamp::now().unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello world",
+            },
+        )
+        .unwrap();
+
+        // Edited bytes must be verified again
+        let mut bytes = r1.clone().into_vec();
+        assert_eq!(OwnedRecord::from_vec(bytes.clone()).unwrap(), r1);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(OwnedRecord::from_vec(bytes).is_err());
+    }
+
+    #[test]
+    fn test_record_from_bytes_verified() {
+        use rand::rngs::OsRng;
+
+        let signing_secret_key = SecretKey::generate(&mut OsRng);
+        let r1 = OwnedRecord::new(
+            &signing_secret_key,
+            &RecordParts {
+                kind: Kind::KEY_SCHEDULE,
+                deterministic_key: None,
+                timestamp: Timestamp::now().unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello world",
+            },
+        )
+        .unwrap();
+
+        assert_eq!(Record::from_bytes_verified(r1.as_bytes()).unwrap(), &*r1);
+        let mut tampered = r1.as_bytes().to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(Record::from_bytes_verified(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_record_from_bytes_with_max_len() {
+        use rand::rngs::OsRng;
+
+        let signing_secret_key = SecretKey::generate(&mut OsRng);
+        let r1 = OwnedRecord::new(
+            &signing_secret_key,
+            &RecordParts {
+                kind: Kind::KEY_SCHEDULE,
+                deterministic_key: None,
+                timestamp: Timestamp::now().unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello world",
+            },
+        )
+        .unwrap();
+
+        assert!(unsafe { Record::from_bytes_with_max_len(r1.as_bytes(), 200) }.is_err());
+        assert!(
+            unsafe { Record::from_bytes_with_max_len(r1.as_bytes(), r1.as_bytes().len()) }.is_ok()
+        );
+    }
+
+    #[test]
+    fn test_record_trailing_bytes() {
+        use rand::rngs::OsRng;
+
+        let signing_secret_key = SecretKey::generate(&mut OsRng);
+        let r1 = OwnedRecord::new(
+            &signing_secret_key,
+            &RecordParts {
+                kind: Kind::KEY_SCHEDULE,
+                deterministic_key: None,
+                timestamp: Timestamp::now().unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello world",
+            },
+        )
+        .unwrap();
+
+        let mut stream = r1.as_bytes().to_vec();
+        stream.extend_from_slice(b"next");
+        let (r3, consumed) = unsafe { Record::from_bytes_prefix(&stream).unwrap() };
+        assert_eq!(r3, &*r1);
+        assert_eq!(&stream[consumed..], b"next");
+        assert!(unsafe { Record::from_bytes_strict(r1.as_bytes()) }.is_ok());
+        let err = unsafe { Record::from_bytes_strict(&stream) }.unwrap_err();
+        assert!(matches!(err.inner, InnerError::TrailingBytes(4)));
+        let err = Record::from_bytes_verified(&stream).unwrap_err();
+        assert!(matches!(err.inner, InnerError::TrailingBytes(4)));
+    }
+
+    #[test]
+    fn test_record_with_seeded_rng() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let make = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let secret_key = SecretKey::generate(&mut rng);
+            OwnedRecord::new_with_rng(
+                &mut rng,
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        // The same seed gives the same key, address and signature
+        assert_eq!(make(7), make(7));
+        assert_ne!(make(7).address(), make(8).address());
+        make(7).verify().unwrap();
+    }
+
+    #[test]
+    fn test_predicted_address() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let mut parts = RecordParts {
+            kind: Kind::MICROBLOG_ROOT,
+            deterministic_key: Some(b"profile"),
+            timestamp: Timestamp::from_millis(1000).unwrap(),
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: b"hello",
+        };
+        let address = parts.predicted_address(&secret_key.public()).unwrap();
+        let id = parts.predicted_id(&secret_key.public()).unwrap().unwrap();
+        let record = OwnedRecord::new(&secret_key, &parts).unwrap();
+        assert_eq!(record.address(), address);
+        assert_eq!(record.id(), id);
+
+        parts.deterministic_key = None;
+        assert!(parts.predicted_address(&secret_key.public()).is_none());
+        assert!(parts.predicted_id(&secret_key.public()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_large_record() {
+        use rand::rngs::OsRng;
+
+        // Large enough to take the parallel hashing path when enabled
+        let payload = vec![0x55_u8; 512 * 1024];
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::BLOG_POST,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: &payload,
+            },
+        )
+        .unwrap();
+
+        let mut hasher = blake3::Hasher::new();
+        let _ = hasher.update(&record.as_bytes()[super::HASHABLE_RANGE]);
+        let mut expected = [0_u8; 64];
+        hasher.finalize_xof().fill(&mut expected);
+        assert_eq!(record.full_hash(), expected);
+        record.verify().unwrap();
+    }
+
+    #[test]
+    fn test_record_builder() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let mut builder =
+            RecordBuilder::new(Kind::MICROBLOG_ROOT, Timestamp::from_millis(1000).unwrap());
+        let _ = builder
+            .set_deterministic_key(b"key")
+            .set_app_flags(7)
+            .set_payload("hello");
+        assert_eq!(builder.record_len(), 208 + 8);
+        let _ = builder
+            .add_tag(&OwnedTag::new_subkey(&secret_key.public()))
+            .unwrap();
+        let len = builder.record_len();
+
+        let record = builder.sign(&secret_key).unwrap();
+        assert_eq!(record.as_bytes().len(), len);
+        assert_eq!(record.payload_bytes(), b"hello");
+        assert_eq!(record.app_flags(), 7);
+        assert_eq!(record.tags_bytes(), builder.tags().as_bytes());
+        assert_eq!(
+            Some(record.address()),
+            builder.parts().predicted_address(&secret_key.public())
+        );
+        record.verify().unwrap();
+    }
+
+    #[test]
+    fn test_verify_delegation() {
+        use rand::rngs::OsRng;
+
+        let master = SecretKey::generate(&mut OsRng);
+        let subkey = SecretKey::generate(&mut OsRng);
+        let at = |millis: u64| Timestamp::from_millis(millis).unwrap();
+        let sign = |signer: &SecretKey, millis: u64| {
+            OwnedRecord::new_replacement(
+                signer,
+                Address::new_random(master.public(), Kind::MICROBLOG_ROOT),
+                at(millis),
+                RecordFlags::empty(),
+                0,
+                b"",
+                b"hello",
+            )
+            .unwrap()
+        };
+
+        let mut schedule = KeySchedule::new();
+        schedule.add_subkey(
+            subkey.public(),
+            SubkeyWindow {
+                valid_from: at(1000),
+                valid_until: Some(at(2000)),
+            },
+        );
+
+        sign(&master, 500).verify_delegation(&schedule).unwrap();
+        sign(&subkey, 1500).verify_delegation(&schedule).unwrap();
+        let before = sign(&subkey, 999).verify_delegation(&schedule);
+        assert!(matches!(
+            before.unwrap_err().inner,
+            InnerError::SigningKeyNotDelegated
+        ));
+        let after = sign(&subkey, 2001).verify_delegation(&schedule);
+        assert!(matches!(
+            after.unwrap_err().inner,
+            InnerError::SigningKeyExpired
+        ));
+        let other = SecretKey::generate(&mut OsRng);
+        assert!(sign(&other, 1500).verify_delegation(&schedule).is_err());
+
+        // Only records from the revocation on are rejected
+        schedule.revoke(subkey.public(), at(1500));
+        sign(&subkey, 1499).verify_delegation(&schedule).unwrap();
+        let revoked = sign(&subkey, 1500).verify_delegation(&schedule);
+        assert_eq!(revoked.unwrap_err().kind(), ErrorKind::UntrustedKey);
+
+        // The author's own key needs no delegation, even if listed as
+        // revoked
+        schedule.revoke(master.public(), at(0));
+        sign(&master, 1500).verify_delegation(&schedule).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_unsigned() {
+        use rand::rngs::{OsRng, StdRng};
+        use rand::SeedableRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let parts = RecordParts {
+            kind: Kind::MICROBLOG_ROOT,
+            deterministic_key: Some(b"key"),
+            timestamp: Timestamp::from_millis(1000).unwrap(),
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: b"hello",
+        };
+        let expected = OwnedRecord::new(&secret_key, &parts).unwrap();
+
+        let unsigned = Record::prepare_unsigned(secret_key.public(), &parts).unwrap();
+        assert_eq!(unsigned.id(), expected.id());
+        assert_eq!(unsigned.address(), expected.address());
+        assert_eq!(unsigned.signing_public_key(), secret_key.public());
+
+        // Signed out of band
+        let signature = Signer::sign_prehash(&secret_key, unsigned.prehash()).unwrap();
+        let mut bad = signature;
+        bad[63] ^= 1;
+        assert!(unsigned.clone().attach_signature(&bad).is_err());
+        let record = unsigned.attach_signature(&signature).unwrap();
+        assert_eq!(record, expected);
+
+        // A seeded rng gives the same random address as new_with_rng()
+        let parts = RecordParts {
+            deterministic_key: None,
+            ..parts
+        };
+        let seeded = || StdRng::seed_from_u64(7);
+        let expected = OwnedRecord::new_with_rng(&mut seeded(), &secret_key, &parts).unwrap();
+        let unsigned =
+            Record::prepare_unsigned_with_rng(&mut seeded(), secret_key.public(), &parts).unwrap();
+        assert_eq!(unsigned.address(), expected.address());
+        let record = OwnedRecord::new_with_signer_with_rng(&mut seeded(), &secret_key, &parts);
+        assert_eq!(record.unwrap(), expected);
+    }
 }
@@ -0,0 +1,254 @@
+use crate::{Error, InnerError, OwnedRecord, Record, MAX_RECORD_LEN};
+
+/// Frames records on a byte stream, such as a socket.
+///
+/// Records carry their own lengths, so they are written back to back with
+/// no extra framing. When reading, the length is checked as soon as the
+/// header has arrived, so an oversized record is rejected before its body
+/// is buffered. Each record is fully verified as it is decoded.
+///
+/// The inherent methods work on a `Vec<u8>` and depend on no runtime. With
+/// the `tokio-util` feature, `RecordCodec` also implements
+/// `tokio_util::codec::{Decoder, Encoder}` on `BytesMut`, for use with
+/// `Framed`, `FramedRead` and `FramedWrite`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordCodec {
+    max_record_len: usize,
+}
+
+impl Default for RecordCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordCodec {
+    /// Create a `RecordCodec` accepting records up to `MAX_RECORD_LEN`
+    #[must_use]
+    pub fn new() -> RecordCodec {
+        RecordCodec {
+            max_record_len: MAX_RECORD_LEN,
+        }
+    }
+
+    /// Create a `RecordCodec` accepting records up to `max_record_len`
+    /// bytes, capped at `MAX_RECORD_LEN`
+    #[must_use]
+    pub fn with_max_record_len(max_record_len: usize) -> RecordCodec {
+        RecordCodec {
+            max_record_len: max_record_len.min(MAX_RECORD_LEN),
+        }
+    }
+
+    /// The longest record this codec accepts
+    #[must_use]
+    pub fn max_record_len(&self) -> usize {
+        self.max_record_len
+    }
+
+    /// Take the next record from the front of `src`, or `None` if more
+    /// bytes are needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is too long or fails to verify. The
+    /// stream cannot be resynchronized after an error.
+    pub fn decode(&mut self, src: &mut Vec<u8>) -> Result<Option<OwnedRecord>, Error> {
+        let Some(len) = self.next_len(src)? else {
+            return Ok(None);
+        };
+        let rest = src.split_off(len);
+        let bytes = std::mem::replace(src, rest);
+        OwnedRecord::from_vec(bytes).map(Some)
+    }
+
+    /// Take the next record from the front of `src` once the stream has
+    /// ended, or `None` if `src` is empty
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` as with `decode()`, or if the stream ended partway
+    /// through a record
+    pub fn decode_eof(&mut self, src: &mut Vec<u8>) -> Result<Option<OwnedRecord>, Error> {
+        match self.decode(src)? {
+            Some(record) => Ok(Some(record)),
+            None if src.is_empty() => Ok(None),
+            None => Err(InnerError::EndOfInput.into()),
+        }
+    }
+
+    /// Append a record to `dst`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is longer than this codec accepts
+    pub fn encode(&mut self, record: &Record, dst: &mut Vec<u8>) -> Result<(), Error> {
+        self.check_len(record)?;
+        dst.extend_from_slice(record.as_bytes());
+        Ok(())
+    }
+
+    // The length of the record at the front of `src`, or `None` if its
+    // header has not all arrived
+    fn next_len(self, src: &[u8]) -> Result<Option<usize>, Error> {
+        // SAFETY: callers verify the record before returning it
+        match unsafe { Record::from_bytes_with_max_len(src, self.max_record_len) } {
+            Ok(record) => Ok(Some(record.as_bytes().len())),
+            Err(e) if matches!(e.inner, InnerError::EndOfInput) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn check_len(self, record: &Record) -> Result<(), Error> {
+        if record.as_bytes().len() > self.max_record_len {
+            return Err(InnerError::RecordTooLong.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl tokio_util::codec::Decoder for RecordCodec {
+    type Item = OwnedRecord;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<OwnedRecord>, Error> {
+        let Some(len) = self.next_len(src)? else {
+            return Ok(None);
+        };
+        let bytes = src.split_to(len);
+        OwnedRecord::from_vec(bytes.to_vec()).map(Some)
+    }
+
+    fn decode_eof(&mut self, src: &mut bytes::BytesMut) -> Result<Option<OwnedRecord>, Error> {
+        match tokio_util::codec::Decoder::decode(self, src)? {
+            Some(record) => Ok(Some(record)),
+            None if src.is_empty() => Ok(None),
+            None => Err(InnerError::EndOfInput.into()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl tokio_util::codec::Encoder<&Record> for RecordCodec {
+    type Error = Error;
+
+    fn encode(&mut self, record: &Record, dst: &mut bytes::BytesMut) -> Result<(), Error> {
+        self.check_len(record)?;
+        dst.extend_from_slice(record.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl tokio_util::codec::Encoder<OwnedRecord> for RecordCodec {
+    type Error = Error;
+
+    fn encode(&mut self, record: OwnedRecord, dst: &mut bytes::BytesMut) -> Result<(), Error> {
+        tokio_util::codec::Encoder::<&Record>::encode(self, &record, dst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_record_codec() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let make = |payload: &[u8]| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload,
+                },
+            )
+            .unwrap()
+        };
+        let first = make(b"hello");
+        let second = make(&[7; 1000]);
+
+        let mut codec = RecordCodec::new();
+        let mut stream = Vec::new();
+        codec.encode(&first, &mut stream).unwrap();
+        codec.encode(&second, &mut stream).unwrap();
+
+        // Feed the stream in a few bytes at a time
+        let mut src = Vec::new();
+        let mut decoded = Vec::new();
+        for chunk in stream.chunks(100) {
+            src.extend_from_slice(chunk);
+            while let Some(record) = codec.decode(&mut src).unwrap() {
+                decoded.push(record);
+            }
+        }
+        assert_eq!(decoded, vec![first.clone(), second.clone()]);
+        assert!(src.is_empty());
+        assert!(codec.decode_eof(&mut src).unwrap().is_none());
+
+        // A partial record at the end of the stream
+        let mut src = second.as_bytes()[..300].to_vec();
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(codec.decode_eof(&mut src).is_err());
+
+        // Too long, rejected from the header alone
+        let mut codec = RecordCodec::with_max_record_len(500);
+        assert!(codec.encode(&second, &mut Vec::new()).is_err());
+        let mut src = second.as_bytes()[..208].to_vec();
+        let err = codec.decode(&mut src).unwrap_err();
+        assert!(matches!(err.inner, InnerError::RecordTooLong));
+    }
+
+    #[cfg(feature = "tokio-util")]
+    #[test]
+    fn test_tokio_util_codec() {
+        use bytes::BytesMut;
+        use rand::rngs::OsRng;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut codec = RecordCodec::new();
+        let mut stream = BytesMut::new();
+        Encoder::<&Record>::encode(&mut codec, &record, &mut stream).unwrap();
+        Encoder::<OwnedRecord>::encode(&mut codec, record.clone(), &mut stream).unwrap();
+
+        let mut src = stream.split_to(100);
+        assert!(Decoder::decode(&mut codec, &mut src).unwrap().is_none());
+        src.unsplit(stream);
+        assert_eq!(
+            Decoder::decode(&mut codec, &mut src).unwrap(),
+            Some(record.clone())
+        );
+        assert_eq!(
+            Decoder::decode_eof(&mut codec, &mut src).unwrap(),
+            Some(record.clone())
+        );
+        assert!(Decoder::decode_eof(&mut codec, &mut src).unwrap().is_none());
+
+        let mut src = BytesMut::from(&record.as_bytes()[..100]);
+        assert!(Decoder::decode_eof(&mut codec, &mut src).is_err());
+    }
+}
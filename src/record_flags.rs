@@ -1,6 +1,15 @@
 use bitflags::bitflags;
 
-/// Server usage flags
+/// Record flags
+///
+/// Flag sets can be composed at compile time, since the `bitflags`
+/// operations such as `union()` are `const fn`:
+///
+/// ```
+/// # use mosaic_core::RecordFlags;
+/// const PRIVATE: RecordFlags = RecordFlags::FROM_AUTHOR.union(RecordFlags::TO_RECIPIENTS);
+/// # assert!(PRIVATE.contains(RecordFlags::TO_RECIPIENTS));
+/// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RecordFlags(u16);
 
@@ -0,0 +1,260 @@
+use crate::{zbase32, Record, RecordFlags};
+use std::fmt::Write;
+
+/// The layout a `RecordFormatter` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FormatMode {
+    /// A single line with the id, kind, timestamp, author and payload
+    Summary,
+
+    /// Every field on its own line (the `Display` layout of a `Record`)
+    #[default]
+    Full,
+
+    /// A hex dump of the record bytes, 16 bytes per line
+    HexDump,
+
+    /// A single line of space-separated `key=value` pairs for logs and
+    /// scripts. Text payloads are quoted and escaped.
+    Machine,
+}
+
+/// How a `RecordFormatter` renders the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PayloadFormat {
+    /// As text if the record is flagged `PRINTABLE`, otherwise as zbase32
+    #[default]
+    Auto,
+
+    /// As text, replacing invalid UTF-8
+    Text,
+
+    /// As zbase32
+    ZBase32,
+
+    /// As lowercase hex
+    Hex,
+
+    /// Not at all
+    Omit,
+}
+
+/// A configurable pretty-printer for records.
+///
+/// The default formatter produces the same output as the `Display`
+/// implementation of `Record`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RecordFormatter {
+    /// The layout
+    pub mode: FormatMode,
+
+    /// How the payload is rendered. Ignored in `FormatMode::HexDump`.
+    pub payload: PayloadFormat,
+
+    /// If set, a rendered payload longer than this many characters is cut
+    /// short and marked with `...`
+    pub max_payload_chars: Option<usize>,
+}
+
+impl RecordFormatter {
+    /// Create a `RecordFormatter` with the default (full) layout
+    #[must_use]
+    pub fn new() -> RecordFormatter {
+        RecordFormatter::default()
+    }
+
+    /// Create a `RecordFormatter` with the given layout
+    #[must_use]
+    pub fn with_mode(mode: FormatMode) -> RecordFormatter {
+        RecordFormatter {
+            mode,
+            ..RecordFormatter::default()
+        }
+    }
+
+    /// Format a record into a `String`
+    #[must_use]
+    pub fn format(&self, record: &Record) -> String {
+        let mut s = String::new();
+        let _ = self.write(&mut s, record);
+        s
+    }
+
+    /// Format a record into any `std::fmt::Write`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` only if the writer does
+    pub fn write<W: Write + ?Sized>(&self, w: &mut W, record: &Record) -> std::fmt::Result {
+        match self.mode {
+            FormatMode::Summary => self.write_summary(w, record),
+            FormatMode::Full => self.write_full(w, record),
+            FormatMode::HexDump => write_hex_dump(w, record.as_bytes()),
+            FormatMode::Machine => self.write_machine(w, record),
+        }
+    }
+
+    fn write_summary<W: Write + ?Sized>(&self, w: &mut W, record: &Record) -> std::fmt::Result {
+        write!(
+            w,
+            "{} kind={} ts={} by {}",
+            record.id().printable(),
+            record.kind(),
+            record.timestamp(),
+            record.author_public_key().printable()
+        )?;
+        if let Some((_, payload)) = self.render_payload(record) {
+            write!(w, ": {payload}")?;
+        }
+        writeln!(w)
+    }
+
+    fn write_full<W: Write + ?Sized>(&self, w: &mut W, record: &Record) -> std::fmt::Result {
+        writeln!(w, "id: {}", record.id().printable())?;
+        writeln!(w, "  address: {}", record.address().printable())?;
+        writeln!(
+            w,
+            "  author key: {}",
+            record.author_public_key().printable()
+        )?;
+        writeln!(
+            w,
+            "  signing key: {}",
+            record.signing_public_key().printable()
+        )?;
+        writeln!(w, "  timestamp: {}", record.timestamp())?;
+        writeln!(w, "  kind: {}", record.kind())?;
+        writeln!(w, "  flags: {} {}", record.flags(), record.app_flags())?;
+        writeln!(
+            w,
+            "  tags (zbase32): {}",
+            zbase32::encode(record.tags_bytes())
+        )?;
+        match self.render_payload(record) {
+            Some(("text", payload)) => writeln!(w, "  payload: {payload}"),
+            Some((encoding, payload)) => writeln!(w, "  payload ({encoding}): {payload}"),
+            None => Ok(()),
+        }
+    }
+
+    fn write_machine<W: Write + ?Sized>(&self, w: &mut W, record: &Record) -> std::fmt::Result {
+        write!(
+            w,
+            "id={} address={} author={} signer={} timestamp_ms={} kind={} flags={:#06x} app_flags={:#06x} tags={}",
+            record.id().printable(),
+            record.address().printable(),
+            record.author_public_key().printable(),
+            record.signing_public_key().printable(),
+            record.timestamp().as_millis(),
+            record.kind().0,
+            record.flags().bits(),
+            record.app_flags(),
+            zbase32::encode(record.tags_bytes()),
+        )?;
+        match self.render_payload(record) {
+            Some(("text", payload)) => writeln!(w, " payload={payload:?}"),
+            Some((encoding, payload)) => writeln!(w, " payload_{encoding}={payload}"),
+            None => writeln!(w),
+        }
+    }
+
+    // The payload's encoding name and rendering, truncated if configured
+    fn render_payload(&self, record: &Record) -> Option<(&'static str, String)> {
+        let bytes = record.payload_bytes();
+        let (encoding, mut rendered) = match self.payload {
+            PayloadFormat::Auto if record.flags().contains(RecordFlags::PRINTABLE) => {
+                ("text", String::from_utf8_lossy(bytes).into_owned())
+            }
+            PayloadFormat::Text => ("text", String::from_utf8_lossy(bytes).into_owned()),
+            PayloadFormat::Auto | PayloadFormat::ZBase32 => ("zbase32", zbase32::encode(bytes)),
+            PayloadFormat::Hex => ("hex", to_hex(bytes)),
+            PayloadFormat::Omit => return None,
+        };
+        if let Some(max) = self.max_payload_chars {
+            if let Some((cut, _)) = rendered.char_indices().nth(max) {
+                rendered.truncate(cut);
+                rendered.push_str("...");
+            }
+        }
+        Some((encoding, rendered))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn write_hex_dump<W: Write + ?Sized>(w: &mut W, bytes: &[u8]) -> std::fmt::Result {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        write!(w, "{:08x} ", line * 16)?;
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(b) => write!(w, " {b:02x}")?,
+                None => w.write_str("   ")?,
+            }
+        }
+        w.write_str("  |")?;
+        for &b in chunk {
+            w.write_char(if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            })?;
+        }
+        writeln!(w, "|")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedRecord, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_record_formatter() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::PRINTABLE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"Hello \"world\"",
+            },
+        )
+        .unwrap();
+
+        // The default layout is the Display layout
+        assert_eq!(RecordFormatter::new().format(&record), format!("{record}"));
+
+        let mut formatter = RecordFormatter::with_mode(FormatMode::Summary);
+        formatter.max_payload_chars = Some(5);
+        let summary = formatter.format(&record);
+        assert_eq!(summary.lines().count(), 1);
+        assert!(summary.ends_with(": Hello...\n"));
+
+        formatter.mode = FormatMode::Machine;
+        formatter.max_payload_chars = None;
+        let machine = formatter.format(&record);
+        assert!(machine.contains("timestamp_ms=1000 "));
+        assert!(machine.ends_with(" payload=\"Hello \\\"world\\\"\"\n"));
+        formatter.payload = PayloadFormat::Hex;
+        assert!(formatter
+            .format(&record)
+            .ends_with(" payload_hex=48656c6c6f2022776f726c6422\n"));
+
+        formatter.mode = FormatMode::HexDump;
+        let dump = formatter.format(&record);
+        assert_eq!(dump.lines().count(), record.as_bytes().len().div_ceil(16));
+        assert!(dump.contains("|Hello \"world\"...|"));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{Id, OwnedRecord, Record};
+use std::collections::BTreeMap;
+
+/// A `RecordSet` is a set of records with no duplicates, keyed by `Id`.
+///
+/// Iteration is in timestamp order (oldest first) since `Id`s sort in time
+/// order. This is the standard in-memory container for query results and
+/// sync buffers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordSet(BTreeMap<Id, OwnedRecord>);
+
+impl RecordSet {
+    /// Create a new empty `RecordSet`
+    #[must_use]
+    pub fn new() -> RecordSet {
+        RecordSet::default()
+    }
+
+    /// Insert a record if it is not already present. Returns true if it
+    /// was inserted.
+    pub fn insert(&mut self, record: OwnedRecord) -> bool {
+        let id = record.id();
+        if self.0.contains_key(&id) {
+            return false;
+        }
+        let _ = self.0.insert(id, record);
+        true
+    }
+
+    /// Insert a copy of a borrowed record if it is not already present.
+    /// Returns true if it was inserted.
+    pub fn insert_record(&mut self, record: &Record) -> bool {
+        if self.0.contains_key(&record.id()) {
+            return false;
+        }
+        self.insert(record.to_owned())
+    }
+
+    /// Is a record with this `Id` present?
+    #[must_use]
+    pub fn contains(&self, id: &Id) -> bool {
+        self.0.contains_key(id)
+    }
+
+    /// Get a record by `Id`
+    #[must_use]
+    pub fn get(&self, id: &Id) -> Option<&OwnedRecord> {
+        self.0.get(id)
+    }
+
+    /// Remove a record by `Id`
+    pub fn remove(&mut self, id: &Id) -> Option<OwnedRecord> {
+        self.0.remove(id)
+    }
+
+    /// Number of records
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Is the set empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Records that are in either set
+    #[must_use]
+    pub fn union(&self, other: &RecordSet) -> RecordSet {
+        let mut output = self.clone();
+        for (id, record) in &other.0 {
+            let _ = output.0.entry(*id).or_insert_with(|| record.clone());
+        }
+        output
+    }
+
+    /// Records that are in this set but not in the other
+    #[must_use]
+    pub fn difference(&self, other: &RecordSet) -> RecordSet {
+        RecordSet(
+            self.0
+                .iter()
+                .filter(|(id, _)| !other.0.contains_key(id))
+                .map(|(id, record)| (*id, record.clone()))
+                .collect(),
+        )
+    }
+
+    /// Iterate in timestamp order, oldest first
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &OwnedRecord> {
+        self.0.values()
+    }
+
+    /// Iterate over the `Id`s in timestamp order, oldest first
+    #[must_use]
+    pub fn ids(&self) -> impl DoubleEndedIterator<Item = &Id> {
+        self.0.keys()
+    }
+}
+
+impl FromIterator<OwnedRecord> for RecordSet {
+    fn from_iter<I: IntoIterator<Item = OwnedRecord>>(iter: I) -> RecordSet {
+        let mut set = RecordSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<OwnedRecord> for RecordSet {
+    fn extend<I: IntoIterator<Item = OwnedRecord>>(&mut self, iter: I) {
+        for record in iter {
+            let _ = self.insert(record);
+        }
+    }
+}
+
+impl IntoIterator for RecordSet {
+    type Item = OwnedRecord;
+    type IntoIter = std::collections::btree_map::IntoValues<Id, OwnedRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_values()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_record_set() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let r1 = make(1000);
+        let r2 = make(2000);
+        let r3 = make(3000);
+
+        let mut a = RecordSet::new();
+        assert!(a.insert(r3.clone()));
+        assert!(a.insert(r1.clone()));
+        assert!(!a.insert(r1.clone()));
+        assert_eq!(a.len(), 2);
+
+        let b: RecordSet = vec![r2.clone(), r3.clone()].into_iter().collect();
+
+        let union = a.union(&b);
+        let in_order: Vec<&OwnedRecord> = union.iter().collect();
+        assert_eq!(in_order, vec![&r1, &r2, &r3]);
+
+        let difference = a.difference(&b);
+        assert_eq!(difference.len(), 1);
+        assert!(difference.contains(&r1.id()));
+    }
+}
@@ -0,0 +1,558 @@
+use crate::archive::{read_record, write_record};
+use crate::backup::{
+    read_chunk, read_header, write_chunk, write_end, write_header, HEADER_LEN as BACKUP_HEADER_LEN,
+};
+use crate::{
+    Address, ArchiveFormat, BackupProgress, Cursor, Direction, Error, Filter, Id, MemorySnapshot,
+    OwnedRecord, Page, Record, Snapshot, VerifyLevel, WriteBatch, WriteOp, WriteResult,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+
+/// How many records `RecordStore::import()` writes per `WriteBatch`
+pub const IMPORT_BATCH_SIZE: usize = 1024;
+
+/// The outcome of putting a record into a `RecordStore`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PutOutcome {
+    /// The record was stored
+    Stored,
+
+    /// The record was already stored
+    Duplicate,
+
+    /// The record was deleted previously and will not be stored again
+    Deleted,
+
+    /// A newer record at the same address is already stored, so this
+    /// record was not stored
+    Superseded,
+
+    /// Storing the record would exceed a quota, so it was not stored
+    QuotaExceeded,
+
+    /// The record is ephemeral, so it was not stored
+    Ephemeral,
+
+    /// The record is longer than the store's maximum record length, so it
+    /// was not stored
+    TooLarge,
+}
+
+/// What a `RecordStore` does with a record when it is deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DeletionMode {
+    /// The record is removed
+    #[default]
+    Purge,
+
+    /// The record is retained but hidden. It is no longer returned by
+    /// `get_by_id()`, `get_by_address()`, or by queries unless the
+    /// filter sets `include_deleted`. This is for servers that must
+    /// retain content they no longer serve.
+    Hide,
+}
+
+/// A `RecordStore` is a storage backend for records.
+///
+/// Servers, caches and clients all store records. They can share
+/// query logic and swap backends by coding against this trait.
+///
+/// Records are stored and returned verified; backends do not need to
+/// verify records themselves.
+///
+/// Deleted `Id`s are remembered so that deleted records are never stored
+/// again, and deleted records are never returned by queries unless the
+/// filter sets `include_deleted`.
+///
+/// Ephemeral records (see `Record::is_ephemeral()`) are never stored;
+/// `put()` returns `PutOutcome::Ephemeral` for them.
+pub trait RecordStore {
+    /// Store a record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn put(&mut self, record: &Record) -> Result<PutOutcome, Error>;
+
+    /// Get a record by its `Id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error>;
+
+    /// Get the current record at an `Address`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error>;
+
+    /// Delete a record by its `Id`. Returns true if the record was present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn delete(&mut self, id: &Id) -> Result<bool, Error>;
+
+    /// Remove a record by its `Id` without remembering it as deleted, so
+    /// that it may be stored again later. This is for caches and stores
+    /// layered on this one that drop records they no longer need, not for
+    /// deletions. Returns true if the record was present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn evict(&mut self, id: &Id) -> Result<bool, Error>;
+
+    /// Apply a `WriteBatch`, returning the result of each operation in
+    /// order.
+    ///
+    /// The default implementation applies each operation on its own.
+    /// Transactional backends should override this to apply the batch in
+    /// one transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails. Transactional backends apply
+    /// none of the batch in that case; others may have applied the
+    /// operations before the failure.
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<Vec<WriteResult>, Error> {
+        let mut results: Vec<WriteResult> = Vec::with_capacity(batch.len());
+        for op in batch {
+            results.push(match op {
+                WriteOp::Put(record) => WriteResult::Put(self.put(&record)?),
+                WriteOp::Delete(id) => WriteResult::Delete(self.delete(&id)?),
+            });
+        }
+        Ok(results)
+    }
+
+    /// Has this `Id` been deleted?
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error>;
+
+    /// Iterate over every stored record, in no particular order. This
+    /// includes records hidden by `DeletionMode::Hide`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails. Backends that read lazily
+    /// yield an `Err` item if they fail part way through.
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error>;
+
+    /// Query records matching the `Filter`, newest first, honoring the
+    /// filter's `limit`.
+    ///
+    /// The default implementation scans every record. Backends with
+    /// indexes should override this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the filter is invalid or if the backend fails
+    fn query(&self, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+        filter.validate()?;
+        let mut output: Vec<OwnedRecord> = vec![];
+        for record in self.records()? {
+            let record = record?;
+            if filter.matches(&record)
+                && (filter.include_deleted || !self.is_deleted(&record.id())?)
+            {
+                output.push(record);
+            }
+        }
+        sort_and_limit(&mut output, filter);
+        Ok(output)
+    }
+
+    /// Query one page of records matching the `Filter`, starting just past
+    /// the `Cursor`, in the cursor's direction. The filter's `limit` is the
+    /// page size.
+    ///
+    /// The default implementation scans every record. Backends with
+    /// indexes should override this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the filter is invalid or if the backend fails
+    fn page(&self, filter: &Filter, cursor: &Cursor) -> Result<Page, Error> {
+        filter.validate()?;
+        let mut output: Vec<OwnedRecord> = vec![];
+        for record in self.records()? {
+            let record = record?;
+            if cursor.admits(&record.id())
+                && filter.matches(&record)
+                && (filter.include_deleted || !self.is_deleted(&record.id())?)
+            {
+                output.push(record);
+            }
+        }
+        match cursor.direction() {
+            Direction::NewestFirst => output.sort_by_key(|r| std::cmp::Reverse(r.id())),
+            Direction::OldestFirst => output.sort_by_key(|r| r.id()),
+        }
+        if let Some(limit) = filter.limit {
+            output.truncate(limit as usize);
+        }
+        Ok(Page::new(output, filter, cursor.direction()))
+    }
+
+    /// Take a consistent read-only `Snapshot` of the store, unaffected by
+    /// later writes.
+    ///
+    /// The default implementation copies every record into a
+    /// `MemorySnapshot`. Backends with multi-version concurrency should
+    /// override this to avoid the copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn snapshot(&self) -> Result<Box<dyn Snapshot + Send>, Error> {
+        let mut records: BTreeMap<Id, OwnedRecord> = BTreeMap::new();
+        let mut by_address: HashMap<Address, Id> = HashMap::new();
+        let mut deleted: HashSet<Id> = HashSet::new();
+        for record in self.records()? {
+            let record = record?;
+            let id = record.id();
+            if self.is_deleted(&id)? {
+                let _ = deleted.insert(id);
+            }
+            let current = by_address
+                .get(&record.address())
+                .and_then(|current| records.get(current));
+            if current.is_none_or(|current| record.supersedes(current)) {
+                let _ = by_address.insert(record.address(), id);
+            }
+            let _ = records.insert(id, record);
+        }
+        Ok(Box::new(MemorySnapshot::new(records, by_address, deleted)))
+    }
+
+    /// Compact the store: remove records superseded by a newer version at
+    /// the same address, and reclaim the space left by removed and
+    /// garbage-collected records. Returns the number of records removed.
+    ///
+    /// The default implementation scans every record and evicts the
+    /// superseded ones, so that they are removed even under
+    /// `DeletionMode::Hide`. Backends that drop superseded versions on
+    /// `put()` should override this to reclaim space instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn compact(&mut self) -> Result<usize, Error> {
+        let superseded = superseded_ids(self.records()?)?;
+        let mut count: usize = 0;
+        for id in superseded {
+            if self.evict(&id)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Stream a backup of every record to the writer, in checksummed
+    /// chunks of `BACKUP_CHUNK_RECORDS`, oldest first. Deleted records,
+    /// including those hidden by `DeletionMode::Hide`, are not included.
+    ///
+    /// `progress` is updated after each chunk. To resume an interrupted
+    /// backup, truncate the output to `progress.offset` and call this
+    /// again with the same `progress`; the header is only written when
+    /// `progress.last_id` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails or if writing fails
+    fn backup(&self, writer: &mut dyn Write, progress: &mut BackupProgress) -> Result<(), Error> {
+        let mut cursor = match progress.last_id {
+            None => {
+                write_header(writer)?;
+                progress.offset = BACKUP_HEADER_LEN;
+                Cursor::start(Direction::OldestFirst)
+            }
+            Some(id) => Cursor::after(Direction::OldestFirst, id),
+        };
+        let mut filter = Filter::new();
+        filter.limit = Some(crate::BACKUP_CHUNK_RECORDS);
+        loop {
+            let page = self.page(&filter, &cursor)?;
+            if let Some(last) = page.records.last() {
+                progress.offset += write_chunk(writer, &page.records)?;
+                progress.chunks += 1;
+                progress.records += page.records.len() as u64;
+                progress.last_id = Some(last.id());
+            }
+            match page.next {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+        write_end(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Restore a backup written by `backup()`. Each chunk's checksum is
+    /// checked, every record is fully verified, and each chunk is written
+    /// as one `WriteBatch`.
+    ///
+    /// `progress` is updated after each chunk. Chunks at or before
+    /// `progress.last_id` are skipped, so an interrupted restore can be
+    /// resumed by reading the backup again from the start with the same
+    /// `progress`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backup is malformed or corrupt, if a record
+    /// fails verification, or if the backend fails
+    fn restore(
+        &mut self,
+        reader: &mut dyn Read,
+        progress: &mut BackupProgress,
+    ) -> Result<(), Error> {
+        read_header(reader)?;
+        let mut offset = BACKUP_HEADER_LEN;
+        while let Some((records, len)) = read_chunk(reader)? {
+            offset += len;
+            let last = records.last().map(|r| r.id());
+            if let (Some(done), Some(last)) = (progress.last_id, last) {
+                if last <= done {
+                    continue;
+                }
+            }
+            let count = records.len() as u64;
+            let mut batch = WriteBatch::new();
+            for record in records {
+                batch.put(record);
+            }
+            let _ = self.write_batch(batch)?;
+            progress.chunks += 1;
+            progress.records += count;
+            progress.last_id = last;
+            progress.offset = offset;
+        }
+        Ok(())
+    }
+
+    /// Export the records matching the `Filter` to an archive. Returns the
+    /// number of records written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the filter is invalid, if the backend fails, or
+    /// if writing fails
+    fn export(
+        &self,
+        filter: &Filter,
+        format: ArchiveFormat,
+        writer: &mut dyn Write,
+    ) -> Result<usize, Error> {
+        let records = self.query(filter)?;
+        for record in &records {
+            write_record(writer, format, record)?;
+        }
+        writer.flush()?;
+        Ok(records.len())
+    }
+
+    /// Import records from an archive. Returns the number of records
+    /// stored; records that are duplicates, deleted or superseded are
+    /// skipped. Records are written in batches of `IMPORT_BATCH_SIZE`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the archive is malformed, if a record fails
+    /// verification, or if the backend fails. Records before the failure
+    /// remain stored.
+    fn import(
+        &mut self,
+        format: ArchiveFormat,
+        reader: &mut dyn BufRead,
+        verify: VerifyLevel,
+    ) -> Result<usize, Error> {
+        let stored = |results: Vec<WriteResult>| {
+            results
+                .iter()
+                .filter(|r| **r == WriteResult::Put(PutOutcome::Stored))
+                .count()
+        };
+
+        let mut count: usize = 0;
+        let mut batch = WriteBatch::new();
+        loop {
+            match read_record(reader, format, verify) {
+                Ok(Some(record)) => {
+                    batch.put(record);
+                    if batch.len() >= IMPORT_BATCH_SIZE {
+                        count += stored(self.write_batch(std::mem::take(&mut batch))?);
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = self.write_batch(batch)?;
+                    return Err(e);
+                }
+            }
+        }
+        count += stored(self.write_batch(batch)?);
+        Ok(count)
+    }
+}
+
+/// The `Id`s of records superseded by another record at the same address
+pub(crate) fn superseded_ids<I>(records: I) -> Result<Vec<Id>, Error>
+where
+    I: Iterator<Item = Result<OwnedRecord, Error>>,
+{
+    let mut latest: HashMap<Address, OwnedRecord> = HashMap::new();
+    let mut output: Vec<Id> = vec![];
+    for record in records {
+        let record = record?;
+        match latest.get(&record.address()) {
+            Some(current) if !record.supersedes(current) => output.push(record.id()),
+            Some(current) => {
+                output.push(current.id());
+                let _ = latest.insert(record.address(), record);
+            }
+            None => {
+                let _ = latest.insert(record.address(), record);
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Sort records newest first (ties broken by `Id`) and truncate to the
+/// filter's limit
+pub(crate) fn sort_and_limit(records: &mut Vec<OwnedRecord>, filter: &Filter) {
+    records.sort_by_key(|r| std::cmp::Reverse(r.id()));
+    if let Some(limit) = filter.limit {
+        records.truncate(limit as usize);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_superseded_ids() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::PROFILE,
+                    deterministic_key: Some(b"profile"),
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let v1 = make(1000);
+        let v2 = make(2000);
+        let v3 = make(3000);
+        let mut ids = superseded_ids([v2.clone(), v3, v1.clone()].into_iter().map(Ok)).unwrap();
+        ids.sort();
+        assert_eq!(ids, vec![v1.id(), v2.id()]);
+    }
+
+    // A log that keeps every version and hides deleted records, relying on
+    // the default `compact()`
+    #[derive(Default)]
+    struct LogStore {
+        records: Vec<OwnedRecord>,
+        deleted: HashSet<Id>,
+    }
+
+    impl RecordStore for LogStore {
+        fn put(&mut self, record: &Record) -> Result<PutOutcome, Error> {
+            self.records.push(record.to_owned());
+            Ok(PutOutcome::Stored)
+        }
+
+        fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+            Ok(self
+                .records
+                .iter()
+                .find(|r| r.id() == *id && !self.deleted.contains(id))
+                .cloned())
+        }
+
+        fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+            Ok(self
+                .records
+                .iter()
+                .filter(|r| r.address() == *address && !self.deleted.contains(&r.id()))
+                .max_by_key(|r| r.id())
+                .cloned())
+        }
+
+        fn delete(&mut self, id: &Id) -> Result<bool, Error> {
+            Ok(self.deleted.insert(*id))
+        }
+
+        fn evict(&mut self, id: &Id) -> Result<bool, Error> {
+            let len = self.records.len();
+            self.records.retain(|r| r.id() != *id);
+            Ok(self.records.len() < len)
+        }
+
+        fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+            Ok(self.deleted.contains(id))
+        }
+
+        fn records(
+            &self,
+        ) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+            Ok(Box::new(self.records.iter().cloned().map(Ok)))
+        }
+    }
+
+    #[test]
+    fn test_default_compact() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::PROFILE,
+                    deterministic_key: Some(b"profile"),
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let mut store = LogStore::default();
+        let (v1, v2, v3) = (make(1000), make(2000), make(3000));
+        for record in [&v1, &v2, &v3] {
+            let _ = store.put(record).unwrap();
+        }
+        assert_eq!(store.compact().unwrap(), 2);
+        let remaining: Vec<Id> = store.records().unwrap().map(|r| r.unwrap().id()).collect();
+        assert_eq!(remaining, vec![v3.id()]);
+        assert!(!store.is_deleted(&v1.id()).unwrap());
+        assert_eq!(store.compact().unwrap(), 0);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::tag::iter_tags;
+use crate::{
+    DraftRecord, Error, InnerError, Kind, OwnedRecord, OwnedTag, Record, RecordFlags, SecretKey,
+    Timestamp,
+};
+use std::collections::HashMap;
+
+/// What a `RecordTemplate` requires of a record's payload
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PayloadSchema {
+    /// Any payload
+    #[default]
+    Any,
+
+    /// No payload
+    Empty,
+
+    /// UTF-8 text of at most this many bytes
+    Text {
+        /// The maximum length in bytes
+        max_len: usize,
+    },
+
+    /// Binary data of between `min_len` and `max_len` bytes
+    Bytes {
+        /// The minimum length in bytes
+        min_len: usize,
+
+        /// The maximum length in bytes
+        max_len: usize,
+    },
+
+    /// An application-defined check
+    Custom(fn(&[u8]) -> bool),
+}
+
+impl PayloadSchema {
+    /// Does the payload conform?
+    #[must_use]
+    pub fn accepts(&self, payload: &[u8]) -> bool {
+        match *self {
+            PayloadSchema::Any => true,
+            PayloadSchema::Empty => payload.is_empty(),
+            PayloadSchema::Text { max_len } => {
+                payload.len() <= max_len && std::str::from_utf8(payload).is_ok()
+            }
+            PayloadSchema::Bytes { min_len, max_len } => {
+                (min_len..=max_len).contains(&payload.len())
+            }
+            PayloadSchema::Custom(check) => check(payload),
+        }
+    }
+}
+
+/// The fixed shape of one kind of record an application creates: its
+/// kind, the tags every such record carries, its payload schema and its
+/// default flags.
+///
+/// Instantiating a template with per-record values gives a `DraftRecord`,
+/// so records of the same sort are always made the same way. The same
+/// template checks records received from others with `check()`.
+#[derive(Debug, Clone)]
+pub struct RecordTemplate {
+    /// The kind of record
+    pub kind: Kind,
+
+    /// Tags every record carries, ahead of any per-record tags
+    pub tags: Vec<OwnedTag>,
+
+    /// What the payload must be
+    pub payload: PayloadSchema,
+
+    /// The flags of every record
+    pub flags: RecordFlags,
+
+    /// The application flags of every record
+    pub app_flags: u16,
+}
+
+impl RecordTemplate {
+    /// Create a template for records of `kind`, with no fixed tags, any
+    /// payload and no flags
+    #[must_use]
+    pub fn new(kind: Kind) -> RecordTemplate {
+        RecordTemplate {
+            kind,
+            tags: vec![],
+            payload: PayloadSchema::Any,
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+        }
+    }
+
+    /// Create a draft record from this template and the per-record values
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the payload does not match the schema
+    pub fn instantiate(
+        &self,
+        timestamp: Timestamp,
+        tags: &[OwnedTag],
+        payload: &[u8],
+    ) -> Result<DraftRecord, Error> {
+        if !self.payload.accepts(payload) {
+            return Err(InnerError::TemplateMismatch("payload").into());
+        }
+        let tags_bytes: Vec<u8> = self
+            .tags
+            .iter()
+            .chain(tags)
+            .flat_map(|t| t.as_bytes().iter().copied())
+            .collect();
+        Ok(DraftRecord {
+            kind: self.kind,
+            deterministic_key: None,
+            timestamp,
+            flags: self.flags,
+            app_flags: self.app_flags,
+            tags_bytes,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Create and sign a record from this template and the per-record
+    /// values
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the payload does not match the schema, or if
+    /// signing fails
+    pub fn create(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+        tags: &[OwnedTag],
+        payload: &[u8],
+    ) -> Result<OwnedRecord, Error> {
+        self.instantiate(timestamp, tags, payload)?.sign(secret_key)
+    }
+
+    /// Check that a record was made from this template: it has the
+    /// template's kind and flags, carries every fixed tag, and has a
+    /// conforming payload
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` describing the first mismatch
+    pub fn check(&self, record: &Record) -> Result<(), Error> {
+        if record.kind() != self.kind {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        if !record.flags().contains(self.flags) {
+            return Err(InnerError::TemplateMismatch("flags").into());
+        }
+        for tag in &self.tags {
+            if !iter_tags(record.tags_bytes()).any(|t| t.as_bytes() == tag.as_bytes()) {
+                return Err(InnerError::MissingTag(tag.get_type()).into());
+            }
+        }
+        if !self.payload.accepts(record.payload_bytes()) {
+            return Err(InnerError::TemplateMismatch("payload").into());
+        }
+        Ok(())
+    }
+}
+
+/// Named `RecordTemplate`s, registered once at startup and instantiated
+/// throughout an application
+#[derive(Debug, Clone, Default)]
+pub struct TemplateRegistry {
+    templates: HashMap<String, RecordTemplate>,
+}
+
+impl TemplateRegistry {
+    /// Create an empty `TemplateRegistry`
+    #[must_use]
+    pub fn new() -> TemplateRegistry {
+        TemplateRegistry::default()
+    }
+
+    /// Register a template under `name`, replacing any previous one
+    pub fn register(&mut self, name: &str, template: RecordTemplate) {
+        let _ = self.templates.insert(name.to_owned(), template);
+    }
+
+    /// The template registered under `name`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&RecordTemplate> {
+        self.templates.get(name)
+    }
+
+    /// Create a draft record from the template registered under `name`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if no template is registered under `name`, or if
+    /// the payload does not match its schema
+    pub fn instantiate(
+        &self,
+        name: &str,
+        timestamp: Timestamp,
+        tags: &[OwnedTag],
+        payload: &[u8],
+    ) -> Result<DraftRecord, Error> {
+        self.get(name)
+            .ok_or_else(|| InnerError::UnknownTemplate(name.to_owned()).into_err())?
+            .instantiate(timestamp, tags, payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Reference, TagType};
+
+    #[test]
+    fn test_record_template() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let topic = OwnedTag::new(TagType::new(0x8010), b"rust").unwrap();
+        let mut registry = TemplateRegistry::new();
+        registry.register(
+            "rust-post",
+            RecordTemplate {
+                tags: vec![topic],
+                payload: PayloadSchema::Text { max_len: 500 },
+                flags: RecordFlags::PRINTABLE,
+                ..RecordTemplate::new(Kind::MICROBLOG_ROOT)
+            },
+        );
+        let ts = Timestamp::from_millis(1000).unwrap();
+
+        let address = crate::Address::new_random(secret_key.public(), Kind::MICROBLOG_ROOT);
+        let reply = OwnedTag::new_reply(
+            &Reference::from_bytes(address.as_bytes()).unwrap(),
+            Kind::MICROBLOG_ROOT,
+        );
+        let draft = registry
+            .instantiate("rust-post", ts, std::slice::from_ref(&reply), b"Hello")
+            .unwrap();
+        assert_eq!(draft.kind, Kind::MICROBLOG_ROOT);
+        assert_eq!(draft.flags, RecordFlags::PRINTABLE);
+        let record = draft.sign(&secret_key).unwrap();
+
+        let template = registry.get("rust-post").unwrap();
+        template.check(&record).unwrap();
+        assert_eq!(iter_tags(record.tags_bytes()).count(), 2);
+
+        assert!(registry.instantiate("rust-post", ts, &[], &[0xFF]).is_err());
+        assert!(registry.instantiate("go-post", ts, &[], b"Hello").is_err());
+
+        // Records not made from the template are caught
+        let other = RecordTemplate::new(Kind::MICROBLOG_ROOT)
+            .create(&secret_key, ts, &[], b"Hello")
+            .unwrap();
+        assert!(template.check(&other).is_err());
+    }
+}
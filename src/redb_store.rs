@@ -0,0 +1,536 @@
+use crate::kv_store::{
+    impl_kv_store, KeyRange, KvBackend, KvRead, KvSnapshot, KvStore, KvTable, KvWrite, ScanFn,
+};
+use crate::{Error, Id, Record};
+use redb::{
+    Database, MultimapTable, MultimapTableDefinition, ReadTransaction, ReadableMultimapTable,
+    ReadableTable, Table, TableDefinition, WriteTransaction,
+};
+use std::path::{Path, PathBuf};
+
+// The tables are laid out as described on `KvTable`. Tables with many
+// values per key are multimap tables.
+const RECORDS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("records");
+const ADDRESSES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("addresses");
+const DELETED: TableDefinition<&[u8], &[u8]> = TableDefinition::new("deleted");
+const AUTHORS: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("authors");
+const KINDS: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("kinds");
+const TAGS: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("tags");
+const TAG_VALUES: MultimapTableDefinition<&[u8], &[u8]> =
+    MultimapTableDefinition::new("tag_values");
+
+// The definition of a table, by whether it has many values per key
+enum Definition {
+    Single(TableDefinition<'static, &'static [u8], &'static [u8]>),
+    Multi(MultimapTableDefinition<'static, &'static [u8], &'static [u8]>),
+}
+
+fn definition(table: KvTable) -> Definition {
+    match table {
+        KvTable::Records => Definition::Single(RECORDS),
+        KvTable::Addresses => Definition::Single(ADDRESSES),
+        KvTable::Deleted => Definition::Single(DELETED),
+        KvTable::Authors => Definition::Multi(AUTHORS),
+        KvTable::Kinds => Definition::Multi(KINDS),
+        KvTable::Tags => Definition::Multi(TAGS),
+        KvTable::TagValues => Definition::Multi(TAG_VALUES),
+    }
+}
+
+/// A `RecordStore` persisted in an embedded [redb](https://docs.rs/redb)
+/// database file.
+///
+/// Writes are transactional and crash safe. Records are indexed by author,
+/// kind, tag, tag value prefix, timestamp and address, and replacement and
+/// deletion are handled the same way as in `MemoryStore`.
+///
+/// Use `with_record()` to view stored record bytes without copying them.
+pub struct RedbStore(KvStore<RedbBackend>);
+
+impl RedbStore {
+    /// Open (or create) a `RedbStore` at the given file path
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the database cannot be opened or created
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<RedbStore, Error> {
+        let db = Database::create(path.as_ref())?;
+
+        // Create all tables so that read transactions can always open them
+        let txn = db.begin_write()?;
+        {
+            let _ = Tables::open(&txn)?;
+        }
+        txn.commit()?;
+
+        Ok(RedbStore(KvStore::new(RedbBackend {
+            db,
+            path: path.as_ref().to_owned(),
+        })))
+    }
+
+    /// View a stored record without copying it out of the database. Records
+    /// hidden by `DeletionMode::Hide` are included.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the database fails
+    pub fn with_record<F, R>(&self, id: &Id, f: F) -> Result<Option<R>, Error>
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        self.0.with_record(id, f)
+    }
+}
+
+/// A `Snapshot` of a `RedbStore`.
+///
+/// This holds a redb read transaction, so it costs nothing to take and
+/// does not block writers. Space freed by later writes is not reclaimed
+/// until the snapshot is dropped.
+pub struct RedbSnapshot(KvSnapshot<RedbReader>);
+
+impl_kv_store!(RedbStore, RedbSnapshot);
+
+pub(crate) struct RedbBackend {
+    db: Database,
+    path: PathBuf,
+}
+
+impl KvBackend for RedbBackend {
+    type Reader = RedbReader;
+    type Snapshot = RedbSnapshot;
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<RedbReader, Error> {
+        Ok(RedbReader(self.db.begin_read()?))
+    }
+
+    fn snapshot(&self) -> Result<RedbSnapshot, Error> {
+        Ok(RedbSnapshot(KvSnapshot::new(self.read()?)))
+    }
+
+    fn write<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn KvWrite) -> Result<T, Error>,
+    {
+        let txn = self.db.begin_write()?;
+        let (output, dirty) = {
+            let mut tables = Tables::open(&txn)?;
+            let output = f(&mut tables)?;
+            (output, tables.dirty)
+        };
+        if dirty {
+            txn.commit()?;
+        } else {
+            txn.abort()?;
+        }
+        Ok(output)
+    }
+
+    fn reclaim(&mut self) -> Result<(), Error> {
+        let _ = self.db.compact()?;
+        Ok(())
+    }
+}
+
+fn get_in(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    key: &[u8],
+    f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+) -> Result<bool, Error> {
+    let Some(guard) = table.get(key)? else {
+        return Ok(false);
+    };
+    f(guard.value())?;
+    Ok(true)
+}
+
+fn get_in_multi(
+    table: &impl ReadableMultimapTable<&'static [u8], &'static [u8]>,
+    key: &[u8],
+    f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+) -> Result<bool, Error> {
+    let Some(guard) = table.get(key)?.next() else {
+        return Ok(false);
+    };
+    f(guard?.value())?;
+    Ok(true)
+}
+
+fn scan_in(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    range: KeyRange<'_>,
+    reverse: bool,
+    f: &mut ScanFn<'_>,
+) -> Result<(), Error> {
+    let mut iter = table.range::<&[u8]>(range)?;
+    while let Some(item) = if reverse {
+        iter.next_back()
+    } else {
+        iter.next()
+    } {
+        let (key, value) = item?;
+        if !f(key.value(), value.value())? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn scan_in_multi(
+    table: &impl ReadableMultimapTable<&'static [u8], &'static [u8]>,
+    range: KeyRange<'_>,
+    reverse: bool,
+    f: &mut ScanFn<'_>,
+) -> Result<(), Error> {
+    let mut iter = table.range::<&[u8]>(range)?;
+    while let Some(item) = if reverse {
+        iter.next_back()
+    } else {
+        iter.next()
+    } {
+        let (key, mut values) = item?;
+        while let Some(value) = if reverse {
+            values.next_back()
+        } else {
+            values.next()
+        } {
+            if !f(key.value(), value?.value())? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+// A read transaction
+pub(crate) struct RedbReader(ReadTransaction);
+
+impl KvRead for RedbReader {
+    fn get(
+        &self,
+        table: KvTable,
+        key: &[u8],
+        f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        match definition(table) {
+            Definition::Single(def) => get_in(&self.0.open_table(def)?, key, f),
+            Definition::Multi(def) => get_in_multi(&self.0.open_multimap_table(def)?, key, f),
+        }
+    }
+
+    fn scan(
+        &self,
+        table: KvTable,
+        range: KeyRange<'_>,
+        reverse: bool,
+        f: &mut ScanFn<'_>,
+    ) -> Result<(), Error> {
+        match definition(table) {
+            Definition::Single(def) => scan_in(&self.0.open_table(def)?, range, reverse, f),
+            Definition::Multi(def) => {
+                scan_in_multi(&self.0.open_multimap_table(def)?, range, reverse, f)
+            }
+        }
+    }
+}
+
+// The tables of a write transaction
+struct Tables<'txn> {
+    records: Table<'txn, &'static [u8], &'static [u8]>,
+    addresses: Table<'txn, &'static [u8], &'static [u8]>,
+    deleted: Table<'txn, &'static [u8], &'static [u8]>,
+    authors: MultimapTable<'txn, &'static [u8], &'static [u8]>,
+    kinds: MultimapTable<'txn, &'static [u8], &'static [u8]>,
+    tags: MultimapTable<'txn, &'static [u8], &'static [u8]>,
+    tag_values: MultimapTable<'txn, &'static [u8], &'static [u8]>,
+    dirty: bool,
+}
+
+// A table of a write transaction, by whether it has many values per key
+enum TableMut<'a, 'txn> {
+    Single(&'a mut Table<'txn, &'static [u8], &'static [u8]>),
+    Multi(&'a mut MultimapTable<'txn, &'static [u8], &'static [u8]>),
+}
+
+impl<'txn> Tables<'txn> {
+    fn open(txn: &'txn WriteTransaction) -> Result<Tables<'txn>, Error> {
+        Ok(Tables {
+            records: txn.open_table(RECORDS)?,
+            addresses: txn.open_table(ADDRESSES)?,
+            deleted: txn.open_table(DELETED)?,
+            authors: txn.open_multimap_table(AUTHORS)?,
+            kinds: txn.open_multimap_table(KINDS)?,
+            tags: txn.open_multimap_table(TAGS)?,
+            tag_values: txn.open_multimap_table(TAG_VALUES)?,
+            dirty: false,
+        })
+    }
+
+    fn table(&mut self, table: KvTable) -> TableMut<'_, 'txn> {
+        match table {
+            KvTable::Records => TableMut::Single(&mut self.records),
+            KvTable::Addresses => TableMut::Single(&mut self.addresses),
+            KvTable::Deleted => TableMut::Single(&mut self.deleted),
+            KvTable::Authors => TableMut::Multi(&mut self.authors),
+            KvTable::Kinds => TableMut::Multi(&mut self.kinds),
+            KvTable::Tags => TableMut::Multi(&mut self.tags),
+            KvTable::TagValues => TableMut::Multi(&mut self.tag_values),
+        }
+    }
+}
+
+impl KvRead for Tables<'_> {
+    fn get(
+        &self,
+        table: KvTable,
+        key: &[u8],
+        f: &mut dyn FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        match table {
+            KvTable::Records => get_in(&self.records, key, f),
+            KvTable::Addresses => get_in(&self.addresses, key, f),
+            KvTable::Deleted => get_in(&self.deleted, key, f),
+            KvTable::Authors => get_in_multi(&self.authors, key, f),
+            KvTable::Kinds => get_in_multi(&self.kinds, key, f),
+            KvTable::Tags => get_in_multi(&self.tags, key, f),
+            KvTable::TagValues => get_in_multi(&self.tag_values, key, f),
+        }
+    }
+
+    fn scan(
+        &self,
+        table: KvTable,
+        range: KeyRange<'_>,
+        reverse: bool,
+        f: &mut ScanFn<'_>,
+    ) -> Result<(), Error> {
+        match table {
+            KvTable::Records => scan_in(&self.records, range, reverse, f),
+            KvTable::Addresses => scan_in(&self.addresses, range, reverse, f),
+            KvTable::Deleted => scan_in(&self.deleted, range, reverse, f),
+            KvTable::Authors => scan_in_multi(&self.authors, range, reverse, f),
+            KvTable::Kinds => scan_in_multi(&self.kinds, range, reverse, f),
+            KvTable::Tags => scan_in_multi(&self.tags, range, reverse, f),
+            KvTable::TagValues => scan_in_multi(&self.tag_values, range, reverse, f),
+        }
+    }
+}
+
+impl KvWrite for Tables<'_> {
+    fn insert(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.dirty = true;
+        match self.table(table) {
+            TableMut::Single(t) => {
+                let _ = t.insert(key, value)?;
+            }
+            TableMut::Multi(t) => {
+                let _ = t.insert(key, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, table: KvTable, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.dirty = true;
+        match self.table(table) {
+            TableMut::Single(t) => {
+                let _ = t.remove(key)?;
+            }
+            TableMut::Multi(t) => {
+                let _ = t.remove(key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        BackupProgress, Cursor, Direction, Filter, Kind, MemoryStore, OwnedRecord, PutOutcome,
+        RecordFlags, RecordParts, RecordStore, SecretKey, Timestamp, WriteBatch, WriteResult,
+    };
+
+    #[test]
+    fn test_redb_store() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let make = |millis: u64| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::PROFILE,
+                    deterministic_key: Some(b"profile"),
+                    timestamp: Timestamp::from_millis(millis).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        let path = std::env::temp_dir().join(format!("mosaic-redb-{}", rand::random::<u64>()));
+        let mut store = RedbStore::open(&path).unwrap();
+
+        let profile1 = make(1000);
+        let profile2 = make(2000);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Duplicate);
+        assert_eq!(store.put(&profile2).unwrap(), PutOutcome::Stored);
+        assert_eq!(store.put(&profile1).unwrap(), PutOutcome::Superseded);
+        assert!(store.get_by_id(&profile1.id()).unwrap().is_none());
+
+        // Reopen and check persistence
+        drop(store);
+        let mut store = RedbStore::open(&path).unwrap();
+        assert_eq!(
+            store.get_by_address(&profile2.address()).unwrap(),
+            Some(profile2.clone())
+        );
+        let mut filter = Filter::new();
+        filter.kinds = vec![Kind::PROFILE];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        filter.until = Some(Timestamp::from_millis(2000).unwrap());
+        assert!(store.query(&filter).unwrap().is_empty());
+        filter.since = Some(Timestamp::from_millis(1000).unwrap());
+        filter.until = Some(Timestamp::from_millis(2001).unwrap());
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        let mut filter = Filter::new();
+        filter.addresses = vec![profile2.address()];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+        let mut filter = Filter::new();
+        filter.ids = vec![profile1.id(), profile2.id()];
+        assert_eq!(store.query(&filter).unwrap(), vec![profile2.clone()]);
+
+        assert!(store.delete(&profile2.id()).unwrap());
+        assert!(store.get_by_address(&profile2.address()).unwrap().is_none());
+        let snapshot = store.snapshot().unwrap();
+        assert!(snapshot.get_by_id(&profile2.id()).unwrap().is_none());
+        drop(snapshot);
+        assert_eq!(store.compact().unwrap(), 0);
+
+        let mut batch = WriteBatch::new();
+        batch.put(profile1.clone());
+        batch.delete(profile1.id());
+        assert_eq!(
+            store.write_batch(batch).unwrap(),
+            vec![
+                WriteResult::Put(PutOutcome::Stored),
+                WriteResult::Delete(true)
+            ]
+        );
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_redb_store_paging() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+
+        let path = std::env::temp_dir().join(format!("mosaic-redb-{}", rand::random::<u64>()));
+        let mut store = RedbStore::open(&path).unwrap();
+        let mut records: Vec<OwnedRecord> = vec![];
+        for i in 1..=5 {
+            let record = OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind: Kind::MICROBLOG_ROOT,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(i * 1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"hello",
+                },
+            )
+            .unwrap();
+            assert_eq!(store.put(&record).unwrap(), PutOutcome::Stored);
+            records.push(record);
+        }
+
+        // Through the records table, and through the kind index
+        let mut by_kind = Filter::new();
+        by_kind.kinds = vec![Kind::MICROBLOG_ROOT];
+        for mut filter in [Filter::new(), by_kind] {
+            filter.limit = Some(2);
+            filter.since = Some(Timestamp::from_millis(2000).unwrap());
+            for direction in [Direction::OldestFirst, Direction::NewestFirst] {
+                let mut cursor = Cursor::start(direction);
+                let mut seen: Vec<OwnedRecord> = vec![];
+                loop {
+                    let page = store.page(&filter, &cursor).unwrap();
+                    assert!(page.records.len() <= 2);
+                    seen.extend(page.records);
+                    match page.next {
+                        Some(next) => cursor = next,
+                        None => break,
+                    }
+                }
+                let mut expected = records[1..].to_vec();
+                if direction == Direction::NewestFirst {
+                    expected.reverse();
+                }
+                assert_eq!(seen, expected);
+            }
+        }
+
+        let mut backup: Vec<u8> = vec![];
+        let mut progress = BackupProgress::default();
+        store.backup(&mut backup, &mut progress).unwrap();
+        assert_eq!(progress.records, 5);
+        let mut restored = MemoryStore::new();
+        restored
+            .restore(&mut backup.as_slice(), &mut BackupProgress::default())
+            .unwrap();
+        assert_eq!(restored.len(), 5);
+
+        // Records are read lazily in batches
+        let mut batch = WriteBatch::new();
+        for i in 0..300 {
+            batch.put(
+                OwnedRecord::new(
+                    &secret_key,
+                    &RecordParts {
+                        kind: Kind::MICROBLOG_ROOT,
+                        deterministic_key: None,
+                        timestamp: Timestamp::from_millis(10_000 + i).unwrap(),
+                        flags: RecordFlags::empty(),
+                        app_flags: 0,
+                        tags_bytes: b"",
+                        payload: b"hello",
+                    },
+                )
+                .unwrap(),
+            );
+        }
+        let _ = store.write_batch(batch).unwrap();
+        assert_eq!(store.records().unwrap().count(), 305);
+        let snapshot = store.snapshot().unwrap();
+        let ids: Vec<_> = snapshot
+            .records()
+            .unwrap()
+            .map(|r| r.unwrap().id())
+            .collect();
+        assert_eq!(ids.len(), 305);
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        drop(snapshot);
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
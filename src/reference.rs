@@ -1,4 +1,4 @@
-use crate::{Address, Error, Id, InnerError};
+use crate::{zbase32, Address, Error, Id, InnerError};
 
 /// A Reference (either an Id or an Address)
 ///
@@ -30,7 +30,7 @@ impl Reference {
     /// Convert a `Reference` into the human printable `moref0` form.
     #[must_use]
     pub fn printable(&self) -> String {
-        format!("moref0{}", z32::encode(self.as_ref()))
+        format!("moref0{}", zbase32::encode(self.as_ref()))
     }
 
     /// Import a `Reference` from its printable form
@@ -42,7 +42,11 @@ impl Reference {
         if !s.starts_with("moref0") {
             return Err(InnerError::InvalidPrintable.into_err());
         }
-        let bytes = z32::decode(&s.as_bytes()[6..])?;
+        let bytes = zbase32::decode(&s.as_bytes()[6..])?;
+        // Reject strings that only decode because spare bits are ignored
+        if zbase32::encode(&bytes).as_bytes() != &s.as_bytes()[6..] {
+            return Err(InnerError::InvalidPrintable.into_err());
+        }
         let bytes: [u8; 48] = bytes
             .try_into()
             .map_err(|_| InnerError::ReferenceLength.into_err())?;
@@ -111,6 +115,18 @@ impl Reference {
     }
 }
 
+impl From<Id> for Reference {
+    fn from(id: Id) -> Reference {
+        Reference(*id.as_bytes())
+    }
+}
+
+impl From<Address> for Reference {
+    fn from(address: Address) -> Reference {
+        Reference(*address.as_bytes())
+    }
+}
+
 impl AsRef<[u8]> for Reference {
     fn as_ref(&self) -> &[u8] {
         self.0.as_ref()
@@ -123,6 +139,14 @@ impl std::fmt::Display for Reference {
     }
 }
 
+impl std::str::FromStr for Reference {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Reference, Error> {
+        Reference::from_printable(s)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -138,6 +162,7 @@ mod test {
         assert!(refer.as_address().is_none());
         let id = refer.into_id().unwrap();
         assert_eq!(format!("{id}"), printable);
+        assert_eq!(printable.parse::<Reference>().unwrap(), Reference::from(id));
 
         let printable =
             "moref01ge91q91o36bcfrk7qfhpnydyyobh88zknproi8j5791e5mekfez1ye6zrifbhh6m1dtizcsp4y5w";
@@ -0,0 +1,233 @@
+use crate::tag::iter_tags;
+use crate::{PublicKey, Record, TagType};
+#[cfg(feature = "uri")]
+use {
+    crate::{Error, ServerBootstrap, UserBootstrap},
+    std::collections::HashMap,
+    std::future::Future,
+};
+
+/// Looks up the bootstrap records published for a public key.
+///
+/// The methods return plain `Future`s and name no runtime types, so a
+/// resolver can be driven by tokio, async-std, smol or a hand-rolled
+/// executor. With the `dht` feature the Mainline DHT client `AsyncDht`
+/// is a `Resolver`.
+///
+/// This is only available with the `uri` feature, on which
+/// `ServerBootstrap` depends.
+#[cfg(feature = "uri")]
+pub trait Resolver {
+    /// Resolve the `ServerBootstrap` published for a server's `PublicKey`
+    fn resolve_server(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<ServerBootstrap>, Error>> + Send;
+
+    /// Resolve the `UserBootstrap` published for a user's `PublicKey`
+    fn resolve_user(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<UserBootstrap>, Error>> + Send;
+}
+
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+impl Resolver for mainline::async_dht::AsyncDht {
+    fn resolve_server(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<ServerBootstrap>, Error>> + Send {
+        ServerBootstrap::read_from_dht(public_key, self)
+    }
+
+    fn resolve_user(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<UserBootstrap>, Error>> + Send {
+        UserBootstrap::read_from_dht(public_key, self)
+    }
+}
+
+/// A `Resolver` over bootstrap records held in memory, for tests,
+/// simulations and clients that cache bootstraps themselves
+#[cfg(feature = "uri")]
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    servers: HashMap<PublicKey, ServerBootstrap>,
+    users: HashMap<PublicKey, UserBootstrap>,
+}
+
+#[cfg(feature = "uri")]
+impl MemoryResolver {
+    /// Create an empty `MemoryResolver`
+    #[must_use]
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    /// Set the `ServerBootstrap` for a server
+    pub fn insert_server(&mut self, public_key: PublicKey, bootstrap: ServerBootstrap) {
+        let _ = self.servers.insert(public_key, bootstrap);
+    }
+
+    /// Set the `UserBootstrap` for a user
+    pub fn insert_user(&mut self, public_key: PublicKey, bootstrap: UserBootstrap) {
+        let _ = self.users.insert(public_key, bootstrap);
+    }
+}
+
+#[cfg(feature = "uri")]
+impl Resolver for MemoryResolver {
+    fn resolve_server(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<ServerBootstrap>, Error>> + Send {
+        std::future::ready(Ok(self.servers.get(&public_key).cloned()))
+    }
+
+    fn resolve_user(
+        &self,
+        public_key: PublicKey,
+    ) -> impl Future<Output = Result<Option<UserBootstrap>, Error>> + Send {
+        std::future::ready(Ok(self.users.get(&public_key).cloned()))
+    }
+}
+
+/// The servers a record's `SERVER_HINT` tags say it, and the records it
+/// references, can also be found on
+#[must_use]
+pub fn server_hints(record: &Record) -> Vec<PublicKey> {
+    iter_tags(record.tags_bytes())
+        .filter(|t| t.get_type() == TagType::SERVER_HINT)
+        .filter_map(|t| t.get_server_hint().ok().flatten())
+        .collect()
+}
+
+/// The servers a record's `AUTHOR_SERVER_HINT` tags say records by
+/// `author` can be found on
+#[must_use]
+pub fn author_server_hints(record: &Record, author: &PublicKey) -> Vec<PublicKey> {
+    iter_tags(record.tags_bytes())
+        .filter(|t| t.get_type() == TagType::AUTHOR_SERVER_HINT)
+        .filter(|t| t.get_public_key().ok().flatten().as_ref() == Some(author))
+        .filter_map(|t| t.get_server_hint().ok().flatten())
+        .collect()
+}
+
+/// The servers to look for a user's records on: the servers in their
+/// `UserBootstrap`, followed by any hinted servers not already listed.
+///
+/// Hints, as gathered with `server_hints()` and `author_server_hints()`,
+/// are the fallback when the user has no bootstrap or it cannot be
+/// resolved.
+///
+/// # Errors
+///
+/// Returns an `Err` if resolving fails and there are no hints to fall
+/// back on
+#[cfg(feature = "uri")]
+pub async fn resolve_user_servers<R: Resolver + ?Sized>(
+    resolver: &R,
+    user: PublicKey,
+    hints: &[PublicKey],
+) -> Result<Vec<PublicKey>, Error> {
+    let mut servers: Vec<PublicKey> = match resolver.resolve_user(user).await {
+        Ok(bootstrap) => bootstrap
+            .map(|b| b.inner().iter().map(|(_, key)| *key).collect())
+            .unwrap_or_default(),
+        Err(e) if hints.is_empty() => return Err(e),
+        Err(_) => vec![],
+    };
+    for hint in hints {
+        if !servers.contains(hint) {
+            servers.push(*hint);
+        }
+    }
+    Ok(servers)
+}
+
+#[cfg(all(test, feature = "uri"))]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedRecord, OwnedTag, RecordFlags, RecordParts, SecretKey, Timestamp};
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    // Drive a future that never waits, without any runtime
+    fn now_or_never<F: Future>(future: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future was not ready"),
+        }
+    }
+
+    #[test]
+    fn test_memory_resolver() {
+        use rand::rngs::OsRng;
+
+        let server_key = SecretKey::generate(&mut OsRng).public();
+        let user_key = SecretKey::generate(&mut OsRng).public();
+        let server = ServerBootstrap::from_dht_string_and_seq("S\nwss://test.example", 1).unwrap();
+
+        let mut resolver = MemoryResolver::new();
+        resolver.insert_server(server_key, server.clone());
+
+        let found = now_or_never(resolver.resolve_server(server_key)).unwrap();
+        assert_eq!(found, Some(server));
+        assert!(now_or_never(resolver.resolve_server(user_key))
+            .unwrap()
+            .is_none());
+        assert!(now_or_never(resolver.resolve_user(user_key))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_resolve_user_servers() {
+        use rand::rngs::OsRng;
+
+        let author = SecretKey::generate(&mut OsRng);
+        let other = SecretKey::generate(&mut OsRng).public();
+        let [home, mirror, elsewhere] =
+            std::array::from_fn(|_| SecretKey::generate(&mut OsRng).public());
+
+        let mut tags = OwnedTag::new_server_hint(&mirror).as_bytes().to_vec();
+        tags.extend_from_slice(
+            OwnedTag::new_author_server_hint(&author.public(), &home).as_bytes(),
+        );
+        tags.extend_from_slice(OwnedTag::new_author_server_hint(&other, &elsewhere).as_bytes());
+        let record = OwnedRecord::new(
+            &author,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: &tags,
+                payload: b"",
+            },
+        )
+        .unwrap();
+        assert_eq!(server_hints(&record), vec![mirror]);
+        assert_eq!(author_server_hints(&record, &author.public()), vec![home]);
+
+        let mut hints = author_server_hints(&record, &author.public());
+        hints.extend(server_hints(&record));
+
+        // With no bootstrap, the hints are used
+        let mut resolver = MemoryResolver::new();
+        let servers =
+            now_or_never(resolve_user_servers(&resolver, author.public(), &hints)).unwrap();
+        assert_eq!(servers, vec![home, mirror]);
+
+        // Bootstrap servers come first, hints are not repeated
+        let mut bootstrap = UserBootstrap::new();
+        bootstrap.append_server(crate::user_bootstrap::ServerUsage::OUTBOX, mirror);
+        resolver.insert_user(author.public(), bootstrap);
+        let servers =
+            now_or_never(resolve_user_servers(&resolver, author.public(), &hints)).unwrap();
+        assert_eq!(servers, vec![mirror, home]);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{Error, Id, Kind, Record, RecordFlags, RecordStore, ServerPolicy, Timestamp};
+use std::collections::HashMap;
+
+/// How long records of one kind are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KindRetention {
+    /// Records this many milliseconds old or older are expired
+    pub max_age_ms: Option<u64>,
+
+    /// Only the newest this many records are kept
+    pub max_count: Option<usize>,
+}
+
+/// A `RetentionPlan` decides which stored records have expired.
+///
+/// Each kind may have a maximum age and a maximum count. Kinds without an
+/// entry use the `default` retention, which keeps everything unless set.
+///
+/// Stores refuse records flagged `EPHEMERAL`, but a store filled by other
+/// means may still hold some. They are expired once they are
+/// `ephemeral_max_age_ms` old, regardless of their kind. The default of
+/// zero expires them as soon as they are seen by the garbage collector.
+///
+/// The ages are measured against the `now` passed in, so a plan applied to
+/// the same records at the same time yields the same result on every
+/// `RecordStore` implementation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPlan {
+    /// Retention by kind
+    pub kinds: HashMap<Kind, KindRetention>,
+
+    /// Retention for kinds not listed in `kinds`
+    pub default: KindRetention,
+
+    /// Ephemeral records this many milliseconds old or older are expired
+    pub ephemeral_max_age_ms: u64,
+}
+
+impl RetentionPlan {
+    /// Create a `RetentionPlan` which keeps every non-ephemeral record
+    #[must_use]
+    pub fn new() -> RetentionPlan {
+        RetentionPlan::default()
+    }
+
+    /// The retention for a kind
+    #[must_use]
+    pub fn retention_for(&self, kind: Kind) -> KindRetention {
+        self.kinds.get(&kind).copied().unwrap_or(self.default)
+    }
+
+    /// Is the record too old to keep at time `now`?
+    ///
+    /// This only considers age; counts are considered by `expired()`.
+    #[must_use]
+    pub fn is_too_old(&self, record: &Record, now: Timestamp) -> bool {
+        let age = now
+            .as_millis()
+            .saturating_sub(record.timestamp().as_millis());
+        if record.flags().contains(RecordFlags::EPHEMERAL) && age >= self.ephemeral_max_age_ms {
+            return true;
+        }
+        match self.retention_for(record.kind()).max_age_ms {
+            Some(max) => age >= max,
+            None => false,
+        }
+    }
+
+    /// Find the records in the store that have expired at time `now`,
+    /// returned in batches of at most `batch_size` ids, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    pub fn expired<S: RecordStore + ?Sized>(
+        &self,
+        store: &S,
+        now: Timestamp,
+        batch_size: usize,
+    ) -> Result<ExpiredBatches, Error> {
+        let mut expired: Vec<Id> = vec![];
+        let mut by_kind: HashMap<Kind, Vec<Id>> = HashMap::new();
+        for record in store.records()? {
+            let record = record?;
+            if self.is_too_old(&record, now) {
+                expired.push(record.id());
+            } else if self.retention_for(record.kind()).max_count.is_some() {
+                by_kind.entry(record.kind()).or_default().push(record.id());
+            }
+        }
+
+        for (kind, mut ids) in by_kind {
+            if let Some(max) = self.retention_for(kind).max_count {
+                if ids.len() > max {
+                    ids.sort_by_key(|id| std::cmp::Reverse(*id));
+                    expired.extend_from_slice(&ids[max..]);
+                }
+            }
+        }
+
+        expired.sort();
+        Ok(ExpiredBatches {
+            ids: expired.into_iter(),
+            batch_size: batch_size.max(1),
+        })
+    }
+
+    /// Delete every expired record from the store. Returns the number of
+    /// records deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails. Records deleted before the
+    /// failure remain deleted.
+    pub fn collect_garbage<S: RecordStore + ?Sized>(
+        &self,
+        store: &mut S,
+        now: Timestamp,
+        batch_size: usize,
+    ) -> Result<usize, Error> {
+        let mut count: usize = 0;
+        for batch in self.expired(&*store, now, batch_size)? {
+            for id in &batch {
+                if store.delete(id)? {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl From<&ServerPolicy> for RetentionPlan {
+    fn from(policy: &ServerPolicy) -> RetentionPlan {
+        RetentionPlan {
+            kinds: policy
+                .retention
+                .iter()
+                .map(|(kind, ms)| {
+                    (
+                        *kind,
+                        KindRetention {
+                            max_age_ms: Some(*ms),
+                            max_count: None,
+                        },
+                    )
+                })
+                .collect(),
+            ..RetentionPlan::default()
+        }
+    }
+}
+
+/// Batches of expired record ids, from `RetentionPlan::expired()`
+#[derive(Debug)]
+pub struct ExpiredBatches {
+    ids: std::vec::IntoIter<Id>,
+    batch_size: usize,
+}
+
+impl Iterator for ExpiredBatches {
+    type Item = Vec<Id>;
+
+    fn next(&mut self) -> Option<Vec<Id>> {
+        let batch: Vec<Id> = self.ids.by_ref().take(self.batch_size).collect();
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MemoryStore, OwnedRecord, PutOutcome, RecordParts, SecretKey};
+
+    #[test]
+    fn test_retention_plan() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let make = |kind: Kind, ms: u64, flags: RecordFlags| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(ms).unwrap(),
+                    flags,
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload: b"",
+                },
+            )
+            .unwrap()
+        };
+
+        let mut store = MemoryStore::new();
+        for ms in [1000, 2000, 3000, 4000] {
+            let _ = store
+                .put(&make(Kind::CHAT_MESSAGE, ms, RecordFlags::empty()))
+                .unwrap();
+            let _ = store
+                .put(&make(Kind::BLOG_POST, ms, RecordFlags::empty()))
+                .unwrap();
+        }
+        assert_eq!(
+            store
+                .put(&make(Kind::MICROBLOG_ROOT, 4500, RecordFlags::EPHEMERAL))
+                .unwrap(),
+            PutOutcome::Ephemeral
+        );
+
+        let mut plan = RetentionPlan::new();
+        let _ = plan.kinds.insert(
+            Kind::CHAT_MESSAGE,
+            KindRetention {
+                max_age_ms: Some(2500),
+                max_count: None,
+            },
+        );
+        let _ = plan.kinds.insert(
+            Kind::BLOG_POST,
+            KindRetention {
+                max_age_ms: None,
+                max_count: Some(3),
+            },
+        );
+
+        let now = Timestamp::from_millis(5000).unwrap();
+        let batches: Vec<Vec<Id>> = plan.expired(&store, now, 2).unwrap().collect();
+        // chat at 1000 and 2000, and the oldest blog post
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), 2);
+        assert!(batches[0][0] < batches[0][1]);
+
+        assert_eq!(plan.collect_garbage(&mut store, now, 10).unwrap(), 3);
+        assert_eq!(store.len(), 5);
+        assert_eq!(plan.collect_garbage(&mut store, now, 10).unwrap(), 0);
+    }
+}
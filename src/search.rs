@@ -0,0 +1,59 @@
+/// A `Tokenizer` splits text into normalized search tokens.
+///
+/// Servers that index payload text should use the same `Tokenizer` for
+/// indexing payloads and for splitting the search term of a `Filter`,
+/// otherwise terms will not match.
+pub trait Tokenizer {
+    /// Split text into normalized tokens
+    fn tokenize(&self, text: &str) -> Vec<String>;
+}
+
+/// The default `Tokenizer`.
+///
+/// Text is split on any character that is not alphanumeric (in the Unicode
+/// sense, so non-Latin scripts work) and each token is lowercased.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnicodeTokenizer;
+
+impl Tokenizer for UnicodeTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+}
+
+/// Returns true if every token of `term` appears among the tokens of `text`.
+///
+/// An empty search term matches everything.
+pub(crate) fn text_matches<T: Tokenizer + ?Sized>(tokenizer: &T, term: &str, text: &str) -> bool {
+    let text_tokens = tokenizer.tokenize(text);
+    tokenizer
+        .tokenize(term)
+        .iter()
+        .all(|t| text_tokens.contains(t))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unicode_tokenizer() {
+        let tokens = UnicodeTokenizer.tokenize("Hello, World! Ünïcödé--ΑΒΓ 42");
+        assert_eq!(tokens, vec!["hello", "world", "ünïcödé", "αβγ", "42"]);
+
+        assert!(text_matches(
+            &UnicodeTokenizer,
+            "world HELLO",
+            "Hello, World!"
+        ));
+        assert!(!text_matches(
+            &UnicodeTokenizer,
+            "hello there",
+            "Hello, World!"
+        ));
+        assert!(text_matches(&UnicodeTokenizer, "", "Hello, World!"));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{Error, InnerError, SecretKey};
+use ed25519_dalek::Sha512;
+use hmac::{Hmac, Mac};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+type HmacSha512 = Hmac<Sha512>;
+
+const MASTER_HMAC_KEY: &[u8] = b"ed25519 seed";
+const HARDENED: u32 = 0x8000_0000;
+
+/// Derivation path index of a user's master key
+pub const MASTER_KEY_INDEX: u32 = 0;
+
+/// Derivation path index under which device subkeys are derived
+pub const DEVICE_KEY_INDEX: u32 = 1;
+
+/// Derivation path index under which throwaway identities are derived
+pub const THROWAWAY_KEY_INDEX: u32 = 2;
+
+/// A seed from which any number of `SecretKey`s are derived
+/// deterministically, so that a single backup restores them all.
+///
+/// Derivation is SLIP-0010 for ed25519 (HMAC-SHA512), so the same seed and
+/// path give the same key in any SLIP-0010 wallet. As SLIP-0010 requires
+/// for ed25519, every index is hardened: an index `i` is derived as
+/// `i + 2^31`, written `i'`, and indices that already have the top bit set
+/// are taken as hardened. The master key is at `m/0'`, device subkeys at
+/// `m/1'/i'` and throwaway identities at `m/2'/i'`.
+///
+/// Seeds are 16 to 64 bytes, as in BIP-32. The seed bytes are zeroed on
+/// drop.
+#[derive(Clone)]
+pub struct Seed(Vec<u8>);
+
+// A node in the derivation tree: a key and a chain code
+struct Node([u8; 64]);
+
+impl Node {
+    fn from_hmac(key: &[u8], parts: &[&[u8]]) -> Node {
+        let mut mac = HmacSha512::new_from_slice(key).expect("HMAC takes keys of any length");
+        for part in parts {
+            mac.update(part);
+        }
+        Node(mac.finalize().into_bytes().into())
+    }
+
+    fn child(&self, index: u32) -> Node {
+        let index = (index | HARDENED).to_be_bytes();
+        Node::from_hmac(&self.0[32..], &[&[0], &self.0[..32], index.as_slice()])
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Seed {
+    /// Generate a new random 32-byte `Seed`
+    pub fn generate<R: rand_core::CryptoRngCore + ?Sized>(csprng: &mut R) -> Seed {
+        let mut bytes = vec![0; 32];
+        csprng.fill_bytes(&mut bytes);
+        Seed(bytes)
+    }
+
+    /// Create a `Seed` from bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if there are fewer than 16 or more than 64 bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Seed, Error> {
+        if !(16..=64).contains(&bytes.len()) {
+            return Err(InnerError::SeedLength.into());
+        }
+        Ok(Seed(bytes.to_vec()))
+    }
+
+    /// View the seed bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn master_node(&self) -> Node {
+        Node::from_hmac(MASTER_HMAC_KEY, &[&self.0])
+    }
+
+    /// Derive the `SecretKey` at a derivation path
+    #[must_use]
+    pub fn derive(&self, path: &[u32]) -> SecretKey {
+        let mut node = self.master_node();
+        for index in path {
+            node = node.child(*index);
+        }
+        let mut key = Zeroizing::new([0; 32]);
+        key.copy_from_slice(&node.0[..32]);
+        SecretKey::from_bytes(&key)
+    }
+
+    /// Derive the user's master `SecretKey`
+    #[must_use]
+    pub fn master_key(&self) -> SecretKey {
+        self.derive(&[MASTER_KEY_INDEX])
+    }
+
+    /// Derive the `SecretKey` for a device subkey
+    #[must_use]
+    pub fn device_key(&self, index: u32) -> SecretKey {
+        self.derive(&[DEVICE_KEY_INDEX, index])
+    }
+
+    /// Derive the `SecretKey` for a throwaway identity
+    #[must_use]
+    pub fn throwaway_key(&self, index: u32) -> SecretKey {
+        self.derive(&[THROWAWAY_KEY_INDEX, index])
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Seed {}
+
+impl std::fmt::Debug for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Seed").field(&"<redacted>").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seed_derivation() {
+        let seed = Seed::from_bytes(&[7; 32]).unwrap();
+
+        // Derivation is deterministic
+        let master = seed.master_key();
+        assert_eq!(
+            master.as_bytes(),
+            seed.derive(&[MASTER_KEY_INDEX]).as_bytes()
+        );
+        assert_eq!(
+            seed.device_key(3).as_bytes(),
+            Seed::from_bytes(&[7; 32]).unwrap().device_key(3).as_bytes()
+        );
+
+        // Every path yields a different key, and indices are hardened
+        let keys = [
+            master.public(),
+            seed.device_key(0).public(),
+            seed.device_key(1).public(),
+            seed.throwaway_key(0).public(),
+            seed.derive(&[]).public(),
+            Seed::from_bytes(&[8; 32]).unwrap().master_key().public(),
+        ];
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(seed.derive(&[HARDENED]).public(), master.public());
+
+        assert!(Seed::from_bytes(&[7; 15]).is_err());
+        assert!(Seed::from_bytes(&[7; 65]).is_err());
+
+        // Pinned, so the derivation cannot change unnoticed
+        assert_eq!(master.public().printable(), MASTER_PUBLIC);
+    }
+
+    #[test]
+    fn test_slip10_vectors() {
+        use std::fmt::Write;
+
+        let hex = |bytes: &[u8]| {
+            bytes.iter().fold(String::new(), |mut s, b| {
+                let _ = write!(s, "{b:02x}");
+                s
+            })
+        };
+
+        // SLIP-0010 test vector 1 for ed25519
+        let seed_bytes: Vec<u8> = (0..16).collect();
+        let seed = Seed::from_bytes(&seed_bytes).unwrap();
+        for (path, secret, public) in SLIP10_VECTOR_1 {
+            let key = seed.derive(path);
+            assert_eq!(hex(key.as_bytes()), secret);
+            assert_eq!(hex(key.public().as_bytes()), public);
+        }
+    }
+
+    const MASTER_PUBLIC: &str = "mopub0pcgcsbcegsjbffgcghagiwc6mamaazbrjndqgx6kdy1cm9gbr11o";
+
+    // (path, private key, public key without the leading 00)
+    const SLIP10_VECTOR_1: [(&[u32], &str, &str); 3] = [
+        (
+            &[],
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+        ),
+        (
+            &[0],
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            "8c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+        ),
+        (
+            &[0, 1],
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            "1932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+        ),
+    ];
+}
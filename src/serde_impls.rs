@@ -0,0 +1,198 @@
+#[cfg(feature = "uri")]
+use crate::ServerBootstrap;
+use crate::{
+    zbase32, Address, Id, OwnedRecord, PublicKey, Record, RecordFlags, Timestamp, UserBootstrap,
+};
+use serde::de::{Error as DeError, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Human-readable formats (JSON, TOML, ...) get text encodings: records as
+// z-base-32 (as in JSONL archives) and keys, ids and addresses in their
+// printable form. Binary formats get compact bytes. Bootstraps are their
+// DHT string with the sequence number, in either kind of format.
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bytes")
+    }
+
+    fn visit_bytes<E: DeError>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: DeError>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut output: Vec<u8> = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            output.push(byte);
+        }
+        Ok(output)
+    }
+}
+
+fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    deserializer.deserialize_byte_buf(BytesVisitor)
+}
+
+// Records can only be deserialized as an `OwnedRecord`
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&zbase32::encode(self.as_bytes()))
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+impl Serialize for OwnedRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OwnedRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OwnedRecord, D::Error> {
+        let bytes = if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            zbase32::decode(s.as_bytes()).map_err(D::Error::custom)?
+        } else {
+            deserialize_bytes(deserializer)?
+        };
+        OwnedRecord::from_vec(bytes).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.printable())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PublicKey, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            PublicKey::from_printable(&s).map_err(D::Error::custom)
+        } else {
+            let bytes: [u8; 32] = deserialize_bytes(deserializer)?
+                .try_into()
+                .map_err(|_| D::Error::custom("public key must be 32 bytes"))?;
+            PublicKey::from_bytes(&bytes).map_err(D::Error::custom)
+        }
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_millis())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        let millis = u64::deserialize(deserializer)?;
+        Timestamp::from_millis(millis).ok_or_else(|| D::Error::custom("timestamp out of range"))
+    }
+}
+
+impl Serialize for RecordFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordFlags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RecordFlags, D::Error> {
+        Ok(RecordFlags::from_bits_retain(u16::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.printable())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Id, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            Id::from_printable(&s).map_err(D::Error::custom)
+        } else {
+            let bytes: [u8; 48] = deserialize_bytes(deserializer)?
+                .try_into()
+                .map_err(|_| D::Error::custom("id must be 48 bytes"))?;
+            Id::from_bytes(&bytes).map_err(D::Error::custom)
+        }
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.printable())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            Address::from_printable(&s).map_err(D::Error::custom)
+        } else {
+            let bytes: [u8; 48] = deserialize_bytes(deserializer)?
+                .try_into()
+                .map_err(|_| D::Error::custom("address must be 48 bytes"))?;
+            Address::from_bytes(&bytes).map_err(D::Error::custom)
+        }
+    }
+}
+
+#[cfg(feature = "uri")]
+impl Serialize for ServerBootstrap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.to_dht_string(), self.seq()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "uri")]
+impl<'de> Deserialize<'de> for ServerBootstrap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ServerBootstrap, D::Error> {
+        let (s, seq) = <(String, i64)>::deserialize(deserializer)?;
+        ServerBootstrap::from_dht_string_and_seq(&s, seq).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for UserBootstrap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.to_dht_string(), self.seq()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for UserBootstrap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<UserBootstrap, D::Error> {
+        let (s, seq) = <(String, i64)>::deserialize(deserializer)?;
+        UserBootstrap::from_dht_string_and_seq(&s, seq).map_err(D::Error::custom)
+    }
+}
@@ -1,11 +1,22 @@
-use crate::{Error, InnerError, PublicKey, SecretKey};
+use crate::{Error, InnerError};
 use http::Uri;
-use mainline::async_dht::AsyncDht;
-use mainline::{Id, MutableItem};
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+use {
+    crate::{PublicKey, SecretKey},
+    mainline::{async_dht::AsyncDht, Id, MutableItem},
+};
 
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
 pub(crate) const DHT_SERVER_SALT: &[u8] = b"msb24";
 
 /// Bootstrap record for a server
+///
+/// With the `dht` feature, a server publishes its `ServerBootstrap` to
+/// Mainline DHT as a mutable item signed by its key with
+/// `write_to_dht()`, and clients fetch it with `read_from_dht()`. The DHT
+/// client checks the signature of every item it retrieves against the
+/// server's public key, so a fetched bootstrap is known to come from the
+/// server.
 #[derive(Debug, Clone)]
 pub struct ServerBootstrap(Vec<Uri>, i64);
 
@@ -112,7 +123,10 @@ impl ServerBootstrap {
 
         Ok(ServerBootstrap(output, seq))
     }
+}
 
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+impl ServerBootstrap {
     /// Try to read a `ServerBootstrap` record for the given `PublicKey`
     /// using the supplied `Dht` state object
     ///
@@ -120,6 +134,10 @@ impl ServerBootstrap {
     ///
     /// Returns an error if the Dht was shutdown. If bad data is returned from the Dht, that is
     /// ignored; if all Dht nodes return bad data you will get `None`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(pubkey = %pubkey), err)
+    )]
     pub async fn read_from_dht(
         pubkey: PublicKey,
         dht: &AsyncDht,
@@ -144,6 +162,15 @@ impl ServerBootstrap {
     /// # Errors
     ///
     /// Returns an `Err` if it couldn't write to the Dht
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(pubkey = %secret_key.public(), seq = self.1),
+            err
+        )
+    )]
     pub async fn write_to_dht(
         &mut self,
         secret_key: SecretKey,
@@ -195,6 +222,7 @@ mod test {
         assert_eq!(s, &s2);
     }
 
+    #[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
     #[tokio::test]
     async fn test_server_bootstrap_dht() {
         use crate::SecretKey;
@@ -0,0 +1,242 @@
+use crate::{Error, InnerError, Kind, Policy, Record, RejectReason, MAX_RECORD_LEN};
+use std::collections::HashMap;
+
+/// Operator-configured limits on the records a server accepts and keeps.
+///
+/// A `ServerPolicy` is a `Policy` and so can be added to an
+/// `AcceptancePipeline`. It can be written to and read from a simple
+/// configuration string of `key = value` lines:
+///
+/// ```text
+/// max_record_bytes = 65536
+/// allowed_kinds = 1, 2, 3
+/// blocked_kinds = 6
+/// required_pow = 8
+/// max_tags = 32
+/// retention = 3:86400000, 6:3600000
+/// ```
+///
+/// Retention is a list of `kind:milliseconds` pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerPolicy {
+    /// Maximum record length in bytes
+    pub max_record_bytes: usize,
+
+    /// If non-empty, only these kinds are accepted
+    pub allowed_kinds: Vec<Kind>,
+
+    /// These kinds are never accepted
+    pub blocked_kinds: Vec<Kind>,
+
+    /// Minimum proof-of-work difficulty (see `Id::pow_difficulty()`)
+    pub required_pow: u32,
+
+    /// Maximum number of tags
+    pub max_tags: usize,
+
+    /// How long (in milliseconds) records of each kind are kept. Kinds not
+    /// listed are kept forever.
+    pub retention: HashMap<Kind, u64>,
+}
+
+impl Default for ServerPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerPolicy {
+    /// Create a permissive `ServerPolicy`
+    #[must_use]
+    pub fn new() -> ServerPolicy {
+        ServerPolicy {
+            max_record_bytes: MAX_RECORD_LEN,
+            allowed_kinds: vec![],
+            blocked_kinds: vec![],
+            required_pow: 0,
+            max_tags: usize::MAX,
+            retention: HashMap::new(),
+        }
+    }
+
+    /// Evaluate a record against this policy
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the record is rejected
+    pub fn evaluate(&self, record: &Record) -> Result<(), RejectReason> {
+        let len = record.as_bytes().len();
+        if len > self.max_record_bytes {
+            return Err(RejectReason::TooLarge {
+                len,
+                max: self.max_record_bytes,
+            });
+        }
+
+        let kind = record.kind();
+        if self.blocked_kinds.contains(&kind)
+            || (!self.allowed_kinds.is_empty() && !self.allowed_kinds.contains(&kind))
+        {
+            return Err(RejectReason::KindNotAllowed(kind));
+        }
+
+        let pow = record.id().pow_difficulty();
+        if pow < self.required_pow {
+            return Err(RejectReason::InsufficientPow {
+                required: self.required_pow,
+                actual: pow,
+            });
+        }
+
+        if crate::tag::count_tags(record.tags_bytes()) > self.max_tags {
+            return Err(RejectReason::Policy("too many tags".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// How long records of this kind are kept, in milliseconds, if limited
+    #[must_use]
+    pub fn retention_for(&self, kind: Kind) -> Option<u64> {
+        self.retention.get(&kind).copied()
+    }
+
+    /// Encode a `ServerPolicy` into a configuration string
+    #[must_use]
+    pub fn to_config_string(&self) -> String {
+        use std::fmt::Write;
+
+        fn kinds(v: &[Kind]) -> String {
+            v.iter()
+                .map(|k| k.0.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        }
+
+        let mut retention: Vec<(&Kind, &u64)> = self.retention.iter().collect();
+        retention.sort_by_key(|(k, _)| k.0);
+        let retention = retention
+            .iter()
+            .map(|(k, ms)| format!("{}:{ms}", k.0))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let mut output = String::new();
+        let _ = writeln!(output, "max_record_bytes = {}", self.max_record_bytes);
+        let _ = writeln!(output, "allowed_kinds = {}", kinds(&self.allowed_kinds));
+        let _ = writeln!(output, "blocked_kinds = {}", kinds(&self.blocked_kinds));
+        let _ = writeln!(output, "required_pow = {}", self.required_pow);
+        let _ = writeln!(output, "max_tags = {}", self.max_tags);
+        let _ = writeln!(output, "retention = {retention}");
+        output
+    }
+
+    /// Translate a configuration string into a `ServerPolicy`. Keys that
+    /// are not present keep their default values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if a line is malformed or a key is unknown
+    pub fn from_config_string(s: &str) -> Result<ServerPolicy, Error> {
+        fn bad() -> Error {
+            InnerError::InvalidServerPolicyString.into_err()
+        }
+
+        fn kinds(v: &str) -> Result<Vec<Kind>, Error> {
+            v.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<u16>().map(Kind).map_err(|_| bad()))
+                .collect()
+        }
+
+        let mut policy = ServerPolicy::new();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(bad)?;
+            let value = value.trim();
+            match key.trim() {
+                "max_record_bytes" => policy.max_record_bytes = value.parse().map_err(|_| bad())?,
+                "allowed_kinds" => policy.allowed_kinds = kinds(value)?,
+                "blocked_kinds" => policy.blocked_kinds = kinds(value)?,
+                "required_pow" => policy.required_pow = value.parse().map_err(|_| bad())?,
+                "max_tags" => policy.max_tags = value.parse().map_err(|_| bad())?,
+                "retention" => {
+                    policy.retention.clear();
+                    for pair in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                        let (kind, ms) = pair.split_once(':').ok_or_else(bad)?;
+                        let kind = Kind(kind.trim().parse().map_err(|_| bad())?);
+                        let ms = ms.trim().parse().map_err(|_| bad())?;
+                        let _ = policy.retention.insert(kind, ms);
+                    }
+                }
+                _ => return Err(bad()),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl Policy for ServerPolicy {
+    fn check(&self, record: &Record) -> Result<(), RejectReason> {
+        self.evaluate(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedRecord, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_server_policy() {
+        use rand::rngs::OsRng;
+
+        let mut policy = ServerPolicy::new();
+        policy.max_record_bytes = 65536;
+        policy.allowed_kinds = vec![Kind::MICROBLOG_ROOT, Kind::CHAT_MESSAGE];
+        policy.blocked_kinds = vec![Kind::CHAT_MESSAGE];
+        policy.max_tags = 0;
+        let _ = policy.retention.insert(Kind::CHAT_MESSAGE, 3_600_000);
+
+        let s = policy.to_config_string();
+        let policy2 = ServerPolicy::from_config_string(&s).unwrap();
+        assert_eq!(policy, policy2);
+        assert!(ServerPolicy::from_config_string("bogus = 1").is_err());
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let make = |kind: Kind, tags: &[u8]| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: tags,
+                    payload: b"hello",
+                },
+            )
+            .unwrap()
+        };
+
+        assert!(policy.evaluate(&make(Kind::MICROBLOG_ROOT, b"")).is_ok());
+        assert_eq!(
+            policy.evaluate(&make(Kind::CHAT_MESSAGE, b"")),
+            Err(RejectReason::KindNotAllowed(Kind::CHAT_MESSAGE))
+        );
+        assert_eq!(
+            policy.evaluate(&make(Kind::PROFILE, b"")),
+            Err(RejectReason::KindNotAllowed(Kind::PROFILE))
+        );
+        let tag = crate::OwnedTag::new_nostr_sister(&[0; 32]);
+        assert!(policy
+            .evaluate(&make(Kind::MICROBLOG_ROOT, tag.as_bytes()))
+            .is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{Address, Id};
+
+/// Which of `n` shards owns the record with this `Id`.
+///
+/// The mapping is stable across versions and platforms so that every node
+/// of a deployment agrees on it: the 48 bytes of the `Id` are hashed with
+/// BLAKE3, the first 8 bytes of the hash are read as a big-endian `u64`,
+/// and the result is that value modulo `n`.
+///
+/// Hashing spreads records evenly even though ids begin with a timestamp.
+/// An `n` of zero is treated as one.
+#[must_use]
+pub fn shard_for(id: &Id, n: u32) -> u32 {
+    shard_for_bytes(id.as_bytes(), n)
+}
+
+/// Which of `n` shards owns the records at this `Address`.
+///
+/// The mapping is the same as `shard_for()` but over the 48 bytes of the
+/// `Address`, so every version of an addressable record lands on the same
+/// shard. An `n` of zero is treated as one.
+#[must_use]
+pub fn shard_for_address(address: &Address, n: u32) -> u32 {
+    shard_for_bytes(address.as_bytes(), n)
+}
+
+fn shard_for_bytes(bytes: &[u8; 48], n: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let hash = blake3::hash(bytes);
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&hash.as_bytes()[0..8]);
+    #[allow(clippy::cast_possible_truncation)]
+    let shard = (u64::from_be_bytes(prefix) % u64::from(n)) as u32;
+    shard
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shard_for() {
+        let id = Id::from_bytes(&[
+            0, 1, 150, 132, 236, 230, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+            17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38,
+            39, 40,
+        ])
+        .unwrap();
+        assert_eq!(shard_for(&id, 0), 0);
+        assert_eq!(shard_for(&id, 1), 0);
+        // These values must never change
+        assert_eq!(shard_for(&id, 16), 1);
+        assert_eq!(shard_for(&id, 1000), 385);
+
+        let mut counts = [0_u32; 4];
+        for i in 0..400_u32 {
+            let mut bytes = *id.as_bytes();
+            bytes[8..12].copy_from_slice(&i.to_be_bytes());
+            let id = Id::from_bytes(&bytes).unwrap();
+            counts[shard_for(&id, 4) as usize] += 1;
+        }
+        assert!(counts.iter().all(|c| *c > 50));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{Error, PublicKey, SecretKey};
+use std::future::Future;
+
+/// Signs records without needing the secret key to be in this process.
+///
+/// Records are signed with Ed25519ph under the context `b"Mosaic"`. The
+/// prehash is the 64-byte BLAKE3 hash of the record, which the signer
+/// signs as it is; it never sees the record itself. This lets hardware
+/// security modules and OS keystores sign records, as well as a
+/// `SecretKey`. See `AsyncSigner` for signers that must wait, such as
+/// remote signing services.
+///
+/// See `OwnedRecord::new_with_signer()`
+pub trait Signer {
+    /// The public key of the signing key
+    fn public_key(&self) -> PublicKey;
+
+    /// Sign a 64-byte prehash, returning the signature
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the signature cannot be produced
+    fn sign_prehash(&self, prehash: &[u8; 64]) -> Result<[u8; 64], Error>;
+}
+
+/// Like a `Signer`, but the signature may take a while to arrive.
+///
+/// The returned `Future` names no runtime types, as with `Resolver`.
+///
+/// See `OwnedRecord::new_with_async_signer()`
+pub trait AsyncSigner {
+    /// The public key of the signing key
+    fn public_key(&self) -> PublicKey;
+
+    /// Sign a 64-byte prehash, returning the signature
+    fn sign_prehash(
+        &self,
+        prehash: &[u8; 64],
+    ) -> impl Future<Output = Result<[u8; 64], Error>> + Send;
+}
+
+impl Signer for SecretKey {
+    fn public_key(&self) -> PublicKey {
+        self.public()
+    }
+
+    fn sign_prehash(&self, prehash: &[u8; 64]) -> Result<[u8; 64], Error> {
+        let digest = crate::crypto::Prehash(*prehash);
+        let signature = self
+            .to_signing_key()
+            .sign_prehashed(digest, Some(b"Mosaic"))?;
+        Ok(signature.to_bytes())
+    }
+}
+
+impl AsyncSigner for SecretKey {
+    fn public_key(&self) -> PublicKey {
+        self.public()
+    }
+
+    fn sign_prehash(
+        &self,
+        prehash: &[u8; 64],
+    ) -> impl Future<Output = Result<[u8; 64], Error>> + Send {
+        std::future::ready(Signer::sign_prehash(self, prehash))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedRecord, RecordFlags, RecordParts, Timestamp};
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    // Stands in for a keystore that only hands out signatures
+    struct Keystore(SecretKey, bool);
+
+    impl Signer for Keystore {
+        fn public_key(&self) -> PublicKey {
+            self.0.public()
+        }
+
+        fn sign_prehash(&self, prehash: &[u8; 64]) -> Result<[u8; 64], Error> {
+            let mut signature = Signer::sign_prehash(&self.0, prehash)?;
+            if self.1 {
+                signature[0] ^= 1;
+            }
+            Ok(signature)
+        }
+    }
+
+    #[test]
+    fn test_signer() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let parts = RecordParts {
+            kind: Kind::MICROBLOG_ROOT,
+            deterministic_key: Some(b"key"),
+            timestamp: Timestamp::from_millis(1000).unwrap(),
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: b"hello",
+        };
+        let expected = OwnedRecord::new(&secret_key, &parts).unwrap();
+
+        let keystore = Keystore(SecretKey::from_bytes(secret_key.as_bytes()), false);
+        let record = OwnedRecord::new_with_signer(&keystore, &parts).unwrap();
+        assert_eq!(record, expected);
+
+        let future = pin!(OwnedRecord::new_with_async_signer(&secret_key, &parts));
+        let Poll::Ready(record) = future.poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("future was not ready");
+        };
+        assert_eq!(record.unwrap(), expected);
+
+        // A bad signature is caught
+        let keystore = Keystore(SecretKey::from_bytes(secret_key.as_bytes()), true);
+        assert!(OwnedRecord::new_with_signer(&keystore, &parts).is_err());
+    }
+}
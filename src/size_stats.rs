@@ -0,0 +1,235 @@
+use crate::{Kind, Record};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// The number of size classes in a `SizeHistogram`
+pub const SIZE_CLASSES: usize = 26;
+
+/// Every record has a header of this many bytes
+const HEADER_LEN: u64 = 208;
+
+/// A histogram of sizes in power-of-two size classes.
+///
+/// Class 0 counts sizes of zero. Class `n` counts sizes from `2^(n-1)`
+/// up to but not including `2^n`, except that the last class also counts
+/// everything larger.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeHistogram {
+    /// Count of sizes in each size class
+    pub classes: [u64; SIZE_CLASSES],
+
+    /// Sum of all sizes observed
+    pub total: u64,
+
+    /// Largest size observed
+    pub max: u64,
+}
+
+impl SizeHistogram {
+    /// The size class that `size` falls in
+    #[must_use]
+    pub fn class_of(size: u64) -> usize {
+        ((u64::BITS - size.leading_zeros()) as usize).min(SIZE_CLASSES - 1)
+    }
+
+    /// The smallest size counted in size class `class`
+    #[must_use]
+    pub fn class_floor(class: usize) -> u64 {
+        match class {
+            0 => 0,
+            c => 1 << (c - 1),
+        }
+    }
+
+    /// Number of sizes observed
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.classes.iter().sum()
+    }
+
+    /// Observe a size
+    pub fn observe(&mut self, size: u64) {
+        self.classes[Self::class_of(size)] += 1;
+        self.total += size;
+        self.max = self.max.max(size);
+    }
+
+    /// Add the observations of another histogram to this one
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for (mine, theirs) in self.classes.iter_mut().zip(other.classes.iter()) {
+            *mine += theirs;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Sizes of the records of one kind observed by a `SizeStats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KindSizes {
+    /// Number of records observed
+    pub records: u64,
+
+    /// Bytes spent on headers. Headers are a fixed length, so this is
+    /// only kept as a total.
+    pub header_bytes: u64,
+
+    /// Unpadded length of the tags section
+    pub tags: SizeHistogram,
+
+    /// Unpadded length of the payload
+    pub payload: SizeHistogram,
+
+    /// Full length of the record, including padding
+    pub record: SizeHistogram,
+}
+
+impl KindSizes {
+    fn observe(&mut self, record: &Record) {
+        self.records += 1;
+        self.header_bytes += HEADER_LEN;
+        self.tags.observe(record.tags_len() as u64);
+        self.payload.observe(record.payload_len() as u64);
+        self.record.observe(record.as_bytes().len() as u64);
+    }
+
+    /// Add the observations of another `KindSizes` to this one
+    pub fn merge(&mut self, other: &KindSizes) {
+        self.records += other.records;
+        self.header_bytes += other.header_bytes;
+        self.tags.merge(&other.tags);
+        self.payload.merge(&other.payload);
+        self.record.merge(&other.record);
+    }
+}
+
+/// A `SizeStats` accumulates histograms of record sizes per kind, so that
+/// operators can tune size limits and storage layouts with real data.
+///
+/// `AcceptancePipeline` feeds the records it accepts and `MemoryStore`
+/// the records it stores, if one is set. Others can call `observe()`
+/// directly. Use `snapshot()` to export what has been gathered.
+///
+/// `SizeStats` is cheap to clone; clones share the same histograms.
+#[derive(Debug, Clone, Default)]
+pub struct SizeStats(Arc<Mutex<HashMap<Kind, KindSizes>>>);
+
+impl SizeStats {
+    /// Create a new empty `SizeStats`
+    #[must_use]
+    pub fn new() -> SizeStats {
+        SizeStats::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Kind, KindSizes>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Observe the sizes of a record
+    pub fn observe(&self, record: &Record) {
+        self.lock()
+            .entry(record.kind())
+            .or_default()
+            .observe(record);
+    }
+
+    /// The sizes observed so far for each kind, ordered by kind
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(Kind, KindSizes)> {
+        let mut kinds: Vec<(Kind, KindSizes)> = self.lock().iter().map(|(k, s)| (*k, *s)).collect();
+        kinds.sort_by_key(|(k, _)| k.as_u16());
+        kinds
+    }
+
+    /// The sizes observed so far for one kind
+    #[must_use]
+    pub fn get(&self, kind: Kind) -> KindSizes {
+        self.lock().get(&kind).copied().unwrap_or_default()
+    }
+
+    /// The sizes observed so far across all kinds
+    #[must_use]
+    pub fn total(&self) -> KindSizes {
+        let mut total = KindSizes::default();
+        for sizes in self.lock().values() {
+            total.merge(sizes);
+        }
+        total
+    }
+
+    /// Forget everything observed so far
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OwnedRecord, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_size_classes() {
+        assert_eq!(SizeHistogram::class_of(0), 0);
+        assert_eq!(SizeHistogram::class_of(1), 1);
+        assert_eq!(SizeHistogram::class_of(2), 2);
+        assert_eq!(SizeHistogram::class_of(3), 2);
+        assert_eq!(SizeHistogram::class_of(1024), 11);
+        assert_eq!(SizeHistogram::class_of(u64::MAX), SIZE_CLASSES - 1);
+        for class in 0..SIZE_CLASSES {
+            assert_eq!(
+                SizeHistogram::class_of(SizeHistogram::class_floor(class)),
+                class
+            );
+        }
+    }
+
+    #[test]
+    fn test_size_stats() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let make = |kind: Kind, payload: &[u8]| {
+            OwnedRecord::new(
+                &secret_key,
+                &RecordParts {
+                    kind,
+                    deterministic_key: None,
+                    timestamp: Timestamp::from_millis(1000).unwrap(),
+                    flags: RecordFlags::empty(),
+                    app_flags: 0,
+                    tags_bytes: b"",
+                    payload,
+                },
+            )
+            .unwrap()
+        };
+
+        let stats = SizeStats::new();
+        let shared = stats.clone();
+        shared.observe(&make(Kind::MICROBLOG_ROOT, b"hello"));
+        shared.observe(&make(Kind::MICROBLOG_ROOT, &[0; 100]));
+        shared.observe(&make(Kind::PROFILE, b""));
+
+        let microblog = stats.get(Kind::MICROBLOG_ROOT);
+        assert_eq!(microblog.records, 2);
+        assert_eq!(microblog.header_bytes, 2 * HEADER_LEN);
+        assert_eq!(microblog.payload.count(), 2);
+        assert_eq!(microblog.payload.total, 105);
+        assert_eq!(microblog.payload.max, 100);
+        assert_eq!(microblog.payload.classes[SizeHistogram::class_of(5)], 1);
+        assert_eq!(microblog.payload.classes[SizeHistogram::class_of(100)], 1);
+        assert_eq!(microblog.tags.classes[0], 2);
+        assert_eq!(microblog.record.total, 2 * HEADER_LEN + 8 + 104);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, Kind::PROFILE);
+        assert_eq!(stats.total().records, 3);
+
+        stats.reset();
+        assert!(shared.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{Error, OwnedRecord, Record, RecordParts, SecretKey};
+use std::ops::Deref;
+
+/// A record stored inline in a fixed-size buffer of `N` bytes.
+///
+/// Creating a `SmallRecord` does not allocate, which suits embedded
+/// targets and hot server paths where most records are short. A record
+/// with no tags and a payload of up to `N - 208` bytes (rounded down to a
+/// multiple of 8) fits; for example `SmallRecord<512>` holds payloads of
+/// up to 304 bytes.
+#[derive(Clone)]
+pub struct SmallRecord<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> SmallRecord<N> {
+    /// Create and sign a new record in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record does not fit in `N` bytes, if reserved
+    /// flags are set, or if signing fails.
+    pub fn new(signing_secret_key: &SecretKey, parts: &RecordParts) -> Result<Self, Error> {
+        let mut buffer = [0; N];
+        let len = Record::write_record(&mut buffer, signing_secret_key, parts)?
+            .as_bytes()
+            .len();
+        Ok(SmallRecord { buffer, len })
+    }
+
+    /// Copy an existing record into a `SmallRecord`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record does not fit in `N` bytes.
+    pub fn from_record(record: &Record) -> Result<Self, Error> {
+        let bytes = record.as_bytes();
+        if bytes.len() > N {
+            return Err(crate::InnerError::EndOfOutput.into());
+        }
+        let mut buffer = [0; N];
+        buffer[..bytes.len()].copy_from_slice(bytes);
+        Ok(SmallRecord {
+            buffer,
+            len: bytes.len(),
+        })
+    }
+
+    /// Copy into an `OwnedRecord` on the heap
+    #[must_use]
+    pub fn to_owned_record(&self) -> OwnedRecord {
+        (**self).to_owned()
+    }
+}
+
+impl<const N: usize> Deref for SmallRecord<N> {
+    type Target = Record;
+
+    fn deref(&self) -> &Record {
+        Record::from_inner(&self.buffer[..self.len])
+    }
+}
+
+impl<const N: usize> std::fmt::Debug for SmallRecord<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SmallRecord").field(&&**self).finish()
+    }
+}
+
+impl<const N: usize> PartialEq for SmallRecord<N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<const N: usize> Eq for SmallRecord<N> {}
+
+impl<const N: usize> std::fmt::Display for SmallRecord<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, RecordFlags, Timestamp};
+
+    #[test]
+    fn test_small_record() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let parts = RecordParts {
+            kind: Kind::MICROBLOG_ROOT,
+            deterministic_key: None,
+            timestamp: Timestamp::from_millis(1000).unwrap(),
+            flags: RecordFlags::empty(),
+            app_flags: 0,
+            tags_bytes: b"",
+            payload: b"hello world",
+        };
+
+        let small: SmallRecord<256> = SmallRecord::new(&secret_key, &parts).unwrap();
+        small.verify().unwrap();
+        assert_eq!(small.payload_bytes(), b"hello world");
+        assert_eq!(small.to_owned_record().as_bytes(), small.as_bytes());
+        assert_eq!(SmallRecord::<256>::from_record(&small).unwrap(), small);
+
+        // Too large for the buffer
+        let payload = [b'x'; 49];
+        let parts = RecordParts {
+            payload: &payload,
+            ..parts
+        };
+        assert!(SmallRecord::<256>::new(&secret_key, &parts).is_err());
+        assert!(SmallRecord::<512>::new(&secret_key, &parts).is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{Address, Error, Filter, Id, OwnedRecord};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A consistent, read-only view of a `RecordStore` at a point in time.
+///
+/// A snapshot does not change while writes to the store proceed, so it
+/// can be used to generate Merkle roots, exports and reconciliation
+/// fingerprints without blocking ingestion. Get one from
+/// `RecordStore::snapshot()`.
+pub trait Snapshot {
+    /// Get a record by its `Id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error>;
+
+    /// Get the current record at an `Address`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error>;
+
+    /// Had this `Id` been deleted?
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error>;
+
+    /// Iterate over every record, in no particular order. This includes
+    /// records hidden by `DeletionMode::Hide`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the backend fails. Backends that read lazily
+    /// yield an `Err` item if they fail part way through.
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error>;
+
+    /// Query records matching the `Filter`, newest first, honoring the
+    /// filter's `limit`, as `RecordStore::query()` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the filter is invalid or if the backend fails
+    fn query(&self, filter: &Filter) -> Result<Vec<OwnedRecord>, Error> {
+        filter.validate()?;
+        let mut output: Vec<OwnedRecord> = vec![];
+        for record in self.records()? {
+            let record = record?;
+            if filter.matches(&record)
+                && (filter.include_deleted || !self.is_deleted(&record.id())?)
+            {
+                output.push(record);
+            }
+        }
+        crate::record_store::sort_and_limit(&mut output, filter);
+        Ok(output)
+    }
+}
+
+/// A `Snapshot` held in memory, copied out of a store
+#[derive(Debug, Clone, Default)]
+pub struct MemorySnapshot {
+    records: BTreeMap<Id, OwnedRecord>,
+    by_address: HashMap<Address, Id>,
+    deleted: HashSet<Id>,
+}
+
+impl MemorySnapshot {
+    pub(crate) fn new(
+        records: BTreeMap<Id, OwnedRecord>,
+        by_address: HashMap<Address, Id>,
+        deleted: HashSet<Id>,
+    ) -> MemorySnapshot {
+        MemorySnapshot {
+            records,
+            by_address,
+            deleted,
+        }
+    }
+
+    /// Number of records in the snapshot
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Is the snapshot empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl Snapshot for MemorySnapshot {
+    fn get_by_id(&self, id: &Id) -> Result<Option<OwnedRecord>, Error> {
+        if self.deleted.contains(id) {
+            return Ok(None);
+        }
+        Ok(self.records.get(id).cloned())
+    }
+
+    fn get_by_address(&self, address: &Address) -> Result<Option<OwnedRecord>, Error> {
+        match self.by_address.get(address) {
+            Some(id) => self.get_by_id(id),
+            None => Ok(None),
+        }
+    }
+
+    fn is_deleted(&self, id: &Id) -> Result<bool, Error> {
+        Ok(self.deleted.contains(id))
+    }
+
+    fn records(&self) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord, Error>> + '_>, Error> {
+        Ok(Box::new(self.records.values().cloned().map(Ok)))
+    }
+}
@@ -0,0 +1,109 @@
+// Magic bytes at the start of a payload, and the content type they mark
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x00asm", "application/wasm"),
+];
+
+// Content types inside a RIFF container, by the four bytes at offset 8
+const RIFF: &[(&[u8], &str)] = &[
+    (b"WEBP", "image/webp"),
+    (b"WAVE", "audio/wav"),
+    (b"AVI ", "video/x-msvideo"),
+];
+
+// Content types inside an ISO media file, by the brand at offset 8
+const FTYP: &[(&[u8], &str)] = &[
+    (b"avif", "image/avif"),
+    (b"avis", "image/avif"),
+    (b"heic", "image/heic"),
+    (b"heix", "image/heic"),
+    (b"mif1", "image/heif"),
+    (b"qt  ", "video/quicktime"),
+    (b"M4A ", "audio/mp4"),
+];
+
+/// Guess the content type of a payload from its leading magic bytes.
+///
+/// This is for records that do not say what their payload is. Payloads
+/// with no recognized magic bytes are `text/plain; charset=utf-8` if they
+/// are valid UTF-8 without control characters (other than whitespace),
+/// and `application/octet-stream` otherwise. An empty payload is text.
+///
+/// Compressed payloads should be decompressed first, or they will be
+/// reported as `application/zstd`.
+#[must_use]
+pub fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    for (magic, content_type) in MAGIC {
+        if bytes.starts_with(magic) {
+            return content_type;
+        }
+    }
+
+    if bytes.len() >= 12 {
+        let (container, subtype) = (&bytes[0..4], &bytes[8..12]);
+        if container == b"RIFF" {
+            if let Some((_, content_type)) = RIFF.iter().find(|(t, _)| *t == subtype) {
+                return content_type;
+            }
+        }
+        if &bytes[4..8] == b"ftyp" {
+            let brand = FTYP.iter().find(|(b, _)| *b == subtype);
+            return brand.map_or("video/mp4", |(_, content_type)| content_type);
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+            "text/plain; charset=utf-8"
+        }
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sniff_content_type() {
+        assert_eq!(
+            sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_content_type(b"\xff\xd8\xff\xe0\0\x10JFIF"),
+            "image/jpeg"
+        );
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(
+            sniff_content_type(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            "image/avif"
+        );
+        assert_eq!(
+            sniff_content_type(b"\0\0\0\x18ftypisom\0\0\0\0"),
+            "video/mp4"
+        );
+        assert_eq!(
+            sniff_content_type("héllo\n\tworld".as_bytes()),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(sniff_content_type(b""), "text/plain; charset=utf-8");
+        assert_eq!(
+            sniff_content_type(b"hello\0world"),
+            "application/octet-stream"
+        );
+        assert_eq!(sniff_content_type(b"\xc3\x28"), "application/octet-stream");
+    }
+}
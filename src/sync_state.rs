@@ -0,0 +1,291 @@
+use crate::{
+    Error, Id, InnerError, Kind, OwnedRecord, PublicKey, Record, RecordFlags, RecordParts,
+    SecretKey, Timestamp,
+};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// The deterministic key of sync state records, so that each new state
+/// replaces the last
+pub const SYNC_STATE_KEY: &[u8] = b"sync-state";
+
+/// What one device has fetched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSyncState {
+    /// When the device last synced
+    pub last_synced: Timestamp,
+
+    /// The `Id` of the last record fetched from each stream (a server, a
+    /// feed, a conversation, as the application names them)
+    pub cursors: BTreeMap<String, Id>,
+}
+
+impl DeviceSyncState {
+    /// A device state with no cursors
+    #[must_use]
+    pub fn new(last_synced: Timestamp) -> DeviceSyncState {
+        DeviceSyncState {
+            last_synced,
+            cursors: BTreeMap::new(),
+        }
+    }
+}
+
+/// What each of a user's devices has fetched, as carried by a
+/// `SYNC_STATE` record.
+///
+/// Devices are identified by their device keys. Each device updates its
+/// own entry and publishes the whole state; devices reconcile states
+/// published concurrently with `merge()`.
+///
+/// The state is encoded as, for each device in key order:
+/// the 32-byte device key, the 6-byte last-synced timestamp, a one-byte
+/// cursor count, and for each cursor a one-byte name length, the UTF-8
+/// name and the 48-byte `Id`. The payload of a sync state record is that
+/// encoding sealed with `crypto::seal()` from the user's key to itself, so
+/// only the user can read it.
+///
+/// Sync state records are flagged `FROM_AUTHOR`, `TO_RECIPIENTS` and
+/// `NO_BRIDGE` so that servers accept them only from the user and serve
+/// them only to the user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncState(BTreeMap<PublicKey, DeviceSyncState>);
+
+impl SyncState {
+    /// Create an empty `SyncState`
+    #[must_use]
+    pub fn new() -> SyncState {
+        SyncState::default()
+    }
+
+    /// The state of a device
+    #[must_use]
+    pub fn device(&self, device: &PublicKey) -> Option<&DeviceSyncState> {
+        self.0.get(device)
+    }
+
+    /// Record that a device fetched up to `id` from `stream` at
+    /// `timestamp`
+    pub fn update(&mut self, device: PublicKey, stream: &str, id: Id, timestamp: Timestamp) {
+        let state = self
+            .0
+            .entry(device)
+            .or_insert_with(|| DeviceSyncState::new(timestamp));
+        state.last_synced = state.last_synced.max(timestamp);
+        let cursor = state.cursors.entry(stream.to_owned()).or_insert(id);
+        *cursor = (*cursor).max(id);
+    }
+
+    /// Iterate over the devices and their states, in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&PublicKey, &DeviceSyncState)> {
+        self.0.iter()
+    }
+
+    /// The furthest any device has fetched from `stream`
+    #[must_use]
+    pub fn fetched_up_to(&self, stream: &str) -> Option<Id> {
+        self.0
+            .values()
+            .filter_map(|d| d.cursors.get(stream))
+            .max()
+            .copied()
+    }
+
+    /// Merge a state published by another device.
+    ///
+    /// For each device, the entry synced later wins. Entries synced at the
+    /// same time are combined, keeping the furthest cursor of each
+    /// stream.
+    pub fn merge(&mut self, other: &SyncState) {
+        for (device, theirs) in &other.0 {
+            match self.0.get_mut(device) {
+                None => {
+                    let _ = self.0.insert(*device, theirs.clone());
+                }
+                Some(ours) if theirs.last_synced > ours.last_synced => {
+                    *ours = theirs.clone();
+                }
+                Some(ours) if theirs.last_synced == ours.last_synced => {
+                    for (stream, id) in &theirs.cursors {
+                        let cursor = ours.cursors.entry(stream.clone()).or_insert(*id);
+                        *cursor = (*cursor).max(*id);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Encode the state, unsealed
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if a device has more than 255 cursors or a stream
+    /// name is longer than 255 bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        for (device, state) in &self.0 {
+            bytes.extend_from_slice(device.as_bytes());
+            bytes.extend_from_slice(&state.last_synced.to_bytes());
+            let count = u8::try_from(state.cursors.len())
+                .map_err(|_| InnerError::InvalidSyncState.into_err())?;
+            bytes.push(count);
+            for (stream, id) in &state.cursors {
+                let len = u8::try_from(stream.len())
+                    .map_err(|_| InnerError::InvalidSyncState.into_err())?;
+                bytes.push(len);
+                bytes.extend_from_slice(stream.as_bytes());
+                bytes.extend_from_slice(id.as_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a state encoded with `to_bytes()`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the payload is truncated or malformed
+    #[allow(clippy::missing_panics_doc)]
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<SyncState, Error> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+            if bytes.len() < len {
+                return Err(InnerError::InvalidSyncState.into());
+            }
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            Ok(head)
+        }
+
+        let mut state = SyncState::new();
+        while !bytes.is_empty() {
+            let device = PublicKey::from_bytes(take(&mut bytes, 32)?.try_into().unwrap())?;
+            let last_synced = Timestamp::from_bytes(take(&mut bytes, 6)?.try_into().unwrap())?;
+            let mut device_state = DeviceSyncState::new(last_synced);
+            let count = take(&mut bytes, 1)?[0];
+            for _ in 0..count {
+                let len = take(&mut bytes, 1)?[0] as usize;
+                let stream = std::str::from_utf8(take(&mut bytes, len)?)?.to_owned();
+                let id = Id::from_bytes(take(&mut bytes, 48)?.try_into().unwrap())?;
+                let _ = device_state.cursors.insert(stream, id);
+            }
+            let _ = state.0.insert(device, device_state);
+        }
+        Ok(state)
+    }
+
+    /// Create a sync state record, sealed to the user's own key
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the state cannot be encoded or is too large for
+    /// a record, or if sealing or signing fails
+    pub fn to_record(
+        &self,
+        secret_key: &SecretKey,
+        timestamp: Timestamp,
+    ) -> Result<OwnedRecord, Error> {
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::SYNC_STATE,
+                deterministic_key: Some(SYNC_STATE_KEY),
+                timestamp,
+                flags: RecordFlags::FROM_AUTHOR
+                    | RecordFlags::TO_RECIPIENTS
+                    | RecordFlags::NO_BRIDGE,
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: &crate::crypto::seal(&self.to_bytes()?, secret_key, &secret_key.public())?,
+            },
+        )
+    }
+
+    /// Read a sync state from a sync state record sealed to the user's key
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a `SYNC_STATE`, if its payload
+    /// was not sealed by and to `secret_key`, or if the state is malformed
+    pub fn from_record(record: &Record, secret_key: &SecretKey) -> Result<SyncState, Error> {
+        if record.kind() != Kind::SYNC_STATE {
+            return Err(InnerError::UnexpectedKind(record.kind()).into());
+        }
+        let payload = Zeroizing::new(crate::crypto::open(
+            record.payload_bytes(),
+            secret_key,
+            &secret_key.public(),
+        )?);
+        SyncState::from_bytes(&payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sync_state_merge() {
+        use rand::rngs::OsRng;
+
+        let user = SecretKey::generate(&mut OsRng);
+        let laptop = SecretKey::generate(&mut OsRng).public();
+        let phone = SecretKey::generate(&mut OsRng).public();
+        let ts = |ms| Timestamp::from_millis(ms).unwrap();
+        let ids: Vec<Id> = [100, 150, 200, 300]
+            .into_iter()
+            .map(|ms| {
+                OwnedRecord::new(
+                    &user,
+                    &RecordParts {
+                        kind: Kind::MICROBLOG_ROOT,
+                        deterministic_key: None,
+                        timestamp: ts(ms),
+                        flags: RecordFlags::empty(),
+                        app_flags: 0,
+                        tags_bytes: b"",
+                        payload: b"",
+                    },
+                )
+                .unwrap()
+                .id()
+            })
+            .collect();
+
+        let mut on_laptop = SyncState::new();
+        on_laptop.update(laptop, "home", ids[0], ts(1000));
+        let mut on_phone = on_laptop.clone();
+
+        on_laptop.update(laptop, "home", ids[3], ts(3000));
+        on_phone.update(phone, "home", ids[2], ts(2000));
+        on_phone.update(phone, "mentions", ids[1], ts(2000));
+
+        on_laptop.merge(&on_phone);
+        on_phone.merge(&on_laptop);
+        assert_eq!(on_laptop, on_phone);
+        assert_eq!(on_laptop.fetched_up_to("home"), Some(ids[3]));
+        assert_eq!(on_laptop.fetched_up_to("mentions"), Some(ids[1]));
+        assert_eq!(on_laptop.device(&laptop).unwrap().last_synced, ts(3000));
+
+        let record = on_laptop.to_record(&user, ts(4000)).unwrap();
+        assert_eq!(SyncState::from_record(&record, &user).unwrap(), on_laptop);
+
+        // Only the user can read it
+        let bytes = on_laptop.to_bytes().unwrap();
+        assert!(!record
+            .payload_bytes()
+            .windows(32)
+            .any(|w| w == laptop.as_bytes().as_slice()));
+        assert_eq!(
+            record.payload_bytes().len(),
+            bytes.len() + crate::crypto::SEAL_OVERHEAD
+        );
+        let stranger = SecretKey::generate(&mut OsRng);
+        assert_eq!(
+            SyncState::from_record(&record, &stranger)
+                .unwrap_err()
+                .kind(),
+            crate::ErrorKind::Decryption
+        );
+    }
+}
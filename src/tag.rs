@@ -2,10 +2,31 @@ use crate::{Error, InnerError, Kind, PublicKey, Reference};
 use std::ops::{Deref, DerefMut};
 
 /// A type of tag
+///
+/// Applications can define their own tag types as constants with
+/// `TagType::new()`, and use them in match patterns.
+///
+/// Tag types below `0x8000` are allocated by the Mosaic specification, and
+/// tag types from `0x8000` are application-specific. The server hint tags
+/// this crate adds have no allocation yet, so they use the
+/// application-specific range from `0xF001`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TagType(pub u16);
 
 impl TagType {
+    /// Create a `TagType` from its numeric value
+    #[must_use]
+    pub const fn new(tag_type: u16) -> TagType {
+        TagType(tag_type)
+    }
+
+    /// The numeric value of this `TagType`
+    #[must_use]
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
     /// [Notify Public Key](https://stevefarroll.github.io/mosaic-spec/core_tags/#notify-public-key)
     pub const NOTIFY_PUBLIC_KEY: TagType = TagType(0x1);
 
@@ -21,6 +42,14 @@ impl TagType {
     /// [Subkey](https://stevefarroll.github.io/mosaic-spec/core_tags/#subkey)
     pub const SUBKEY: TagType = TagType(0x10);
 
+    /// Server Hint: the record, and the records it references, can also
+    /// be found on this server
+    pub const SERVER_HINT: TagType = TagType(0xF001);
+
+    /// Author Server Hint: records by this author can be found on this
+    /// server
+    pub const AUTHOR_SERVER_HINT: TagType = TagType(0xF002);
+
     /// [Content Segment: User Mention](https://stevefarroll.github.io/mosaic-spec/core_tags/#content-segment-user-mention)
     pub const CONTENT_SEGMENT_USER_MENTION: TagType = TagType(0x20);
 
@@ -46,12 +75,6 @@ impl std::fmt::Display for TagType {
     }
 }
 
-impl TagType {
-    fn into_u16(self) -> u16 {
-        self.0
-    }
-}
-
 /// A single `Tag`, unsized (borrowed)
 ///
 /// See also `OwnedTag` for the owned variant.
@@ -78,29 +101,46 @@ impl Tag {
     ///
     /// # Errors
     ///
-    /// Errors if the input isn't long enough.
+    /// Errors if the input isn't long enough, or if the length byte is
+    /// shorter than the tag header.
     ///
     /// # Safety
     ///
     /// Be sure the input is a valid Tag.
-    #[allow(clippy::missing_panics_doc)]
     pub unsafe fn from_bytes(input: &[u8]) -> Result<&Tag, Error> {
-        if input.len() < 2 {
+        let Some(&len) = input.get(2) else {
             return Err(InnerError::EndOfInput.into());
-        }
-        let len = input[2] as usize;
-        if input.len() < len {
+        };
+        let len = len as usize;
+        if len < 3 || input.len() < len {
             return Err(InnerError::EndOfInput.into());
         }
         Ok(Self::from_inner(&input[0..len]))
     }
 
+    // Check that `input` is exactly one tag
+    fn check_len(input: &[u8]) -> Result<(), Error> {
+        let Some(&len) = input.get(2) else {
+            return Err(InnerError::EndOfInput.into());
+        };
+        if len < 3 || input.len() != len as usize {
+            return Err(InnerError::TagLength.into());
+        }
+        Ok(())
+    }
+
     /// Copy to an allocated owned data type
     #[must_use]
     pub fn to_owned(&self) -> OwnedTag {
         OwnedTag(self.0.to_owned())
     }
 
+    /// View a `Tag` as a slice of bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
     /// Get the type of tag this is
     #[must_use]
     #[allow(clippy::missing_panics_doc)]
@@ -108,20 +148,60 @@ impl Tag {
         TagType(u16::from_le_bytes(self.0[0..2].try_into().unwrap()))
     }
 
-    /// Get the public key (for types that have one)
+    /// Get the value of the tag: the public key, reference, id or URL it
+    /// carries, without the type, length, offset or kind fields. For tag
+    /// types not known to this library, this is everything after the
+    /// length byte.
+    #[must_use]
+    pub fn get_value(&self) -> &[u8] {
+        let start = match self.get_type() {
+            TagType::NOTIFY_PUBLIC_KEY
+            | TagType::REPLY
+            | TagType::ROOT
+            | TagType::NOSTR_SISTER
+            | TagType::SUBKEY
+            | TagType::SERVER_HINT
+            | TagType::AUTHOR_SERVER_HINT
+            | TagType::CONTENT_SEGMENT_USER_MENTION
+            | TagType::CONTENT_SEGMENT_SERVER_MENTION
+            | TagType::CONTENT_SEGMENT_URL
+            | TagType::CONTENT_SEGMENT_IMAGE
+            | TagType::CONTENT_SEGMENT_VIDEO => 8,
+            TagType::CONTENT_SEGMENT_QUOTE => 16,
+            _ => 3,
+        };
+        self.0.get(start..).unwrap_or(&[])
+    }
+
+    /// Get the public key (for types that have one). For an
+    /// `AUTHOR_SERVER_HINT` this is the author.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if the tag public key section is invalid
-    #[allow(clippy::missing_panics_doc)]
+    /// Returns an `Err` if the tag public key section is short or invalid
     pub fn get_public_key(&self) -> Result<Option<PublicKey>, Error> {
         match self.get_type() {
             TagType::NOTIFY_PUBLIC_KEY
             | TagType::SUBKEY
+            | TagType::SERVER_HINT
+            | TagType::AUTHOR_SERVER_HINT
             | TagType::CONTENT_SEGMENT_USER_MENTION
-            | TagType::CONTENT_SEGMENT_SERVER_MENTION => Ok(Some(PublicKey::from_bytes(
-                &self.0[8..40].try_into().unwrap(),
-            )?)),
+            | TagType::CONTENT_SEGMENT_SERVER_MENTION => {
+                Ok(Some(PublicKey::from_bytes(self.array(8)?)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Get the hinted server (for `SERVER_HINT` and `AUTHOR_SERVER_HINT`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the tag server key section is short or invalid
+    pub fn get_server_hint(&self) -> Result<Option<PublicKey>, Error> {
+        match self.get_type() {
+            TagType::SERVER_HINT => Ok(Some(PublicKey::from_bytes(self.array(8)?)?)),
+            TagType::AUTHOR_SERVER_HINT => Ok(Some(PublicKey::from_bytes(self.array(40)?)?)),
             _ => Ok(None),
         }
     }
@@ -130,26 +210,20 @@ impl Tag {
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if the tag reference section is invalid
-    #[allow(clippy::missing_panics_doc)]
+    /// Returns an `Err` if the tag reference section is short or invalid
     pub fn get_reference(&self) -> Result<Option<Reference>, Error> {
         match self.get_type() {
-            TagType::REPLY | TagType::ROOT => Ok(Some(Reference::from_bytes(
-                self.0[8..56].try_into().unwrap(),
-            )?)),
-            TagType::CONTENT_SEGMENT_QUOTE => Ok(Some(Reference::from_bytes(
-                self.0[16..64].try_into().unwrap(),
-            )?)),
+            TagType::REPLY | TagType::ROOT => Ok(Some(Reference::from_bytes(self.array(8)?)?)),
+            TagType::CONTENT_SEGMENT_QUOTE => Ok(Some(Reference::from_bytes(self.array(16)?)?)),
             _ => Ok(None),
         }
     }
 
     /// Get the nostr sister id (for `NOSTR_SISTER` tag only)
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
     pub fn get_nostr_sister_id(&self) -> Option<[u8; 32]> {
         match self.get_type() {
-            TagType::NOSTR_SISTER => Some(self.0[8..40].try_into().unwrap()),
+            TagType::NOSTR_SISTER => self.array(8).ok().copied(),
             _ => None,
         }
     }
@@ -163,29 +237,31 @@ impl Tag {
         match self.get_type() {
             TagType::CONTENT_SEGMENT_URL
             | TagType::CONTENT_SEGMENT_IMAGE
-            | TagType::CONTENT_SEGMENT_VIDEO => Ok(Some(std::str::from_utf8(&self.0[8..])?)),
+            | TagType::CONTENT_SEGMENT_VIDEO => {
+                let bytes = self
+                    .0
+                    .get(8..)
+                    .ok_or_else(|| InnerError::EndOfInput.into_err())?;
+                Ok(Some(std::str::from_utf8(bytes)?))
+            }
             _ => Ok(None),
         }
     }
 
     /// Get kind (for types that have one)
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
     pub fn get_kind(&self) -> Option<Kind> {
-        match self.get_type() {
-            TagType::REPLY | TagType::ROOT => {
-                Some(Kind(u16::from_le_bytes(self.0[6..8].try_into().unwrap())))
-            }
-            TagType::CONTENT_SEGMENT_QUOTE => {
-                Some(Kind(u16::from_le_bytes(self.0[14..16].try_into().unwrap())))
-            }
-            _ => None,
-        }
+        let start = match self.get_type() {
+            TagType::REPLY | TagType::ROOT => 6,
+            TagType::CONTENT_SEGMENT_QUOTE => 14,
+            _ => return None,
+        };
+        let bytes = self.array(start).ok()?;
+        Some(Kind(u16::from_le_bytes(*bytes)))
     }
 
     /// Get offset (for content segment tags)
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
     pub fn get_offset(&self) -> Option<u32> {
         match self.get_type() {
             TagType::CONTENT_SEGMENT_USER_MENTION
@@ -194,12 +270,21 @@ impl Tag {
             | TagType::CONTENT_SEGMENT_URL
             | TagType::CONTENT_SEGMENT_IMAGE
             | TagType::CONTENT_SEGMENT_VIDEO => {
-                Some(u32::from_le_bytes(self.0[4..8].try_into().unwrap()))
+                self.array(4).ok().map(|bytes| u32::from_le_bytes(*bytes))
             }
             _ => None,
         }
     }
 
+    // The `N` bytes at `start`, which a malformed tag may be missing.
+    // Accessors use this rather than panicking, so that they never allocate.
+    fn array<const N: usize>(&self, start: usize) -> Result<&[u8; N], Error> {
+        self.0
+            .get(start..start + N)
+            .and_then(|s| s.try_into().ok())
+            .ok_or_else(|| InnerError::EndOfInput.into_err())
+    }
+
     /// Write a new `NOTIFY_PUBLIC_KEY` tag to the buffer
     ///
     /// # Errors
@@ -301,6 +386,48 @@ impl Tag {
         Ok(Tag::from_inner(&buffer[..LEN]))
     }
 
+    /// Create a new `SERVER_HINT` tag
+    ///
+    /// # Errors
+    ///
+    /// Errors if the buffer isn't long enough.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_server_hint<'a>(
+        buffer: &'a mut [u8],
+        server_key: &PublicKey,
+    ) -> Result<&'a Tag, Error> {
+        const LEN: usize = 40;
+        if buffer.len() < LEN {
+            return Err(InnerError::EndOfOutput.into());
+        }
+        buffer[0..2].copy_from_slice(TagType::SERVER_HINT.0.to_le_bytes().as_slice());
+        buffer[2] = LEN as u8;
+        buffer[8..LEN].copy_from_slice(server_key.as_bytes().as_slice());
+        Ok(Tag::from_inner(&buffer[..LEN]))
+    }
+
+    /// Create a new `AUTHOR_SERVER_HINT` tag
+    ///
+    /// # Errors
+    ///
+    /// Errors if the buffer isn't long enough.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_author_server_hint<'a>(
+        buffer: &'a mut [u8],
+        author_key: &PublicKey,
+        server_key: &PublicKey,
+    ) -> Result<&'a Tag, Error> {
+        const LEN: usize = 72;
+        if buffer.len() < LEN {
+            return Err(InnerError::EndOfOutput.into());
+        }
+        buffer[0..2].copy_from_slice(TagType::AUTHOR_SERVER_HINT.0.to_le_bytes().as_slice());
+        buffer[2] = LEN as u8;
+        buffer[8..40].copy_from_slice(author_key.as_bytes().as_slice());
+        buffer[40..LEN].copy_from_slice(server_key.as_bytes().as_slice());
+        Ok(Tag::from_inner(&buffer[..LEN]))
+    }
+
     /// Create a new `CONTENT_SEGMENT_USER_MENTION` tag
     ///
     /// # Errors
@@ -446,6 +573,46 @@ impl Tag {
     }
 }
 
+/// An iterator over the tags in a tags area. Iteration stops at the first
+/// malformed tag.
+///
+/// See `Record::tags()` and `OwnedTagSet::iter()`
+#[derive(Debug, Clone)]
+pub struct TagIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for TagIter<'a> {
+    type Item = &'a Tag;
+
+    fn next(&mut self) -> Option<&'a Tag> {
+        let bytes = self.bytes;
+        let offset = self.offset;
+        if offset + 3 > bytes.len() {
+            return None;
+        }
+        let len = bytes[offset + 2] as usize;
+        if len < 3 || offset + len > bytes.len() {
+            return None;
+        }
+        self.offset += len;
+        Some(Tag::from_inner(&bytes[offset..offset + len]))
+    }
+}
+
+/// Iterate over the tags in a tags area. Iteration stops at the first
+/// malformed tag.
+pub(crate) fn iter_tags(bytes: &[u8]) -> TagIter<'_> {
+    TagIter { bytes, offset: 0 }
+}
+
+/// Count the tags in a tags area. Counting stops at the first malformed
+/// tag.
+pub(crate) fn count_tags(bytes: &[u8]) -> usize {
+    iter_tags(bytes).count()
+}
+
 /// A single `OwnedTag`
 ///
 /// See also `Tag` for the borrowed variant.
@@ -461,12 +628,12 @@ impl OwnedTag {
     #[allow(clippy::missing_panics_doc)]
     pub fn new<T: AsRef<[u8]>>(ty: TagType, value: &T) -> Result<OwnedTag, Error> {
         let len = value.as_ref().len();
-        if len > 253 {
+        if len > 252 {
             return Err(InnerError::TagTooLong.into());
         }
         let mut buffer = vec![0; 3 + len];
-        buffer[0..1].copy_from_slice(ty.into_u16().to_be_bytes().as_slice());
-        buffer[2] = u8::try_from(len).unwrap();
+        buffer[0..2].copy_from_slice(ty.as_u16().to_le_bytes().as_slice());
+        buffer[2] = u8::try_from(3 + len).unwrap();
         buffer[3..].copy_from_slice(value.as_ref());
         Ok(OwnedTag(buffer))
     }
@@ -531,6 +698,30 @@ impl OwnedTag {
         OwnedTag(bytes)
     }
 
+    /// Create a new `SERVER_HINT` tag
+    ///
+    /// To avoid copies, consider `Tag::write_server_hint()`
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn new_server_hint(server_key: &PublicKey) -> OwnedTag {
+        const LEN: usize = 40;
+        let mut bytes: Vec<u8> = vec![0; LEN];
+        let _ = Tag::write_server_hint(&mut bytes, server_key).unwrap();
+        OwnedTag(bytes)
+    }
+
+    /// Create a new `AUTHOR_SERVER_HINT` tag
+    ///
+    /// To avoid copies, consider `Tag::write_author_server_hint()`
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn new_author_server_hint(author_key: &PublicKey, server_key: &PublicKey) -> OwnedTag {
+        const LEN: usize = 72;
+        let mut bytes: Vec<u8> = vec![0; LEN];
+        let _ = Tag::write_author_server_hint(&mut bytes, author_key, server_key).unwrap();
+        OwnedTag(bytes)
+    }
+
     /// Create a new `CONTENT_SEGMENT_USER_MENTION` tag
     ///
     /// To avoid copies, consider `Tag::write_content_segment_user_mention()`
@@ -604,6 +795,90 @@ impl OwnedTag {
     }
 }
 
+/// An `OwnedTagSet` builds the tags area of a record.
+///
+/// Add tags with `push()`, then pass `as_bytes()` as the `tags_bytes` of
+/// `RecordParts`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct OwnedTagSet(Vec<u8>);
+
+impl OwnedTagSet {
+    /// Create a new empty `OwnedTagSet`
+    #[must_use]
+    pub fn new() -> OwnedTagSet {
+        OwnedTagSet::default()
+    }
+
+    /// Add a tag
+    ///
+    /// # Errors
+    ///
+    /// Errors if the tags area would be too long for a record
+    pub fn push(&mut self, tag: &Tag) -> Result<(), Error> {
+        if self.0.len() + tag.as_bytes().len() > u16::MAX as usize {
+            return Err(InnerError::RecordTooLong.into());
+        }
+        self.0.extend_from_slice(tag.as_bytes());
+        Ok(())
+    }
+
+    /// Iterate over the tags
+    #[must_use]
+    pub fn iter(&self) -> TagIter<'_> {
+        iter_tags(&self.0)
+    }
+
+    /// Number of tags
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Are there no tags?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// View as the bytes of a tags area
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Take the bytes of the tags area
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a OwnedTagSet {
+    type Item = &'a Tag;
+    type IntoIter = TagIter<'a>;
+
+    fn into_iter(self) -> TagIter<'a> {
+        self.iter()
+    }
+}
+
+impl TryFrom<Vec<u8>> for OwnedTagSet {
+    type Error = Error;
+
+    /// Take the bytes of a tags area, which must consist entirely of
+    /// well-formed tags
+    fn try_from(bytes: Vec<u8>) -> Result<OwnedTagSet, Error> {
+        let parsed: usize = iter_tags(&bytes).map(|t| t.as_bytes().len()).sum();
+        if parsed != bytes.len() {
+            return Err(InnerError::TagLength.into());
+        }
+        if bytes.len() > u16::MAX as usize {
+            return Err(InnerError::RecordTooLong.into());
+        }
+        Ok(OwnedTagSet(bytes))
+    }
+}
+
 impl Deref for OwnedTag {
     type Target = Tag;
 
@@ -618,6 +893,49 @@ impl DerefMut for OwnedTag {
     }
 }
 
+impl AsRef<[u8]> for Tag {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for &'a Tag {
+    type Error = Error;
+
+    /// View bytes as a `Tag`. Unlike `Tag::from_bytes()`, the bytes must be
+    /// exactly one tag, with no trailing data.
+    fn try_from(bytes: &'a [u8]) -> Result<&'a Tag, Error> {
+        Tag::check_len(bytes)?;
+        Ok(Tag::from_inner(bytes))
+    }
+}
+
+impl AsRef<[u8]> for OwnedTag {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for OwnedTag {
+    type Error = Error;
+
+    /// Copy bytes into an `OwnedTag`. The bytes must be exactly one tag.
+    fn try_from(bytes: &[u8]) -> Result<OwnedTag, Error> {
+        Tag::check_len(bytes)?;
+        Ok(OwnedTag(bytes.to_owned()))
+    }
+}
+
+impl TryFrom<Vec<u8>> for OwnedTag {
+    type Error = Error;
+
+    /// Take bytes as an `OwnedTag`. The bytes must be exactly one tag.
+    fn try_from(bytes: Vec<u8>) -> Result<OwnedTag, Error> {
+        Tag::check_len(&bytes)?;
+        Ok(OwnedTag(bytes))
+    }
+}
+
 #[cfg(test)]
 macro_rules! test_tag_type {
     ($new:expr, $typ:expr) => {{
@@ -670,6 +988,16 @@ mod test {
         let v = test_tag_type!(OwnedTag::new_subkey(&public_key), TagType::SUBKEY);
         assert_eq!(v.get_public_key().unwrap().unwrap(), public_key);
 
+        let v = test_tag_type!(OwnedTag::new_server_hint(&public_key), TagType::SERVER_HINT);
+        assert_eq!(v.get_server_hint().unwrap().unwrap(), public_key);
+
+        let v = test_tag_type!(
+            OwnedTag::new_author_server_hint(&public_key, &public_key),
+            TagType::AUTHOR_SERVER_HINT
+        );
+        assert_eq!(v.get_public_key().unwrap().unwrap(), public_key);
+        assert_eq!(v.get_server_hint().unwrap().unwrap(), public_key);
+
         let v = test_tag_type!(
             OwnedTag::new_content_segment_user_mention(&public_key, offset),
             TagType::CONTENT_SEGMENT_USER_MENTION
@@ -693,24 +1021,71 @@ mod test {
         assert_eq!(v.get_offset().unwrap(), offset);
 
         let v = test_tag_type!(
-            OwnedTag::new_content_segment_url(&url, offset),
+            OwnedTag::new_content_segment_url(url, offset),
             TagType::CONTENT_SEGMENT_URL
         );
         assert_eq!(v.get_url().unwrap().unwrap(), url);
         assert_eq!(v.get_offset().unwrap(), offset);
 
         let v = test_tag_type!(
-            OwnedTag::new_content_segment_image(&url, offset),
+            OwnedTag::new_content_segment_image(url, offset),
             TagType::CONTENT_SEGMENT_IMAGE
         );
         assert_eq!(v.get_url().unwrap().unwrap(), url);
         assert_eq!(v.get_offset().unwrap(), offset);
 
         let v = test_tag_type!(
-            OwnedTag::new_content_segment_video(&url, offset),
+            OwnedTag::new_content_segment_video(url, offset),
             TagType::CONTENT_SEGMENT_VIDEO
         );
         assert_eq!(v.get_url().unwrap().unwrap(), url);
         assert_eq!(v.get_offset().unwrap(), offset);
+
+        let tag: &Tag = v.as_ref().try_into().unwrap();
+        assert_eq!(tag, &*v);
+        assert_eq!(OwnedTag::try_from(v.as_ref().to_vec()).unwrap(), v);
+        let mut long = v.as_ref().to_vec();
+        long.push(0);
+        assert!(OwnedTag::try_from(&long[..]).is_err());
+        assert!(<&Tag>::try_from(&long[..2]).is_err());
+    }
+
+    #[test]
+    fn test_tag_set() {
+        use crate::{Kind, OwnedRecord, RecordFlags, RecordParts, Timestamp};
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let public_key = secret_key.public();
+
+        let mut tags = OwnedTagSet::new();
+        assert!(tags.is_empty());
+        tags.push(&OwnedTag::new_notify_public_key(&public_key))
+            .unwrap();
+        tags.push(&OwnedTag::new_subkey(&public_key)).unwrap();
+        assert_eq!(tags.len(), 2);
+
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: tags.as_bytes(),
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+        let types: Vec<TagType> = record.tags().map(Tag::get_type).collect();
+        assert_eq!(types, vec![TagType::NOTIFY_PUBLIC_KEY, TagType::SUBKEY]);
+        assert!(record.tags().eq(tags.iter()));
+
+        let copy = OwnedTagSet::try_from(record.tags_bytes().to_vec()).unwrap();
+        assert_eq!(copy, tags);
+        let mut bytes = tags.into_bytes();
+        bytes.push(0);
+        assert!(OwnedTagSet::try_from(bytes).is_err());
     }
 }
@@ -0,0 +1,191 @@
+use crate::tag::iter_tags;
+use crate::{Id, Record, Tag, TagType};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The key under which a tag is indexed: the tag type (little-endian)
+/// followed by the first 16 bytes of the BLAKE3 hash of the tag value.
+///
+/// Distinct values may rarely share a key, so lookups may return extra
+/// `Id`s; stores always check candidates against the `Filter` afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TagKey([u8; 18]);
+
+impl TagKey {
+    /// Compute the key for a tag type and value
+    #[must_use]
+    pub fn new(tag_type: TagType, value: &[u8]) -> TagKey {
+        let mut bytes = [0; 18];
+        bytes[0..2].copy_from_slice(tag_type.0.to_le_bytes().as_slice());
+        bytes[2..18].copy_from_slice(&blake3::hash(value).as_bytes()[0..16]);
+        TagKey(bytes)
+    }
+
+    /// Compute the key for a tag
+    #[must_use]
+    pub fn from_tag(tag: &Tag) -> TagKey {
+        TagKey::new(tag.get_type(), tag.get_value())
+    }
+
+    /// View the key as bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 18] {
+        &self.0
+    }
+
+    /// The keys of every tag in a record, without duplicates
+    #[must_use]
+    pub fn for_record(record: &Record) -> BTreeSet<TagKey> {
+        iter_tags(record.tags_bytes())
+            .map(TagKey::from_tag)
+            .collect()
+    }
+}
+
+/// An index from tag values to the `Id`s of the records carrying them.
+///
+/// This makes tag-based queries (replies to a record, mentions of a
+/// key) a lookup rather than a scan. Tag values are also kept in order so
+/// that prefix queries are a range scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagIndex {
+    by_key: BTreeMap<TagKey, BTreeSet<Id>>,
+    by_value: BTreeMap<Vec<u8>, BTreeSet<Id>>,
+}
+
+// The ordered index key of a tag: the type (big-endian, so that each
+// type's values are contiguous) followed by the value
+pub(crate) fn value_key(tag_type: TagType, value: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(2 + value.len());
+    key.extend_from_slice(&tag_type.0.to_be_bytes());
+    key.extend_from_slice(value);
+    key
+}
+
+// The ordered index keys of every tag in a record, without duplicates
+pub(crate) fn value_keys(record: &Record) -> BTreeSet<Vec<u8>> {
+    iter_tags(record.tags_bytes())
+        .map(|tag| value_key(tag.get_type(), tag.get_value()))
+        .collect()
+}
+
+impl TagIndex {
+    /// Create a new empty `TagIndex`
+    #[must_use]
+    pub fn new() -> TagIndex {
+        TagIndex::default()
+    }
+
+    /// Index every tag of a record
+    pub fn insert(&mut self, record: &Record) {
+        let id = record.id();
+        for key in TagKey::for_record(record) {
+            let _ = self.by_key.entry(key).or_default().insert(id);
+        }
+        for key in value_keys(record) {
+            let _ = self.by_value.entry(key).or_default().insert(id);
+        }
+    }
+
+    /// Remove a record from the index
+    pub fn remove(&mut self, record: &Record) {
+        let id = record.id();
+        for key in TagKey::for_record(record) {
+            if let Some(set) = self.by_key.get_mut(&key) {
+                let _ = set.remove(&id);
+                if set.is_empty() {
+                    let _ = self.by_key.remove(&key);
+                }
+            }
+        }
+        for key in value_keys(record) {
+            if let Some(set) = self.by_value.get_mut(&key) {
+                let _ = set.remove(&id);
+                if set.is_empty() {
+                    let _ = self.by_value.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// The `Id`s of records with a tag of this type and value
+    pub fn get(&self, tag_type: TagType, value: &[u8]) -> impl Iterator<Item = &Id> {
+        self.by_key
+            .get(&TagKey::new(tag_type, value))
+            .into_iter()
+            .flatten()
+    }
+
+    /// The `Id`s of records with a tag of this type whose value starts with
+    /// `prefix`. A record with several matching tags is returned once per
+    /// tag.
+    pub fn get_prefix(&self, tag_type: TagType, prefix: &[u8]) -> impl Iterator<Item = &Id> {
+        let start = value_key(tag_type, prefix);
+        self.by_value
+            .range(start.clone()..)
+            .take_while(move |(key, _)| key.starts_with(&start))
+            .flat_map(|(_, ids)| ids)
+    }
+
+    /// Number of distinct tag keys indexed
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    /// Is the index empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Address, Kind, OwnedRecord, OwnedTag, RecordFlags, RecordParts, Reference, SecretKey,
+        Timestamp,
+    };
+
+    #[test]
+    fn test_tag_index() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let address = Address::new_deterministic(secret_key.public(), Kind::MICROBLOG_ROOT, b"x");
+        let target = Reference::from_bytes(address.as_bytes()).unwrap();
+
+        let mut tags: Vec<u8> = vec![];
+        tags.extend_from_slice(OwnedTag::new_reply(&target, Kind::MICROBLOG_ROOT).as_bytes());
+        tags.extend_from_slice(OwnedTag::new_notify_public_key(&secret_key.public()).as_bytes());
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::REPLY_COMMENT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: &tags,
+                payload: b"",
+            },
+        )
+        .unwrap();
+
+        let mut index = TagIndex::new();
+        index.insert(&record);
+        assert_eq!(index.len(), 2);
+        let found: Vec<&Id> = index.get(TagType::REPLY, target.as_bytes()).collect();
+        assert_eq!(found, vec![&record.id()]);
+        assert_eq!(index.get(TagType::ROOT, target.as_bytes()).count(), 0);
+        let found: Vec<&Id> = index
+            .get_prefix(TagType::REPLY, &target.as_bytes()[..20])
+            .collect();
+        assert_eq!(found, vec![&record.id()]);
+        assert_eq!(index.get_prefix(TagType::ROOT, b"").count(), 0);
+
+        index.remove(&record);
+        assert!(index.is_empty());
+    }
+}
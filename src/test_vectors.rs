@@ -0,0 +1,210 @@
+//! Known-answer test vectors.
+//!
+//! These vectors let other implementations of the Mosaic specification
+//! check their key derivation, record layout, hashing, signing and
+//! printable encodings against this reference implementation. Every value
+//! here is deterministic: ed25519 signatures do not use randomness, so
+//! signing the same record parts with the same key always gives the same
+//! bytes.
+//!
+//! `verify()` recomputes every vector with this crate and is run as part
+//! of its test suite.
+
+use crate::{
+    Error, InnerError, Kind, OwnedRecord, OwnedTag, PublicKey, Record, RecordFlags, RecordParts,
+    SecretKey, Timestamp,
+};
+
+/// The secret key: the bytes `0x00` through `0x1f`
+pub const SECRET_KEY: [u8; 32] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+];
+
+/// `SECRET_KEY` in its printable `mosec0` form
+pub const SECRET_KEY_PRINTABLE: &str = "mosec0yyyoryarywdyqnyjbefoadeqbhebnrounoktcfaadrpbs8y7daxo";
+
+/// The public key of `SECRET_KEY`
+pub const PUBLIC_KEY: [u8; 32] = [
+    0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0, 0x99,
+    0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55, 0x31, 0xb8,
+];
+
+/// `PUBLIC_KEY` in its printable `mopub0` form
+pub const PUBLIC_KEY_PRINTABLE: &str = "mopub0yqooxx9u3aemh8mo5wcqq16yufu6jitouq1o4za751dger1igghy";
+
+/// The kind of `RECORD`
+pub const RECORD_KIND: Kind = Kind::MICROBLOG_ROOT;
+
+/// The deterministic key `RECORD`'s address is derived from
+pub const RECORD_DETERMINISTIC_KEY: &[u8] = b"test-vector";
+
+/// The timestamp of `RECORD`, in milliseconds since the UNIX epoch
+pub const RECORD_TIMESTAMP_MS: u64 = 1_700_000_000_000;
+
+/// The payload of `RECORD`
+pub const RECORD_PAYLOAD: &[u8] = b"Hello, Mosaic!";
+
+/// A record signed by `SECRET_KEY`, with a deterministic address, the
+/// `PRINTABLE` flag, and a single tag notifying `PUBLIC_KEY`
+pub const RECORD: [u8; 264] = [
+    0x47, 0xf4, 0x8f, 0x82, 0xf1, 0x93, 0x0e, 0x0d, 0xbe, 0xf5, 0x15, 0x25, 0x9f, 0x01, 0xf3, 0x41,
+    0xb0, 0x70, 0x1f, 0x0d, 0x89, 0xdd, 0xa3, 0x04, 0xb7, 0xbe, 0x24, 0xeb, 0xfe, 0xf8, 0xc6, 0x17,
+    0xfb, 0xdd, 0x8e, 0x2f, 0xa7, 0xf3, 0x61, 0xa1, 0x05, 0x18, 0xbc, 0x5f, 0xb2, 0xa5, 0xac, 0xa3,
+    0x9b, 0xa8, 0x5a, 0x49, 0x16, 0x44, 0xba, 0xb3, 0x59, 0x1b, 0x99, 0x6c, 0x12, 0xe3, 0x2c, 0x07,
+    0x01, 0x8b, 0xcf, 0xe5, 0x68, 0x00, 0x00, 0x00, 0x55, 0x09, 0x18, 0x0a, 0x76, 0x83, 0x25, 0xcb,
+    0xcd, 0xb6, 0xec, 0xfe, 0x15, 0x5c, 0x03, 0x85, 0x36, 0x3d, 0x53, 0x04, 0x4f, 0xc2, 0xaf, 0x39,
+    0x4e, 0x61, 0x75, 0xc0, 0xff, 0x83, 0x2e, 0xa8, 0x41, 0x62, 0x2f, 0x97, 0x06, 0xd5, 0xf8, 0xb9,
+    0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0, 0x99,
+    0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55, 0x31, 0xb8,
+    0xcc, 0x41, 0x15, 0x0f, 0x41, 0x51, 0x29, 0x8b, 0x4f, 0x76, 0x7c, 0x74, 0xbb, 0x1b, 0x03, 0x00,
+    0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0, 0x99,
+    0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55, 0x31, 0xb8,
+    0x20, 0x00, 0x00, 0x68, 0xe5, 0xcf, 0x8b, 0x01, 0x00, 0x00, 0x28, 0x00, 0x0e, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe,
+    0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0, 0x99, 0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f,
+    0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55, 0x31, 0xb8, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x4d,
+    0x6f, 0x73, 0x61, 0x69, 0x63, 0x21, 0x00, 0x00,
+];
+
+/// The full 64-byte BLAKE3 hash of `RECORD`
+pub const RECORD_HASH: [u8; 64] = [
+    0x55, 0x09, 0x18, 0x0a, 0x76, 0x83, 0x25, 0xcb, 0xcd, 0xb6, 0xec, 0xfe, 0x15, 0x5c, 0x03, 0x85,
+    0x36, 0x3d, 0x53, 0x04, 0x4f, 0xc2, 0xaf, 0x39, 0x4e, 0x61, 0x75, 0xc0, 0xff, 0x83, 0x2e, 0xa8,
+    0x41, 0x62, 0x2f, 0x97, 0x06, 0xd5, 0xf8, 0xb9, 0x58, 0x3d, 0xbc, 0x4c, 0xd9, 0x0d, 0xe2, 0x4a,
+    0x87, 0x7c, 0x94, 0xde, 0x9f, 0xbc, 0xf4, 0x8a, 0x5b, 0x2b, 0x3d, 0xf3, 0x16, 0xf9, 0xd8, 0x03,
+];
+
+/// The ed25519 signature of `RECORD`
+pub const RECORD_SIGNATURE: [u8; 64] = [
+    0x47, 0xf4, 0x8f, 0x82, 0xf1, 0x93, 0x0e, 0x0d, 0xbe, 0xf5, 0x15, 0x25, 0x9f, 0x01, 0xf3, 0x41,
+    0xb0, 0x70, 0x1f, 0x0d, 0x89, 0xdd, 0xa3, 0x04, 0xb7, 0xbe, 0x24, 0xeb, 0xfe, 0xf8, 0xc6, 0x17,
+    0xfb, 0xdd, 0x8e, 0x2f, 0xa7, 0xf3, 0x61, 0xa1, 0x05, 0x18, 0xbc, 0x5f, 0xb2, 0xa5, 0xac, 0xa3,
+    0x9b, 0xa8, 0x5a, 0x49, 0x16, 0x44, 0xba, 0xb3, 0x59, 0x1b, 0x99, 0x6c, 0x12, 0xe3, 0x2c, 0x07,
+];
+
+/// The `Id` of `RECORD`
+pub const RECORD_ID: [u8; 48] = [
+    0x01, 0x8b, 0xcf, 0xe5, 0x68, 0x00, 0x00, 0x00, 0x55, 0x09, 0x18, 0x0a, 0x76, 0x83, 0x25, 0xcb,
+    0xcd, 0xb6, 0xec, 0xfe, 0x15, 0x5c, 0x03, 0x85, 0x36, 0x3d, 0x53, 0x04, 0x4f, 0xc2, 0xaf, 0x39,
+    0x4e, 0x61, 0x75, 0xc0, 0xff, 0x83, 0x2e, 0xa8, 0x41, 0x62, 0x2f, 0x97, 0x06, 0xd5, 0xf8, 0xb9,
+];
+
+/// `RECORD_ID` in its printable `moref0` form
+pub const RECORD_ID_PRINTABLE: &str =
+    "moref0ygfh93meyyyyyiejdyf8py3f3xg5p586niqy8bjs8ijoeu6nihhwhamiad9agmieeftn9fag4zhm1";
+
+/// The `Address` of `RECORD`
+pub const RECORD_ADDRESS: [u8; 48] = [
+    0xcc, 0x41, 0x15, 0x0f, 0x41, 0x51, 0x29, 0x8b, 0x4f, 0x76, 0x7c, 0x74, 0xbb, 0x1b, 0x03, 0x00,
+    0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0, 0x99,
+    0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55, 0x31, 0xb8,
+];
+
+/// `RECORD_ADDRESS` in its printable `moref0` form
+pub const RECORD_ADDRESS_PRINTABLE: &str =
+    "moref03tytkd4bkrwasu5sxt4msgadyyb4nb796x8bbxo7qdqtt34mancsx3gsgnp4kdk9dzqec3y1kwa5o";
+
+/// Server URIs, in the form users may enter them
+pub const SERVER_URIS: [&str; 2] = [
+    "wss://relay.example.com",
+    "https://mosaic.example.org:8443/",
+];
+
+/// `SERVER_URIS` encoded as a server bootstrap DHT value
+pub const SERVER_BOOTSTRAP_DHT_STRING: &str =
+    "S\nwss://relay.example.com\nhttps://mosaic.example.org:8443";
+
+fn check(ok: bool, what: &'static str) -> Result<(), Error> {
+    if ok {
+        Ok(())
+    } else {
+        Err(InnerError::TestVectorMismatch(what).into())
+    }
+}
+
+/// Recompute every vector with this crate and compare. The server
+/// bootstrap vector is only checked with the `uri` feature.
+///
+/// # Errors
+///
+/// Returns an `Err` naming the first vector that does not match
+pub fn verify() -> Result<(), Error> {
+    let secret_key = SecretKey::from_bytes(&SECRET_KEY);
+    check(
+        secret_key.printable() == SECRET_KEY_PRINTABLE,
+        "SECRET_KEY_PRINTABLE",
+    )?;
+
+    let public_key = secret_key.public();
+    check(public_key.as_bytes() == &PUBLIC_KEY, "PUBLIC_KEY")?;
+    check(
+        public_key.printable() == PUBLIC_KEY_PRINTABLE,
+        "PUBLIC_KEY_PRINTABLE",
+    )?;
+    check(
+        PublicKey::from_printable(PUBLIC_KEY_PRINTABLE)? == public_key,
+        "PUBLIC_KEY_PRINTABLE",
+    )?;
+
+    let tag = OwnedTag::new_notify_public_key(&public_key);
+    let timestamp = Timestamp::from_millis(RECORD_TIMESTAMP_MS)
+        .ok_or_else(|| InnerError::TimeOutOfRange.into_err())?;
+    let record = OwnedRecord::new(
+        &secret_key,
+        &RecordParts {
+            kind: RECORD_KIND,
+            deterministic_key: Some(RECORD_DETERMINISTIC_KEY),
+            timestamp,
+            flags: RecordFlags::PRINTABLE,
+            app_flags: 0,
+            tags_bytes: tag.as_bytes(),
+            payload: RECORD_PAYLOAD,
+        },
+    )?;
+    check(record.as_bytes() == RECORD, "RECORD")?;
+
+    let record = Record::from_bytes_verified(&RECORD)?;
+    check(record.full_hash() == RECORD_HASH, "RECORD_HASH")?;
+    check(
+        record.signature().to_bytes() == RECORD_SIGNATURE,
+        "RECORD_SIGNATURE",
+    )?;
+    check(record.id().as_bytes() == &RECORD_ID, "RECORD_ID")?;
+    check(
+        record.id().printable() == RECORD_ID_PRINTABLE,
+        "RECORD_ID_PRINTABLE",
+    )?;
+    check(
+        record.address().as_bytes() == &RECORD_ADDRESS,
+        "RECORD_ADDRESS",
+    )?;
+    check(
+        record.address().printable() == RECORD_ADDRESS_PRINTABLE,
+        "RECORD_ADDRESS_PRINTABLE",
+    )?;
+
+    #[cfg(feature = "uri")]
+    {
+        let mut bootstrap = crate::ServerBootstrap::new();
+        for uri in SERVER_URIS {
+            bootstrap.append_uri(uri.parse()?)?;
+        }
+        check(
+            bootstrap.to_dht_string() == SERVER_BOOTSTRAP_DHT_STRING,
+            "SERVER_BOOTSTRAP_DHT_STRING",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vectors() {
+        verify().unwrap();
+    }
+}
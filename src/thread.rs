@@ -0,0 +1,273 @@
+use crate::tag::iter_tags;
+use crate::{Address, Error, Id, OwnedRecord, Record, Reference, TagType};
+use std::collections::{HashMap, HashSet};
+
+/// A record in a `Thread` and the replies to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadNode {
+    /// The record
+    pub record: OwnedRecord,
+
+    /// Direct replies, oldest first
+    pub replies: Vec<ThreadNode>,
+}
+
+impl ThreadNode {
+    /// The number of records in this subtree, including this one
+    #[must_use]
+    pub fn len(&self) -> usize {
+        1 + self.replies.iter().map(ThreadNode::len).sum::<usize>()
+    }
+
+    /// Always false; a node holds at least its own record
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// A reply tree assembled by a `ThreadBuilder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// The root record and the replies reachable from it, if the root
+    /// record was supplied
+    pub root: Option<ThreadNode>,
+
+    /// Subtrees whose parent was not supplied, oldest first
+    pub orphans: Vec<ThreadNode>,
+}
+
+/// Assembles the records of a thread into a reply tree.
+///
+/// A record belongs to the thread if it is the root, or if it has a `ROOT`
+/// or `REPLY` tag referencing the root. Each record hangs under the record
+/// its `REPLY` tag references, whether by `Id` or by `Address`. Records are
+/// verified as they are inserted, and only the latest record at each
+/// `Address` is kept.
+///
+/// Replies and orphans are ordered by timestamp and then by `Id`, so every
+/// client builds the same tree from the same records.
+#[derive(Debug, Clone)]
+pub struct ThreadBuilder {
+    root: Reference,
+    records: HashMap<Address, OwnedRecord>,
+}
+
+impl ThreadBuilder {
+    /// Create a `ThreadBuilder` for the thread rooted at `root`
+    #[must_use]
+    pub fn new(root: Reference) -> ThreadBuilder {
+        ThreadBuilder {
+            root,
+            records: HashMap::new(),
+        }
+    }
+
+    /// Add a record. Returns false if it is not part of this thread or is
+    /// superseded by a record already added at its address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record does not verify
+    pub fn insert(&mut self, record: &Record) -> Result<bool, Error> {
+        record.verify()?;
+        if !self.is_root(record) && !references(record).any(|(_, r)| r == self.root) {
+            return Ok(false);
+        }
+        let address = record.address();
+        if let Some(current) = self.records.get(&address) {
+            if !record.supersedes(current) {
+                return Ok(false);
+            }
+        }
+        let _ = self.records.insert(address, record.to_owned());
+        Ok(true)
+    }
+
+    /// The number of records added
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Have no records been added?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Assemble the tree
+    #[must_use]
+    pub fn build(&self) -> Thread {
+        let by_id: HashMap<Id, &OwnedRecord> = self.records.values().map(|r| (r.id(), r)).collect();
+
+        // Resolve each record's parent among the supplied records
+        let mut children: HashMap<Address, Vec<&OwnedRecord>> = HashMap::new();
+        let mut parentless: Vec<&OwnedRecord> = Vec::new();
+        let mut root = None;
+        for record in self.records.values() {
+            if self.is_root(record) {
+                root = Some(record);
+                continue;
+            }
+            let parent = reply_target(record).and_then(|target| match target.as_id() {
+                Some(id) => by_id.get(&id).copied(),
+                None => target
+                    .as_address()
+                    .and_then(|address| self.records.get(&address)),
+            });
+            match parent {
+                Some(parent) => children.entry(parent.address()).or_default().push(record),
+                None => parentless.push(record),
+            }
+        }
+        for siblings in children.values_mut() {
+            sort(siblings);
+        }
+        sort(&mut parentless);
+
+        let mut visited = HashSet::new();
+        let root = root.map(|r| grow(r, &children, &mut visited));
+        let mut orphans: Vec<ThreadNode> = parentless
+            .into_iter()
+            .map(|r| grow(r, &children, &mut visited))
+            .collect();
+
+        // Records replying to one another in a cycle (possible only through
+        // addresses) are unreachable from either; keep them as orphans.
+        let mut unreached: Vec<&OwnedRecord> = self
+            .records
+            .values()
+            .filter(|r| !visited.contains(&r.address()))
+            .collect();
+        sort(&mut unreached);
+        for record in unreached {
+            if !visited.contains(&record.address()) {
+                orphans.push(grow(record, &children, &mut visited));
+            }
+        }
+
+        Thread { root, orphans }
+    }
+
+    fn is_root(&self, record: &Record) -> bool {
+        self.root.as_id() == Some(record.id()) || self.root.as_address() == Some(record.address())
+    }
+}
+
+// The references in a record's REPLY and ROOT tags
+fn references(record: &Record) -> impl Iterator<Item = (TagType, Reference)> + '_ {
+    iter_tags(record.tags_bytes())
+        .filter(|t| t.get_type() == TagType::REPLY || t.get_type() == TagType::ROOT)
+        .filter_map(|t| Some((t.get_type(), t.get_reference().ok()??)))
+}
+
+fn reply_target(record: &Record) -> Option<Reference> {
+    references(record)
+        .find(|(ty, _)| *ty == TagType::REPLY)
+        .map(|(_, r)| r)
+}
+
+fn sort(records: &mut [&OwnedRecord]) {
+    records.sort_by_key(|r| (r.timestamp(), r.id()));
+}
+
+fn grow(
+    record: &OwnedRecord,
+    children: &HashMap<Address, Vec<&OwnedRecord>>,
+    visited: &mut HashSet<Address>,
+) -> ThreadNode {
+    let _ = visited.insert(record.address());
+    let mut replies = Vec::new();
+    for reply in children.get(&record.address()).into_iter().flatten() {
+        if !visited.contains(&reply.address()) {
+            replies.push(grow(reply, children, visited));
+        }
+    }
+    ThreadNode {
+        record: record.clone(),
+        replies,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedTag, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    fn post(secret_key: &SecretKey, ms: u64, tags: &[OwnedTag]) -> OwnedRecord {
+        let tags_bytes: Vec<u8> = tags.iter().flat_map(|t| t.as_bytes().to_vec()).collect();
+        OwnedRecord::new(
+            secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(ms).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: &tags_bytes,
+                payload: b"",
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_thread_builder() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let root = post(&secret_key, 1000, &[]);
+        let root_ref = Reference::from_bytes(root.id().as_bytes()).unwrap();
+        let root_tag = OwnedTag::new_root(&root_ref, Kind::MICROBLOG_ROOT);
+        let reply_to = |r: &OwnedRecord| {
+            let refer = Reference::from_bytes(r.address().as_bytes()).unwrap();
+            OwnedTag::new_reply(&refer, Kind::MICROBLOG_ROOT)
+        };
+
+        let a = post(
+            &secret_key,
+            3000,
+            &[
+                root_tag.clone(),
+                OwnedTag::new_reply(&root_ref, Kind::MICROBLOG_ROOT),
+            ],
+        );
+        let b = post(
+            &secret_key,
+            2000,
+            &[
+                root_tag.clone(),
+                OwnedTag::new_reply(&root_ref, Kind::MICROBLOG_ROOT),
+            ],
+        );
+        let a1 = post(&secret_key, 4000, &[root_tag.clone(), reply_to(&a)]);
+        let missing = post(&secret_key, 5000, std::slice::from_ref(&root_tag));
+        let orphan = post(&secret_key, 6000, &[root_tag.clone(), reply_to(&missing)]);
+        let unrelated = post(&secret_key, 7000, &[]);
+
+        let mut builder = ThreadBuilder::new(root_ref);
+        for record in [&orphan, &a1, &a, &b, &root] {
+            assert!(builder.insert(record).unwrap());
+        }
+        assert!(!builder.insert(&unrelated).unwrap());
+        assert!(!builder.insert(&a).unwrap());
+
+        let thread = builder.build();
+        let root_node = thread.root.unwrap();
+        assert_eq!(root_node.record, root);
+        assert_eq!(root_node.len(), 4);
+        // Oldest reply first
+        assert_eq!(root_node.replies[0].record, b);
+        assert_eq!(root_node.replies[1].record, a);
+        assert_eq!(root_node.replies[1].replies[0].record, a1);
+        assert_eq!(thread.orphans.len(), 1);
+        assert_eq!(thread.orphans[0].record, orphan);
+
+        // Tampered records are refused
+        let mut bytes = b.as_bytes().to_vec();
+        bytes[300] ^= 1;
+        let tampered = unsafe { Record::from_bytes(&bytes).unwrap() };
+        assert!(builder.insert(tampered).is_err());
+    }
+}
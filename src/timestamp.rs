@@ -147,10 +147,39 @@ impl Timestamp {
     }
 }
 
+// A `Timestamp` is held as a `u64`, so there are no bytes to lend out through
+// `AsRef<[u8]>`. Converting into `[u8; 6]` serves the same purpose.
+impl From<Timestamp> for [u8; 6] {
+    fn from(timestamp: Timestamp) -> [u8; 6] {
+        timestamp.to_bytes()
+    }
+}
+
+impl TryFrom<&[u8]> for Timestamp {
+    type Error = Error;
+
+    /// From a 6-byte little-endian slice, as with `Timestamp::from_bytes()`
+    fn try_from(bytes: &[u8]) -> Result<Timestamp, Error> {
+        let bytes: &[u8; 6] = bytes
+            .try_into()
+            .map_err(|_| InnerError::TimestampLength.into_err())?;
+        Timestamp::from_bytes(bytes)
+    }
+}
+
+impl TryFrom<[u8; 6]> for Timestamp {
+    type Error = Error;
+
+    /// From a 6-byte little-endian array, as with `Timestamp::from_bytes()`
+    fn try_from(bytes: [u8; 6]) -> Result<Timestamp, Error> {
+        Timestamp::from_bytes(&bytes)
+    }
+}
+
 // https://data.iana.org/time-zones/data/leap-seconds.list
 //
-// Expires 28 June 2025
-const LEAP_SECONDS_EXPIRE: u64 = 1_751_068_800; // unixtime
+// Expires 28 December 2026
+const LEAP_SECONDS_EXPIRE: u64 = 1_798_416_000; // unixtime
                                                 //
 #[allow(clippy::unreadable_literal)]
 fn iana_ntp_leap_seconds() -> Vec<u64> {
@@ -159,7 +188,7 @@ fn iana_ntp_leap_seconds() -> Vec<u64> {
         2272060800, //	10	# 1 Jan 1972      // 63072000
         2287785600, //	11	# 1 Jul 1972      // 78796800
         2303683200, //	12	# 1 Jan 1973      // 94694400
-        2335219200, //	13	# 1 Jan 1974      // 126230400
+        2335219200, //	13	# 1 Jan 1974      // 126_230_400
         2366755200, //	14	# 1 Jan 1975      // 157766400
         2398291200, //	15	# 1 Jan 1976      // 189302400
         2429913600, //	16	# 1 Jan 1977      // 220924800
@@ -200,17 +229,21 @@ mod test {
     #[test]
     fn test_timestamp() {
         // Test a date in 1986 (14 leap seconds elapsed)
-        let timestamp = Timestamp::from_unixtime(500000000, 987).unwrap();
-        assert_eq!(timestamp.as_millis(), 500000014987);
+        let timestamp = Timestamp::from_unixtime(500_000_000, 987).unwrap();
+        assert_eq!(timestamp.as_millis(), 500_000_014_987);
 
         // Test a date in 2024 (28 leap seconds elapsed)
-        let timestamp = Timestamp::from_unixtime(1732950200, 1).unwrap();
-        assert_eq!(timestamp.as_millis(), 1732950228001);
+        let timestamp = Timestamp::from_unixtime(1_732_950_200, 1).unwrap();
+        assert_eq!(timestamp.as_millis(), 1_732_950_228_001);
 
         // convert to and from a slice and compare
         let bytes = timestamp.to_bytes();
         let timestamp2 = Timestamp::from_bytes(&bytes).unwrap();
         assert_eq!(timestamp, timestamp2);
+        let bytes: [u8; 6] = timestamp.into();
+        assert_eq!(Timestamp::try_from(bytes).unwrap(), timestamp);
+        assert_eq!(Timestamp::try_from(&bytes[..]).unwrap(), timestamp);
+        assert!(Timestamp::try_from(&bytes[..5]).is_err());
 
         // Print now
         println!("NOW={}", Timestamp::now().unwrap());
@@ -219,7 +252,7 @@ mod test {
     #[test]
     fn test_timestamp_unixtime_conversions() {
         // Trial 10 seconds before and after the 4th leapsecond
-        for u in 126230400 - 10..126230400 + 10 {
+        for u in 126_230_400 - 10..126_230_400 + 10 {
             let ts = Timestamp::from_unixtime(u, 500).unwrap();
             println!("{ts:?}"); // so you can see the leap
             let (u2, _) = ts.to_unixtime();
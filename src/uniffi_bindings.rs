@@ -0,0 +1,425 @@
+// UniFFI passes arguments by value
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::{
+    Error, Filter, Kind, OwnedRecord, PublicKey, RecordFlags, RecordParts, SecretKey,
+    ServerBootstrap, Timestamp, UserBootstrap,
+};
+use std::sync::Arc;
+
+/// An error crossing the `UniFFI` boundary. The message is the `Display` of
+/// the underlying `Error`.
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum MosaicError {
+    /// Any error from this crate
+    Error(String),
+}
+
+impl std::fmt::Display for MosaicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MosaicError::Error(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl std::error::Error for MosaicError {}
+
+impl From<Error> for MosaicError {
+    fn from(e: Error) -> MosaicError {
+        MosaicError::Error(e.to_string())
+    }
+}
+
+/// A `SecretKey` for `UniFFI` bindings
+#[derive(Debug, uniffi::Object)]
+pub struct MosaicSecretKey(SecretKey);
+
+#[uniffi::export]
+impl MosaicSecretKey {
+    /// Generate a new secret key
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn generate() -> Arc<MosaicSecretKey> {
+        Arc::new(MosaicSecretKey(SecretKey::generate(&mut rand_core::OsRng)))
+    }
+
+    /// Import a secret key from its printable form
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the input is not a valid printable secret key
+    #[uniffi::constructor]
+    pub fn from_printable(s: String) -> Result<Arc<MosaicSecretKey>, MosaicError> {
+        Ok(Arc::new(MosaicSecretKey(SecretKey::from_printable(&s)?)))
+    }
+
+    /// The printable form
+    #[must_use]
+    pub fn printable(&self) -> String {
+        self.0.printable()
+    }
+
+    /// The public key
+    #[must_use]
+    pub fn public_key(&self) -> Arc<MosaicPublicKey> {
+        Arc::new(MosaicPublicKey(self.0.public()))
+    }
+}
+
+/// A `PublicKey` for `UniFFI` bindings
+#[derive(Debug, Clone, Copy, uniffi::Object)]
+pub struct MosaicPublicKey(PublicKey);
+
+#[uniffi::export]
+impl MosaicPublicKey {
+    /// Import a public key from its printable form
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the input is not a valid printable public key
+    #[uniffi::constructor]
+    pub fn from_printable(s: String) -> Result<Arc<MosaicPublicKey>, MosaicError> {
+        Ok(Arc::new(MosaicPublicKey(PublicKey::from_printable(&s)?)))
+    }
+
+    /// Import a public key from its 32 bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the input is not 32 bytes or not a valid key
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<MosaicPublicKey>, MosaicError> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| MosaicError::Error("Key data length is not 32 bytes".to_owned()))?;
+        Ok(Arc::new(MosaicPublicKey(PublicKey::from_bytes(&bytes)?)))
+    }
+
+    /// The printable form
+    #[must_use]
+    pub fn printable(&self) -> String {
+        self.0.printable()
+    }
+
+    /// The 32 bytes of the key
+    #[must_use]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+}
+
+/// A `Record` for `UniFFI` bindings
+#[derive(Debug, uniffi::Object)]
+pub struct MosaicRecord(OwnedRecord);
+
+#[uniffi::export]
+impl MosaicRecord {
+    /// Create and sign a new record with a random address
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the timestamp or flags are invalid, or if the
+    /// record is too long
+    #[uniffi::constructor]
+    pub fn new(
+        secret_key: &MosaicSecretKey,
+        kind: u16,
+        timestamp_ms: u64,
+        flags: u16,
+        app_flags: u16,
+        tags: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Result<Arc<MosaicRecord>, MosaicError> {
+        let timestamp = Timestamp::from_millis(timestamp_ms)
+            .ok_or_else(|| MosaicError::Error("Time out of range".to_owned()))?;
+        let flags = RecordFlags::from_bits(flags)
+            .ok_or_else(|| MosaicError::Error("Reserved flags used".to_owned()))?;
+        let record = OwnedRecord::new(
+            &secret_key.0,
+            &RecordParts {
+                kind: Kind(kind),
+                deterministic_key: None,
+                timestamp,
+                flags,
+                app_flags,
+                tags_bytes: &tags,
+                payload: &payload,
+            },
+        )?;
+        Ok(Arc::new(MosaicRecord(record)))
+    }
+
+    /// Parse and verify a record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is invalid
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<MosaicRecord>, MosaicError> {
+        Ok(Arc::new(MosaicRecord(OwnedRecord::from_vec(bytes)?)))
+    }
+
+    /// Verify the record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is invalid
+    pub fn verify(&self) -> Result<(), MosaicError> {
+        Ok(self.0.verify()?)
+    }
+
+    /// The serialized record
+    #[must_use]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+
+    /// The printable `Id`
+    #[must_use]
+    pub fn id(&self) -> String {
+        self.0.id().printable()
+    }
+
+    /// The printable `Address`
+    #[must_use]
+    pub fn address(&self) -> String {
+        self.0.address().printable()
+    }
+
+    /// The author's public key
+    #[must_use]
+    pub fn author_public_key(&self) -> Arc<MosaicPublicKey> {
+        Arc::new(MosaicPublicKey(self.0.author_public_key()))
+    }
+
+    /// The signing public key
+    #[must_use]
+    pub fn signing_public_key(&self) -> Arc<MosaicPublicKey> {
+        Arc::new(MosaicPublicKey(self.0.signing_public_key()))
+    }
+
+    /// The kind
+    #[must_use]
+    pub fn kind(&self) -> u16 {
+        self.0.kind().0
+    }
+
+    /// The timestamp in milliseconds
+    #[must_use]
+    pub fn timestamp_ms(&self) -> u64 {
+        self.0.timestamp().as_millis()
+    }
+
+    /// The flags
+    #[must_use]
+    pub fn flags(&self) -> u16 {
+        self.0.flags().bits()
+    }
+
+    /// The application flags
+    #[must_use]
+    pub fn app_flags(&self) -> u16 {
+        self.0.app_flags()
+    }
+
+    /// The tag bytes
+    #[must_use]
+    pub fn tags(&self) -> Vec<u8> {
+        self.0.tags_bytes().to_vec()
+    }
+
+    /// The payload bytes
+    #[must_use]
+    pub fn payload(&self) -> Vec<u8> {
+        self.0.payload_bytes().to_vec()
+    }
+}
+
+/// A `Filter` for `UniFFI` bindings
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct MosaicFilter {
+    /// Match only records of these kinds
+    #[uniffi(default = [])]
+    pub kinds: Vec<u16>,
+
+    /// Match only records by these authors
+    #[uniffi(default = [])]
+    pub author_keys: Vec<Arc<MosaicPublicKey>>,
+
+    /// Match only records whose payload text contains this search term
+    #[uniffi(default = None)]
+    pub search: Option<String>,
+
+    /// Match only records at or after this time, in milliseconds
+    #[uniffi(default = None)]
+    pub since_ms: Option<u64>,
+
+    /// Match only records before this time, in milliseconds
+    #[uniffi(default = None)]
+    pub until_ms: Option<u64>,
+
+    /// Return at most this many records
+    #[uniffi(default = None)]
+    pub limit: Option<u32>,
+}
+
+impl TryFrom<MosaicFilter> for Filter {
+    type Error = MosaicError;
+
+    fn try_from(f: MosaicFilter) -> Result<Filter, MosaicError> {
+        let timestamp = |ms: u64| {
+            Timestamp::from_millis(ms)
+                .ok_or_else(|| MosaicError::Error("Time out of range".to_owned()))
+        };
+        let filter = Filter {
+            kinds: f.kinds.into_iter().map(Kind).collect(),
+            author_keys: f.author_keys.iter().map(|pk| pk.0).collect(),
+            search: f.search,
+            since: f.since_ms.map(timestamp).transpose()?,
+            until: f.until_ms.map(timestamp).transpose()?,
+            limit: f.limit,
+            ..Filter::default()
+        };
+        filter.validate()?;
+        Ok(filter)
+    }
+}
+
+/// Does the record match the filter?
+///
+/// # Errors
+///
+/// Returns an `Err` if the filter is invalid
+#[uniffi::export]
+pub fn filter_matches(filter: MosaicFilter, record: &MosaicRecord) -> Result<bool, MosaicError> {
+    Ok(Filter::try_from(filter)?.matches(&record.0))
+}
+
+/// A `ServerBootstrap` for `UniFFI` bindings
+#[derive(Debug, uniffi::Object)]
+pub struct MosaicServerBootstrap(ServerBootstrap);
+
+#[uniffi::export]
+impl MosaicServerBootstrap {
+    /// Parse from the DHT string form and a sequence number
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the string is not a valid server bootstrap
+    #[uniffi::constructor]
+    pub fn from_dht_string(s: String, seq: i64) -> Result<Arc<MosaicServerBootstrap>, MosaicError> {
+        Ok(Arc::new(MosaicServerBootstrap(
+            ServerBootstrap::from_dht_string_and_seq(&s, seq)?,
+        )))
+    }
+
+    /// The DHT string form
+    #[must_use]
+    pub fn to_dht_string(&self) -> String {
+        self.0.to_dht_string()
+    }
+
+    /// The server's endpoint URLs
+    #[must_use]
+    pub fn uris(&self) -> Vec<String> {
+        self.0.inner().iter().map(ToString::to_string).collect()
+    }
+
+    /// The sequence number
+    #[must_use]
+    pub fn seq(&self) -> i64 {
+        self.0.seq()
+    }
+}
+
+/// One server entry of a `UserBootstrap`
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct MosaicUserServer {
+    /// The server usage bits (outbox, inbox, encryption)
+    pub usage: u8,
+
+    /// The server's public key
+    pub public_key: Arc<MosaicPublicKey>,
+}
+
+/// A `UserBootstrap` for `UniFFI` bindings
+#[derive(Debug, uniffi::Object)]
+pub struct MosaicUserBootstrap(UserBootstrap);
+
+#[uniffi::export]
+impl MosaicUserBootstrap {
+    /// Parse from the DHT string form and a sequence number
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the string is not a valid user bootstrap
+    #[uniffi::constructor]
+    pub fn from_dht_string(s: String, seq: i64) -> Result<Arc<MosaicUserBootstrap>, MosaicError> {
+        Ok(Arc::new(MosaicUserBootstrap(
+            UserBootstrap::from_dht_string_and_seq(&s, seq)?,
+        )))
+    }
+
+    /// The DHT string form
+    #[must_use]
+    pub fn to_dht_string(&self) -> String {
+        self.0.to_dht_string()
+    }
+
+    /// The user's servers
+    #[must_use]
+    pub fn servers(&self) -> Vec<MosaicUserServer> {
+        self.0
+            .inner()
+            .iter()
+            .map(|(usage, pk)| MosaicUserServer {
+                usage: usage.bits(),
+                public_key: Arc::new(MosaicPublicKey(*pk)),
+            })
+            .collect()
+    }
+
+    /// The sequence number
+    #[must_use]
+    pub fn seq(&self) -> i64 {
+        self.0.seq()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uniffi_bindings() {
+        let secret_key = MosaicSecretKey::generate();
+        let public_key = secret_key.public_key();
+        let record = MosaicRecord::new(
+            &secret_key,
+            Kind::MICROBLOG_ROOT.0,
+            1000,
+            0,
+            0,
+            vec![],
+            b"hello".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(record.author_public_key().bytes(), public_key.bytes());
+
+        let copy = MosaicRecord::from_bytes(record.bytes()).unwrap();
+        assert_eq!(copy.id(), record.id());
+
+        let mut filter = MosaicFilter {
+            kinds: vec![Kind::MICROBLOG_ROOT.0],
+            author_keys: vec![public_key],
+            ..MosaicFilter::default()
+        };
+        assert!(filter_matches(filter.clone(), &record).unwrap());
+        filter.since_ms = Some(2000);
+        assert!(!filter_matches(filter.clone(), &record).unwrap());
+        filter.until_ms = Some(1000);
+        assert!(filter_matches(filter, &record).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{Error, Record, Timestamp, VerifyPolicy};
+
+/// The bytes of a record whose lengths have been checked but whose hash
+/// and signature have not.
+///
+/// This is the safe alternative to `unsafe Record::from_bytes()`: the only
+/// way to get a `&Record` out of it is `verify()`, so unverified bytes
+/// cannot be used as a record by mistake. Nothing is copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnverifiedRecord<'a>(&'a [u8]);
+
+impl<'a> UnverifiedRecord<'a> {
+    /// Check the lengths of the record at the start of `input`. Any bytes
+    /// after the record are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the input is shorter than the lengths in its header say, or if
+    /// the length is more than `MAX_RECORD_LEN` bytes.
+    pub fn from_bytes(input: &'a [u8]) -> Result<UnverifiedRecord<'a>, Error> {
+        Ok(Self::from_bytes_prefix(input)?.0)
+    }
+
+    /// Check the lengths of the record at the start of `input`, returning it
+    /// along with the number of bytes it takes up.
+    ///
+    /// # Errors
+    ///
+    /// Errors as with `from_bytes()`.
+    pub fn from_bytes_prefix(input: &'a [u8]) -> Result<(UnverifiedRecord<'a>, usize), Error> {
+        // SAFETY: the record is only handed out by verify()
+        let (record, len) = unsafe { Record::from_bytes_prefix(input)? };
+        Ok((UnverifiedRecord(record.as_bytes()), len))
+    }
+
+    /// The bytes of the record
+    #[must_use]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Verify the record
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any verification fails. See `Record::verify()`
+    pub fn verify(self) -> Result<&'a Record, Error> {
+        let record = Record::from_inner(self.0);
+        record.verify()?;
+        Ok(record)
+    }
+
+    /// Verify the record against a `VerifyPolicy`, taking `now` as the
+    /// current time
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any check fails. See `VerifyPolicy::check()`
+    pub fn verify_with(self, policy: &VerifyPolicy, now: Timestamp) -> Result<&'a Record, Error> {
+        let record = Record::from_inner(self.0);
+        policy.check(record, now)?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{InnerError, Kind, OwnedRecord, RecordFlags, RecordParts, SecretKey};
+
+    #[test]
+    fn test_unverified_record() {
+        use rand::rngs::OsRng;
+
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut bytes = record.as_bytes().to_vec();
+        bytes.extend_from_slice(b"next");
+        let (unverified, len) = UnverifiedRecord::from_bytes_prefix(&bytes).unwrap();
+        assert_eq!(len, record.as_bytes().len());
+        assert_eq!(unverified.as_bytes(), record.as_bytes());
+        assert_eq!(unverified.verify().unwrap(), &*record);
+
+        let last = record.as_bytes().len() - 8;
+        bytes[last] ^= 1;
+        let unverified = UnverifiedRecord::from_bytes(&bytes).unwrap();
+        assert!(unverified.verify().is_err());
+
+        let err = UnverifiedRecord::from_bytes(&bytes[..100]).unwrap_err();
+        assert!(matches!(err.inner, InnerError::EndOfInput));
+    }
+}
@@ -1,8 +1,9 @@
-use crate::{Error, InnerError};
+use crate::{Address, Error, Id, InnerError, PublicKey, Reference};
 use http::uri::PathAndQuery;
 use http::Uri;
 
 const PATH_AND_QUERY: &str = "/";
+const MOSAIC_SCHEME: &str = "mosaic://";
 
 pub(crate) fn clean_uri(uri: Uri) -> Result<Uri, Error> {
     let mut parts = uri.into_parts();
@@ -17,3 +18,228 @@ pub(crate) fn clean_uri(uri: Uri) -> Result<Uri, Error> {
     let uri = Uri::from_parts(parts)?;
     Ok(uri)
 }
+
+// Strip `mosaic://<kind>/` and return the single path segment after it
+fn strip_mosaic_uri<'a>(s: &'a str, kind: &str) -> Result<&'a str, Error> {
+    let rest = s
+        .strip_prefix(MOSAIC_SCHEME)
+        .and_then(|rest| rest.strip_prefix(kind))
+        .and_then(|rest| rest.strip_prefix('/'))
+        .ok_or_else(|| InnerError::InvalidMosaicUri.into_err())?;
+    if rest.is_empty() || rest.contains(['/', '?', '#']) {
+        return Err(InnerError::InvalidMosaicUri.into());
+    }
+    Ok(rest)
+}
+
+/// A link to a server, of the form `mosaic://server/mopub0...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerUri(PublicKey);
+
+impl ServerUri {
+    /// Create a `ServerUri` for a server's `PublicKey`
+    #[must_use]
+    pub fn new(public_key: PublicKey) -> ServerUri {
+        ServerUri(public_key)
+    }
+
+    /// The server's `PublicKey`
+    #[must_use]
+    pub fn public_key(&self) -> PublicKey {
+        self.0
+    }
+}
+
+impl From<PublicKey> for ServerUri {
+    fn from(public_key: PublicKey) -> ServerUri {
+        ServerUri(public_key)
+    }
+}
+
+impl From<ServerUri> for PublicKey {
+    fn from(uri: ServerUri) -> PublicKey {
+        uri.0
+    }
+}
+
+impl std::str::FromStr for ServerUri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ServerUri, Error> {
+        Ok(ServerUri(PublicKey::from_printable(strip_mosaic_uri(
+            s, "server",
+        )?)?))
+    }
+}
+
+impl std::fmt::Display for ServerUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{MOSAIC_SCHEME}server/{}", self.0)
+    }
+}
+
+/// A link to a user, of the form `mosaic://user/mopub0...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UserUri(PublicKey);
+
+impl UserUri {
+    /// Create a `UserUri` for a user's `PublicKey`
+    #[must_use]
+    pub fn new(public_key: PublicKey) -> UserUri {
+        UserUri(public_key)
+    }
+
+    /// The user's `PublicKey`
+    #[must_use]
+    pub fn public_key(&self) -> PublicKey {
+        self.0
+    }
+}
+
+impl From<PublicKey> for UserUri {
+    fn from(public_key: PublicKey) -> UserUri {
+        UserUri(public_key)
+    }
+}
+
+impl From<UserUri> for PublicKey {
+    fn from(uri: UserUri) -> PublicKey {
+        uri.0
+    }
+}
+
+impl std::str::FromStr for UserUri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<UserUri, Error> {
+        Ok(UserUri(PublicKey::from_printable(strip_mosaic_uri(
+            s, "user",
+        )?)?))
+    }
+}
+
+impl std::fmt::Display for UserUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{MOSAIC_SCHEME}user/{}", self.0)
+    }
+}
+
+/// A link to a record by `Id` or `Address`, of the form
+/// `mosaic://record/moref0...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordUri(Reference);
+
+impl RecordUri {
+    /// Create a `RecordUri` for a `Reference`
+    #[must_use]
+    pub fn new(reference: Reference) -> RecordUri {
+        RecordUri(reference)
+    }
+
+    /// The `Reference` linked to
+    #[must_use]
+    pub fn reference(&self) -> Reference {
+        self.0
+    }
+
+    /// The `Id` linked to, if this links to an `Id`
+    #[must_use]
+    pub fn as_id(&self) -> Option<Id> {
+        self.0.as_id()
+    }
+
+    /// The `Address` linked to, if this links to an `Address`
+    #[must_use]
+    pub fn as_address(&self) -> Option<Address> {
+        self.0.as_address()
+    }
+}
+
+impl From<Reference> for RecordUri {
+    fn from(reference: Reference) -> RecordUri {
+        RecordUri(reference)
+    }
+}
+
+impl From<Id> for RecordUri {
+    fn from(id: Id) -> RecordUri {
+        RecordUri(id.into())
+    }
+}
+
+impl From<Address> for RecordUri {
+    fn from(address: Address) -> RecordUri {
+        RecordUri(address.into())
+    }
+}
+
+impl From<RecordUri> for Reference {
+    fn from(uri: RecordUri) -> Reference {
+        uri.0
+    }
+}
+
+impl std::str::FromStr for RecordUri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RecordUri, Error> {
+        Ok(RecordUri(Reference::from_printable(strip_mosaic_uri(
+            s, "record",
+        )?)?))
+    }
+}
+
+impl std::fmt::Display for RecordUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{MOSAIC_SCHEME}record/{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SecretKey;
+
+    #[test]
+    fn test_mosaic_uris() {
+        use rand::rngs::OsRng;
+
+        let public_key = SecretKey::generate(&mut OsRng).public();
+        let server = ServerUri::new(public_key);
+        let s = server.to_string();
+        assert_eq!(s, format!("mosaic://server/{}", public_key.printable()));
+        assert_eq!(s.parse::<ServerUri>().unwrap(), server);
+        assert_eq!(PublicKey::from(server), public_key);
+
+        let user = UserUri::from(public_key);
+        assert_eq!(user.to_string().parse::<UserUri>().unwrap(), user);
+
+        // The kind is checked
+        assert!(s.parse::<UserUri>().is_err());
+        assert!(s.parse::<RecordUri>().is_err());
+
+        let printable =
+            "moref0ygmettbi4ayybx8cwuj1ucd86dcz86enodrbup44w6tqz93tjz9ougw1kdgw7wdacuenwk93kyob1";
+        let id = Id::from_printable(printable).unwrap();
+        let record = RecordUri::from(id);
+        let s = record.to_string();
+        assert_eq!(s, format!("mosaic://record/{printable}"));
+        let parsed: RecordUri = s.parse().unwrap();
+        assert_eq!(parsed.as_id(), Some(id));
+        assert!(parsed.as_address().is_none());
+
+        // Anything extra is rejected
+        for bad in [
+            format!("{s}/"),
+            format!("{s}?x=1"),
+            format!("{s}#top"),
+            format!("MOSAIC://record/{printable}"),
+            "mosaic://record/".to_owned(),
+            format!("mosaic:record/{printable}"),
+        ] {
+            let err = bad.parse::<RecordUri>().unwrap_err();
+            assert_eq!(err.kind(), crate::ErrorKind::InvalidUri, "{bad}");
+        }
+        assert!("mosaic://record/moref0abc".parse::<RecordUri>().is_err());
+    }
+}
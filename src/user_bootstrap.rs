@@ -1,9 +1,13 @@
-use crate::{Error, InnerError, PublicKey, SecretKey};
+use crate::{Error, InnerError, PublicKey};
 use bitflags::bitflags;
-use mainline::async_dht::AsyncDht;
-use mainline::{Id, MutableItem};
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+use {
+    crate::SecretKey,
+    mainline::{async_dht::AsyncDht, Dht, Id, MutableItem},
+};
 
 // note: this has been updated from "mub24" because printable pubkeys have changed.
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
 pub(crate) const DHT_USER_SALT: &[u8] = b"mub25";
 
 /// Server usage flags
@@ -125,7 +129,10 @@ impl UserBootstrap {
 
         Ok(UserBootstrap(output, seq))
     }
+}
 
+#[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+impl UserBootstrap {
     /// Try to read a `UserBootstrap` record for the given `PublicKey`
     /// using the supplied `Dht` state object
     ///
@@ -133,6 +140,10 @@ impl UserBootstrap {
     ///
     /// Returns an error if the Dht was shutdown. If bad data is returned from the Dht, that is
     /// ignored; if all Dht nodes return bad data you will get `None`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(pubkey = %pubkey), err)
+    )]
     pub async fn read_from_dht(
         pubkey: PublicKey,
         dht: &AsyncDht,
@@ -141,13 +152,40 @@ impl UserBootstrap {
             .get_mutable_most_recent(pubkey.as_bytes(), Some(DHT_USER_SALT))
             .await;
 
-        if let Some(mi) = mutable_item {
-            let s = std::str::from_utf8(mi.value())?;
-            let ub = UserBootstrap::from_dht_string_and_seq(s, mi.seq())?;
-            Ok(Some(ub))
-        } else {
-            Ok(None)
-        }
+        mutable_item
+            .map(|mi| UserBootstrap::from_mutable_item(&mi))
+            .transpose()
+    }
+
+    /// Try to read a `UserBootstrap` record for the given `PublicKey`
+    /// using the supplied blocking `Dht` state object
+    ///
+    /// DHT nodes drop items that have not been republished for a while, so
+    /// `None` may mean the user's bootstrap has expired rather than that it
+    /// was never written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the Dht was shutdown, or if the most recent item
+    /// does not contain a valid `UserBootstrap`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(pubkey = %pubkey), err)
+    )]
+    pub fn read_from_dht_blocking(
+        pubkey: PublicKey,
+        dht: &Dht,
+    ) -> Result<Option<UserBootstrap>, Error> {
+        dht.get_mutable_most_recent(pubkey.as_bytes(), Some(DHT_USER_SALT))
+            .map(|mi| UserBootstrap::from_mutable_item(&mi))
+            .transpose()
+    }
+
+    // The DHT client has already verified the item's signature against the
+    // public key it was looked up by.
+    fn from_mutable_item(mi: &MutableItem) -> Result<UserBootstrap, Error> {
+        let s = std::str::from_utf8(mi.value())?;
+        UserBootstrap::from_dht_string_and_seq(s, mi.seq())
     }
 
     /// Try to write a `UserBootstrap` record for the given `PublicKey`
@@ -157,6 +195,15 @@ impl UserBootstrap {
     /// # Errors
     ///
     /// Returns an `Err` if it couldn't write to the Dht
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(pubkey = %secret_key.public(), seq = self.1),
+            err
+        )
+    )]
     pub async fn write_to_dht(
         &mut self,
         secret_key: SecretKey,
@@ -208,6 +255,7 @@ mod test {
         assert_eq!(s, &s2);
     }
 
+    #[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
     #[tokio::test]
     async fn test_user_bootstrap_dht() {
         use crate::SecretKey;
@@ -244,4 +292,20 @@ mod test {
             println!("Stored new at {id}");
         }
     }
+
+    #[cfg(all(feature = "dht", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_user_bootstrap_dht_blocking() {
+        let dht = mainline::Dht::client().unwrap();
+        let printable = "mosec07oryrgz94em4nrhgwizkgfjax8pciuik6qwd6huzo647m38rdnny";
+        let public_key = crate::SecretKey::from_printable(printable)
+            .unwrap()
+            .public();
+
+        // It may have expired from the DHT, in which case there is nothing to check
+        if let Some(ubs) = UserBootstrap::read_from_dht_blocking(public_key, &dht).unwrap() {
+            let s = "U\n3 mopub0embq17gjmxub6m9mhrg4y33htppzoi6desbenjzzrbzzc4qm7bwo\n1 mopub01cqdc3i8j9pgdepwuxysqjus44eobwhijdnhg5wyopfng5rfmtyy";
+            assert_eq!(ubs.to_dht_string(), s);
+        }
+    }
 }
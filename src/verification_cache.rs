@@ -0,0 +1,194 @@
+use crate::{BudgetHandle, Error, Id, Record};
+use std::collections::{HashMap, VecDeque};
+
+// Approximate memory used by one entry: the Id twice (map and order),
+// the signature, and hash map overhead
+const ENTRY_BYTES: usize = 48 * 2 + 64 + 32;
+
+/// A bounded cache of records that have passed full verification.
+///
+/// Relays often receive the same record on several connections.
+/// `verify()` fully verifies a record the first time it is seen and
+/// remembers its `Id` and signature. Later, a record with the same `Id`
+/// and signature skips the signature check.
+///
+/// The `Id` commits to the content hash but not to the signature, so the
+/// hash and every other check are still run on a cache hit; only the
+/// (expensive) signature verification is skipped.
+///
+/// Once full, the oldest entries are evicted. The cache may also account
+/// its memory against a `MemoryBudget`.
+#[derive(Debug)]
+pub struct VerificationCache {
+    signatures: HashMap<Id, [u8; 64]>,
+    order: VecDeque<Id>,
+    capacity: usize,
+    budget: Option<BudgetHandle>,
+    hits: u64,
+    misses: u64,
+}
+
+impl VerificationCache {
+    /// Create a `VerificationCache` holding at most `capacity` entries. A
+    /// capacity of zero is treated as one.
+    #[must_use]
+    pub fn new(capacity: usize) -> VerificationCache {
+        VerificationCache {
+            signatures: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+            budget: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Account memory against a `MemoryBudget` component. If the budget
+    /// cannot be reserved, the oldest entries are evicted to make room.
+    pub fn set_budget(&mut self, budget: BudgetHandle) {
+        if let Some(old) = self.budget.take() {
+            old.release(self.order.len() * ENTRY_BYTES);
+        }
+        while !self.order.is_empty() && !budget.try_reserve(self.order.len() * ENTRY_BYTES) {
+            self.evict_oldest();
+        }
+        self.budget = Some(budget);
+    }
+
+    /// Verify a record, skipping the signature check if this `Id` and
+    /// signature have already been verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is invalid. See `Record::verify()`.
+    pub fn verify(&mut self, record: &Record) -> Result<(), Error> {
+        let id = record.id();
+        let signature = record.signature().to_bytes();
+        if self.signatures.get(&id) == Some(&signature) {
+            self.hits += 1;
+            return record.verify_except_signature();
+        }
+
+        self.misses += 1;
+        record.verify()?;
+        self.insert(id, signature);
+        Ok(())
+    }
+
+    /// Has this record's `Id` and signature already been verified?
+    #[must_use]
+    pub fn contains(&self, record: &Record) -> bool {
+        self.signatures.get(&record.id()) == Some(&record.signature().to_bytes())
+    }
+
+    fn insert(&mut self, id: Id, signature: [u8; 64]) {
+        if let Some(existing) = self.signatures.get_mut(&id) {
+            // A different valid signature for the same Id; keep the newest
+            *existing = signature;
+            return;
+        }
+        if self.order.len() == self.capacity {
+            self.evict_oldest();
+        }
+        while !self
+            .budget
+            .as_ref()
+            .is_none_or(|budget| budget.try_reserve(ENTRY_BYTES))
+        {
+            if self.order.is_empty() {
+                return;
+            }
+            self.evict_oldest();
+        }
+        let _ = self.signatures.insert(id, signature);
+        self.order.push_back(id);
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(id) = self.order.pop_front() {
+            let _ = self.signatures.remove(&id);
+            if let Some(ref budget) = self.budget {
+                budget.release(ENTRY_BYTES);
+            }
+        }
+    }
+
+    /// Number of entries
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Is the cache empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Number of verifications that skipped the signature check
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of full verifications
+    #[must_use]
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+impl Drop for VerificationCache {
+    fn drop(&mut self) {
+        if let Some(ref budget) = self.budget {
+            budget.release(self.order.len() * ENTRY_BYTES);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, OwnedRecord, RecordFlags, RecordParts, SecretKey, Timestamp};
+
+    #[test]
+    fn test_verification_cache() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut cache = VerificationCache::new(10);
+        cache.verify(&record).unwrap();
+        cache.verify(&record).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // Same Id, corrupted signature: not a hit, and fails
+        let mut bytes = record.as_bytes().to_vec();
+        bytes[0] ^= 1;
+        let bad = unsafe { Record::from_bytes(&bytes) }.unwrap();
+        assert!(!cache.contains(bad));
+        assert!(cache.verify(bad).is_err());
+
+        // Same Id and signature, tampered content: a hit, but fails
+        let mut bytes = record.as_bytes().to_vec();
+        let last = bytes.len() - 8;
+        bytes[last] ^= 1;
+        let bad = unsafe { Record::from_bytes(&bytes) }.unwrap();
+        assert!(cache.contains(bad));
+        assert!(cache.verify(bad).is_err());
+    }
+}
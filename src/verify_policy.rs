@@ -0,0 +1,238 @@
+use crate::{Error, InnerError, KeySchedule, PublicKey, Record, SubkeyWindow, Timestamp};
+use std::collections::HashMap;
+
+/// A `VerifyPolicy` decides whether a record can be trusted, combining
+/// hash and signature verification with checks on the signing key and
+/// the timestamp.
+///
+/// A record signed by a key other than its author's must be signed by a
+/// subkey the author has delegated, either with `add_subkey()` or through
+/// a key schedule record passed to `add_key_schedule()`. A delegated
+/// subkey may only sign records timestamped within its window. Revoked
+/// keys are not trusted, as signing keys or as authors, for records
+/// timestamped from when they were revoked.
+///
+/// See `Record::verify_with()`
+#[derive(Debug, Clone)]
+pub struct VerifyPolicy {
+    /// Require records not signed by their author to be signed by a
+    /// delegated subkey (true by default)
+    pub require_delegation: bool,
+
+    /// If set, how far in the future (in milliseconds) a timestamp may be
+    pub max_future_ms: Option<u64>,
+
+    /// If set, how far in the past (in milliseconds) a timestamp may be
+    pub max_age_ms: Option<u64>,
+
+    // Subkeys delegated by each author, with when they may sign
+    subkeys: HashMap<PublicKey, HashMap<PublicKey, SubkeyWindow>>,
+    // Revoked keys, with the timestamp they were revoked from
+    revoked: HashMap<PublicKey, Timestamp>,
+}
+
+impl Default for VerifyPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VerifyPolicy {
+    /// Create a new `VerifyPolicy` that requires delegation and does not
+    /// check timestamps
+    #[must_use]
+    pub fn new() -> VerifyPolicy {
+        VerifyPolicy {
+            require_delegation: true,
+            max_future_ms: None,
+            max_age_ms: None,
+            subkeys: HashMap::new(),
+            revoked: HashMap::new(),
+        }
+    }
+
+    /// Trust records by `author` signed with `subkey` that are timestamped
+    /// no later than `expires`, if given
+    #[allow(clippy::missing_panics_doc)]
+    pub fn add_subkey(&mut self, author: PublicKey, subkey: PublicKey, expires: Option<Timestamp>) {
+        let window = SubkeyWindow {
+            valid_from: Timestamp::from_millis(0).unwrap(),
+            valid_until: expires,
+        };
+        let _ = self
+            .subkeys
+            .entry(author)
+            .or_default()
+            .insert(subkey, window);
+    }
+
+    /// Trust the subkeys of `author`'s `KeySchedule` within their windows,
+    /// and revoke the keys it revokes from when it revokes them
+    pub fn add_schedule(&mut self, author: PublicKey, schedule: &KeySchedule) {
+        let subkeys = self.subkeys.entry(author).or_default();
+        for (subkey, window) in schedule.subkeys() {
+            let _ = subkeys.insert(*subkey, *window);
+        }
+        for (key, at) in schedule.revoked() {
+            let revoked = self.revoked.entry(*key).or_insert(*at);
+            *revoked = (*revoked).min(*at);
+        }
+    }
+
+    /// Trust the subkeys of a key schedule record, as `add_schedule()`
+    /// does
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the record is not a valid key schedule. See
+    /// `KeySchedule::from_record()`.
+    pub fn add_key_schedule(&mut self, record: &Record) -> Result<(), Error> {
+        let schedule = KeySchedule::from_record(record)?;
+        self.add_schedule(record.author_public_key(), &schedule);
+        Ok(())
+    }
+
+    /// Never trust records signed or authored by `key`
+    #[allow(clippy::missing_panics_doc)]
+    pub fn revoke(&mut self, key: PublicKey) {
+        let _ = self.revoked.insert(key, Timestamp::from_millis(0).unwrap());
+    }
+
+    /// Has `key` been revoked, from any time?
+    #[must_use]
+    pub fn is_revoked(&self, key: &PublicKey) -> bool {
+        self.revoked.contains_key(key)
+    }
+
+    /// Check a record against this policy, taking `now` as the current
+    /// time.
+    ///
+    /// The checks on keys and timestamps run before the more expensive
+    /// hash and signature verification.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` describing the first check that fails
+    pub fn check(&self, record: &Record, now: Timestamp) -> Result<(), Error> {
+        let author = record.author_public_key();
+        let signer = record.signing_public_key();
+        let timestamp = record.timestamp();
+        let revoked = |key| self.revoked.get(key).is_some_and(|at| timestamp >= *at);
+        if revoked(&author) || revoked(&signer) {
+            return Err(InnerError::SigningKeyRevoked.into());
+        }
+
+        if signer != author && self.require_delegation {
+            match self.subkeys.get(&author).and_then(|s| s.get(&signer)) {
+                Some(window) if window.contains(timestamp) => {}
+                Some(window) if timestamp > window.valid_from => {
+                    return Err(InnerError::SigningKeyExpired.into());
+                }
+                _ => return Err(InnerError::SigningKeyNotDelegated.into()),
+            }
+        }
+
+        let ts = timestamp.as_millis();
+        if let Some(max_future) = self.max_future_ms {
+            if ts > now.as_millis().saturating_add(max_future) {
+                return Err(InnerError::TimestampInFuture.into());
+            }
+        }
+        if let Some(max_age) = self.max_age_ms {
+            if ts < now.as_millis().saturating_sub(max_age) {
+                return Err(InnerError::TimestampTooOld.into());
+            }
+        }
+
+        record.verify()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ErrorKind, Kind, OwnedRecord, RecordFlags, SecretKey};
+
+    #[test]
+    fn test_verify_policy() {
+        use rand::rngs::OsRng;
+
+        let master = SecretKey::generate(&mut OsRng);
+        let subkey = SecretKey::generate(&mut OsRng);
+        let now = Timestamp::from_millis(10_000_000).unwrap();
+
+        // A record by `master`, signed by `signer`
+        let make = |signer: &SecretKey, kind: Kind, millis: u64, tags_bytes: &[u8]| {
+            OwnedRecord::new_replacement(
+                signer,
+                Address::new_random(master.public(), kind),
+                Timestamp::from_millis(millis).unwrap(),
+                RecordFlags::empty(),
+                0,
+                tags_bytes,
+                b"hello",
+            )
+            .unwrap()
+        };
+
+        let mut policy = VerifyPolicy::new();
+        policy.max_future_ms = Some(60_000);
+        policy.max_age_ms = Some(1_000_000);
+
+        let own = make(&master, Kind::MICROBLOG_ROOT, 9_999_000, b"");
+        own.verify_with(&policy, now).unwrap();
+
+        let by_subkey = make(&subkey, Kind::MICROBLOG_ROOT, 9_999_000, b"");
+        let err = by_subkey.verify_with(&policy, now).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UntrustedKey);
+
+        let mut schedule = KeySchedule::new();
+        schedule.add_subkey(
+            subkey.public(),
+            SubkeyWindow {
+                valid_from: Timestamp::from_millis(9_000_000).unwrap(),
+                valid_until: None,
+            },
+        );
+        let schedule_record = schedule
+            .to_record(&master, Timestamp::from_millis(9_000_000).unwrap())
+            .unwrap();
+        policy.add_key_schedule(&schedule_record).unwrap();
+        by_subkey.verify_with(&policy, now).unwrap();
+
+        // Not before the window opens
+        let early = make(&subkey, Kind::MICROBLOG_ROOT, 8_999_999, b"");
+        assert!(matches!(
+            early.verify_with(&policy, now).unwrap_err().inner,
+            InnerError::SigningKeyNotDelegated
+        ));
+
+        // Expiry
+        policy.add_subkey(
+            master.public(),
+            subkey.public(),
+            Some(Timestamp::from_millis(9_500_000).unwrap()),
+        );
+        assert!(matches!(
+            by_subkey.verify_with(&policy, now).unwrap_err().inner,
+            InnerError::SigningKeyExpired
+        ));
+        policy.add_subkey(master.public(), subkey.public(), None);
+
+        // Skew
+        let future = make(&master, Kind::MICROBLOG_ROOT, 10_100_000, b"");
+        let err = future.verify_with(&policy, now).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidTime);
+        assert!(!err.is_invalid_input());
+        let old = make(&master, Kind::MICROBLOG_ROOT, 1_000, b"");
+        assert!(old.verify_with(&policy, now).is_err());
+
+        // Revocation
+        policy.revoke(subkey.public());
+        assert!(matches!(
+            by_subkey.verify_with(&policy, now).unwrap_err().inner,
+            InnerError::SigningKeyRevoked
+        ));
+        own.verify_with(&policy, now).unwrap();
+    }
+}
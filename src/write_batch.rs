@@ -0,0 +1,120 @@
+use crate::{Id, OwnedRecord, PutOutcome};
+
+/// One operation in a `WriteBatch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOp {
+    /// Store a record
+    Put(OwnedRecord),
+
+    /// Delete a record by its `Id`
+    Delete(Id),
+}
+
+/// The result of one operation in a `WriteBatch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WriteResult {
+    /// The outcome of a `WriteOp::Put`
+    Put(PutOutcome),
+
+    /// The outcome of a `WriteOp::Delete`: true if the record was present
+    Delete(bool),
+}
+
+/// A group of puts and deletes applied to a `RecordStore` together.
+///
+/// Backends with transactions apply a batch in a single transaction (and
+/// a single fsync), which is much faster than applying each operation on
+/// its own. Operations are applied in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<WriteOp>,
+}
+
+impl WriteBatch {
+    /// Create a new empty `WriteBatch`
+    #[must_use]
+    pub fn new() -> WriteBatch {
+        WriteBatch::default()
+    }
+
+    /// Add a put
+    pub fn put(&mut self, record: OwnedRecord) {
+        self.ops.push(WriteOp::Put(record));
+    }
+
+    /// Add a delete
+    pub fn delete(&mut self, id: Id) {
+        self.ops.push(WriteOp::Delete(id));
+    }
+
+    /// The operations, in order
+    #[must_use]
+    pub fn ops(&self) -> &[WriteOp] {
+        &self.ops
+    }
+
+    /// Number of operations
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Is the batch empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl IntoIterator for WriteBatch {
+    type Item = WriteOp;
+    type IntoIter = std::vec::IntoIter<WriteOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, MemoryStore, RecordFlags, RecordParts, RecordStore, SecretKey, Timestamp};
+
+    #[test]
+    fn test_write_batch() {
+        use rand::rngs::OsRng;
+
+        let mut csprng = OsRng;
+        let secret_key = SecretKey::generate(&mut csprng);
+        let record = OwnedRecord::new(
+            &secret_key,
+            &RecordParts {
+                kind: Kind::MICROBLOG_ROOT,
+                deterministic_key: None,
+                timestamp: Timestamp::from_millis(1000).unwrap(),
+                flags: RecordFlags::empty(),
+                app_flags: 0,
+                tags_bytes: b"",
+                payload: b"hello",
+            },
+        )
+        .unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.put(record.clone());
+        batch.put(record.clone());
+        batch.delete(record.id());
+        assert_eq!(batch.len(), 3);
+
+        let mut store = MemoryStore::new();
+        assert_eq!(
+            store.write_batch(batch).unwrap(),
+            vec![
+                WriteResult::Put(PutOutcome::Stored),
+                WriteResult::Put(PutOutcome::Duplicate),
+                WriteResult::Delete(true),
+            ]
+        );
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Error, InnerError};
+
+// The z-base-32 alphabet
+// (https://philzimmermann.com/docs/human-oriented-base-32-encoding.txt)
+const ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+const DECODE: [u8; 256] = {
+    let mut table: [u8; 256] = [0xFF; 256];
+    let mut i: u8 = 0;
+    while i < 32 {
+        table[ALPHABET[i as usize] as usize] = i;
+        i += 1;
+    }
+    table
+};
+
+/// Encode bytes as z-base-32. The last character carries zero bits if the
+/// input is not a multiple of 5 bits.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u16 = 0;
+    let mut bits: u32 = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(char::from(ALPHABET[usize::from((buffer >> bits) & 0x1F)]));
+        }
+    }
+    if bits > 0 {
+        output.push(char::from(
+            ALPHABET[usize::from((buffer << (5 - bits)) & 0x1F)],
+        ));
+    }
+    output
+}
+
+/// Decode z-base-32 into whole bytes. Spare bits at the end are ignored, so
+/// callers that need a canonical encoding should compare against
+/// `encode()` of the result.
+pub(crate) fn decode(s: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut output: Vec<u8> = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer: u16 = 0;
+    let mut bits: u32 = 0;
+    for c in s {
+        let value = DECODE[usize::from(*c)];
+        if value == 0xFF {
+            return Err(InnerError::InvalidZBase32(*c).into());
+        }
+        buffer = (buffer << 5) | u16::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            #[allow(clippy::cast_possible_truncation)]
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zbase32() {
+        let data = "The quick brown fox jumps over the lazy dog. 👀";
+        let encoded =
+            "ktwgkedtqiwsg43ycj3g675qrbug66bypj4s4hdurbzzc3m1rb4go3jyptozw6jyctzsqmty6nx3dyy";
+        assert_eq!(encode(data.as_bytes()), encoded);
+        assert_eq!(decode(encoded.as_bytes()).unwrap(), data.as_bytes());
+        assert_eq!(decode(b"qb1ze3m1").unwrap(), b"peter");
+        assert_eq!(encode(b""), "");
+        for len in 0..70 {
+            let bytes: Vec<u8> = (0..len)
+                .map(|i: u8| i.wrapping_mul(37).wrapping_add(11))
+                .collect();
+            assert_eq!(decode(encode(&bytes).as_bytes()).unwrap(), bytes);
+        }
+        assert!(decode(b"qb1ze3m0").is_err());
+    }
+}
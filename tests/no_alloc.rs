@@ -0,0 +1,65 @@
+//! Verifying a valid record and parsing tags must not allocate.
+//!
+//! This is an integration test so that its counting allocator does not
+//! affect the unit tests.
+
+use mosaic_core::{test_vectors, Record, Tag};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_during<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_verify_does_not_allocate() {
+    let record = unsafe { Record::from_bytes(&test_vectors::RECORD) }.unwrap();
+
+    let count = allocations_during(|| {
+        std::hint::black_box(record.verify()).unwrap();
+    });
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn test_tag_parsing_does_not_allocate() {
+    let record = unsafe { Record::from_bytes(&test_vectors::RECORD) }.unwrap();
+    let tags = record.tags_bytes();
+    let short = [0x01, 0x00, 0x05, 0x00, 0x00];
+
+    let count = allocations_during(|| {
+        for bytes in [tags, &short[..], &tags[..2]] {
+            if let Ok(tag) = unsafe { Tag::from_bytes(bytes) } {
+                let _ = std::hint::black_box(tag.get_type());
+                let _ = std::hint::black_box(tag.get_value());
+                let _ = std::hint::black_box(tag.get_public_key());
+                let _ = std::hint::black_box(tag.get_reference());
+                let _ = std::hint::black_box(tag.get_kind());
+                let _ = std::hint::black_box(tag.get_offset());
+            }
+        }
+    });
+    assert_eq!(count, 0);
+}